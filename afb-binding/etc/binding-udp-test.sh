@@ -0,0 +1,77 @@
+#!/bin/bash
+
+# end to end test: binding reads golden frames from udp, sensor verbs and change
+# feed are then checked through afb-client. Run from repository root after 'cargo build'
+
+# use libafb development version if any
+export LD_LIBRARY_PATH="/usr/local/lib64:$LD_LIBRARY_PATH"
+export PATH="/usr/local/lib64:$PATH"
+
+API="ws://localhost:1235/api"
+FRAME="linky-lib/test/frames/standard-mono.tic"
+LOGDIR=$(mktemp -d /tmp/linky-udp-test.XXXXXX)
+
+if ! test -f $CARGO_TARGET_DIR/debug/libafb_linky.so; then
+    echo "FATAL: missing libafb_linky.so use: cargo build"
+    exit 1
+fi
+
+for cmd in afb-binder afb-client; do
+    if ! which $cmd >/dev/null 2>&1; then
+        echo "FATAL: missing $cmd"
+        exit 1
+    fi
+done
+
+afb-binder -v --config=afb-binding/etc/binding-udp-test.json > $LOGDIR/binder.log 2>&1 &
+BINDER=$!
+trap "kill $BINDER $LISTENER 2>/dev/null" EXIT
+
+# wait for binder websocket
+for retry in $(seq 1 50); do
+    afb-client $API linky sensors >/dev/null 2>&1 && break
+    sleep 0.1
+done
+
+# listen to change feed while frames are received
+(echo 'linky changes {"action":"subscribe"}'; sleep 3) | afb-client -H $API > $LOGDIR/events.log 2>&1 &
+LISTENER=$!
+sleep 0.5
+
+# one frame per datagram, as sent by wifi gateways
+for count in 1 2 3; do
+    cat $FRAME > /dev/udp/127.0.0.1/2323
+    sleep 0.5
+done
+
+FAILED=0
+check() {
+    local label=$1 verb=$2 expected=$3
+    local reply=$(afb-client -H $API linky $verb '{"action":"read"}' 2>&1)
+    if echo "$reply" | grep -q "$expected"; then
+        echo "OK   $label"
+    else
+        echo "FAIL $label expected:$expected reply:$reply"
+        FAILED=1
+    fi
+}
+
+check "instant power" SINSTS 750
+check "energy withdrawn" EAST 54878
+check "current phase 1" IRMS1 3
+check "voltage phase 1" URMS1 232
+check "tariff label" LTARF "HEURE  PLEINE"
+
+wait $LISTENER
+if grep -q "SINSTS" $LOGDIR/events.log; then
+    echo "OK   change feed"
+else
+    echo "FAIL change feed, see $LOGDIR/events.log"
+    FAILED=1
+fi
+
+if test $FAILED -ne 0; then
+    echo "binder log: $LOGDIR/binder.log"
+    exit 1
+fi
+rm -rf $LOGDIR
@@ -12,27 +12,685 @@
 
 use crate::prelude::*;
 use afbv4::prelude::*;
+use linky::prelude::*;
 
 AfbDataConverter!(api_actions, ApiAction);
 use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, Debug, Default)]
 #[serde(rename_all = "lowercase", tag = "action")]
+// UPPERCASE actions as in afbv4 samples, serde lowercases them on the wire
+#[allow(clippy::upper_case_acronyms)]
 pub(crate) enum ApiAction {
     #[default]
     READ,
     INFO,
+    // multi-phase sensors accept '{"action":"subscribe","phase":2}'
+    SUBSCRIBE { phase: Option<usize> },
+    UNSUBSCRIBE { phase: Option<usize> },
+    HISTORY,
+    STATS,
+}
+
+AfbDataConverter!(sensor_query, SensorQuery);
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum SensorAction {
+    #[default]
+    READ,
+    INFO,
+    SUBSCRIBE,
+    UNSUBSCRIBE,
+    HISTORY,
+    STATS,
+}
+
+// numeric and energy sensors accept '{"action":"read","phases":[1,2],"unit":"kW","history":60}'
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct SensorQuery {
+    pub action: SensorAction,
+    pub phase: Option<usize>,       // single phase event subscription
+    pub phases: Option<Vec<usize>>, // read/history subset, same numbering as phase
+    pub unit: Option<String>,       // sensor unit, its 'k' multiple (kWh, kVA) or 'W'/'kW' from VA
+    pub history: Option<usize>,     // last samples only, also appended to read reply
+    pub raw: bool,                  // meter value before sensor scale/offset calibration
+}
+
+AfbDataConverter!(stats_actions, StatsAction);
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "lowercase", tag = "action")]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum StatsAction {
+    #[default]
+    READ,
+}
+
+AfbDataConverter!(selfconso_actions, SelfConsoAction);
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "lowercase", tag = "action")]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum SelfConsoAction {
+    #[default]
+    READ,
     SUBSCRIBE,
     UNSUBSCRIBE,
+    // inverter side production as '{"action":"production","energy":152300,"power":2300}'
+    // energy is a cumulative Wh counter, power in W
+    PRODUCTION {
+        energy: Option<u64>,
+        power: Option<i32>,
+    },
+}
+
+#[cfg(feature = "sqlite")]
+AfbDataConverter!(store_query, StoreQuery);
+// stored samples as '{"uid":"SINSTS","from":1699610000000,"to":1699620000000,"count":100}'
+// range bounds are epoch ms, missing bounds select every stored sample
+#[cfg(feature = "sqlite")]
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub(crate) struct StoreQuery {
+    pub uid: String,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub count: Option<usize>,
+}
+
+AfbDataConverter!(control_actions, ControlAction);
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "lowercase", tag = "action")]
+#[allow(clippy::upper_case_acronyms)]
+pub(crate) enum ControlAction {
+    #[default]
+    REOPEN,
+    // new source block, same syntax as binding config 'network', 'serial' or 'replay'
+    RECONFIGURE {
+        network: Option<serde_json::Value>,
+        serial: Option<serde_json::Value>,
+        replay: Option<serde_json::Value>,
+    },
+    // clear cached values, statistics and cycle counters, all sensors and link counters
+    // when uids is missing
+    RESET { uids: Option<Vec<String>> },
+    // replace runtime sensors tuning, same syntax as binding config 'sensors' array
+    SENSORS { sensors: Vec<serde_json::Value> },
+}
+
+// sensor read reply layout, V2 wraps every value as '{"value":..,"unit":..,"ts":..,"quality":..}'
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ReplySchema {
+    V1,
+    V2,
 }
 
-pub(crate) struct LinkyConfig {
-    pub device: &'static str,
-    pub parity: &'static str,
-    pub speed: u32,
+// READ unit conversion, '{"Wh":"kWh","VA":"W","power_factor":0.9}' sets per base unit default
+// targets, power factor estimates active power from apparent power (VA to W)
+#[derive(Clone, Copy)]
+pub(crate) struct UnitConvert {
+    pub targets: &'static [(&'static str, &'static str)], // (sensor unit, reply unit)
+    pub power_factor: f64,
+}
+
+impl UnitConvert {
+    // multiplier from sensor unit to requested unit, None when not convertible
+    pub fn factor(&self, base: &str, unit: &str) -> Option<f64> {
+        if base.is_empty() {
+            return None;
+        }
+        if unit == base {
+            return Some(1.0);
+        }
+        if unit.strip_prefix('k') == Some(base) {
+            return Some(0.001);
+        }
+        match (base, unit) {
+            ("VA", "W") => Some(self.power_factor),
+            ("VA", "kW") => Some(self.power_factor / 1000.0),
+            _ => None,
+        }
+    }
+
+    // config default reply unit for a sensor unit
+    pub fn target(&self, base: &str) -> Option<&'static str> {
+        self.targets
+            .iter()
+            .find(|(from, _)| *from == base)
+            .map(|(_, unit)| *unit)
+    }
+}
+
+impl Default for UnitConvert {
+    fn default() -> Self {
+        UnitConvert {
+            targets: &[],
+            power_factor: 1.0,
+        }
+    }
+}
+
+// afb permissions are checked per verb, read only verbs and admin verbs get distinct acls
+#[derive(Clone, Copy)]
+pub(crate) struct BindingAcls {
+    pub read: &'static AfbPermission,  // sensors read, info, subscribe, history, stats
+    pub admin: &'static AfbPermission, // control reopen, reconfigure, reset, sensors
+}
+
+// per sensor event tuning, uid matches TicObject uid (IINST, SINSTS, ...)
+#[derive(Clone, Copy)]
+pub(crate) struct SensorConfig {
+    pub uid: &'static str,
+    pub enabled: bool,     // disabled sensors neither cache values nor push events
+    pub min_interval: u32, // ms between two events, 0 no limit
+    pub delta: u32,        // absolute change in sensor unit before pushing
+    pub delta_pct: u32,    // relative change in % of last pushed value
+    pub history: usize,    // number of pushed samples kept for 'history' action
+    pub window: usize,     // number of received values used for min/max/avg 'stats' action
+    pub scale: f64,        // calibration applied to meter value before caching and events
+    pub offset: f64,       // added after scale, in sensor unit
+}
+
+impl SensorConfig {
+    pub fn new(uid: &'static str) -> SensorConfig {
+        SensorConfig {
+            uid,
+            enabled: true,
+            min_interval: 0,
+            delta: 0,
+            delta_pct: 0,
+            history: 0,
+            window: 0,
+            scale: 1.0,
+            offset: 0.0,
+        }
+    }
+
+    pub fn is_calibrated(&self) -> bool {
+        self.scale != 1.0 || self.offset != 0.0
+    }
+
+    // 'value * scale + offset' rounded to sensor unit, raw value when not calibrated
+    pub fn calibrate(&self, value: i64) -> i64 {
+        if !self.is_calibrated() {
+            return value;
+        }
+        (value as f64 * self.scale + self.offset).round() as i64
+    }
+}
+
+pub(crate) struct BindingConfig {
+    pub prefix: &'static str,
+    pub source: LinkyConfig,
+    pub backup: Option<LinkyConfig>,
+    pub failover: u32,
+    pub hotplug: bool,
+    pub backoff: u32,
     pub cycle: u32,
+    pub units: bool,
+    pub strict: bool,
+    pub checksum_alarm: u32, // checksum failures per minute raising link-quality-degraded
+    pub buffer: usize,
+    pub line_max: usize, // decode buffer, longer lines are dropped as too long
+    pub line_budget: usize, // lines decoded per wakeup before yielding to main loop, 0 no limit
+    pub fault_max: u32, // consecutive source failures before giving up, 0 never
+    pub watchdog: u32,
+    pub health: u32,
+    pub margin: u32,
+    pub horizon: u32,
+    pub imbalance: u32,
+    pub voltage: (i32, i32, u32),
+    pub injection: (u32, u32),
+    pub shedding: Option<SheddingConfig>,
+    pub selfconso: usize,
+    pub production: bool, // producer meter sensor family (SINSTI, SMAXIN, EAIT, ERQ)
+    pub outages: usize,   // closed power outages kept by 'outages' verb, 0 disables detection
+    pub ramp: (u32, u32),
+    pub ocpp: (OcppVersion, u32),
+    pub energy: u32, // ms between two energy manager events
+    pub charge: ChargeConfig,
+    pub charging: ChargingConfig,
+    pub trend: usize,
+    pub schema: ReplySchema,
+    pub phase_keys: bool,
+    pub convert: UnitConvert,
+    pub batch: bool,
+    pub labels: Vec<&'static TicObject>,
+    pub sensors: Vec<SensorConfig>,
+    pub preset: Option<&'static [&'static str]>, // registered sensors, None: every sensor
+    pub history: usize,
+    pub window: usize,
+    pub consumption: Vec<(EnergyPeriod, usize)>,
+    pub cost: Option<CostConfig>,
+    pub tariff_names: Vec<String>, // NTARF period names, tariff index 1-n
+    pub storage: Option<StorageConfig>,
+    pub state: Option<StateConfig>,
+    pub csv: Option<CsvConfig>,
+    pub influx: Option<InfluxConfig>,
+    pub redis: Option<RedisConfig>,
+    pub sparkplug: Option<SparkplugConfig>,
+    pub modbus: Option<(&'static str, u16)>,
+    pub dbus: Option<(bool, &'static str)>,
+    pub hooks: Vec<HookConfig>,
+    pub overpower: Option<(&'static str, &'static str)>, // api/verb called on over power
+}
+
+pub(crate) struct SheddingConfig {
+    pub reserve: u32,
+    pub loads: Vec<SheddingLoad>,
+}
+
+pub(crate) struct StorageConfig {
+    pub path: &'static str,
+    pub retention: u32, // days, 0: keep forever
+    pub interval: u32,  // s between two stored samples of the same sensor
+    pub uids: Vec<String>,
+}
+
+pub(crate) struct StateConfig {
+    pub path: &'static str,
+    pub interval: u32, // s between two saves, 0: only on exit
+}
+
+pub(crate) struct CsvConfig {
+    pub path: &'static str,
+    pub size: u32, // kB before rotation, 0: no size rotation
+    pub daily: bool,
+    pub uids: Vec<String>,
+}
+
+pub(crate) struct InfluxConfig {
+    pub url: &'static str,
+    pub org: &'static str,
+    pub bucket: &'static str,
+    pub token: &'static str,
+    pub measurement: &'static str,
+    pub flush: u32,    // ms between two pushes
+    pub buffer: usize, // lines kept while database is unreachable
+    pub uids: Vec<String>,
+}
+
+pub(crate) struct RedisConfig {
+    pub host: &'static str,
+    pub port: u16,
+    pub password: &'static str,
+    pub stream: &'static str,
+    pub maxlen: usize, // stream entries, 0: uncapped
+    pub flush: u32,    // ms between two pushes
+    pub buffer: usize, // entries kept while server is unreachable
+    pub uids: Vec<String>,
+}
+
+pub(crate) struct SparkplugConfig {
+    pub host: &'static str,
+    pub port: u16,
+    pub username: &'static str,
+    pub password: &'static str,
+    pub group: &'static str,
+    pub node: &'static str,
+    pub keepalive: u32, // s
+    pub interval: u32,  // ms between two NDATA
+    pub uids: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct ChargeConfig {
+    pub margin: u32,     // A kept below subscription
+    pub min: u32,        // A, lower limits pause charging
+    pub max: u32,        // A, EVSE rating
+    pub hysteresis: u32, // A of headroom before raising limit
+    pub ramp: u32,       // A/s while raising limit
+}
+
+// IEC 61851 minimum, 32A EVSE
+impl Default for ChargeConfig {
+    fn default() -> Self {
+        ChargeConfig {
+            margin: 0,
+            min: 6,
+            max: 32,
+            hysteresis: 2,
+            ramp: 1,
+        }
+    }
+}
+
+pub(crate) struct ChargingConfig {
+    pub max: u32,            // W, 0: subscribed power
+    pub reduced: u32,        // W during reduced tariff indexes
+    pub price: Option<f64>,  // per kWh, more expensive indexes are reduced
+    pub indexes: Vec<usize>, // tariff indexes always reduced
+}
+
+// afb subcall fired on ADPS/ADIR over current alarm
+#[derive(Clone)]
+pub(crate) struct HookConfig {
+    pub api: &'static str,
+    pub verb: &'static str,
+    pub args: String, // json payload, parsed once at config time
+    pub retry: u32,   // extra attempts after a failed call
+    pub delay: u32,   // ms between two attempts
+    pub holdoff: u32, // s before an alarm may fire the hook again
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct CostConfig {
+    pub currency: &'static str,
+    pub prices: PriceTable,
+}
+
+// sensors without explicit config keep default behavior
+pub(crate) fn find_sensor(
+    sensors: &[SensorConfig],
+    uid: &'static str,
+    history: usize,
+    window: usize,
+) -> SensorConfig {
+    match sensors.iter().find(|sensor| sensor.uid == uid) {
+        Some(sensor) => *sensor,
+        None => SensorConfig {
+            history,
+            window,
+            ..SensorConfig::new(uid)
+        },
+    }
+}
+
+// secrets never leave the binding, only their presence is reported
+fn mask_secret(value: &str) -> &'static str {
+    if value.is_empty() {
+        ""
+    } else {
+        "********"
+    }
+}
+
+fn uids_jsonc(uids: &[String]) -> Result<JsoncObj, AfbError> {
+    let juids = JsoncObj::array();
+    for (idx, uid) in uids.iter().enumerate() {
+        juids.insert(idx, uid.as_str())?;
+    }
+    Ok(juids)
+}
+
+impl BindingConfig {
+    pub fn get_sensor(&self, uid: &'static str) -> SensorConfig {
+        find_sensor(&self.sensors, uid, self.history, self.window)
+    }
+
+    // sensor verbs outside of preset are not registered, custom labels always are
+    pub fn is_exposed(&self, tic: &TicObject) -> bool {
+        let custom = self.labels.iter().any(|label| label.get_uid() == tic.get_uid());
+        let production = self.production && PRODUCTION_UIDS.contains(&tic.get_uid());
+        custom || production || preset_exposed(self.preset, tic.get_uid())
+    }
+
+    // effective meter config once defaults are applied, keys follow binding config syntax
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("prefix", self.prefix)?;
+        let jsource = self.source.to_jsonc()?;
+        if let LinkyConfig::Serial { .. } = self.source {
+            jsource.add("hotplug", self.hotplug)?;
+        }
+        jsonc.add("source", jsource)?;
+        if let Some(backup) = &self.backup {
+            jsonc.add("backup", backup.to_jsonc()?)?;
+            jsonc.add("failover", self.failover)?;
+        }
+        jsonc.add("reconnect-max", self.backoff)?;
+        jsonc.add("cycle", self.cycle)?;
+        jsonc.add("units", self.units)?;
+        jsonc.add("strict", self.strict)?;
+        jsonc.add("checksum_alarm", self.checksum_alarm)?;
+        jsonc.add("buffer", self.buffer as u32)?;
+        jsonc.add("line_max", self.line_max as u32)?;
+        jsonc.add("line_budget", self.line_budget as u32)?;
+        jsonc.add("fault_max", self.fault_max)?;
+        jsonc.add("watchdog", self.watchdog)?;
+        jsonc.add("health", self.health)?;
+        jsonc.add("margin", self.margin)?;
+        jsonc.add("imbalance", self.imbalance)?;
+        jsonc.add("selfconso", self.selfconso as u32)?;
+        jsonc.add("production", self.production)?;
+        jsonc.add("outages", self.outages as u32)?;
+        jsonc.add("history", self.history as u32)?;
+        jsonc.add("window", self.window as u32)?;
+        let schema = match self.schema {
+            ReplySchema::V1 => "v1",
+            ReplySchema::V2 => "v2",
+        };
+        jsonc.add("schema", schema)?;
+        jsonc.add("phase_keys", self.phase_keys)?;
+        let jconvert = JsoncObj::new();
+        for (base, unit) in self.convert.targets {
+            jconvert.add(base, *unit)?;
+        }
+        jconvert.add("power_factor", self.convert.power_factor)?;
+        jsonc.add("convert", jconvert)?;
+        jsonc.add("batch", self.batch)?;
+
+        let joverload = JsoncObj::new();
+        joverload.add("horizon", self.horizon)?;
+        joverload.add("samples", self.trend as u32)?;
+        jsonc.add("overload", joverload)?;
+
+        let jvoltage = JsoncObj::new();
+        jvoltage.add("low", self.voltage.0)?;
+        jvoltage.add("high", self.voltage.1)?;
+        jvoltage.add("duration_ms", self.voltage.2)?;
+        jsonc.add("voltage", jvoltage)?;
+
+        let jinjection = JsoncObj::new();
+        jinjection.add("threshold", self.injection.0)?;
+        jinjection.add("timeout", self.injection.1)?;
+        jsonc.add("injection", jinjection)?;
+
+        let jramp = JsoncObj::new();
+        jramp.add("window", self.ramp.0)?;
+        jramp.add("threshold", self.ramp.1)?;
+        jsonc.add("ramp", jramp)?;
+
+        let jocpp = JsoncObj::new();
+        jocpp.add("version", self.ocpp.0.as_str())?;
+        jocpp.add("interval", self.ocpp.1)?;
+        jsonc.add("ocpp", jocpp)?;
+
+        let jenergy = JsoncObj::new();
+        jenergy.add("interval", self.energy)?;
+        jsonc.add("energy", jenergy)?;
+
+        let jcharge = JsoncObj::new();
+        jcharge.add("margin", self.charge.margin)?;
+        jcharge.add("min", self.charge.min)?;
+        jcharge.add("max", self.charge.max)?;
+        jcharge.add("hysteresis", self.charge.hysteresis)?;
+        jcharge.add("ramp", self.charge.ramp)?;
+        jsonc.add("charge", jcharge)?;
+
+        let jcharging = JsoncObj::new();
+        jcharging.add("max", self.charging.max)?;
+        jcharging.add("reduced", self.charging.reduced)?;
+        if let Some(price) = self.charging.price {
+            jcharging.add("price", price)?;
+        }
+        let jindexes = JsoncObj::array();
+        for (idx, index) in self.charging.indexes.iter().enumerate() {
+            jindexes.insert(idx, *index as u32)?;
+        }
+        jcharging.add("indexes", jindexes)?;
+        jsonc.add("charging", jcharging)?;
+
+        // disabled periods are reported as 0
+        let jconsumption = JsoncObj::new();
+        for period in [EnergyPeriod::DAY, EnergyPeriod::WEEK, EnergyPeriod::MONTH] {
+            let size = match self.consumption.iter().find(|(value, _)| *value == period) {
+                Some((_, size)) => *size as u32,
+                None => 0,
+            };
+            jconsumption.add(period.as_str(), size)?;
+        }
+        jsonc.add("consumption", jconsumption)?;
+
+        let jlabels = JsoncObj::array();
+        for (idx, tic) in self.labels.iter().enumerate() {
+            let jlabel = JsoncObj::new();
+            jlabel.add("label", tic.get_uid())?;
+            jlabel.add("name", tic.get_name())?;
+            jlabel.add("unit", tic.get_unit().as_str())?;
+            jlabels.insert(idx, jlabel)?;
+        }
+        jsonc.add("labels", jlabels)?;
+
+        let jsensors = JsoncObj::array();
+        for (idx, sensor) in self.sensors.iter().enumerate() {
+            let jsensor = JsoncObj::new();
+            jsensor.add("uid", sensor.uid)?;
+            jsensor.add("enabled", sensor.enabled)?;
+            jsensor.add("min_interval_ms", sensor.min_interval)?;
+            jsensor.add("delta", sensor.delta)?;
+            jsensor.add("delta_pct", sensor.delta_pct)?;
+            jsensor.add("history", sensor.history as u32)?;
+            jsensor.add("window", sensor.window as u32)?;
+            jsensor.add("scale", sensor.scale)?;
+            jsensor.add("offset", sensor.offset)?;
+            jsensors.insert(idx, jsensor)?;
+        }
+        jsonc.add("sensors", jsensors)?;
+        if let Some(uids) = self.preset {
+            let jpreset = JsoncObj::array();
+            for (idx, uid) in uids.iter().enumerate() {
+                jpreset.insert(idx, *uid)?;
+            }
+            jsonc.add("preset", jpreset)?;
+        }
+
+        if let Some(shedding) = &self.shedding {
+            let jshedding = JsoncObj::new();
+            jshedding.add("reserve", shedding.reserve)?;
+            jshedding.add("loads", shedding.loads.len() as u32)?;
+            jsonc.add("shedding", jshedding)?;
+        }
+
+        if let Some(cost) = &self.cost {
+            let jcost = JsoncObj::new();
+            jcost.add("currency", cost.currency)?;
+            jsonc.add("cost", jcost)?;
+        }
+
+        if !self.tariff_names.is_empty() {
+            let jnames = JsoncObj::array();
+            for (idx, name) in self.tariff_names.iter().enumerate() {
+                jnames.insert(idx, name.as_str())?;
+            }
+            jsonc.add("tariff_names", jnames)?;
+        }
+
+        if let Some(storage) = &self.storage {
+            let jstorage = JsoncObj::new();
+            jstorage.add("path", storage.path)?;
+            jstorage.add("retention", storage.retention)?;
+            jstorage.add("interval", storage.interval)?;
+            jstorage.add("uids", uids_jsonc(&storage.uids)?)?;
+            jsonc.add("storage", jstorage)?;
+        }
+
+        if let Some(state) = &self.state {
+            let jstate = JsoncObj::new();
+            jstate.add("path", state.path)?;
+            jstate.add("interval", state.interval)?;
+            jsonc.add("state", jstate)?;
+        }
+
+        if let Some(csv) = &self.csv {
+            let jcsv = JsoncObj::new();
+            jcsv.add("path", csv.path)?;
+            jcsv.add("size_kb", csv.size)?;
+            jcsv.add("daily", csv.daily)?;
+            jcsv.add("uids", uids_jsonc(&csv.uids)?)?;
+            jsonc.add("csv", jcsv)?;
+        }
+
+        if let Some(influx) = &self.influx {
+            let jinflux = JsoncObj::new();
+            jinflux.add("url", influx.url)?;
+            jinflux.add("org", influx.org)?;
+            jinflux.add("bucket", influx.bucket)?;
+            jinflux.add("token", mask_secret(influx.token))?;
+            jinflux.add("measurement", influx.measurement)?;
+            jinflux.add("flush", influx.flush)?;
+            jinflux.add("buffer", influx.buffer as u32)?;
+            jinflux.add("uids", uids_jsonc(&influx.uids)?)?;
+            jsonc.add("influx", jinflux)?;
+        }
+
+        if let Some(redis) = &self.redis {
+            let jredis = JsoncObj::new();
+            jredis.add("host", redis.host)?;
+            jredis.add("port", redis.port as u32)?;
+            jredis.add("password", mask_secret(redis.password))?;
+            jredis.add("stream", redis.stream)?;
+            jredis.add("maxlen", redis.maxlen as u32)?;
+            jredis.add("flush", redis.flush)?;
+            jredis.add("buffer", redis.buffer as u32)?;
+            jredis.add("uids", uids_jsonc(&redis.uids)?)?;
+            jsonc.add("redis", jredis)?;
+        }
+
+        if let Some(spb) = &self.sparkplug {
+            let jspb = JsoncObj::new();
+            jspb.add("host", spb.host)?;
+            jspb.add("port", spb.port as u32)?;
+            jspb.add("username", spb.username)?;
+            jspb.add("password", mask_secret(spb.password))?;
+            jspb.add("group", spb.group)?;
+            jspb.add("node", spb.node)?;
+            jspb.add("keepalive", spb.keepalive)?;
+            jspb.add("interval", spb.interval)?;
+            jspb.add("uids", uids_jsonc(&spb.uids)?)?;
+            jsonc.add("sparkplug", jspb)?;
+        }
+
+        if let Some((addr, port)) = self.modbus {
+            let jmodbus = JsoncObj::new();
+            jmodbus.add("addr", addr)?;
+            jmodbus.add("port", port as u32)?;
+            jsonc.add("modbus", jmodbus)?;
+        }
+
+        if let Some((system, name)) = self.dbus {
+            let jdbus = JsoncObj::new();
+            jdbus.add("bus", if system { "system" } else { "session" })?;
+            jdbus.add("name", name)?;
+            jsonc.add("dbus", jdbus)?;
+        }
+
+        if let Some((api, verb)) = self.overpower {
+            let joverpower = JsoncObj::new();
+            joverpower.add("api", api)?;
+            joverpower.add("verb", verb)?;
+            jsonc.add("overpower", joverpower)?;
+        }
+
+        if !self.hooks.is_empty() {
+            let jhooks = JsoncObj::array();
+            for (idx, hook) in self.hooks.iter().enumerate() {
+                let jhook = JsoncObj::new();
+                jhook.add("api", hook.api)?;
+                jhook.add("verb", hook.verb)?;
+                jhook.add("args", JsoncObj::parse(hook.args.as_str())?)?;
+                jhook.add("retry", hook.retry)?;
+                jhook.add("delay", hook.delay)?;
+                jhook.add("holdoff", hook.holdoff)?;
+                jhooks.insert(idx, jhook)?;
+            }
+            jsonc.add("hooks", jhooks)?;
+        }
+
+        Ok(jsonc)
+    }
 }
 
-impl AfbApiControls for LinkyConfig {
+impl AfbApiControls for BindingConfig {
     fn config(&mut self, api: &AfbApi, jconf: JsoncObj) -> Result<(), AfbError> {
         afb_log_msg!(Debug, api, "api={} config={}", api.get_uid(), jconf);
         Ok(())
@@ -44,80 +702,1714 @@ impl AfbApiControls for LinkyConfig {
     }
 }
 
-// Binding init callback started at binding load time before any API exist
-// -----------------------------------------
-pub fn binding_init(rootv4: AfbApiV4, jconf: JsoncObj) -> Result<&'static AfbApi, AfbError> {
-    afb_log_msg!(Info, rootv4, "config:{}", jconf);
+// '${VAR}' within paths, addresses and credentials is replaced by its environment value,
+// an undefined variable fails config rather than silently using an empty string
+pub(crate) fn expand_env(value: String) -> Result<&'static str, AfbError> {
+    if !value.contains("${") {
+        return Ok(to_static_str(value));
+    }
 
-    // add binding custom converter
-    api_actions::register()?;
+    let mut expanded = String::new();
+    let mut rest = value.as_str();
+    while let Some(start) = rest.find("${") {
+        let end = match rest[start..].find('}') {
+            Some(idx) => start + idx,
+            None => return afb_error!("linky-config-fail", "unterminated '${{' in {}", value),
+        };
+        let name = &rest[start + 2..end];
+        match std::env::var(name) {
+            Ok(var) => {
+                expanded.push_str(&rest[..start]);
+                expanded.push_str(var.as_str());
+            }
+            Err(_) => return afb_error!("linky-config-fail", "undefined '{}' in {}", name, value),
+        }
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(to_static_str(expanded))
+}
 
-    let uid = if let Ok(value) = jconf.get::<String>("uid") {
+// accepted keys per config block, binder own keys ('path', 'permission') are tolerated at root
+const ROOT_KEYS: &[&str] = &[
+    "uid", "api", "info", "path", "permission", "permision", "admin", "permissions", "meters",
+];
+const METER_KEYS: &[&str] = &[
+    "prefix", "cycle", "units", "strict", "checksum_alarm", "serial", "network", "replay",
+    "line_max", "backup", "failover", "reconnect-max", "watchdog", "health", "margin", "overload",
+    "imbalance", "voltage", "injection", "schema", "history", "window", "labels", "consumption",
+    "ramp", "ocpp", "energy", "charge", "charging", "selfconso", "shedding", "cost", "storage",
+    "csv", "influx", "redis", "sparkplug", "modbus", "dbus", "sensors", "hooks", "overpower",
+    "tariff_names", "phase_keys", "batch", "state", "convert", "production", "outages",
+    "line_budget", "fault_max",
+];
+const SERIAL_KEYS: &[&str] = &[
+    "device", "speed", "parity", "reconnect", "canonical", "buffered", "hotplug", "pflags",
+    "iflags", "cflags", "lflags", "profile",
+];
+const NETWORK_KEYS: &[&str] = &[
+    "protocol", "mode", "host", "port", "url", "interval", "reconnect", "buffer", "ca", "cert",
+    "key", "multicast", "iface", "psk_identity", "psk_key",
+];
+const HOOK_KEYS: &[&str] = &["api", "verb", "args", "retry", "delay", "holdoff"];
+const REPLAY_KEYS: &[&str] = &["path", "speed", "interval", "loop"];
+const CONVERT_KEYS: &[&str] = &["A", "V", "W", "Wh", "VA", "power_factor"];
+const SENSOR_KEYS: &[&str] = &[
+    "uid", "enabled", "min_interval_ms", "delta", "delta_pct", "history", "window", "scale",
+    "offset",
+];
+
+// sensors accepting a 'sensors' tuning entry, custom labels are added at runtime
+const SENSOR_UIDS: &[&str] = &[
+    "IINST", "SINSTS", "ADPS", "ADSC", "PCOUP", "NTARF", "IRMS", "URMS", "PF", "CHARGE", "EAST",
+    "EASF", "EAIT", "IMAX", "PMAX",
+];
+
+// sensor presets, phases are carried by the same multi-phase sensors for mono and tri
+const PRESET_STANDARD: &[&str] = &[
+    "SINSTS", "IRMS", "URMS", "PF", "PCOUP", "ADSC", "NTARF", "LTARF", "NGTF", "STGE", "EAST",
+    "EASF", "EAIT", "DATE", "SMAXSN", "PJOURF1", "PPOINTE", "CHARGE",
+];
+// prosumer sensors registered by '"production":true' whatever the preset
+pub(crate) const PRODUCTION_UIDS: &[&str] = &["SINSTI", "SMAXIN", "SMAXIN-1", "EAIT", "ERQ"];
+const PRESET_HISTORIC: &[&str] = &["IINST", "ADPS", "CHARGE", "DEMAIN", "IMAX", "PMAX"];
+
+pub(crate) fn sensor_preset(name: &str) -> Result<Option<&'static [&'static str]>, AfbError> {
+    match name {
+        "standard-mono" | "standard-tri" => Ok(Some(PRESET_STANDARD)),
+        "historic" => Ok(Some(PRESET_HISTORIC)),
+        "all" => Ok(None),
+        _ => afb_error!(
+            "linky-config-fail",
+            "sensors preset:{} not in [standard-mono, standard-tri, historic, all]",
+            name
+        ),
+    }
+}
+
+pub(crate) fn preset_exposed(preset: Option<&[&str]>, uid: &str) -> bool {
+    match preset {
+        None => true,
+        Some(uids) => uids.contains(&uid),
+    }
+}
+
+// a typo in a key would silently fall back to its default, reject keys not listed for the block
+pub(crate) fn check_keys(jobj: &JsoncObj, block: &str, keys: &[&[&str]]) -> Result<(), AfbError> {
+    if !jobj.is_type(Jtype::Object) {
+        return afb_error!("linky-config-fail", "'{}' should be an object, got {}", block, jobj);
+    }
+    for entry in jobj.expand()? {
+        if !keys.iter().any(|list| list.contains(&entry.key.as_str())) {
+            let expected: Vec<&str> = keys.iter().flat_map(|list| list.iter().copied()).collect();
+            return afb_error!(
+                "linky-config-fail",
+                "unknown key '{}' in '{}' (expected {})",
+                entry.key,
+                block,
+                expected.join("|")
+            );
+        }
+    }
+    Ok(())
+}
+
+// parse sensor tuning as '{"uid":"SINSTS","min_interval_ms":5000,"delta":100,"history":300,"window":60}'
+pub(crate) fn parse_sensor(
+    jsensor: JsoncObj,
+    history: usize,
+    window: usize,
+) -> Result<SensorConfig, AfbError> {
+    check_keys(&jsensor, "sensors", &[SENSOR_KEYS])?;
+
+    let uid = if let Ok(value) = jsensor.get::<String>("uid") {
         to_static_str(value)
     } else {
-        "linky"
+        return afb_error!("linky-sensor-fail", "mandatory sensor 'uid' missing in {}", jsensor);
     };
 
-    let api = if let Ok(value) = jconf.get::<String>("api") {
+    let mut sensor = SensorConfig::new(uid);
+    sensor.history = history;
+    sensor.window = window;
+
+    // events are coalesced and pushed at most once per interval
+    if let Ok(value) = jsensor.get::<u32>("min_interval_ms") {
+        sensor.min_interval = value;
+    }
+
+    // when both are set, change should pass the highest threshold
+    if let Ok(value) = jsensor.get::<u32>("delta") {
+        sensor.delta = value;
+    }
+
+    if let Ok(value) = jsensor.get::<u32>("delta_pct") {
+        sensor.delta_pct = value;
+    }
+
+    if let Ok(value) = jsensor.get::<u32>("history") {
+        sensor.history = value as usize;
+    }
+
+    if let Ok(value) = jsensor.get::<u32>("window") {
+        sensor.window = value as usize;
+    }
+
+    if let Ok(value) = jsensor.get::<bool>("enabled") {
+        sensor.enabled = value;
+    }
+
+    // current transformer re-emitters, '"scale":2.5,"offset":-1'
+    if let Ok(value) = jsensor.get::<f64>("scale") {
+        if value == 0.0 || !value.is_finite() {
+            return afb_error!("linky-sensor-fail", "sensor:{} invalid scale:{}", uid, value);
+        }
+        sensor.scale = value;
+    }
+
+    if let Ok(value) = jsensor.get::<f64>("offset") {
+        if !value.is_finite() {
+            return afb_error!("linky-sensor-fail", "sensor:{} invalid offset:{}", uid, value);
+        }
+        sensor.offset = value;
+    }
+
+    Ok(sensor)
+}
+
+// parse custom label as '{"label":"FOO","kind":"numeric","unit":"W"}'
+fn parse_label(jlabel: JsoncObj) -> Result<&'static TicObject, AfbError> {
+    let label = if let Ok(value) = jlabel.get::<String>("label") {
         to_static_str(value)
     } else {
-        uid
+        return afb_error!("linky-label-fail", "mandatory label 'label' missing in {}", jlabel);
     };
 
-    let info = if let Ok(value) = jconf.get::<String>("info") {
-        to_static_str(value)
+    // custom labels are decoded before builtin grammar and would shadow it
+    if tic_is_builtin(label) {
+        return afb_error!("linky-label-fail", "label:{} is a builtin label", label);
+    }
+
+    let kind = if let Ok(value) = jlabel.get::<String>("kind") {
+        value
     } else {
-        ""
+        "numeric".to_string()
     };
 
-    let cycle = if let Ok(value) = jconf.get::<u32>("cycle") {
-        value
+    if kind != "numeric" {
+        return afb_error!(
+            "linky-label-fail",
+            "label:{} kind:{} only 'numeric' supported",
+            label,
+            kind
+        );
+    }
+
+    let unit = if let Ok(value) = jlabel.get::<String>("unit") {
+        match TicUnit::from_label(value.as_str()) {
+            Ok(unit) => unit,
+            Err(_) => {
+                return afb_error!("linky-label-fail", "label:{} invalid unit:{}", label, value)
+            }
+        }
     } else {
-        0
+        TicUnit::None
     };
 
-    let permision = if let Ok(value) = jconf.get::<String>("permision") {
-        AfbPermission::new(to_static_str(value))
+    let name = if let Ok(value) = jlabel.get::<String>("name") {
+        to_static_str(value)
     } else {
-        AfbPermission::new("acl:linky:client")
+        label
     };
 
-    let device = if let Ok(value) = jconf.get::<String>("device") {
+    let info = if let Ok(value) = jlabel.get::<String>("info") {
         to_static_str(value)
     } else {
-        return afb_error!(
-            "linky-config-fail",
-            "mandatory label 'device' missing",
-        )
+        "custom label"
+    };
+
+    let tic = Box::new(TicObject::new(label, name, info, unit, 1));
+    Ok(Box::leak(tic))
+}
+
+// parse price table as '{"currency":"EUR","prices":[0.2068,0.2700]}' (per kWh, tariff index 1-n)
+// Tempo contracts may use '"tempo":{"blue":[0.1296,0.1609],"white":[...],"red":[...]}' as
+// [off-peak, peak] prices, they fill tariff index 1-6
+fn parse_cost(jcost: JsoncObj) -> Result<CostConfig, AfbError> {
+    let currency = if let Ok(value) = jcost.get::<String>("currency") {
+        to_static_str(value)
+    } else {
+        "EUR"
     };
 
-    let speed = if let Ok(value) = jconf.get::<u32>("speed") {
+    let mut prices = PriceTable::new();
+    if let Ok(jprices) = jcost.get::<JsoncObj>("prices") {
+        for idx in 0..jprices.count()? {
+            if !prices.set_price(idx + 1, jprices.index::<f64>(idx)?) {
+                return afb_error!(
+                    "linky-cost-fail",
+                    "prices:{} more than {} tariff indexes",
+                    jprices,
+                    TARIFF_INDEX_MAX
+                );
+            }
+        }
+    }
+
+    if let Ok(jtempo) = jcost.get::<JsoncObj>("tempo") {
+        for colour in TEMPO_COLOURS {
+            let jcolour = match jtempo.get::<JsoncObj>(colour) {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+            if jcolour.count()? != 2 {
+                return afb_error!("linky-cost-fail", "tempo:{} should be [off-peak, peak]", colour);
+            }
+            prices.set_tempo(colour, jcolour.index::<f64>(0)?, jcolour.index::<f64>(1)?);
+        }
+    }
+
+    Ok(CostConfig { currency, prices })
+}
+
+// parse shedding advisor as '{"reserve":500,"loads":[{"uid":"evse","priority":1,"power":7400}]}'
+// loads with lowest priority are shed first, power and reserve in VA
+fn parse_shedding(jshedding: JsoncObj) -> Result<SheddingConfig, AfbError> {
+    let reserve = jshedding.get::<u32>("reserve").unwrap_or_default();
+
+    let jloads = if let Ok(value) = jshedding.get::<JsoncObj>("loads") {
         value
     } else {
-        1200
+        return afb_error!("linky-shedding-fail", "mandatory 'loads' missing in {}", jshedding);
     };
 
-    let parity = if let Ok(value) = jconf.get::<String>("parity") {
+    let mut loads = Vec::new();
+    for idx in 0..jloads.count()? {
+        let jload = jloads.index::<JsoncObj>(idx)?;
+        let uid = if let Ok(value) = jload.get::<String>("uid") {
+            value
+        } else {
+            return afb_error!("linky-shedding-fail", "mandatory load 'uid' missing in {}", jload);
+        };
+        let power = if let Ok(value) = jload.get::<u32>("power") {
+            value
+        } else {
+            return afb_error!("linky-shedding-fail", "mandatory load 'power' missing in {}", jload);
+        };
+        let priority = jload.get::<u32>("priority").unwrap_or_default();
+        loads.push(SheddingLoad {
+            uid,
+            priority,
+            power,
+        });
+    }
+
+    Ok(SheddingConfig { reserve, loads })
+}
+
+// parse EV charge limit as '{"margin":2,"min":6,"max":32,"hysteresis":2,"ramp":1}' in A and A/s
+fn parse_charge(jcharge: JsoncObj) -> Result<ChargeConfig, AfbError> {
+    let mut charge = ChargeConfig::default();
+    if let Ok(value) = jcharge.get::<u32>("margin") {
+        charge.margin = value;
+    }
+    if let Ok(value) = jcharge.get::<u32>("min") {
+        charge.min = value;
+    }
+    if let Ok(value) = jcharge.get::<u32>("max") {
+        charge.max = value;
+    }
+    if let Ok(value) = jcharge.get::<u32>("hysteresis") {
+        charge.hysteresis = value;
+    }
+    if let Ok(value) = jcharge.get::<u32>("ramp") {
+        charge.ramp = value;
+    }
+    if charge.min > charge.max {
+        return afb_error!(
+            "linky-charge-fail",
+            "charge min:{} should be <= max:{}",
+            charge.min,
+            charge.max
+        );
+    }
+    Ok(charge)
+}
+
+// parse charging profile as '{"max":7400,"reduced":1400,"price":0.20,"indexes":[2]}' power in W,
+// price threshold needs a 'cost' price table
+fn parse_charging(jcharging: JsoncObj) -> Result<ChargingConfig, AfbError> {
+    let max = jcharging.get::<u32>("max").unwrap_or_default();
+    let reduced = jcharging.get::<u32>("reduced").unwrap_or_default();
+    let price = jcharging.get::<f64>("price").ok();
+
+    let mut indexes = Vec::new();
+    if let Ok(jindexes) = jcharging.get::<JsoncObj>("indexes") {
+        for idx in 0..jindexes.count()? {
+            let index = jindexes.index::<u32>(idx)? as usize;
+            if !(1..=TARIFF_INDEX_MAX).contains(&index) {
+                return afb_error!(
+                    "linky-charging-fail",
+                    "indexes:{} should be within 1..{}",
+                    jindexes,
+                    TARIFF_INDEX_MAX
+                );
+            }
+            indexes.push(index);
+        }
+    }
+
+    Ok(ChargingConfig {
+        max,
+        reduced,
+        price,
+        indexes,
+    })
+}
+
+// parse ADPS/ADIR reaction as '{"api":"evse","verb":"imax","args":{"imax":6},"retry":3}'
+// failed calls are retried every 'delay' ms, 'holdoff' s ignore alarms repeated on next frames
+fn parse_hook(jhook: JsoncObj) -> Result<HookConfig, AfbError> {
+    check_keys(&jhook, "hooks", &[HOOK_KEYS])?;
+    let api = if let Ok(value) = jhook.get::<String>("api") {
         to_static_str(value)
     } else {
-        "even"
+        return afb_error!("linky-hook-fail", "mandatory 'api' missing in {}", jhook);
+    };
+    let verb = if let Ok(value) = jhook.get::<String>("verb") {
+        to_static_str(value)
+    } else {
+        return afb_error!("linky-hook-fail", "mandatory 'verb' missing in {}", jhook);
+    };
+    let args = if let Ok(value) = jhook.get::<JsoncObj>("args") {
+        value.to_string()
+    } else {
+        "{}".to_string()
+    };
+    let retry = jhook.get::<u32>("retry").unwrap_or(3);
+    let delay = jhook.get::<u32>("delay").unwrap_or(1000);
+    let holdoff = jhook.get::<u32>("holdoff").unwrap_or(60);
+    if retry > 0 && delay == 0 {
+        return afb_error!("linky-hook-fail", "api:{} verb:{} retry needs a delay", api, verb);
+    }
+
+    Ok(HookConfig {
+        api,
+        verb,
+        args,
+        retry,
+        delay,
+        holdoff,
+    })
+}
+
+// modbus tcp listen address, any interface on port 502 by default
+fn parse_modbus(jmodbus: JsoncObj) -> Result<(&'static str, u16), AfbError> {
+    if !cfg!(feature = "modbus") {
+        return afb_error!("linky-modbus-fail", "binding built without 'modbus' feature");
+    }
+
+    let addr = if let Ok(value) = jmodbus.get::<String>("addr") {
+        expand_env(value)?
+    } else {
+        "0.0.0.0"
+    };
+    let port = if let Ok(value) = jmodbus.get::<u32>("port") {
+        value as u16
+    } else {
+        502
+    };
+    Ok((addr, port))
+}
+
+// d-bus bus and service name, system bus by default
+#[cfg(feature = "dbus")]
+fn parse_dbus(jdbus: JsoncObj) -> Result<(bool, &'static str), AfbError> {
+    let system = if let Ok(value) = jdbus.get::<String>("bus") {
+        match value.as_str() {
+            "system" => true,
+            "session" => false,
+            _ => return afb_error!("linky-dbus-fail", "bus:{} not in [system, session]", value),
+        }
+    } else {
+        true
+    };
+    let name = if let Ok(value) = jdbus.get::<String>("name") {
+        to_static_str(value)
+    } else {
+        DBUS_NAME
     };
+    Ok((system, name))
+}
 
-    // register data converter
-    // v106::register_datatype() ?;
+#[cfg(not(feature = "dbus"))]
+fn parse_dbus(_jdbus: JsoncObj) -> Result<(bool, &'static str), AfbError> {
+    afb_error!("linky-dbus-fail", "binding built without 'dbus' feature")
+}
 
-    let config = LinkyConfig {
-        device,
-        speed,
-        parity,
-        cycle,
+// parse sqlite storage as '{"path":"/var/lib/linky/linky.db","retention":30,"interval":60}'
+// retention in days, interval in s, optional '"uids":["SINSTS","EAST"]' restricts stored sensors
+fn parse_storage(jstorage: JsoncObj) -> Result<StorageConfig, AfbError> {
+    if !cfg!(feature = "sqlite") {
+        return afb_error!("linky-storage-fail", "binding built without 'sqlite' feature");
+    }
+
+    let path = if let Ok(value) = jstorage.get::<String>("path") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-storage-fail", "mandatory 'path' missing in {}", jstorage);
     };
 
-    // create backend API
-    let api = AfbApi::new(api).set_info(info).set_permission(permision);
-    register_verbs(api, config)?;
+    let retention = jstorage.get::<u32>("retention").unwrap_or(30);
+
+    let interval = jstorage.get::<u32>("interval").unwrap_or(60);
+
+    let mut uids = Vec::new();
+    if let Ok(juids) = jstorage.get::<JsoncObj>("uids") {
+        for idx in 0..juids.count()? {
+            uids.push(juids.index::<String>(idx)?);
+        }
+    }
+
+    Ok(StorageConfig {
+        path,
+        retention,
+        interval,
+        uids,
+    })
+}
+
+// parse state file as '{"path":"/var/lib/linky/state.json","interval":300}'
+fn parse_state(jstate: JsoncObj) -> Result<StateConfig, AfbError> {
+    let path = if let Ok(value) = jstate.get::<String>("path") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-state-fail", "mandatory 'path' missing in {}", jstate);
+    };
+
+    let interval = jstate.get::<u32>("interval").unwrap_or(300);
+
+    Ok(StateConfig { path, interval })
+}
+
+// parse READ unit conversion as '{"Wh":"kWh","VA":"kVA","power_factor":0.9}'
+fn parse_convert(jconvert: JsoncObj) -> Result<UnitConvert, AfbError> {
+    check_keys(&jconvert, "convert", &[CONVERT_KEYS])?;
+
+    let power_factor = if let Ok(value) = jconvert.get::<f64>("power_factor") {
+        if value <= 0.0 || value > 1.0 {
+            return afb_error!("linky-convert-fail", "power_factor:{} should be in ]0,1]", value);
+        }
+        value
+    } else {
+        1.0
+    };
+
+    let mut convert = UnitConvert {
+        targets: &[],
+        power_factor,
+    };
+    let mut targets = Vec::new();
+    for base in CONVERT_KEYS.iter().filter(|key| **key != "power_factor") {
+        if let Ok(value) = jconvert.get::<String>(base) {
+            if convert.factor(base, value.as_str()).is_none() {
+                return afb_error!(
+                    "linky-convert-fail",
+                    "unit:{} not convertible from {}",
+                    value,
+                    base
+                );
+            }
+            targets.push((*base, to_static_str(value)));
+        }
+    }
+    convert.targets = Box::leak(targets.into_boxed_slice());
+    Ok(convert)
+}
+
+// parse csv sink as '{"path":"/var/log/linky/linky.csv","size_kb":10240,"daily":true}'
+// optional '"uids":["SINSTS","EAST"]' restricts logged sensors
+fn parse_csv(jcsv: JsoncObj) -> Result<CsvConfig, AfbError> {
+    let path = if let Ok(value) = jcsv.get::<String>("path") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-csv-fail", "mandatory 'path' missing in {}", jcsv);
+    };
+
+    let size = jcsv.get::<u32>("size_kb").unwrap_or(10240);
+
+    let daily = jcsv.get::<bool>("daily").unwrap_or(true);
+
+    let mut uids = Vec::new();
+    if let Ok(juids) = jcsv.get::<JsoncObj>("uids") {
+        for idx in 0..juids.count()? {
+            uids.push(juids.index::<String>(idx)?);
+        }
+    }
+
+    Ok(CsvConfig {
+        path,
+        size,
+        daily,
+        uids,
+    })
+}
+
+// parse influx sink as '{"url":"http://influx.local:8086","org":"home","bucket":"linky"}'
+// optional '"token"', '"measurement"', '"flush"' (ms), '"buffer"' (lines) and '"uids"' selection
+fn parse_influx(jinflux: JsoncObj) -> Result<InfluxConfig, AfbError> {
+    if !cfg!(feature = "influx") {
+        return afb_error!("linky-influx-fail", "binding built without 'influx' feature");
+    }
+
+    let url = if let Ok(value) = jinflux.get::<String>("url") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-influx-fail", "mandatory 'url' missing in {}", jinflux);
+    };
+
+    let org = if let Ok(value) = jinflux.get::<String>("org") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-influx-fail", "mandatory 'org' missing in {}", jinflux);
+    };
+
+    let bucket = if let Ok(value) = jinflux.get::<String>("bucket") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-influx-fail", "mandatory 'bucket' missing in {}", jinflux);
+    };
+
+    let token = if let Ok(value) = jinflux.get::<String>("token") {
+        expand_env(value)?
+    } else {
+        ""
+    };
+
+    let measurement = if let Ok(value) = jinflux.get::<String>("measurement") {
+        to_static_str(value)
+    } else {
+        "linky"
+    };
+
+    let flush = jinflux.get::<u32>("flush").unwrap_or(10000);
+
+    let buffer = if let Ok(value) = jinflux.get::<u32>("buffer") {
+        value as usize
+    } else {
+        10000
+    };
+
+    let mut uids = Vec::new();
+    if let Ok(juids) = jinflux.get::<JsoncObj>("uids") {
+        for idx in 0..juids.count()? {
+            uids.push(juids.index::<String>(idx)?);
+        }
+    }
+
+    Ok(InfluxConfig {
+        url,
+        org,
+        bucket,
+        token,
+        measurement,
+        flush,
+        buffer,
+        uids,
+    })
+}
+
+// parse redis sink as '{"host":"redis.local","port":6379,"stream":"linky","maxlen":10000}'
+// optional '"password"', '"flush"' (ms), '"buffer"' (entries) and '"uids"' selection
+fn parse_redis(jredis: JsoncObj) -> Result<RedisConfig, AfbError> {
+    if !cfg!(feature = "redis") {
+        return afb_error!("linky-redis-fail", "binding built without 'redis' feature");
+    }
+
+    let host = if let Ok(value) = jredis.get::<String>("host") {
+        expand_env(value)?
+    } else {
+        "127.0.0.1"
+    };
+
+    let port = if let Ok(value) = jredis.get::<u32>("port") {
+        value as u16
+    } else {
+        6379
+    };
+
+    let password = if let Ok(value) = jredis.get::<String>("password") {
+        expand_env(value)?
+    } else {
+        ""
+    };
+
+    let stream = if let Ok(value) = jredis.get::<String>("stream") {
+        to_static_str(value)
+    } else {
+        "linky"
+    };
+
+    let maxlen = if let Ok(value) = jredis.get::<u32>("maxlen") {
+        value as usize
+    } else {
+        10000
+    };
+
+    let flush = jredis.get::<u32>("flush").unwrap_or(1000);
+
+    let buffer = if let Ok(value) = jredis.get::<u32>("buffer") {
+        value as usize
+    } else {
+        10000
+    };
+
+    let mut uids = Vec::new();
+    if let Ok(juids) = jredis.get::<JsoncObj>("uids") {
+        for idx in 0..juids.count()? {
+            uids.push(juids.index::<String>(idx)?);
+        }
+    }
+
+    Ok(RedisConfig {
+        host,
+        port,
+        password,
+        stream,
+        maxlen,
+        flush,
+        buffer,
+        uids,
+    })
+}
+
+// parse sparkplug b mode as '{"host":"broker.local","port":1883,"group":"linky","node":"meter"}'
+// optional '"username"', '"password"', '"keepalive"' (s), '"interval"' (ms) and '"uids"' selection
+fn parse_sparkplug(jspb: JsoncObj) -> Result<SparkplugConfig, AfbError> {
+    if !cfg!(feature = "mqtt") {
+        return afb_error!("linky-sparkplug-fail", "binding built without 'mqtt' feature");
+    }
+
+    let host = if let Ok(value) = jspb.get::<String>("host") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-sparkplug-fail", "mandatory 'host' missing in {}", jspb);
+    };
+
+    let port = if let Ok(value) = jspb.get::<u32>("port") {
+        value as u16
+    } else {
+        1883
+    };
+
+    let username = if let Ok(value) = jspb.get::<String>("username") {
+        expand_env(value)?
+    } else {
+        ""
+    };
+
+    let password = if let Ok(value) = jspb.get::<String>("password") {
+        expand_env(value)?
+    } else {
+        ""
+    };
+
+    let group = if let Ok(value) = jspb.get::<String>("group") {
+        expand_env(value)?
+    } else {
+        "linky"
+    };
+
+    let node = if let Ok(value) = jspb.get::<String>("node") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-sparkplug-fail", "mandatory 'node' missing in {}", jspb);
+    };
+
+    // topic levels may not hold wildcards or separators
+    for label in [group, node] {
+        if label.is_empty() || label.contains(['/', '+', '#']) {
+            return afb_error!("linky-sparkplug-fail", "invalid group/node id:'{}'", label);
+        }
+    }
+
+    let keepalive = jspb.get::<u32>("keepalive").unwrap_or(30);
+
+    let interval = jspb.get::<u32>("interval").unwrap_or(1000);
+
+    let mut uids = Vec::new();
+    if let Ok(juids) = jspb.get::<JsoncObj>("uids") {
+        for idx in 0..juids.count()? {
+            uids.push(juids.index::<String>(idx)?);
+        }
+    }
+
+    Ok(SparkplugConfig {
+        host,
+        port,
+        username,
+        password,
+        group,
+        node,
+        keepalive,
+        interval,
+        uids,
+    })
+}
+
+// parse network source as '{"protocol":"tcp","host":"esp-tic.local","port":23,"reconnect":5000}'
+// "protocol":"tls" adds '"ca":"/etc/linky/ca.pem"' and optional '"cert"/"key"' client identity
+// "protocol":"ws" only requires '"url":"ws://gateway.local/tic"'
+// "protocol":"http" requires '"url"' and fetches it every '"interval"' ms (default 1000)
+// "protocol":"udp" binds 'host:port' and joins optional '"multicast":"239.0.0.42"' group on '"iface"'
+// optional '"buffer":2048' gives room for gateways pushing a whole frame per read (default 512)
+// with '"mode":"listen"' binding waits for gateway connection on 'host:port' (default 0.0.0.0)
+fn parse_network(jnet: JsoncObj) -> Result<LinkyConfig, AfbError> {
+    let protocol = if let Ok(value) = jnet.get::<String>("protocol") {
+        value
+    } else {
+        "tcp".to_string()
+    };
+
+    // delay in ms before reconnecting lost source, 0 disable reconnection
+    let reconnect = jnet.get::<u32>("reconnect").unwrap_or(5000);
+
+    // websocket and http gateways are fully defined by their url
+    if protocol == "ws" || protocol == "http" {
+        let url = if let Ok(value) = jnet.get::<String>("url") {
+            expand_env(value)?
+        } else {
+            return afb_error!("linky-config-fail", "mandatory {} 'url' missing in {}", protocol, jnet);
+        };
+
+        if protocol == "ws" {
+            return Ok(LinkyConfig::WebSocket { url, reconnect });
+        }
+
+        let interval = jnet.get::<u32>("interval").unwrap_or(1000);
+
+        if interval == 0 {
+            return afb_error!("linky-config-fail", "http 'interval' should be > 0ms");
+        }
+        return Ok(LinkyConfig::Http { url, interval });
+    }
+
+    let mode = if let Ok(value) = jnet.get::<String>("mode") {
+        value
+    } else {
+        "client".to_string()
+    };
+
+    let host = if let Ok(value) = jnet.get::<String>("host") {
+        expand_env(value)?
+    } else if mode == "listen" || protocol == "udp" {
+        "0.0.0.0"
+    } else {
+        return afb_error!("linky-config-fail", "mandatory network 'host' missing in {}", jnet);
+    };
+
+    let port = if let Ok(value) = jnet.get::<u32>("port") {
+        match u16::try_from(value) {
+            Ok(port) => port,
+            Err(_) => return afb_error!("linky-config-fail", "invalid network port:{}", value),
+        }
+    } else {
+        return afb_error!("linky-config-fail", "mandatory network 'port' missing in {}", jnet);
+    };
+
+    match (protocol.as_str(), mode.as_str()) {
+        ("tcp", "client") => Ok(LinkyConfig::Tcp {
+            host,
+            port,
+            reconnect,
+        }),
+        ("tcp", "listen") => Ok(LinkyConfig::TcpListen { addr: host, port }),
+        ("tls", "client") => {
+            let ca = if let Ok(value) = jnet.get::<String>("ca") {
+                expand_env(value)?
+            } else {
+                return afb_error!("linky-config-fail", "mandatory tls 'ca' missing in {}", jnet);
+            };
+
+            // client certificate only when gateway verifies its peers
+            let cert = match jnet.get::<String>("cert") {
+                Ok(value) => Some(expand_env(value)?),
+                Err(_) => None,
+            };
+            let key = match jnet.get::<String>("key") {
+                Ok(value) => Some(expand_env(value)?),
+                Err(_) => None,
+            };
+
+            Ok(LinkyConfig::Tls {
+                host,
+                port,
+                reconnect,
+                ca,
+                cert,
+                key,
+            })
+        }
+        ("udp", _) => {
+            let group = match jnet.get::<String>("multicast") {
+                Ok(value) => Some(expand_env(value)?),
+                Err(_) => None,
+            };
+            let iface = match jnet.get::<String>("iface") {
+                Ok(value) => Some(expand_env(value)?),
+                Err(_) => None,
+            };
+            // DTLS sessions with pre-shared key, plain datagrams are dropped
+            let psk = match (jnet.get::<String>("psk_identity"), jnet.get::<String>("psk_key")) {
+                (Ok(_), Ok(_)) if !cfg!(feature = "dtls") => {
+                    return afb_error!("linky-config-fail", "binding built without 'dtls' feature")
+                }
+                (Ok(_), Ok(_)) if group.is_some() => {
+                    return afb_error!(
+                        "linky-config-fail",
+                        "udp 'psk_identity' requires unicast, dtls does not support 'multicast'"
+                    )
+                }
+                (Ok(identity), Ok(key)) => {
+                    let identity = expand_env(identity)?;
+                    let key = expand_env(key)?;
+                    #[cfg(feature = "dtls")]
+                    DtlsPsk::new(identity, key)?;
+                    Some((identity, key))
+                }
+                (Err(_), Err(_)) => None,
+                _ => {
+                    return afb_error!(
+                        "linky-config-fail",
+                        "udp 'psk_identity' and 'psk_key' go together in {}",
+                        jnet
+                    )
+                }
+            };
+            Ok(LinkyConfig::Udp {
+                addr: host,
+                port,
+                group,
+                iface,
+                psk,
+                reconnect,
+            })
+        }
+        _ => afb_error!(
+            "linky-config-fail",
+            "network protocol:{} mode:{} not supported (tcp client|listen, tls client, ws, http, udp)",
+            protocol,
+            mode
+        ),
+    }
+}
+
+// parse capture replay as '{"path":"/var/lib/linky/capture.tic.zst","speed":10,"loop":true}'
+// gzip/zstd captures are detected from file content, '"speed":0' replays without pacing
+fn parse_replay(jreplay: JsoncObj) -> Result<LinkyConfig, AfbError> {
+    let path = if let Ok(value) = jreplay.get::<String>("path") {
+        expand_env(value)?
+    } else {
+        return afb_error!("linky-config-fail", "mandatory replay 'path' missing in {}", jreplay);
+    };
+
+    let speed = jreplay.get::<u32>("speed").unwrap_or(1);
+
+    let interval = jreplay.get::<u32>("interval").unwrap_or(100);
+
+    if interval == 0 {
+        return afb_error!("linky-config-fail", "replay 'interval' should be > 0ms");
+    }
+
+    let repeat = jreplay.get::<bool>("loop").unwrap_or_default();
+
+    Ok(LinkyConfig::Replay {
+        path,
+        speed,
+        interval,
+        repeat,
+    })
+}
+
+const SERIAL_SPEEDS: &str = "1200|9600|19200|57600|115200|auto";
+
+// common TIC adapters, installers pick a name instead of termios details
+struct SerialProfile {
+    name: &'static str,
+    device: &'static str, // '*' matches within file name, first sorted match is used
+    speed: u32,           // 0: auto, meter mode is not known from the adapter
+    parity: &'static str,
+    canonical: bool,
+    flags: &'static [(&'static str, &'static str)], // replace default tty flags when not empty
+}
+
+const SERIAL_PROFILES: &[SerialProfile] = &[
+    // Micro Teleinfo v2/v3, FTDI FT230X announcing 'TINFO' product
+    SerialProfile {
+        name: "microteleinfo-v2",
+        device: "/dev/serial/by-id/usb-*TINFO*",
+        speed: 0,
+        parity: "even",
+        canonical: true,
+        flags: &[],
+    },
+    // PiTInfo hat on Raspberry Pi primary uart, no modem lines
+    SerialProfile {
+        name: "pitinfo",
+        device: "/dev/serial0",
+        speed: 0,
+        parity: "even",
+        canonical: true,
+        flags: &[("cflags", "CS7"), ("cflags", "CLOCAL")],
+    },
+    // GCE Electronics USB teleinfo, FTDI FT232R, raw reads skip stray control bytes
+    SerialProfile {
+        name: "gce-usb-tic",
+        device: "/dev/serial/by-id/usb-*GCE*",
+        speed: 0,
+        parity: "even",
+        canonical: false,
+        flags: &[],
+    },
+];
+
+fn serial_profile(name: &str) -> Result<&'static SerialProfile, AfbError> {
+    match SERIAL_PROFILES.iter().find(|profile| profile.name == name) {
+        Some(profile) => Ok(profile),
+        None => {
+            let names: Vec<&str> = SERIAL_PROFILES.iter().map(|profile| profile.name).collect();
+            afb_error!("linky-config-fail", "serial profile:{} not in {}", name, names.join("|"))
+        }
+    }
+}
+
+// '*' within last path component only, as in '/dev/serial/by-id/usb-*TINFO*'
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return name == first;
+    }
+    if name.len() < first.len() + last.len() || !name.starts_with(first) || !name.ends_with(last) {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+// by-id names hold adapter serial number, resolve pattern to first present device
+fn device_glob(pattern: &'static str) -> Result<&'static str, AfbError> {
+    if !pattern.contains('*') {
+        return Ok(pattern);
+    }
+    let (dir, name) = match pattern.rsplit_once('/') {
+        Some((dir, name)) if !dir.contains('*') => (dir, name),
+        _ => return afb_error!("linky-config-fail", "device:{} '*' only in file name", pattern),
+    };
+    let mut found: Vec<String> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|file| wildcard_match(name, file))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    found.sort();
+    match found.first() {
+        Some(file) => Ok(to_static_str(format!("{}/{}", dir, file))),
+        None => afb_error!("linky-config-fail", "no device matching {}", pattern),
+    }
+}
+
+// parse serial source from 'serial' block or legacy root level keys
+fn parse_serial(jserial: JsoncObj) -> Result<LinkyConfig, AfbError> {
+    // '"profile":"pitinfo"' pre-fills device, speed, parity and flags, explicit keys win
+    let profile = if let Ok(value) = jserial.get::<String>("profile") {
+        Some(serial_profile(value.as_str())?)
+    } else {
+        None
+    };
+
+    let device = if let Ok(value) = jserial.get::<String>("device") {
+        expand_env(value)?
+    } else if let Some(profile) = profile {
+        profile.device
+    } else {
+        return afb_error!(
+            "linky-config-fail",
+            "mandatory label 'device' missing",
+        )
+    };
+    let device = device_glob(device)?;
+
+    // "speed":"auto" probes 1200 then 9600 before locking on working speed, higher speeds
+    // are only used by gateways re-emitting TIC frames and are never probed
+    let speed = if let Ok(value) = jserial.get::<u32>("speed") {
+        match value {
+            1200 | 9600 | 19200 | 57600 | 115200 => value,
+            _ => return afb_error!("linky-config-fail", "speed:{} not in {}", value, SERIAL_SPEEDS),
+        }
+    } else if let Ok(value) = jserial.get::<String>("speed") {
+        match value.as_str() {
+            "auto" => 0,
+            _ => return afb_error!("linky-config-fail", "speed:{} not in {}", value, SERIAL_SPEEDS),
+        }
+    } else if let Some(profile) = profile {
+        profile.speed
+    } else {
+        1200
+    };
+
+    let parity = if let Ok(value) = jserial.get::<String>("parity") {
+        match value.as_str() {
+            "even" | "odd" => to_static_str(value),
+            _ => return afb_error!("linky-config-fail", "invalid parity:{} (even|odd)", value),
+        }
+    } else if let Some(profile) = profile {
+        profile.parity
+    } else {
+        "even"
+    };
+
+    // delay in ms before reopening failing device, 0 disable reopening
+    let reconnect = jserial.get::<u32>("reconnect").unwrap_or(1000);
+
+    // "canonical":false reads raw bytes and splits lines internally (stray control bytes)
+    let canonical = if let Ok(value) = jserial.get::<bool>("canonical") {
+        value
+    } else if let Some(profile) = profile {
+        profile.canonical
+    } else {
+        true
+    };
+
+    // "buffered":true reads up to 4KB per syscall in raw mode, fast gateway feeds
+    let buffered = jserial.get::<bool>("buffered").unwrap_or_default();
+
+    // tty flags as '"cflags":["CS7","CLOCAL","PARENB","CRTSCTS"]' replace the default set,
+    // e.g. for RS485 converters or adapters requiring hardware flow control
+    let mut flags = SerialFlags::default();
+    for kind in ["pflags", "iflags", "cflags", "lflags"] {
+        if let Ok(jflags) = jserial.get::<JsoncObj>(kind) {
+            for idx in 0..jflags.count()? {
+                flags.push(kind, jflags.index::<String>(idx)?.as_str())?;
+            }
+        } else if let Some(profile) = profile {
+            for (_, label) in profile.flags.iter().filter(|(set, _)| *set == kind) {
+                flags.push(kind, label)?;
+            }
+        }
+    }
+
+    Ok(LinkyConfig::Serial {
+        device,
+        speed,
+        parity,
+        reconnect,
+        canonical,
+        buffered,
+        flags,
+    })
+}
+
+// data source is either a network gateway, a capture replay or a local serial device
+pub(crate) fn parse_source(jconf: JsoncObj) -> Result<LinkyConfig, AfbError> {
+    if let Ok(jnet) = jconf.get::<JsoncObj>("network") {
+        check_keys(&jnet, "network", &[NETWORK_KEYS])?;
+        return parse_network(jnet);
+    }
+
+    if let Ok(jreplay) = jconf.get::<JsoncObj>("replay") {
+        check_keys(&jreplay, "replay", &[REPLAY_KEYS])?;
+        return parse_replay(jreplay);
+    }
+
+    // legacy root level serial keys are checked with their meter block
+    let jserial = if let Ok(value) = jconf.get::<JsoncObj>("serial") {
+        check_keys(&value, "serial", &[SERIAL_KEYS])?;
+        value
+    } else {
+        jconf
+    };
+    parse_serial(jserial)
+}
+
+// parse one meter source and options, verbs/events are named 'prefix/NAME' when prefix is set
+fn parse_meter(jconf: JsoncObj, prefix: &'static str) -> Result<BindingConfig, AfbError> {
+    let cycle = if let Ok(value) = jconf.get::<u32>("cycle") {
+        value
+    } else {
+        0
+    };
+
+    // when set, READ responses and events carry sensor unit
+    let units = jconf.get::<bool>("units").unwrap_or_default();
+
+    // strict parser reports unknown labels on 'unknown-label' event
+    let strict = jconf.get::<bool>("strict").unwrap_or_default();
+
+    // checksum failures per minute before 'link-quality-degraded', 0 disables alarm
+    let checksum_alarm = jconf.get::<u32>("checksum_alarm").unwrap_or(10);
+
+    let jserial = if let Ok(value) = jconf.get::<JsoncObj>("serial") {
+        value
+    } else {
+        jconf.clone()
+    };
+
+    let source = parse_source(jconf.clone())?;
+
+    // read buffer in bytes, gateways sending a whole frame per datagram need more than default
+    let buffer = if let Ok(jnet) = jconf.get::<JsoncObj>("network") {
+        if let Ok(value) = jnet.get::<u32>("buffer") {
+            value as usize
+        } else {
+            RING_BUFFER_SZ
+        }
+    } else {
+        RING_BUFFER_SZ
+    };
+
+    // longest accepted line in bytes, custom labels may carry long values
+    let line_max = if let Ok(value) = jconf.get::<u32>("line_max") {
+        if value < 32 || value as usize > RING_BUFFER_MAX {
+            return afb_error!(
+                "linky-config-fail",
+                "line_max:{} should be within 32..{}",
+                value,
+                RING_BUFFER_MAX
+            );
+        }
+        value as usize
+    } else {
+        TIC_LINE_MAX
+    };
+
+    // a burst of buffered datagrams is decoded in slices, main loop runs in between
+    let line_budget = if let Ok(value) = jconf.get::<u32>("line_budget") {
+        value as usize
+    } else {
+        64
+    };
+
+    // without reconnect policy, source failures without any valid line in between
+    // before source is left failed until an admin 'reopen'
+    let fault_max = jconf.get::<u32>("fault_max").unwrap_or(3);
+
+    // optional backup source, same syntax as 'network' or 'serial' block
+    let backup = if let Ok(jbackup) = jconf.get::<JsoncObj>("backup") {
+        if jbackup.contains("device") {
+            check_keys(&jbackup, "backup", &[SERIAL_KEYS])?;
+            Some(parse_serial(jbackup)?)
+        } else {
+            check_keys(&jbackup, "backup", &[NETWORK_KEYS])?;
+            Some(parse_network(jbackup)?)
+        }
+    } else {
+        None
+    };
+
+    // primary silence in ms before switching to backup
+    let failover = jconf.get::<u32>("failover").unwrap_or(10000);
+
+    // reconnection delay doubles after each failure up to 'reconnect-max' ms
+    let backoff = jconf.get::<u32>("reconnect-max").unwrap_or(60000);
+
+    // seconds without any decoded line before 'data-stale' event, 0 disable watchdog
+    let watchdog = jconf.get::<u32>("watchdog").unwrap_or_default();
+
+    // ms between two 'health' heartbeat events, 0 disable heartbeat
+    let health = jconf.get::<u32>("health").unwrap_or(60000);
+
+    // VA kept as safety when computing 'available-power' headroom
+    let margin = jconf.get::<u32>("margin").unwrap_or_default();
+
+    // 'overload-warning' when power trend reaches cutting power within 'horizon' seconds,
+    // trend is computed over last 'samples' SINSTS values
+    let (horizon, trend) = if let Ok(joverload) = jconf.get::<JsoncObj>("overload") {
+        let horizon = joverload.get::<u32>("horizon").unwrap_or(30);
+        let samples = if let Ok(value) = joverload.get::<u32>("samples") {
+            value as usize
+        } else {
+            10
+        };
+        (horizon, samples)
+    } else {
+        (30, 10)
+    };
+
+    // % of deviation from mean phase load raising 'phase-imbalance' alarm
+    let imbalance = jconf.get::<u32>("imbalance").unwrap_or(20);
+
+    // URMS outside 'low'/'high' volts for 'duration_ms' raises 'voltage-sag|swell' events,
+    // defaults follow EN 50160 230V +/-10%
+    let mut voltage = (207, 253, 0);
+    if let Ok(jvoltage) = jconf.get::<JsoncObj>("voltage") {
+        if let Ok(value) = jvoltage.get::<u32>("low") {
+            voltage.0 = value as i32;
+        }
+        if let Ok(value) = jvoltage.get::<u32>("high") {
+            voltage.1 = value as i32;
+        }
+        if let Ok(value) = jvoltage.get::<u32>("duration_ms") {
+            voltage.2 = value;
+        }
+        if voltage.0 >= voltage.1 {
+            return afb_error!(
+                "linky-voltage-invalid",
+                "voltage low:{} should be < high:{}",
+                voltage.0,
+                voltage.1
+            );
+        }
+    }
+
+    // producing when SINSTI is above 'threshold' VA, or while EAIT moved within 'timeout' seconds
+    let mut injection = (0, 60);
+    if let Ok(jinjection) = jconf.get::<JsoncObj>("injection") {
+        if let Ok(value) = jinjection.get::<u32>("threshold") {
+            injection.0 = value;
+        }
+        if let Ok(value) = jinjection.get::<u32>("timeout") {
+            injection.1 = value;
+        }
+    }
+
+    // 'v2' replies sensor reads with a common value/unit/ts/quality envelope
+    let schema = if let Ok(value) = jconf.get::<String>("schema") {
+        match value.as_str() {
+            "v1" => ReplySchema::V1,
+            "v2" => ReplySchema::V2,
+            _ => return afb_error!("linky-schema-invalid", "schema:{} should be v1|v2", value),
+        }
+    } else {
+        ReplySchema::V1
+    };
+
+    // phase sensors reply '{"total":..,"l1":..,"l2":..,"l3":..}' instead of positional arrays
+    let phase_keys = jconf.get::<bool>("phase_keys").unwrap_or_default();
+
+    // READ replies default unit per sensor unit, explicit 'unit' query argument still wins
+    let convert = if let Ok(value) = jconf.get::<JsoncObj>("convert") {
+        parse_convert(value)?
+    } else {
+        UnitConvert::default()
+    };
+
+    // 'changes' feed pushes one event per frame with all its updates
+    let batch = jconf.get::<bool>("batch").unwrap_or_default();
+
+    // default number of samples kept per sensor for 'history' action, 0 disable history
+    let history = if let Ok(value) = jconf.get::<u32>("history") {
+        value as usize
+    } else {
+        0
+    };
+
+    // default number of received values used by sensor 'stats' action, 0 disable statistics
+    let window = if let Ok(value) = jconf.get::<u32>("window") {
+        value as usize
+    } else {
+        60
+    };
+
+    // reopen serial device when USB dongle is replugged
+    let hotplug = jserial.get::<bool>("hotplug").unwrap_or_default();
+
+    // extra labels declared at runtime
+    let mut labels = Vec::new();
+    if let Ok(jlabels) = jconf.get::<JsoncObj>("labels") {
+        for idx in 0..jlabels.count()? {
+            labels.push(parse_label(jlabels.index::<JsoncObj>(idx)?)?);
+        }
+    }
+
+    // closed periods kept by 'consumption' verb, 0 disable a period
+    let mut consumption = Vec::new();
+    let jconsumption = jconf.get::<JsoncObj>("consumption").ok();
+    for (period, default) in [
+        (EnergyPeriod::DAY, 7),
+        (EnergyPeriod::WEEK, 4),
+        (EnergyPeriod::MONTH, 12),
+    ] {
+        let size = match &jconsumption {
+            Some(jvalue) => {
+                if let Ok(value) = jvalue.get::<u32>(period.as_str()) {
+                    value as usize
+                } else {
+                    default
+                }
+            }
+            None => default,
+        };
+        if size > 0 {
+            consumption.push((period, size));
+        }
+    }
+
+    // 'power-ramp' event when SINSTS changes faster than 'threshold' VA/s over 'window' seconds
+    let mut ramp = (10, 1000);
+    if let Ok(jramp) = jconf.get::<JsoncObj>("ramp") {
+        if let Ok(value) = jramp.get::<u32>("window") {
+            ramp.0 = value;
+        }
+        if let Ok(value) = jramp.get::<u32>("threshold") {
+            ramp.1 = value;
+        }
+    }
+
+    // 'ocpp' MeterValue format and event period (s)
+    let mut ocpp = (OcppVersion::V16, 60);
+    if let Ok(jocpp) = jconf.get::<JsoncObj>("ocpp") {
+        if let Ok(value) = jocpp.get::<String>("version") {
+            ocpp.0 = match OcppVersion::from_label(value.as_str()) {
+                Some(version) => version,
+                None => {
+                    return afb_error!("linky-ocpp-fail", "version:{} not in [1.6, 2.0.1]", value)
+                }
+            };
+        }
+        if let Ok(value) = jocpp.get::<u32>("interval") {
+            ocpp.1 = value;
+        }
+    }
+
+    // 'energy' manager event period (ms), over current flag changes are pushed immediately
+    let energy = if let Ok(jenergy) = jconf.get::<JsoncObj>("energy") {
+        jenergy.get::<u32>("interval").unwrap_or(1000)
+    } else {
+        1000
+    };
+
+    // EV charging current left by household load, published as 'CHARGE' sensor
+    let charge = if let Ok(value) = jconf.get::<JsoncObj>("charge") {
+        parse_charge(value)?
+    } else {
+        ChargeConfig::default()
+    };
+
+    // next day power-limit schedule for 'charging-profile' verb
+    let charging = if let Ok(value) = jconf.get::<JsoncObj>("charging") {
+        parse_charging(value)?
+    } else {
+        ChargingConfig {
+            max: 0,
+            reduced: 0,
+            price: None,
+            indexes: Vec::new(),
+        }
+    };
+
+    // closed days kept by 'selfconso' verb
+    let selfconso = if let Ok(value) = jconf.get::<u32>("selfconso") {
+        value as usize
+    } else {
+        7
+    };
+
+    // prosumer installations get SINSTI, SMAXIN, SMAXIN-1, ERQ verbs and 'production' family
+    let production = jconf.get::<bool>("production").unwrap_or_default();
+
+    // serial link failure during meter silence is a probable outage, requires watchdog
+    let outages = if let Ok(value) = jconf.get::<u32>("outages") {
+        value as usize
+    } else {
+        10
+    };
+
+    // optional controllable loads enabling 'shedding' verb
+    let shedding = if let Ok(value) = jconf.get::<JsoncObj>("shedding") {
+        Some(parse_shedding(value)?)
+    } else {
+        None
+    };
+
+    // optional price table enabling 'cost' verb
+    let cost = if let Ok(value) = jconf.get::<JsoncObj>("cost") {
+        Some(parse_cost(value)?)
+    } else {
+        None
+    };
+
+    // NTARF period names as '["HC","HP"]' (tariff index 1-n), override calendar and LTARF names
+    let mut tariff_names = Vec::new();
+    if let Ok(jnames) = jconf.get::<JsoncObj>("tariff_names") {
+        if jnames.count()? > TARIFF_INDEX_MAX {
+            return afb_error!(
+                "linky-tariff-fail",
+                "tariff_names:{} more than {} tariff indexes",
+                jnames,
+                TARIFF_INDEX_MAX
+            );
+        }
+        for idx in 0..jnames.count()? {
+            tariff_names.push(jnames.index::<String>(idx)?);
+        }
+    }
+
+    if charging.price.is_some() && cost.is_none() {
+        return afb_error!("linky-charging-fail", "charging 'price' requires a 'cost' price table");
+    }
+
+    // optional sqlite persistence enabling 'query' verb
+    let storage = if let Ok(value) = jconf.get::<JsoncObj>("storage") {
+        Some(parse_storage(value)?)
+    } else {
+        None
+    };
+
+    // optional consumption, tariff and cost counters persistence across restarts
+    let state = if let Ok(value) = jconf.get::<JsoncObj>("state") {
+        Some(parse_state(value)?)
+    } else {
+        None
+    };
+
+    // optional csv file sink
+    let csv = if let Ok(value) = jconf.get::<JsoncObj>("csv") {
+        Some(parse_csv(value)?)
+    } else {
+        None
+    };
+
+    // optional influxdb push
+    let influx = if let Ok(value) = jconf.get::<JsoncObj>("influx") {
+        Some(parse_influx(value)?)
+    } else {
+        None
+    };
+
+    // optional redis stream push
+    let redis = if let Ok(value) = jconf.get::<JsoncObj>("redis") {
+        Some(parse_redis(value)?)
+    } else {
+        None
+    };
+
+    // optional mqtt sparkplug b edge node
+    let sparkplug = if let Ok(value) = jconf.get::<JsoncObj>("sparkplug") {
+        Some(parse_sparkplug(value)?)
+    } else {
+        None
+    };
+
+    // optional modbus tcp server as '{"addr":"0.0.0.0","port":502}'
+    let modbus = if let Ok(jmodbus) = jconf.get::<JsoncObj>("modbus") {
+        Some(parse_modbus(jmodbus)?)
+    } else {
+        None
+    };
+
+    // optional d-bus service as '{"bus":"system","name":"org.tuxevse.Linky"}'
+    let dbus = if let Ok(jdbus) = jconf.get::<JsoncObj>("dbus") {
+        Some(parse_dbus(jdbus)?)
+    } else {
+        None
+    };
+
+    // '"sensors":"standard-mono"' only registers preset sensor verbs, without further tuning
+    let mut sensors = Vec::new();
+    let mut preset = None;
+    if let Ok(value) = jconf.get::<String>("sensors") {
+        preset = sensor_preset(value.as_str())?;
+    } else if let Ok(jsensors) = jconf.get::<JsoncObj>("sensors") {
+        for idx in 0..jsensors.count()? {
+            let sensor = parse_sensor(jsensors.index::<JsoncObj>(idx)?, history, window)?;
+            let custom = labels.iter().any(|tic| tic.get_uid() == sensor.uid);
+            if !custom && !SENSOR_UIDS.contains(&sensor.uid) {
+                return afb_error!(
+                    "linky-config-fail",
+                    "sensors[{}] invalid uid:{} (expected {} or a custom label)",
+                    idx,
+                    sensor.uid,
+                    SENSOR_UIDS.join("|")
+                );
+            }
+            sensors.push(sensor);
+        }
+    }
+
+    // optional afb subcalls reacting to over current alarms
+    let mut hooks = Vec::new();
+    if let Ok(jhooks) = jconf.get::<JsoncObj>("hooks") {
+        for idx in 0..jhooks.count()? {
+            hooks.push(parse_hook(jhooks.index::<JsoncObj>(idx)?)?);
+        }
+    }
+
+    // optional synchronous call on ADPS/ADIR decode as '{"api":"evse","verb":"trip"}'
+    let overpower = if let Ok(jover) = jconf.get::<JsoncObj>("overpower") {
+        let api = if let Ok(value) = jover.get::<String>("api") {
+            to_static_str(value)
+        } else {
+            return afb_error!("linky-overpower-fail", "mandatory 'api' missing in {}", jover);
+        };
+        let verb = if let Ok(value) = jover.get::<String>("verb") {
+            to_static_str(value)
+        } else {
+            return afb_error!("linky-overpower-fail", "mandatory 'verb' missing in {}", jover);
+        };
+        Some((api, verb))
+    } else {
+        None
+    };
+
+    Ok(BindingConfig {
+        prefix,
+        source,
+        backup,
+        failover,
+        hotplug,
+        backoff,
+        cycle,
+        units,
+        strict,
+        checksum_alarm,
+        buffer,
+        line_max,
+        line_budget,
+        fault_max,
+        watchdog,
+        health,
+        margin,
+        horizon,
+        imbalance,
+        voltage,
+        injection,
+        shedding,
+        selfconso,
+        production,
+        outages,
+        ramp,
+        ocpp,
+        energy,
+        charge,
+        charging,
+        trend,
+        schema,
+        phase_keys,
+        convert,
+        batch,
+        labels,
+        sensors,
+        preset,
+        history,
+        window,
+        consumption,
+        cost,
+        tariff_names,
+        storage,
+        state,
+        csv,
+        influx,
+        redis,
+        sparkplug,
+        modbus,
+        dbus,
+        hooks,
+        overpower,
+    })
+}
+
+// Binding init callback started at binding load time before any API exist
+// -----------------------------------------
+pub fn binding_init(rootv4: AfbApiV4, jconf: JsoncObj) -> Result<&'static AfbApi, AfbError> {
+    afb_log_msg!(Info, rootv4, "config:{}", jconf);
+
+    // add binding custom converter
+    api_actions::register()?;
+    sensor_query::register()?;
+    stats_actions::register()?;
+    control_actions::register()?;
+    selfconso_actions::register()?;
+    #[cfg(feature = "sqlite")]
+    store_query::register()?;
+
+    let uid = if let Ok(value) = jconf.get::<String>("uid") {
+        to_static_str(value)
+    } else {
+        "linky"
+    };
+
+    let api = if let Ok(value) = jconf.get::<String>("api") {
+        to_static_str(value)
+    } else {
+        uid
+    };
+
+    let info = if let Ok(value) = jconf.get::<String>("info") {
+        to_static_str(value)
+    } else {
+        ""
+    };
+
+    // legacy root keys remain valid, 'permissions' object takes precedence
+    let mut read = if let Ok(value) = jconf.get::<String>("permision") {
+        to_static_str(value)
+    } else {
+        "acl:linky:client"
+    };
+
+    // operator verbs (source reopen, reset) require a dedicated permission
+    let mut admin = if let Ok(value) = jconf.get::<String>("admin") {
+        to_static_str(value)
+    } else {
+        "acl:linky:admin"
+    };
+
+    // '{"permissions":{"read":"acl:linky:client","admin":"acl:linky:admin"}}'
+    if let Ok(jacls) = jconf.get::<JsoncObj>("permissions") {
+        if let Ok(value) = jacls.get::<String>("read") {
+            read = to_static_str(value);
+        }
+        if let Ok(value) = jacls.get::<String>("admin") {
+            admin = to_static_str(value);
+        }
+    }
+
+    let acls = BindingAcls {
+        read: AfbPermission::new(read),
+        admin: AfbPermission::new(admin),
+    };
+
+    // one meter per 'meters' entry, or a single meter defined at root level
+    let mut meters = Vec::new();
+    if let Ok(jmeters) = jconf.get::<JsoncObj>("meters") {
+        check_keys(&jconf, "root", &[ROOT_KEYS])?;
+        for idx in 0..jmeters.count()? {
+            let jmeter = jmeters.index::<JsoncObj>(idx)?;
+            check_keys(&jmeter, format!("meters[{}]", idx).as_str(), &[METER_KEYS, SERIAL_KEYS])?;
+            let prefix = if let Ok(value) = jmeter.get::<String>("prefix") {
+                to_static_str(value)
+            } else {
+                to_static_str(format!("meter{}", idx + 1))
+            };
+            meters.push(parse_meter(jmeter, prefix)?);
+        }
+    } else {
+        check_keys(&jconf, "root", &[ROOT_KEYS, METER_KEYS, SERIAL_KEYS])?;
+        meters.push(parse_meter(jconf.clone(), "")?);
+    }
+
+    // register custom parser afb-v4 type within binder
+    linky::prelude::tic_register_type()?;
+
+    // create backend API
+    let api = AfbApi::new(api).set_info(info);
+    let mut shutdown = ApiShutdown::new();
+    for config in meters {
+        register_verbs(api, config, acls, &mut shutdown)?;
+    }
+    api.set_callback(Box::new(shutdown.clone()));
 
-    Ok(api.finalize()?)
+    let api = api.finalize()?;
+    shutdown.set_api(api);
+    Ok(api)
 }
 
 // register binding within libafb
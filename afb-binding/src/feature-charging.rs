@@ -0,0 +1,110 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// next day charging profile from provider calendar, event is pushed when schedule changes
+struct ChargingHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    schedule: SyncLock<Option<Vec<ChargingPeriod>>>, // last pushed schedule
+    tempo: Arc<TempoHandleCtx>,                      // tomorrow colour
+    value: SyncLock<ChargingPlanner>,
+}
+
+impl ChargingHandleCtx {
+    fn update(&self) {
+        self.last_update.set(Some(Instant::now()));
+        let schedule = self.value.lock().get_schedule();
+        if schedule.is_none() || *self.schedule.lock() == schedule {
+            return;
+        }
+        self.schedule.replace(schedule);
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build charging profile error={}", error);
+            }
+        }
+    }
+}
+
+// red Tempo tomorrow selects peak day calendar
+impl TicFeature for ChargingHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        {
+            let mut planner = self.value.lock();
+            match data {
+                TicValue::PJOURF1(value) => planner.set_profile(value.clone()),
+                TicValue::PPOINTE(value) => planner.set_peak_profile(value.clone()),
+                TicValue::PREF(value) => planner.set_subscribed(*value),
+                TicValue::STGE(_) | TicValue::DEMAIN(_) => {}
+                _ => return,
+            }
+            planner.set_peak_day(self.tempo.get_tomorrow() == TempoColour::RED);
+        }
+        self.update();
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.schedule.replace(None);
+        self.value.lock().reset();
+    }
+
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        self.value.lock().to_jsonc()
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register charging profile verb, schedule is meant for ISO 15118 / SmartCharging components
+pub(crate) fn mk_charging(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    tempo: &Arc<TempoHandleCtx>,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(config.prefix, "charging-profile"));
+
+    let charging = &config.charging;
+    let indexes = charging.indexes.clone();
+    let mut planner = ChargingPlanner::new(charging.max, charging.reduced, indexes);
+    if let (Some(price), Some(cost)) = (charging.price, &config.cost) {
+        planner.set_prices(cost.prices, price);
+    }
+
+    let ctx = Arc::new(ChargingHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        schedule: SyncLock::new(None),
+        tempo: tempo.clone(),
+        value: SyncLock::new(planner),
+    });
+
+    let info = "next day power-limit schedule (W) from provider calendar and tariffs";
+    mk_feature_verb(api, "charging-profile", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,146 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// energy withdrawn/injected per day, week and month, event is pushed when a period closes
+struct ConsumptionHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    periods: Vec<SyncLock<PeriodAggregator>>,
+}
+
+impl ConsumptionHandleCtx {
+    fn push(&self, period: EnergyPeriod, record: &PeriodRecord) {
+        let jsonc = match record.to_jsonc() {
+            Ok(value) => value,
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build consumption error={}", error);
+                return;
+            }
+        };
+        if let Err(error) = jsonc.add("period", period.as_str()) {
+            afb_log_msg!(Error, self.event, "fail to build consumption error={}", error);
+            return;
+        }
+        self.event.push(jsonc);
+    }
+
+    pub fn set_withdrawn(&self, value: u64) {
+        self.last_update.set(Some(Instant::now()));
+        for period in &self.periods {
+            period.lock().set_withdrawn(value);
+        }
+    }
+
+    pub fn set_injected(&self, value: u64) {
+        self.last_update.set(Some(Instant::now()));
+        for period in &self.periods {
+            period.lock().set_injected(value);
+        }
+    }
+
+    pub fn set_date(&self, stamp: &TimeStampData) {
+        for period in &self.periods {
+            let mut aggr = period.lock();
+            if let Some(record) = aggr.set_date(stamp) {
+                self.push(aggr.get_period(), &record);
+            }
+        }
+    }
+}
+
+// period aggregation only needs energy counters and meter local date
+impl TicFeature for ConsumptionHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::EAST(value) => self.set_withdrawn(*value),
+            TicValue::EAIT(value) => self.set_injected(*value),
+            TicValue::DATE(value) => self.set_date(value),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        for period in &self.periods {
+            period.lock().reset();
+        }
+    }
+
+    // '{"unit":"Wh","day":{"current":{...},"history":[...]},...}' oldest history first
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", TicUnit::WattHour.as_str())?;
+        for period in &self.periods {
+            let aggr = period.lock();
+            let jperiod = JsoncObj::new();
+            if let Some(current) = aggr.get_current() {
+                jperiod.add("current", current.to_jsonc()?)?;
+            }
+            let jhistory = JsoncObj::array();
+            for (idx, record) in aggr.get_history().iter().enumerate() {
+                jhistory.insert(idx, record.to_jsonc()?)?;
+            }
+            jperiod.add("history", jhistory)?;
+            jsonc.add(aggr.get_period().as_str(), jperiod)?;
+        }
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+
+    fn get_state(&self, state: &mut LinkyState) {
+        state.periods = self.periods.iter().map(|period| period.lock().get_state()).collect();
+    }
+
+    // periods are matched by config position, a changed consumption config drops them
+    fn set_state(&self, state: &LinkyState) {
+        for (idx, period) in self.periods.iter().enumerate() {
+            if let Some(saved) = state.periods.get(idx) {
+                period.lock().set_state(saved);
+            }
+        }
+    }
+}
+
+// register daily/weekly/monthly energy verb, periods roll on meter DATE local midnight
+pub(crate) fn mk_consumption(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(config.prefix, "consumption"));
+    let ctx = Arc::new(ConsumptionHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        periods: config
+            .consumption
+            .iter()
+            .map(|(period, size)| SyncLock::new(PeriodAggregator::new(*period, *size)))
+            .collect(),
+    });
+
+    let info = "energy withdrawn and injected per day, week and month";
+    mk_feature_verb(api, "consumption", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,120 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// running cost from tariff accumulation, event is pushed when a day closes
+struct CostHandleCtx {
+    event: &'static AfbEvent,
+    currency: &'static str,
+    tariffs: Arc<TariffHandleCtx>,
+    value: SyncLock<CostEstimator>,
+}
+
+impl CostHandleCtx {
+    // '{"currency":"EUR","day":1.25}' plus '"month"' when month closes
+    fn push(&self, rollover: &CostRollover) -> Result<(), AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("currency", self.currency)?;
+        jsonc.add("day", rollover.day)?;
+        if let Some(month) = rollover.month {
+            jsonc.add("month", month)?;
+        }
+        self.event.push(jsonc);
+        Ok(())
+    }
+
+    pub fn set_date(&self, stamp: &TimeStampData) {
+        let rollover = {
+            let mut cost = self.value.lock();
+            cost.set_totals(&self.tariffs.get_total());
+            cost.set_date(stamp)
+        };
+        if let Some(rollover) = rollover {
+            if let Err(error) = self.push(&rollover) {
+                afb_log_msg!(Error, self.event, "fail to build cost error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for CostHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        if let TicValue::DATE(value) = data {
+            self.set_date(value);
+        }
+    }
+
+    fn reset(&self) {
+        self.value.lock().reset();
+    }
+
+    // '{"currency":"EUR","today":1.25,"month":32.5,"prices":[...]}' 0 for unpriced index
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let mut cost = self.value.lock();
+        cost.set_totals(&self.tariffs.get_total());
+
+        let jsonc = JsoncObj::new();
+        jsonc.add("currency", self.currency)?;
+        jsonc.add("today", cost.get_today())?;
+        jsonc.add("month", cost.get_month())?;
+        let jprices = JsoncObj::array();
+        for idx in 0..TARIFF_INDEX_MAX {
+            jprices.insert(idx, cost.get_prices().get_price(idx + 1).unwrap_or(0.0))?;
+        }
+        jsonc.add("prices", jprices)?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.tariffs.get_last_update())
+    }
+
+    fn get_state(&self, state: &mut LinkyState) {
+        state.cost = Some(self.value.lock().get_state());
+    }
+
+    fn set_state(&self, state: &LinkyState) {
+        if let Some(cost) = &state.cost {
+            self.value.lock().set_state(cost);
+        }
+    }
+}
+
+// register running cost verb, only when config provides a price table
+pub(crate) fn mk_cost(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    config: &CostConfig,
+    tariffs: &Arc<TariffHandleCtx>,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "cost"));
+    let ctx = Arc::new(CostHandleCtx {
+        event,
+        currency: config.currency,
+        tariffs: tariffs.clone(),
+        value: SyncLock::new(CostEstimator::new(config.prices)),
+    });
+
+    let info = "estimated energy cost for today and current month";
+    mk_feature_verb(api, "cost", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,124 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// historic EJP notice from PEJP and mobile peak from PTEC, event is pushed on state change.
+// PEJP is only sent during the 30 min notice, a PTEC without preceding PEJP closes it.
+struct EjpHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    notice: SyncCell<Option<i32>>, // notice duration (min) while active
+    seen: SyncCell<bool>,          // PEJP received since last PTEC
+    peak: SyncCell<bool>,
+}
+
+impl EjpHandleCtx {
+    fn push(&self) {
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build ejp error={}", error);
+            }
+        }
+    }
+
+    pub fn set_notice(&self, minutes: i32) {
+        self.last_update.set(Some(Instant::now()));
+        self.seen.set(true);
+        if self.notice.get().is_some() {
+            return;
+        }
+        afb_log_msg!(Notice, self.event, "ejp mobile peak in {} min", minutes);
+        self.notice.set(Some(minutes));
+        self.push();
+    }
+
+    pub fn set_period(&self, peak: bool) {
+        self.last_update.set(Some(Instant::now()));
+        let ended = !self.seen.get() && self.notice.get().is_some();
+        self.seen.set(false);
+        if ended {
+            self.notice.set(None);
+        }
+        if ended || peak != self.peak.get() {
+            self.peak.set(peak);
+            self.push();
+        }
+    }
+}
+
+// historic meters split colours over two labels
+impl TicFeature for EjpHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::PEJP(value) => self.set_notice(*value),
+            // EJP periods are 'HN..' normal hours and 'PM..' mobile peak
+            TicValue::PTEC(value) if value.starts_with("HN") || value.starts_with("PM") => {
+                self.set_period(value.starts_with("PM"))
+            }
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.notice.set(None);
+        self.seen.set(false);
+        self.peak.set(false);
+    }
+
+    // '{"notice":true,"minutes":30,"peak":false}'
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("notice", self.notice.get().is_some())?;
+        if let Some(minutes) = self.notice.get() {
+            jsonc.add("minutes", minutes)?;
+        }
+        jsonc.add("peak", self.peak.get())?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register historic EJP notice verb
+pub(crate) fn mk_ejp(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "ejp"));
+    let ctx = Arc::new(EjpHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        notice: SyncCell::new(None),
+        seen: SyncCell::new(false),
+        peak: SyncCell::new(false),
+    });
+
+    let info = "EJP mobile peak notice (PEJP) and current peak period";
+    mk_feature_verb(api, "ejp", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,142 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// over current flag is kept while meter keeps sending ADPS/ADIR
+const EVSE_ALARM_HOLD: Duration = Duration::from_secs(10);
+
+// tux-evse energy manager stream, pushed once per interval or when an over current flag changes
+pub(crate) struct EvseHandleCtx {
+    event: &'static AfbEvent,
+    interval: Duration,
+    pushed: SyncCell<Option<Instant>>,
+    last_update: SyncCell<Option<Instant>>,
+    alarms: SyncCell<[Option<Instant>; 3]>, // last ADPS/ADIR per phase
+    value: SyncLock<EvseEnergy>,
+}
+
+impl EvseHandleCtx {
+    pub(crate) fn get_last_update(&self) -> Option<Instant> {
+        self.last_update.get()
+    }
+
+    fn set_over_current(&self, phase: usize) {
+        let mut alarms = self.alarms.get();
+        alarms[phase - 1] = Some(Instant::now());
+        self.alarms.set(alarms);
+        let changed = self.value.lock().set_over_current(phase, true);
+        self.updated(changed);
+    }
+
+    // ADPS/ADIR are only sent while over current, flags clear once they stopped for a while
+    fn updated(&self, changed: bool) {
+        self.last_update.set(Some(Instant::now()));
+        let mut changed = changed;
+        let mut alarms = self.alarms.get();
+        for (idx, alarm) in alarms.iter_mut().enumerate() {
+            if matches!(alarm, Some(stamp) if stamp.elapsed() >= EVSE_ALARM_HOLD) {
+                *alarm = None;
+                changed |= self.value.lock().set_over_current(idx + 1, false);
+            }
+        }
+        self.alarms.set(alarms);
+
+        if let Some(stamp) = self.pushed.get() {
+            if !changed && stamp.elapsed() < self.interval {
+                return;
+            }
+        }
+        self.pushed.set(Some(Instant::now()));
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build energy error={}", error)
+            }
+        }
+    }
+}
+
+// ADPS is the single phase over current, three phase meters send ADIR1-3
+impl TicFeature for EvseHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::ADPS(_) | TicValue::ADIR1(_) => return self.set_over_current(1),
+            TicValue::ADIR2(_) => return self.set_over_current(2),
+            TicValue::ADIR3(_) => return self.set_over_current(3),
+            _ => {}
+        }
+        {
+            let mut energy = self.value.lock();
+            match data {
+                TicValue::URMS1(value) => energy.set_tension(1, *value),
+                TicValue::URMS2(value) => energy.set_tension(2, *value),
+                TicValue::URMS3(value) => energy.set_tension(3, *value),
+                TicValue::IINST(value) | TicValue::IINST1(value) | TicValue::IRMS1(value) => {
+                    energy.set_current(1, *value)
+                }
+                TicValue::IINST2(value) | TicValue::IRMS2(value) => energy.set_current(2, *value),
+                TicValue::IINST3(value) | TicValue::IRMS3(value) => energy.set_current(3, *value),
+                TicValue::SINSTS(value) => energy.set_power(*value),
+                TicValue::PREF(value) => energy.set_subscribed(*value),
+                _ => return,
+            }
+        }
+        self.updated(false);
+    }
+
+    fn reset(&self) {
+        self.pushed.set(None);
+        self.last_update.set(None);
+        self.alarms.set([None; 3]);
+        self.value.lock().reset();
+    }
+
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        self.value.lock().to_jsonc()
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register tux-evse energy manager verb, charging manager consumes it without translation
+pub(crate) fn mk_evse(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<EvseHandleCtx>, AfbError> {
+    let event = AfbEvent::new(mk_name(config.prefix, "energy"));
+    let ctx = Arc::new(EvseHandleCtx {
+        event,
+        interval: Duration::from_millis(config.energy as u64),
+        pushed: SyncCell::new(None),
+        last_update: SyncCell::new(None),
+        alarms: SyncCell::new([None; 3]),
+        value: SyncLock::new(EvseEnergy::new()),
+    });
+
+    let info = "energy manager view: tension, current per phase, subscription, over current";
+    mk_feature_verb(api, "energy", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
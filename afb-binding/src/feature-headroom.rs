@@ -0,0 +1,130 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// power headroom before cutting, event is pushed once per frame when it changes
+pub(crate) struct HeadroomHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    pushed: SyncCell<[Option<i32>; 4]>,
+    value: SyncLock<PowerHeadroom>,
+}
+
+impl HeadroomHandleCtx {
+    // cutting power, none until PCOUP or PREF is received
+    pub(crate) fn get_limit(&self) -> Option<i32> {
+        self.value.lock().get_limit()
+    }
+
+    // total (0) or per phase (1-3) power left before cutting
+    pub(crate) fn get_available(&self, idx: usize) -> Option<i32> {
+        self.value.lock().get_available(idx)
+    }
+
+    // SINSTS comes before per phase values, push after last phase of the frame
+    pub fn set_power(&self, idx: usize, value: i32) {
+        self.last_update.set(Some(Instant::now()));
+        let available = {
+            let mut headroom = self.value.lock();
+            headroom.set_power(idx, value);
+            if idx != headroom.get_phases() {
+                return;
+            }
+            [0, 1, 2, 3].map(|idx| headroom.get_available(idx))
+        };
+        if available[0].is_none() || available == self.pushed.get() {
+            return;
+        }
+        self.pushed.set(available);
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build headroom error={}", error);
+            }
+        }
+    }
+}
+
+// headroom is computed from power limits and instant power
+impl TicFeature for HeadroomHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::PCOUP(value) => self.value.lock().set_cutting(*value),
+            TicValue::PREF(value) => self.value.lock().set_reference(*value),
+            TicValue::SINSTS(value) => self.set_power(0, *value),
+            TicValue::SINSTS1(value) => self.set_power(1, *value),
+            TicValue::SINSTS2(value) => self.set_power(2, *value),
+            TicValue::SINSTS3(value) => self.set_power(3, *value),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.pushed.set([None; 4]);
+        self.value.lock().reset();
+    }
+
+    // '{"unit":"VA","limit":12000,"margin":600,"available":[7400,...]}' total then per phase
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let headroom = self.value.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", TicUnit::VoltAmpere.as_str())?;
+        if let Some(limit) = headroom.get_limit() {
+            jsonc.add("limit", limit)?;
+        }
+        jsonc.add("margin", headroom.get_margin())?;
+        let javailable = JsoncObj::array();
+        for idx in 0..=headroom.get_phases() {
+            if let Some(value) = headroom.get_available(idx) {
+                javailable.insert(idx, value)?;
+            }
+        }
+        jsonc.add("available", javailable)?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register grid headroom verb, power available before cutting minus safety margin
+pub(crate) fn mk_headroom(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    margin: u32,
+) -> Result<Arc<HeadroomHandleCtx>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "available-power"));
+    let ctx = Arc::new(HeadroomHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        pushed: SyncCell::new([None; 4]),
+        value: SyncLock::new(PowerHeadroom::new(margin)),
+    });
+
+    let info = "power available before cutting (VA) total and per phase";
+    mk_feature_verb(api, "available-power", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,222 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+// ADPS/ADIR reaction hooks, every subcall outcome is pushed on event
+struct HooksHandleCtx {
+    event: &'static AfbEvent,
+    handle: Weak<HooksHandleCtx>, // self, upgraded for calls kept by retry timers
+    api: SyncCell<Option<&'static AfbApi>>, // set once api is finalized
+    hooks: Vec<HookConfig>,
+    fired: SyncLock<Vec<Option<Instant>>>, // last alarm per hook, holdoff reference
+    status: SyncLock<Vec<Option<(bool, u32)>>>, // last outcome per hook (success, attempts)
+}
+
+// pending hook call, cloned into retry timer context
+#[derive(Clone)]
+struct HookCallCtx {
+    handle: Arc<HooksHandleCtx>,
+    idx: usize,
+    phase: usize, // 0: ADPS, 1-3: ADIR1-3
+    current: i32, // over current (A)
+    attempt: u32,
+}
+
+impl HooksHandleCtx {
+    // ADPS/ADIR repeat on every frame while over current, hooks fire once per holdoff
+    fn alarm(&self, phase: usize, current: i32) {
+        for (idx, hook) in self.hooks.iter().enumerate() {
+            let holdoff = Duration::from_secs(hook.holdoff as u64);
+            {
+                let mut fired = self.fired.lock();
+                if matches!(fired[idx], Some(stamp) if stamp.elapsed() < holdoff) {
+                    continue;
+                }
+                fired[idx] = Some(Instant::now());
+            }
+
+            let handle = match self.handle.upgrade() {
+                Some(value) => value,
+                None => return,
+            };
+            let call = HookCallCtx {
+                handle,
+                idx,
+                phase,
+                current,
+                attempt: 1,
+            };
+            if let Err(error) = hook_call(call) {
+                afb_log_msg!(Error, self.event, "hook:{}/{} error={}", hook.api, hook.verb, error);
+            }
+        }
+    }
+
+    // '{"api":"evse","verb":"imax","status":"failure","attempt":4,"phase":0,"current":12}'
+    fn call_jsonc(
+        &self,
+        call: &HookCallCtx,
+        success: bool,
+        error: Option<&AfbError>,
+    ) -> Result<JsoncObj, AfbError> {
+        let hook = &self.hooks[call.idx];
+        let jsonc = JsoncObj::new();
+        jsonc.add("api", hook.api)?;
+        jsonc.add("verb", hook.verb)?;
+        jsonc.add("status", if success { "success" } else { "failure" })?;
+        jsonc.add("attempt", call.attempt)?;
+        jsonc.add("phase", call.phase as u32)?;
+        jsonc.add("current", call.current)?;
+        if let Some(error) = error {
+            jsonc.add("error", error.to_string())?;
+        }
+        Ok(jsonc)
+    }
+
+    fn push(&self, call: &HookCallCtx, success: bool, error: Option<&AfbError>) {
+        self.status.lock()[call.idx] = Some((success, call.attempt));
+        match self.call_jsonc(call, success, error) {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build hook status error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for HooksHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        let (phase, current) = match data {
+            TicValue::ADPS(value) => (0, *value),
+            TicValue::ADIR1(value) => (1, *value),
+            TicValue::ADIR2(value) => (2, *value),
+            TicValue::ADIR3(value) => (3, *value),
+            _ => return,
+        };
+        if current > 0 {
+            self.alarm(phase, current);
+        }
+    }
+
+    // last outcome tells what was already called, it survives a sensors reset
+    fn reset(&self) {}
+
+    // '[{"api":"evse","verb":"imax","status":"success","attempts":1}]' status idle until fired
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let status = self.status.lock();
+        let jsonc = JsoncObj::array();
+        for (idx, hook) in self.hooks.iter().enumerate() {
+            let jhook = JsoncObj::new();
+            jhook.add("api", hook.api)?;
+            jhook.add("verb", hook.verb)?;
+            match status[idx] {
+                None => {
+                    jhook.add("status", "idle")?;
+                }
+                Some((success, attempts)) => {
+                    jhook.add("status", if success { "success" } else { "failure" })?;
+                    jhook.add("attempts", attempts)?;
+                }
+            }
+            jsonc.insert(idx, jhook)?;
+        }
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn set_api(&self, api: &'static AfbApi) {
+        self.api.set(Some(api));
+    }
+}
+
+// failed subcalls are retried from a one shot timer, final outcome is pushed on event
+fn hook_call(call: HookCallCtx) -> Result<(), AfbError> {
+    let ctx = &call.handle;
+    let hook = &ctx.hooks[call.idx];
+    let status = match ctx.api.get() {
+        None => afb_error!("linky-hook-fail", "api not ready"),
+        Some(api) => {
+            let args = JsoncObj::parse(hook.args.as_str())?;
+            AfbSubCall::call_sync(api, hook.api, hook.verb, args)
+        }
+    };
+
+    match status {
+        Ok(_) => {
+            afb_log_msg!(Notice, ctx.event, "hook:{}/{} done", hook.api, hook.verb);
+            ctx.push(&call, true, None);
+        }
+        Err(error) if call.attempt <= hook.retry => {
+            afb_log_msg!(
+                Warning,
+                ctx.event,
+                "hook:{}/{} attempt:{} fail {}, retry in {}ms",
+                hook.api,
+                hook.verb,
+                call.attempt,
+                error,
+                hook.delay
+            );
+            AfbTimer::new("linky-hook")
+                .set_period(hook.delay)
+                .set_decount(1)
+                .set_callback(hook_retry_cb)
+                .set_context(HookCallCtx {
+                    attempt: call.attempt + 1,
+                    ..call.clone()
+                })
+                .start()?;
+        }
+        Err(error) => {
+            afb_log_msg!(Error, ctx.event, "hook:{}/{} fail {}", hook.api, hook.verb, error);
+            ctx.push(&call, false, Some(&error));
+        }
+    }
+    Ok(())
+}
+
+fn hook_retry_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let call = ctx.get_ref::<HookCallCtx>()?;
+    hook_call(call.clone())
+}
+
+// register ADPS/ADIR hooks verb, event reports every subcall outcome
+pub(crate) fn mk_hooks(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    hooks: &[HookConfig],
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "adps-hooks"));
+    let ctx = Arc::new_cyclic(|handle| HooksHandleCtx {
+        event,
+        handle: handle.clone(),
+        api: SyncCell::new(None),
+        hooks: hooks.to_vec(),
+        fired: SyncLock::new(vec![None; hooks.len()]),
+        status: SyncLock::new(vec![None; hooks.len()]),
+    });
+
+    let info = "over current reaction hooks and their last subcall status";
+    mk_feature_verb(api, "adps-hooks", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
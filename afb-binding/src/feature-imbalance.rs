@@ -0,0 +1,111 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// three phase load imbalance, event is pushed when alarm threshold is crossed
+struct ImbalanceHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<PhaseImbalance>,
+}
+
+impl ImbalanceHandleCtx {
+    // phase 3 closes the frame, alarm is evaluated once per frame
+    fn set_phase(&self, phase: usize, value: i32) {
+        self.last_update.set(Some(Instant::now()));
+        let changed = {
+            let mut imbalance = self.value.lock();
+            imbalance.set_phase(phase, value);
+            if phase != 3 {
+                return;
+            }
+            imbalance.update()
+        };
+        if changed.is_none() {
+            return;
+        }
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build imbalance error={}", error);
+            }
+        }
+    }
+}
+
+// historic meters send per phase current, standard ones per phase power
+impl TicFeature for ImbalanceHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::IINST1(value) | TicValue::SINSTS1(value) => self.set_phase(1, *value),
+            TicValue::IINST2(value) | TicValue::SINSTS2(value) => self.set_phase(2, *value),
+            TicValue::IINST3(value) | TicValue::SINSTS3(value) => self.set_phase(3, *value),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.value.lock().reset();
+    }
+
+    // '{"imbalance":30,"threshold":20,"alarm":true,"phases":[10,7,13]}' imbalance in %
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let imbalance = self.value.lock();
+        let jsonc = JsoncObj::new();
+        if let Some(value) = imbalance.get_imbalance() {
+            jsonc.add("imbalance", value)?;
+        }
+        jsonc.add("threshold", imbalance.get_threshold())?;
+        jsonc.add("alarm", imbalance.is_alarm())?;
+        let jphases = JsoncObj::array();
+        for (idx, value) in imbalance.get_values().iter().enumerate() {
+            jphases.insert(idx, value.unwrap_or(0))?;
+        }
+        jsonc.add("phases", jphases)?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register three phase imbalance verb, alarm helps re-balancing before ADIR trips
+pub(crate) fn mk_imbalance(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    threshold: u32,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "phase-imbalance"));
+    let ctx = Arc::new(ImbalanceHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(PhaseImbalance::new(threshold)),
+    });
+
+    let info = "three phase load imbalance (%) and alarm state";
+    mk_feature_verb(api, "phase-imbalance", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
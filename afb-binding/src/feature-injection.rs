@@ -0,0 +1,113 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// grid export state, event is pushed when site starts or stops producing
+struct InjectionHandleCtx {
+    event: &'static AfbEvent,
+    started: Instant,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<InjectionDetector>,
+}
+
+impl InjectionHandleCtx {
+    fn state_str(producing: Option<bool>) -> &'static str {
+        match producing {
+            Some(true) => "producing",
+            Some(false) => "not-producing",
+            None => "unknown",
+        }
+    }
+
+    fn push(&self, producing: bool) {
+        afb_log_msg!(
+            Notice,
+            self.event,
+            "injection {}",
+            InjectionHandleCtx::state_str(Some(producing))
+        );
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build injection error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for InjectionHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        let state = match data {
+            TicValue::SINSTI(value) => self.value.lock().set_power(*value),
+            TicValue::EAIT(value) => {
+                let at = self.started.elapsed().as_secs_f64();
+                self.value.lock().set_energy(at, *value)
+            }
+            _ => return,
+        };
+        self.last_update.set(Some(Instant::now()));
+        if let Some(producing) = state {
+            self.push(producing);
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.value.lock().reset();
+    }
+
+    // '{"state":"producing","power":850}' power only on meters sending SINSTI
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let detector = self.value.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("state", InjectionHandleCtx::state_str(detector.is_producing()))?;
+        if let Some(power) = detector.get_power() {
+            jsonc.add("power", power)?;
+        }
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register grid export state verb for solar aware consumers
+pub(crate) fn mk_injection(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let (threshold, timeout) = config.injection;
+    let event = AfbEvent::new(mk_name(config.prefix, "injection"));
+    let ctx = Arc::new(InjectionHandleCtx {
+        event,
+        started: Instant::now(),
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(InjectionDetector::new(threshold, timeout as f64)),
+    });
+
+    let info = "grid export state (producing|not-producing) from SINSTI or EAIT";
+    mk_feature_verb(api, "injection", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// OCPP MeterValue from energy, power and currents, event is pushed once per interval
+struct OcppHandleCtx {
+    event: &'static AfbEvent,
+    version: OcppVersion,
+    interval: Duration,
+    pushed: SyncCell<Option<Instant>>,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<OcppMeter>,
+}
+
+impl OcppHandleCtx {
+    fn updated(&self) {
+        self.last_update.set(Some(Instant::now()));
+        if let Some(stamp) = self.pushed.get() {
+            if stamp.elapsed() < self.interval {
+                return;
+            }
+        }
+        self.pushed.set(Some(Instant::now()));
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build meter value error={}", error)
+            }
+        }
+    }
+}
+
+// historic meters give per phase current with IINST, standard ones with IRMS
+impl TicFeature for OcppHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        {
+            let mut meter = self.value.lock();
+            match data {
+                TicValue::EAST(value) => meter.set_energy(*value),
+                TicValue::SINSTS(value) => meter.set_power(*value),
+                TicValue::IINST(value) | TicValue::IINST1(value) | TicValue::IRMS1(value) => {
+                    meter.set_current(1, *value)
+                }
+                TicValue::IINST2(value) | TicValue::IRMS2(value) => meter.set_current(2, *value),
+                TicValue::IINST3(value) | TicValue::IRMS3(value) => meter.set_current(3, *value),
+                _ => return,
+            }
+        }
+        self.updated();
+    }
+
+    fn reset(&self) {
+        self.pushed.set(None);
+        self.last_update.set(None);
+        self.value.lock().reset();
+    }
+
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let timestamp = epoch_rfc3339(epoch_ms());
+        self.value.lock().to_jsonc(self.version, timestamp.as_str())
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register OCPP MeterValues verb, ready to be forwarded by an OCPP binding
+pub(crate) fn mk_ocpp(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let (version, interval) = config.ocpp;
+    let event = AfbEvent::new(mk_name(config.prefix, "ocpp"));
+    let ctx = Arc::new(OcppHandleCtx {
+        event,
+        version,
+        interval: Duration::from_secs(interval as u64),
+        pushed: SyncCell::new(None),
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(OcppMeter::new()),
+    });
+
+    let info = "OCPP MeterValue with energy, power and per phase current";
+    mk_feature_verb(api, "ocpp", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
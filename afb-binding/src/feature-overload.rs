@@ -0,0 +1,109 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// power trend against cutting power, event is pushed when warning is raised or cleared
+struct OverloadHandleCtx {
+    event: &'static AfbEvent,
+    started: Instant,
+    headroom: Arc<HeadroomHandleCtx>, // cutting power limit
+    value: SyncLock<OverloadPredictor>,
+}
+
+impl OverloadHandleCtx {
+    fn push(&self, event: OverloadEvent, power: i32, limit: i32) -> Result<(), AfbError> {
+        let jsonc = JsoncObj::new();
+        match event {
+            OverloadEvent::WARNING(seconds) => {
+                jsonc.add("warning", true)?;
+                jsonc.add("seconds", seconds)?;
+            }
+            OverloadEvent::CLEARED => {
+                jsonc.add("warning", false)?;
+            }
+        }
+        jsonc.add("power", power)?;
+        jsonc.add("limit", limit)?;
+        self.event.push(jsonc);
+        Ok(())
+    }
+
+    // prediction starts once cutting power is known
+    fn set_power(&self, power: i32) {
+        let limit = match self.headroom.get_limit() {
+            Some(value) => value,
+            None => return,
+        };
+        let at = self.started.elapsed().as_secs_f64();
+        let event = self.value.lock().push(at, power, limit);
+        if let Some(event) = event {
+            if let Err(error) = self.push(event, power, limit) {
+                afb_log_msg!(Error, self.event, "fail to build overload error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for OverloadHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        if let TicValue::SINSTS(value) = data {
+            self.set_power(*value);
+        }
+    }
+
+    fn reset(&self) {
+        self.value.lock().reset();
+    }
+
+    // '{"warning":true,"seconds":12.5,"slope":85.2}' slope in VA/s
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let predictor = self.value.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("warning", predictor.get_warning().is_some())?;
+        if let Some(seconds) = predictor.get_warning() {
+            jsonc.add("seconds", seconds)?;
+        }
+        if let Some(slope) = predictor.get_slope() {
+            jsonc.add("slope", slope)?;
+        }
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+}
+
+// register overload prediction verb, warns before ADPS fires
+pub(crate) fn mk_overload(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    headroom: &Arc<HeadroomHandleCtx>,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(config.prefix, "overload-warning"));
+    let ctx = Arc::new(OverloadHandleCtx {
+        event,
+        started: Instant::now(),
+        headroom: headroom.clone(),
+        value: SyncLock::new(OverloadPredictor::new(config.horizon, config.trend)),
+    });
+
+    let info = "estimated seconds before instant power reaches cutting power";
+    mk_feature_verb(api, "overload-warning", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,122 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// overpower phase 0 is ADPS, 1-3 ADIR1-3
+const OVERPOWER_LABELS: [&str; 4] = ["ADPS", "ADIR1", "ADIR2", "ADIR3"];
+
+// over power fast path, event is pushed on every ADPS/ADIR decode without cycle/delta filter
+struct OverpowerHandleCtx {
+    event: &'static AfbEvent,
+    callback: Option<(&'static str, &'static str)>, // api/verb called synchronously
+    api: SyncCell<Option<&'static AfbApi>>,         // set once api is finalized
+    last_update: SyncCell<Option<Instant>>,
+    last: SyncCell<Option<(usize, i32)>>, // phase (0: ADPS, 1-3: ADIR1-3), current (A)
+}
+
+impl OverpowerHandleCtx {
+    // '{"label":"ADIR2","phase":2,"current":12}' current in A
+    fn alarm_jsonc(phase: usize, current: i32) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("label", OVERPOWER_LABELS[phase])?;
+        jsonc.add("phase", phase as u32)?;
+        jsonc.add("current", current)?;
+        Ok(jsonc)
+    }
+
+    fn alarm(&self, phase: usize, current: i32) {
+        self.last_update.set(Some(Instant::now()));
+        self.last.set(Some((phase, current)));
+        let jsonc = match OverpowerHandleCtx::alarm_jsonc(phase, current) {
+            Ok(value) => value,
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build overpower error={}", error);
+                return;
+            }
+        };
+        self.event.push(jsonc.clone());
+
+        let (apiname, verbname) = match self.callback {
+            Some(value) => value,
+            None => return,
+        };
+        let status = match self.api.get() {
+            None => afb_error!("linky-overpower-fail", "api not ready"),
+            Some(api) => AfbSubCall::call_sync(api, apiname, verbname, jsonc),
+        };
+        if let Err(error) = status {
+            afb_log_msg!(Error, self.event, "overpower:{}/{} fail {}", apiname, verbname, error);
+        }
+    }
+}
+
+impl TicFeature for OverpowerHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        let (phase, current) = match data {
+            TicValue::ADPS(value) => (0, *value),
+            TicValue::ADIR1(value) => (1, *value),
+            TicValue::ADIR2(value) => (2, *value),
+            TicValue::ADIR3(value) => (3, *value),
+            _ => return,
+        };
+        self.alarm(phase, current);
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.last.set(None);
+    }
+
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        match self.last.get() {
+            Some((phase, current)) => OverpowerHandleCtx::alarm_jsonc(phase, current),
+            None => Ok(JsoncObj::new()),
+        }
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+
+    fn set_api(&self, api: &'static AfbApi) {
+        self.api.set(Some(api));
+    }
+}
+
+// register over power fast path verb, read returns last decoded alarm
+pub(crate) fn mk_overpower(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(config.prefix, "overpower"));
+    let ctx = Arc::new(OverpowerHandleCtx {
+        event,
+        callback: config.overpower,
+        api: SyncCell::new(None),
+        last_update: SyncCell::new(None),
+        last: SyncCell::new(None),
+    });
+
+    let info = "over power alarm pushed as soon as ADPS/ADIR is decoded";
+    mk_feature_verb(api, "overpower", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,111 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// historic three phase PPOT, event is pushed when a phase potential is lost or restored
+struct PhasesHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncCell<Option<PhasePresence>>,
+}
+
+impl PhasesHandleCtx {
+    // '{"present":[true,false,true],"lost":[2],"restored":[]}'
+    fn push(&self, value: &PhasePresence, previous: &PhasePresence) -> Result<(), AfbError> {
+        let jsonc = self.to_jsonc()?;
+        let jlost = JsoncObj::array();
+        for (idx, phase) in value.lost(previous).iter().enumerate() {
+            jlost.insert(idx, *phase as u32)?;
+        }
+        jsonc.add("lost", jlost)?;
+        let jrestored = JsoncObj::array();
+        for (idx, phase) in previous.lost(value).iter().enumerate() {
+            jrestored.insert(idx, *phase as u32)?;
+        }
+        jsonc.add("restored", jrestored)?;
+        self.event.push(jsonc);
+        Ok(())
+    }
+
+    pub fn update(&self, value: PhasePresence) {
+        self.last_update.set(Some(Instant::now()));
+        // first status is only compared to all phases present
+        let previous = self.value.get().unwrap_or(PhasePresence::from_bits(0));
+        self.value.set(Some(value));
+        if value.get_present() == previous.get_present() {
+            return;
+        }
+        for phase in value.lost(&previous) {
+            afb_log_msg!(Warning, self.event, "phase:{} potential lost", phase);
+        }
+        if let Err(error) = self.push(&value, &previous) {
+            afb_log_msg!(Error, self.event, "fail to push phases error={}", error);
+        }
+    }
+}
+
+impl TicFeature for PhasesHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        if let TicValue::PPOT(value) = data {
+            self.update(*value);
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.value.set(None);
+    }
+
+    // '{"present":[true,false,true]}' empty until first PPOT
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        if let Some(value) = self.value.get() {
+            let jpresent = JsoncObj::array();
+            for (idx, present) in value.get_present().iter().enumerate() {
+                jpresent.insert(idx, *present)?;
+            }
+            jsonc.add("present", jpresent)?;
+        }
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register historic three phase presence verb
+pub(crate) fn mk_phases(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "phase-presence"));
+    let ctx = Arc::new(PhasesHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        value: SyncCell::new(None),
+    });
+
+    let info = "phase potential presence from historic PPOT, event on loss or recovery";
+    mk_feature_verb(api, "phase-presence", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
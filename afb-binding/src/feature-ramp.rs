@@ -0,0 +1,101 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// apparent power ramp, event is pushed when ramp goes above threshold
+struct RampHandleCtx {
+    event: &'static AfbEvent,
+    started: Instant,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<RampRate>,
+}
+
+impl RampHandleCtx {
+    // '{"unit":"VA/s","ramp":750.0,"power":4000}'
+    fn push(&self, ramp: f64, power: i32) -> Result<(), AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", "VA/s")?;
+        jsonc.add("ramp", ramp)?;
+        jsonc.add("power", power)?;
+        self.event.push(jsonc);
+        Ok(())
+    }
+
+    fn set_power(&self, power: i32) {
+        self.last_update.set(Some(Instant::now()));
+        let at = self.started.elapsed().as_secs_f64();
+        let ramp = self.value.lock().push(at, power);
+        if let Some(ramp) = ramp {
+            if let Err(error) = self.push(ramp, power) {
+                afb_log_msg!(Error, self.event, "fail to build ramp error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for RampHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        if let TicValue::SINSTS(value) = data {
+            self.set_power(*value);
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.value.lock().reset();
+    }
+
+    // '{"unit":"VA/s","ramp":750.0,"threshold":500.0}'
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let ramp = self.value.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", "VA/s")?;
+        if let Some(value) = ramp.get_ramp() {
+            jsonc.add("ramp", value)?;
+        }
+        jsonc.add("threshold", ramp.get_threshold())?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register apparent power ramp verb, detects big appliances switching on or off
+pub(crate) fn mk_ramp(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let (window, threshold) = config.ramp;
+    let event = AfbEvent::new(mk_name(config.prefix, "power-ramp"));
+    let ctx = Arc::new(RampHandleCtx {
+        event,
+        started: Instant::now(),
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(RampRate::new(window as f64, threshold)),
+    });
+
+    let info = "SINSTS change rate (VA/s) over a sliding window";
+    mk_feature_verb(api, "power-ramp", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// self consumption from grid flows and inverter production, event is pushed at midnight
+struct SelfConsoHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<SelfConsumption>,
+}
+
+impl SelfConsoHandleCtx {
+    // '{"start":..,"withdrawn":..,"injected":..,"produced":..,"ratio":0.75}' energy in Wh
+    fn day_jsonc(day: &SelfConsumptionDay) -> Result<JsoncObj, AfbError> {
+        let jsonc = day.grid.to_jsonc()?;
+        if let Some(produced) = day.produced {
+            jsonc.add("produced", produced)?;
+        }
+        if let Some(ratio) = day.get_ratio() {
+            jsonc.add("ratio", ratio)?;
+        }
+        Ok(jsonc)
+    }
+
+    pub fn set_date(&self, stamp: &TimeStampData) {
+        let day = self.value.lock().set_date(stamp);
+        if let Some(day) = day {
+            match SelfConsoHandleCtx::day_jsonc(&day) {
+                Ok(jsonc) => {
+                    self.event.push(jsonc);
+                }
+                Err(error) => {
+                    afb_log_msg!(Error, self.event, "fail to build selfconso error={}", error);
+                }
+            }
+        }
+    }
+}
+
+impl TicFeature for SelfConsoHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::EAST(value) => self.value.lock().set_withdrawn(*value),
+            TicValue::EAIT(value) => self.value.lock().set_injected(*value),
+            TicValue::SINSTI(value) => self.value.lock().set_injected_power(*value),
+            TicValue::DATE(value) => self.set_date(value),
+            _ => return,
+        }
+        self.last_update.set(Some(Instant::now()));
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.value.lock().reset();
+    }
+
+    // '{"unit":"Wh","instant":0.8,"today":{...},"history":[...]}' oldest history first
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let selfconso = self.value.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", TicUnit::WattHour.as_str())?;
+        if let Some(ratio) = selfconso.get_instant() {
+            jsonc.add("instant", ratio)?;
+        }
+        if let Some(today) = selfconso.get_today() {
+            jsonc.add("today", SelfConsoHandleCtx::day_jsonc(&today)?)?;
+        }
+        let jhistory = JsoncObj::array();
+        for (idx, day) in selfconso.get_history().iter().enumerate() {
+            jhistory.insert(idx, SelfConsoHandleCtx::day_jsonc(day)?)?;
+        }
+        jsonc.add("history", jhistory)?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+struct SelfConsoDataCtx {
+    handle: Arc<SelfConsoHandleCtx>,
+}
+
+fn selfconsocb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<SelfConsoDataCtx>()?;
+
+    let mut response = AfbParams::new();
+    let action = if args.get_count() > 0 {
+        args.get::<&SelfConsoAction>(0)?
+    } else {
+        &SelfConsoAction::READ
+    };
+    match action {
+        SelfConsoAction::READ => {
+            response.push(ctx.handle.to_jsonc()?)?;
+            response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+        }
+        SelfConsoAction::SUBSCRIBE => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        SelfConsoAction::UNSUBSCRIBE => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        SelfConsoAction::PRODUCTION { energy, power } => {
+            let mut selfconso = ctx.handle.value.lock();
+            if let Some(value) = energy {
+                selfconso.set_production(*value);
+            }
+            if let Some(value) = power {
+                selfconso.set_production_power(*value);
+            }
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register self consumption verb, production is pushed by inverter side clients
+pub(crate) fn mk_selfconso(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    size: usize,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "selfconso"));
+    let verb = AfbVerb::new("selfconso");
+
+    let ctx = Arc::new(SelfConsoHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(SelfConsumption::new(size)),
+    });
+
+    verb.set_info("self consumption ratio, instant and per day");
+    verb.set_permission(acl);
+    verb.set_actions("['read', 'subscribe', 'unsubscribe', 'production']")?;
+    verb.set_callback(selfconsocb);
+    verb.set_context(SelfConsoDataCtx {
+        handle: ctx.clone(),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    api.add_event(event);
+    Ok(ctx)
+}
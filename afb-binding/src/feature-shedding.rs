@@ -0,0 +1,130 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// over current alarm keeps its deficit while meter keeps sending it
+const SHEDDING_ALARM_HOLD: Duration = Duration::from_secs(10);
+
+// shedding plan from over current alarms and headroom reserve, event is pushed when plan changes
+struct SheddingHandleCtx {
+    event: &'static AfbEvent,
+    reserve: u32,
+    headroom: Arc<HeadroomHandleCtx>,
+    alarm: SyncCell<Option<(Instant, u32)>>, // last ADPS/ADIR deficit (VA)
+    advisor: SheddingAdvisor,
+    plan: SyncLock<SheddingPlan>,
+}
+
+impl SheddingHandleCtx {
+    // highest of over current deficit and missing reserve on headroom
+    fn get_deficit(&self) -> u32 {
+        let alarm = match self.alarm.get() {
+            Some((stamp, deficit)) if stamp.elapsed() < SHEDDING_ALARM_HOLD => deficit,
+            _ => 0,
+        };
+        let reserve = match self.headroom.get_available(0) {
+            Some(available) => i32::max(self.reserve as i32 - available, 0) as u32,
+            None => 0,
+        };
+        u32::max(alarm, reserve)
+    }
+
+    pub fn update(&self) {
+        let plan = self.advisor.get_plan(self.get_deficit());
+        if plan.loads == self.plan.lock().loads {
+            *self.plan.lock() = plan;
+            return;
+        }
+        *self.plan.lock() = plan;
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build shedding error={}", error);
+            }
+        }
+    }
+}
+
+// ADPS/ADIR give the over current in A, converted to VA on nominal 230V
+impl TicFeature for SheddingHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::ADPS(value)
+            | TicValue::ADIR1(value)
+            | TicValue::ADIR2(value)
+            | TicValue::ADIR3(value) => {
+                if *value > 0 {
+                    self.alarm.set(Some((Instant::now(), *value as u32 * 230)));
+                }
+                self.update();
+            }
+            TicValue::SINSTS(_) | TicValue::IINST(_) => self.update(),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.alarm.set(None);
+        *self.plan.lock() = self.advisor.get_plan(0);
+    }
+
+    // '{"deficit":1200,"shed":2000,"covered":true,"loads":["evse"]}' loads in shedding order
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let plan = self.plan.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("deficit", plan.deficit)?;
+        jsonc.add("shed", plan.shed)?;
+        jsonc.add("covered", plan.is_covered())?;
+        let jloads = JsoncObj::array();
+        for (idx, uid) in plan.loads.iter().enumerate() {
+            jloads.insert(idx, uid.as_str())?;
+        }
+        jsonc.add("loads", jloads)?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+}
+
+// register load shedding advisor verb, only when config declares controllable loads
+pub(crate) fn mk_shedding(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    config: &SheddingConfig,
+    headroom: &Arc<HeadroomHandleCtx>,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "shedding"));
+
+    let advisor = SheddingAdvisor::new(config.loads.clone());
+    let ctx = Arc::new(SheddingHandleCtx {
+        event,
+        reserve: config.reserve,
+        headroom: headroom.clone(),
+        alarm: SyncCell::new(None),
+        plan: SyncLock::new(advisor.get_plan(0)),
+        advisor,
+    });
+
+    let info = "ordered list of loads to shed on over current or low headroom";
+    mk_feature_verb(api, "shedding", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
@@ -0,0 +1,166 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// energy consumed per tariff index, event is pushed when a tariff is credited or at midnight
+pub(crate) struct TariffHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<TariffAccumulator>,
+    names: SyncLock<TariffNames>,
+}
+
+impl TariffHandleCtx {
+    fn push(&self) {
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build tariffs error={}", error);
+            }
+        }
+    }
+
+    pub fn set_tariff(&self, index: i32) {
+        if index > 0 {
+            self.value.lock().set_tariff(index as usize);
+            self.names.lock().set_tariff(index as usize);
+        }
+    }
+
+    pub fn get_name(&self, index: i32) -> Option<String> {
+        self.names.lock().get_name(usize::try_from(index).ok()?)
+    }
+
+    pub(crate) fn get_total(&self) -> [u64; TARIFF_INDEX_MAX] {
+        *self.value.lock().get_total()
+    }
+
+    pub(crate) fn get_last_update(&self) -> Option<Instant> {
+        self.last_update.get()
+    }
+
+    pub fn set_total(&self, value: u64) {
+        self.last_update.set(Some(Instant::now()));
+        if self.value.lock().set_total(value) {
+            self.push();
+        }
+    }
+
+    pub fn set_index(&self, idx: usize, value: u64) {
+        self.last_update.set(Some(Instant::now()));
+        if self.value.lock().set_index(idx, value) {
+            self.push();
+        }
+    }
+
+    pub fn set_date(&self, stamp: &TimeStampData) {
+        if self.value.lock().set_date(stamp) {
+            self.push();
+        }
+    }
+}
+
+// per tariff accumulation needs tariff index, energy counters and meter date
+impl TicFeature for TariffHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::NTARF(value) => self.set_tariff(*value),
+            TicValue::NGTF(value) => self.names.lock().set_calendar(value),
+            TicValue::LTARF(value) => self.names.lock().set_label(value),
+            TicValue::EAST(value) => self.set_total(*value),
+            TicValue::EASF01(value) => self.set_index(0, *value),
+            TicValue::EASF02(value) => self.set_index(1, *value),
+            TicValue::EASF03(value) => self.set_index(2, *value),
+            TicValue::EASF04(value) => self.set_index(3, *value),
+            TicValue::EASF05(value) => self.set_index(4, *value),
+            TicValue::EASF06(value) => self.set_index(5, *value),
+            TicValue::EASF07(value) => self.set_index(6, *value),
+            TicValue::EASF08(value) => self.set_index(7, *value),
+            TicValue::EASF09(value) => self.set_index(8, *value),
+            TicValue::EASF10(value) => self.set_index(9, *value),
+            TicValue::DATE(value) => self.set_date(value),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.value.lock().reset();
+        self.names.lock().reset();
+    }
+
+    // '{"unit":"Wh","tariff":2,"total":[...],"today":[...]}' index 1-10 at array position 0-9
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let accu = self.value.lock();
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", TicUnit::WattHour.as_str())?;
+        if let Some(tariff) = accu.get_tariff() {
+            jsonc.add("tariff", tariff as u32)?;
+            if let Some(name) = self.names.lock().get_name(tariff) {
+                jsonc.add("name", name.as_str())?;
+            }
+        }
+        let jtotal = JsoncObj::array();
+        let jtoday = JsoncObj::array();
+        for idx in 0..TARIFF_INDEX_MAX {
+            jtotal.insert(idx, accu.get_total()[idx])?;
+            jtoday.insert(idx, accu.get_today()[idx])?;
+        }
+        jsonc.add("total", jtotal)?;
+        jsonc.add("today", jtoday)?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+
+    fn get_state(&self, state: &mut LinkyState) {
+        state.tariffs = Some(self.value.lock().get_state());
+    }
+
+    fn set_state(&self, state: &LinkyState) {
+        if let Some(tariffs) = &state.tariffs {
+            self.value.lock().set_state(tariffs);
+        }
+    }
+}
+
+// register energy per tariff index verb, accumulated from NTARF with EAST or EASFxx
+pub(crate) fn mk_tariffs(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<TariffHandleCtx>, AfbError> {
+    let event = AfbEvent::new(mk_name(config.prefix, "energy-by-tariff"));
+    let ctx = Arc::new(TariffHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(TariffAccumulator::new()),
+        names: SyncLock::new(TariffNames::new(config.tariff_names.clone())),
+    });
+
+    let info = "energy consumed per tariff index since startup and today";
+    mk_feature_verb(api, "energy-by-tariff", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
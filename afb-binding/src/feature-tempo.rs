@@ -0,0 +1,104 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// Tempo colours from STGE (standard) or PTEC/DEMAIN (historic), event is pushed on change
+pub(crate) struct TempoHandleCtx {
+    event: &'static AfbEvent,
+    last_update: SyncCell<Option<Instant>>,
+    today: SyncCell<TempoColour>,
+    tomorrow: SyncCell<TempoColour>,
+}
+
+impl TempoHandleCtx {
+    pub(crate) fn get_tomorrow(&self) -> TempoColour {
+        self.tomorrow.get()
+    }
+
+    pub fn update(&self, today: TempoColour, tomorrow: TempoColour) {
+        self.last_update.set(Some(Instant::now()));
+        if today == self.today.get() && tomorrow == self.tomorrow.get() {
+            return;
+        }
+        self.today.set(today);
+        self.tomorrow.set(tomorrow);
+        match self.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build tempo error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for TempoHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::STGE(value) => {
+                self.update(value.get_tempo_today(), value.get_tempo_tomorrow())
+            }
+            TicValue::PTEC(value) => {
+                self.update(TempoColour::from_period(value), self.tomorrow.get())
+            }
+            TicValue::DEMAIN(value) => self.update(self.today.get(), *value),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.last_update.set(None);
+        self.today.set(TempoColour::UNKNOWN);
+        self.tomorrow.set(TempoColour::UNKNOWN);
+    }
+
+    // '{"today":"blue","tomorrow":"unknown"}'
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("today", self.today.get().as_str())?;
+        jsonc.add("tomorrow", self.tomorrow.get().as_str())?;
+        Ok(jsonc)
+    }
+
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        std::slice::from_ref(&self.event)
+    }
+
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        Some(self.last_update.get())
+    }
+}
+
+// register Tempo today/tomorrow colours verb
+pub(crate) fn mk_tempo(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+) -> Result<Arc<TempoHandleCtx>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "tempo"));
+    let ctx = Arc::new(TempoHandleCtx {
+        event,
+        last_update: SyncCell::new(None),
+        today: SyncCell::new(TempoColour::UNKNOWN),
+        tomorrow: SyncCell::new(TempoColour::UNKNOWN),
+    });
+
+    let info = "Tempo colour of today and tomorrow (blue|white|red|unknown)";
+    mk_feature_verb(api, "tempo", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
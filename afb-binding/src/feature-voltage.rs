@@ -0,0 +1,118 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// voltage quality per phase, sag/swell events are pushed once per excursion
+struct VoltageHandleCtx {
+    events: [&'static AfbEvent; 2], // sag, swell
+    started: Instant,
+    duration: u32,
+    value: SyncLock<VoltageMonitor>,
+}
+
+impl VoltageHandleCtx {
+    // '{"phase":1,"volts":198}'
+    fn push(&self, excursion: &VoltageExcursion) -> Result<(), AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("phase", excursion.phase as u32)?;
+        jsonc.add("volts", excursion.volts)?;
+        match excursion.kind {
+            VoltageKind::SAG => self.events[0].push(jsonc),
+            VoltageKind::SWELL => self.events[1].push(jsonc),
+        };
+        Ok(())
+    }
+
+    fn set_phase(&self, phase: usize, volts: i32) {
+        let at = self.started.elapsed().as_secs_f64();
+        let excursion = self.value.lock().push(phase, at, volts);
+        if let Some(excursion) = excursion {
+            if let Err(error) = self.push(&excursion) {
+                afb_log_msg!(Error, self.events[0], "fail to build voltage error={}", error);
+            }
+        }
+    }
+}
+
+impl TicFeature for VoltageHandleCtx {
+    fn dispatch(&self, data: &TicValue) {
+        match data {
+            TicValue::URMS1(value) => self.set_phase(1, *value),
+            TicValue::URMS2(value) => self.set_phase(2, *value),
+            TicValue::URMS3(value) => self.set_phase(3, *value),
+            _ => {}
+        }
+    }
+
+    fn reset(&self) {
+        self.value.lock().reset();
+    }
+
+    // '{"low":207,"high":253,"duration_ms":0,"sags":[0,1,0],"swells":[...],"state":[...]}'
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let monitor = self.value.lock();
+        let (low, high) = monitor.get_limits();
+        let jsonc = JsoncObj::new();
+        jsonc.add("low", low)?;
+        jsonc.add("high", high)?;
+        jsonc.add("duration_ms", self.duration)?;
+        let jsags = JsoncObj::array();
+        let jswells = JsoncObj::array();
+        let jstate = JsoncObj::array();
+        for idx in 0..3 {
+            jsags.insert(idx, monitor.get_sags()[idx])?;
+            jswells.insert(idx, monitor.get_swells()[idx])?;
+            let state = match monitor.get_state(idx + 1) {
+                Some(VoltageKind::SAG) => "sag",
+                Some(VoltageKind::SWELL) => "swell",
+                None => "normal",
+            };
+            jstate.insert(idx, state)?;
+        }
+        jsonc.add("sags", jsags)?;
+        jsonc.add("swells", jswells)?;
+        jsonc.add("state", jstate)?;
+        Ok(jsonc)
+    }
+
+    // subscribe covers both sag and swell events
+    fn get_events(&self) -> &[&'static AfbEvent] {
+        &self.events
+    }
+}
+
+// register voltage quality verb with its sag/swell events
+pub(crate) fn mk_voltage(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Arc<dyn TicFeature>, AfbError> {
+    let (low, high, duration) = config.voltage;
+    let sag = AfbEvent::new(mk_name(config.prefix, "voltage-sag"));
+    let swell = AfbEvent::new(mk_name(config.prefix, "voltage-swell"));
+
+    let ctx = Arc::new(VoltageHandleCtx {
+        events: [sag, swell],
+        started: Instant::now(),
+        duration,
+        value: SyncLock::new(VoltageMonitor::new(low, high, duration as f64 / 1000.0)),
+    });
+
+    let info = "URMS sag/swell counters and current state per phase";
+    mk_feature_verb(api, "voltage-quality", info, acl, ctx.clone())?;
+    Ok(ctx)
+}
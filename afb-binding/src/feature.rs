@@ -0,0 +1,97 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use afbv4::prelude::*;
+use linky::prelude::*;
+use std::sync::Arc;
+use std::time::Instant;
+
+// derived values computed from decoded lines (ramp, overload, ...), each feature owns its verb
+// and event. line_dispatch feeds every line once sensors are updated.
+pub(crate) trait TicFeature: Send + Sync {
+    fn dispatch(&self, data: &TicValue);
+    fn reset(&self);
+    fn get_events(&self) -> &[&'static AfbEvent];
+    fn to_jsonc(&self) -> Result<JsoncObj, AfbError>;
+
+    // 'read' appends a freshness block, features without update tracking return None
+    fn get_freshness(&self) -> Option<Option<Instant>> {
+        None
+    }
+
+    // derived counters kept across restarts, see 'state' config
+    fn get_state(&self, _state: &mut LinkyState) {}
+    fn set_state(&self, _state: &LinkyState) {}
+
+    // subcalls need the finalized api, see ApiShutdown::set_api
+    fn set_api(&self, _api: &'static AfbApi) {}
+}
+
+struct FeatureDataCtx {
+    handle: Arc<dyn TicFeature>,
+}
+
+fn featurecb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<FeatureDataCtx>()?;
+
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ => {
+            response.push(ctx.handle.to_jsonc()?)?;
+            if let Some(last_update) = ctx.handle.get_freshness() {
+                response.push(freshness_jsonc(last_update)?)?;
+            }
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            for event in ctx.handle.get_events() {
+                event.subscribe(rqt)?;
+            }
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            for event in ctx.handle.get_events() {
+                event.unsubscribe(rqt)?;
+            }
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register feature verb and its events, every feature shares 'read|subscribe|unsubscribe'
+pub(crate) fn mk_feature_verb(
+    api: &mut MeterApi,
+    name: &'static str,
+    info: &'static str,
+    acl: &'static AfbPermission,
+    handle: Arc<dyn TicFeature>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new(name);
+    verb.set_info(info);
+    verb.set_permission(acl);
+    verb.set_actions("['read', 'subscribe', 'unsubscribe']")?;
+    verb.set_callback(featurecb);
+    verb.set_context(FeatureDataCtx {
+        handle: handle.clone(),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    for event in handle.get_events() {
+        api.add_event(event);
+    }
+    Ok(())
+}
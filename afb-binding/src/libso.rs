@@ -24,8 +24,88 @@ mod verbs;
 #[path = "binding.rs"]
 mod binding;
 
+#[path = "feature.rs"]
+mod feature;
+
+#[path = "feature-charging.rs"]
+mod charging;
+
+#[path = "feature-consumption.rs"]
+mod consumption;
+
+#[path = "feature-cost.rs"]
+mod cost;
+
+#[path = "feature-ejp.rs"]
+mod ejp;
+
+#[path = "feature-evse.rs"]
+mod evse;
+
+#[path = "feature-headroom.rs"]
+mod headroom;
+
+#[path = "feature-hooks.rs"]
+mod hooks;
+
+#[path = "feature-imbalance.rs"]
+mod imbalance;
+
+#[path = "feature-injection.rs"]
+mod injection;
+
+#[path = "feature-ocpp.rs"]
+mod ocpp;
+
+#[path = "feature-overload.rs"]
+mod overload;
+
+#[path = "feature-overpower.rs"]
+mod overpower;
+
+#[path = "feature-phases.rs"]
+mod phases;
+
+#[path = "feature-ramp.rs"]
+mod ramp;
+
+#[path = "feature-selfconso.rs"]
+mod selfconso;
+
+#[path = "feature-shedding.rs"]
+mod shedding;
+
+#[path = "feature-tariff.rs"]
+mod tariff;
+
+#[path = "feature-tempo.rs"]
+mod tempo;
+
+#[path = "feature-voltage.rs"]
+mod voltage;
+
 pub(crate) mod prelude {
    // pub(crate) use crate::codec::*;
     pub(crate) use crate::verbs::*;
     pub(crate) use crate::binding::*;
+    pub(crate) use crate::feature::*;
+    pub(crate) use crate::charging::*;
+    pub(crate) use crate::consumption::*;
+    pub(crate) use crate::cost::*;
+    pub(crate) use crate::ejp::*;
+    pub(crate) use crate::evse::*;
+    pub(crate) use crate::headroom::*;
+    pub(crate) use crate::hooks::*;
+    pub(crate) use crate::imbalance::*;
+    pub(crate) use crate::injection::*;
+    pub(crate) use crate::ocpp::*;
+    pub(crate) use crate::overload::*;
+    pub(crate) use crate::overpower::*;
+    pub(crate) use crate::phases::*;
+    pub(crate) use crate::ramp::*;
+    pub(crate) use crate::selfconso::*;
+    pub(crate) use crate::shedding::*;
+    pub(crate) use crate::tariff::*;
+    pub(crate) use crate::tempo::*;
+    pub(crate) use crate::voltage::*;
 }
\ No newline at end of file
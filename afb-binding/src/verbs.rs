@@ -11,141 +11,4151 @@
  */
 
 use crate::prelude::*;
-use ::core::mem::MaybeUninit;
 use afbv4::prelude::*;
 use linky::prelude::*;
-use std::cell::Cell;
-use std::rc::Rc;
+use std::any::Any;
+use std::collections::{BTreeMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const STX: u8 = 0x02; // start of frame
+const ETX: u8 = 0x03; // end of frame
+const HOTPLUG_DELAY: u32 = 1000; // ms between device node and by-id link creation
+const BACKUP_RETRY: u32 = 5000; // ms between backup source reopen attempts
+const FAULT_RETRY: u32 = 5000; // ms between reopen attempts without reconnect policy
+const WATCHDOG_TICK: u32 = 1000; // ms between two stale data checks
+#[cfg(feature = "mqtt")]
+const SPARKPLUG_RETRY: u64 = 10; // s between two broker connect attempts
+
+// sensor verbs and their readable name alias share the same actions
+const TIC_ACTIONS: &str = "['read', 'info', 'subscribe', 'unsubscribe']";
+const SENSOR_ACTIONS: &str = "['read', 'info', 'subscribe', 'unsubscribe', 'history', 'stats']";
+
+// widest sensor is EASF01-10, energy counters are 9 digits Wh and require 64bit storage
+const SENSOR_VALUES: usize = 10;
+
+// sensor cache and push bookkeeping, updated as a whole by each received value
+struct SensorState {
+    last_update: Option<Instant>,
+    values: [i64; SENSOR_VALUES],
+    raws: [i64; SENSOR_VALUES], // meter values before calibration
+    count: u32,
+    config: SensorConfig, // runtime tuning, updated by control 'sensors' action
+    reported: [i64; SENSOR_VALUES],
+    pushed: [Option<Instant>; SENSOR_VALUES],
+    pending: [bool; SENSOR_VALUES],
+    history: VecDeque<(u64, [i64; SENSOR_VALUES])>,
+    window: [VecDeque<i64>; SENSOR_VALUES],
+}
 
 struct SensorHandleCtx {
     tic: &'static TicObject,
     event: &'static AfbEvent,
-    values: Cell<[i32; 4]>,
-    count: Cell<u32>,
+    changes: &'static ChangeFeed,
+    phases: Vec<&'static AfbEvent>, // one event per value index, empty for single value sensors
+    units: bool,
+    counter: bool, // energy counter, calibrated value never goes negative
+    state: SyncLock<SensorState>,
+    tariffs: Option<Arc<TariffHandleCtx>>, // NTARF only, resolves tariff period name
+}
+
+// meter horodate, event is pushed on day rollover
+struct StampHandleCtx {
+    tic: &'static TicObject,
+    event: &'static AfbEvent,
+    changes: &'static ChangeFeed,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncCell<Option<TimeStampData>>,
+}
+
+// daily max power, event is pushed on each new maximum
+struct PowerHandleCtx {
+    tic: &'static TicObject,
+    event: &'static AfbEvent,
+    changes: &'static ChangeFeed,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncCell<Option<PowerMaxData>>,
+}
+
+// provider text label (LTARF, NGTF) or historic DEMAIN colour, event is pushed when label changes
+struct TextHandleCtx {
+    tic: &'static TicObject,
+    event: &'static AfbEvent,
+    changes: &'static ChangeFeed,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<Option<String>>,
+}
+
+// status register, event carries changed fields with old and new values
+struct RegisterHandleCtx {
+    tic: &'static TicObject,
+    event: &'static AfbEvent,
+    changes: &'static ChangeFeed,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncCell<Option<RegisterStatus>>,
+}
+
+// probable meter power outages, event is pushed when an outage starts or ends
+struct OutageHandleCtx {
+    event: &'static AfbEvent,
+    value: SyncLock<OutageTracker>,
+}
+
+// EV charge limit from subscription and phase currents, published through 'CHARGE' sensor
+struct ChargeLimitCtx {
+    started: Instant,
+    value: SyncLock<ChargeLimit>,
+}
+
+// producer meter family, members keep their own verb and event, EAIT is shared with consumers
+struct ProductionHandleCtx {
+    sinsti: Arc<SensorHandleCtx>,
+    smaxin: Arc<PowerHandleCtx>,
+    smaxin1: Arc<PowerHandleCtx>,
+    eait: Arc<SensorHandleCtx>,
+    erq: Arc<SensorHandleCtx>,
+}
+
+// sqlite persistence of sensor updates, read back with 'query' verb
+#[cfg(feature = "sqlite")]
+struct StoreHandleCtx {
+    value: SyncLock<SampleStore>,
+}
+
+// csv file sink of sensor updates
+struct CsvHandleCtx {
+    value: SyncLock<CsvSink>,
+}
+
+// influxdb push of sensor updates, buffered lines are flushed on timer
+#[cfg(feature = "influx")]
+struct InfluxHandleCtx {
+    failing: SyncCell<bool>,
+    value: SyncLock<InfluxSink>,
+}
+
+// redis stream push of sensor updates, buffered entries are flushed on timer
+#[cfg(feature = "redis")]
+struct RedisHandleCtx {
+    failing: SyncCell<bool>,
+    value: SyncLock<RedisSink>,
+}
+
+// mqtt sparkplug b edge node, NDATA are batched on timer and NCMD read from broker socket
+#[cfg(feature = "mqtt")]
+struct SparkplugHandleCtx {
+    host: &'static str,
+    port: u16,
+    username: &'static str,
+    password: &'static str,
+    keepalive: u32,
+    client: SyncLock<Option<MqttClient>>,
+    evtfd: SyncCell<Option<&'static AfbEvtFd>>,
+    last_connect: SyncCell<Option<Instant>>,
+    last_ping: SyncCell<Instant>,
+    value: SyncLock<SparkplugNode>,
+}
+
+// d-bus service, values are cached and signaled on change
+#[cfg(feature = "dbus")]
+struct DbusHandleCtx {
+    value: SyncLock<DbusExport>,
+}
+
+// modbus tcp register map, refreshed with every decoded line
+#[cfg(feature = "modbus")]
+struct ModbusHandleCtx {
+    server: ModbusServer,
+    value: SyncLock<ModbusRegisters>,
+}
+
+// one context per accepted modbus client, dropped when client leaves
+#[cfg(feature = "modbus")]
+struct ModbusClientCtx {
+    handle: Arc<ModbusHandleCtx>,
+    client: SyncLock<ModbusClient>,
+}
+
+// provider calendar profile, event is pushed when a new calendar is received
+struct ProfileHandleCtx {
+    tic: &'static TicObject,
+    event: &'static AfbEvent,
+    changes: &'static ChangeFeed,
+    last_update: SyncCell<Option<Instant>>,
+    value: SyncLock<Option<ProviderProfile>>,
+}
+
+// last complete raw frame as received before parsing, optionally recorded as replay capture
+struct RawFrameCtx {
+    event: &'static AfbEvent,
+    source: SyncLock<String>,
+    frame: SyncLock<String>,
+    last: SyncLock<String>,
+    recorder: SyncLock<FrameRecorder>,
+}
+
+struct EventDataCtx {
+    pub cycle: u32,
+    pub reconnect: SyncCell<u32>,
+    pub backoff: u32,
+    pub delay: SyncCell<u32>,
+    pub interval: SyncCell<u32>,
+    pub timer: SyncCell<Option<&'static AfbTimer>>,
+    pub watch: Option<DeviceWatch>,
+    pub online: SyncCell<bool>,
+    pub fault: SyncLock<FaultTracker>, // failures without reconnect policy
+    pub probing: SyncCell<bool>,
+    pub watchdog: u32,
+    pub last_valid: SyncCell<Instant>,
+    pub stale: SyncCell<bool>,
+    pub debug: SyncCell<bool>, // dump every line to afb log, toggled by 'debug' verb
+    pub last_frame: SyncCell<Option<Instant>>, // last ETX or truncated frame
+    pub gateway: SyncCell<Option<(SocketAddr, bool)>>, // last known sender, lost flag
+    pub handle: LinkyHandle,
+    pub buffer: SyncLock<Vec<u8>>, // line decode buffer, 'line_max' bytes
+    pub lines: SyncLock<TicLines>,     // decoded lines scratch, slots kept across wakeups
+    pub budget: usize,                 // lines per wakeup, 0 no limit
+    pub backlog: [SyncCell<bool>; 2],  // budget exhausted per LinkySource, lines left in ring
+    pub resume: SyncCell<bool>,        // budget timer armed
+    pub evtfd: SyncCell<Option<&'static AfbEvtFd>>,
+    pub event: &'static AfbEvent,
+    pub unknown: &'static AfbEvent,
+    pub diag: &'static AfbEvent,
+    pub changes: &'static ChangeFeed,
+    pub raw: Arc<RawFrameCtx>,
+    pub iinst: Arc<SensorHandleCtx>,
+    pub sinsts: Arc<SensorHandleCtx>,
+    pub adsp: Arc<SensorHandleCtx>,
+    pub adsc: Arc<SensorHandleCtx>,
+    pub pcou: Arc<SensorHandleCtx>,
+    pub ntarf: Arc<SensorHandleCtx>,
+    pub irms: Arc<SensorHandleCtx>,
+    pub urms: Arc<SensorHandleCtx>,
+    pub pf: Arc<SensorHandleCtx>,
+    pub factor: SyncLock<PowerFactor>,
+    pub charge: Arc<SensorHandleCtx>,
+    pub imax: Arc<SensorHandleCtx>,
+    pub pmax: Arc<SensorHandleCtx>,
+    pub limiter: ChargeLimitCtx,
+    pub ltarf: Arc<TextHandleCtx>,
+    pub ngtf: Arc<TextHandleCtx>,
+    pub demain: Arc<TextHandleCtx>,
+    pub stge: Arc<RegisterHandleCtx>,
+    pub east: Arc<SensorHandleCtx>,
+    pub easf: Arc<SensorHandleCtx>,
+    pub eait: Arc<SensorHandleCtx>,
+    pub production: Option<Arc<ProductionHandleCtx>>,
+    pub customs: Vec<Arc<SensorHandleCtx>>,
+    pub features: Vec<Arc<dyn TicFeature>>, // derived values fed with every decoded line
+    pub evse: Arc<EvseHandleCtx>,           // also 'ENERGY' view of 'readmany'
+    pub outage: Option<Arc<OutageHandleCtx>>, // serial sources with watchdog only
+    #[cfg(feature = "sqlite")]
+    pub store: Option<Arc<StoreHandleCtx>>,
+    pub state: Option<StateFile>, // derived counters kept across restarts
+    pub csv: Option<Arc<CsvHandleCtx>>,
+    #[cfg(feature = "influx")]
+    pub influx: Option<Arc<InfluxHandleCtx>>,
+    #[cfg(feature = "redis")]
+    pub redis: Option<Arc<RedisHandleCtx>>,
+    #[cfg(feature = "mqtt")]
+    pub sparkplug: Option<Arc<SparkplugHandleCtx>>,
+    #[cfg(feature = "modbus")]
+    pub modbus: Option<Arc<ModbusHandleCtx>>,
+    #[cfg(feature = "dbus")]
+    pub dbus: Option<Arc<DbusHandleCtx>>,
+    pub history: usize, // default sensors tuning, restored when removed from config
+    pub window: usize,
+    pub date: Arc<StampHandleCtx>,
+    pub smaxsn: Arc<PowerHandleCtx>,
+    pub pjourf1: Arc<ProfileHandleCtx>,
+    pub ppointe: Arc<ProfileHandleCtx>,
+}
+
+impl EventDataCtx {
+    // sample sinks receive every numeric and energy sensor update
+    fn has_sinks(&self) -> bool {
+        #[cfg(feature = "sqlite")]
+        if self.store.is_some() {
+            return true;
+        }
+        #[cfg(feature = "dbus")]
+        if self.dbus.is_some() {
+            return true;
+        }
+        #[cfg(feature = "influx")]
+        if self.influx.is_some() {
+            return true;
+        }
+        #[cfg(feature = "redis")]
+        if self.redis.is_some() {
+            return true;
+        }
+        #[cfg(feature = "mqtt")]
+        if self.sparkplug.is_some() {
+            return true;
+        }
+        self.csv.is_some()
+    }
+}
+
+// this method is call each time a message is waiting on session raw_socket
+//AfbEvtFdRegister!(SerialAsyncCtrl, async_serial_cb, EventDataCtx);
+fn async_serial_cb(
+    fd: &AfbEvtFd,
+    revent: u32,
+    ctx: &AfbCtxData, //&mut Arc<EventDataCtx>
+) -> Result<(), AfbError>{
+
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+
+    if revent != AfbEvtFdPoll::IN.bits() {
+        outage_update(ctx, |outage| outage.set_link_lost());
+        if ctx.watch.is_some() {
+            source_unplug(fd, ctx);
+            return Ok(());
+        }
+        if ctx.reconnect.get() > 0 {
+            return source_lost(fd, ctx);
+        }
+        ctx.event.broadcast("tty-error");
+        return source_fault(ctx, LinkyError::ReopenDev);
+    }
+
+    match source_drain(ctx) {
+        Err(LinkyError::ReopenDev | LinkyError::SerialError(_)) if ctx.watch.is_some() => {
+            source_unplug(fd, ctx);
+            return Ok(());
+        }
+        Err(LinkyError::ReopenDev | LinkyError::SerialError(_)) if ctx.reconnect.get() > 0 => {
+            return source_lost(fd, ctx)
+        }
+        Err(error @ (LinkyError::ReopenDev | LinkyError::SerialError(_))) => {
+            outage_update(ctx, |outage| outage.set_link_lost());
+            source_error(ctx, ctx.handle.get_name(), &error);
+            return source_fault(ctx, error);
+        }
+        Err(error) => source_error(ctx, ctx.handle.get_name(), &error),
+        Ok(()) => budget_resume(ctx)?,
+    }
+
+    // listening sources swap fd between listener and accepted client
+    if ctx.handle.get_fd() != fd.get_fd() {
+        fd.unref();
+        ctx.evtfd.set(None);
+        source_start(ctx)?;
+    }
+    Ok(())
+}
+
+// oversized payload was dropped by the ring, report it on diagnostics event
+fn source_overflow(ctx: &EventDataCtx, handle: &LinkyHandle, info: &str) -> Result<(), AfbError> {
+    let jdiag = JsoncObj::new();
+    jdiag.add("source", handle.get_name())?;
+    jdiag.add("error", "ring-overflow")?;
+    jdiag.add("info", info)?;
+    jdiag.add("overflow", handle.get_overflow() as u32)?;
+    ctx.diag.push(jdiag);
+    Ok(())
+}
+
+// checksum failure rate crossed configured threshold, usually a bad optical head
+fn source_quality(ctx: &EventDataCtx, handle: &LinkyHandle, alarm: bool) -> Result<(), AfbError> {
+    let status = if alarm {
+        "link-quality-degraded"
+    } else {
+        "link-quality-restored"
+    };
+    let rate = handle.get_checksum_rate();
+    afb_log_msg!(Warning, ctx.event, "source:{} {} rate:{}/min", handle.get_name(), status, rate);
+    ctx.event.broadcast(status);
+
+    let jdiag = JsoncObj::new();
+    jdiag.add("source", handle.get_name())?;
+    jdiag.add("error", status)?;
+    jdiag.add("rate", rate)?;
+    ctx.diag.push(jdiag);
+    Ok(())
+}
+
+// stable error code on main event, offending data and source on diagnostics event
+fn source_error(ctx: &EventDataCtx, source: &str, error: &LinkyError) {
+    ctx.event.broadcast(error.get_code());
+    let jdiag = match error.to_jsonc() {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    if jdiag.add("source", source).is_ok() {
+        ctx.diag.push(jdiag);
+    }
+}
+
+// read and dispatch every pending line, source failures are returned to caller
+fn source_drain(ctx: &EventDataCtx) -> Result<(), LinkyError> {
+    source_drain_from(ctx, &ctx.handle, LinkySource::Primary)
+}
+
+fn source_drain_from(
+    ctx: &EventDataCtx,
+    handle: &LinkyHandle,
+    origin: LinkySource,
+) -> Result<(), LinkyError> {
+    // network sources may deliver more than one line per wakeup
+    let mut lines = std::mem::take(&mut *ctx.lines.lock());
+    let status = handle
+        .decode_budget(&mut ctx.buffer.lock(), &mut lines, ctx.budget)
+        .map(|more| ctx.backlog[origin as usize].set(more));
+
+    for line in lines.iter_mut() {
+        let frame = line.frame.take();
+        let result = match std::mem::replace(&mut line.value, Err(LinkyError::RetryLater)) {
+            Err(LinkyError::TooLong(info)) => {
+                if let Err(error) = source_overflow(ctx, handle, &info) {
+                    afb_log_msg!(Error, ctx.event, "fail to push diagnostics error={}", error);
+                }
+                continue;
+            }
+            result => {
+                // backup lines are dropped as long as primary is alive
+                match ctx.handle.select(origin) {
+                    None => continue,
+                    Some(true) => {
+                        afb_log_msg!(Notice, ctx.event, "source switch to {}", origin.as_str());
+                        ctx.event.broadcast(format!("source-{}", origin.as_str()));
+                    }
+                    Some(false) => {}
+                }
+                ctx.raw.append(&line.raw);
+                result
+            }
+        };
+        line_trace(ctx, &line.raw, &result);
+
+        // gateways stripping STX/ETX leave DATE as first group of each frame
+        if ctx.changes.batch && !ctx.changes.framed.get() {
+            if let Ok(TicValue::DATE(_)) = result {
+                batch_push(ctx.changes, None);
+            }
+        }
+        line_dispatch(ctx, result);
+
+        // STX always comes alone on its line, frame summary follows its last group
+        if let Some(frame) = frame {
+            ctx.last_frame.set(Some(Instant::now()));
+            frame_push(ctx.changes, &frame);
+        }
+    }
+    lines.clear();
+    ctx.lines.replace(lines);
+
+    if let Some(degraded) = handle.check_quality() {
+        if let Err(error) = source_quality(ctx, handle, degraded) {
+            afb_log_msg!(Error, ctx.event, "fail to push diagnostics error={}", error);
+        }
+    }
+    status
+}
+
+// lines left in ring do not wake the fd poll, resume them once main loop had its turn
+fn budget_resume(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    if ctx.resume.get() || !ctx.backlog.iter().any(|pending| pending.get()) {
+        return Ok(());
+    }
+    ctx.resume.set(true);
+    AfbTimer::new("linky-budget")
+        .set_period(1)
+        .set_decount(1)
+        .set_callback(budget_resume_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    Ok(())
+}
+
+fn budget_resume_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+    ctx.resume.set(false);
+
+    // source failures are left to fd callbacks, they own reconnection
+    if ctx.backlog[LinkySource::Primary as usize].replace(false) {
+        if let Err(error) = source_drain(ctx) {
+            let name = ctx.handle.get_name();
+            afb_log_msg!(Debug, ctx.event, "source:{} resume fail {}", name, error);
+        }
+    }
+    if ctx.backlog[LinkySource::Backup as usize].replace(false) {
+        if let Some(backup) = ctx.handle.get_backup() {
+            if let Err(error) = source_drain_from(ctx, backup, LinkySource::Backup) {
+                let name = backup.get_name();
+                afb_log_msg!(Debug, ctx.event, "backup:{} resume fail {}", name, error);
+            }
+        }
+    }
+    budget_resume(ctx)
+}
+
+// raw line, checksum status and parse result, only when enabled through 'debug' verb
+fn line_trace(ctx: &EventDataCtx, raw: &[u8], result: &Result<TicValue, LinkyError>) {
+    if !ctx.debug.get() {
+        return;
+    }
+    let checksum = match result {
+        Err(LinkyError::ChecksumError(_)) => "fail",
+        Err(LinkyError::EncodingError(_)) => "encoding",
+        Err(LinkyError::RetryLater) => "none",
+        _ => "ok",
+    };
+    afb_log_msg!(
+        Notice,
+        ctx.event,
+        "source:{} raw:{:?} checksum:{} value:{:?}",
+        ctx.handle.get_name(),
+        String::from_utf8_lossy(raw),
+        checksum,
+        result
+    );
+}
+
+// dispatch one decoded line to its sensor, shared by sources and 'inject' verb
+fn line_dispatch(ctx: &EventDataCtx, result: Result<TicValue, LinkyError>) {
+    match result {
+        Err(error) => match error {
+            LinkyError::ChecksumError(_) | LinkyError::RetryLater => {}
+            LinkyError::UnknownLabel(line) => {
+                ctx.unknown.push(line);
+            }
+            _ => {
+                afb_log_msg!(
+                    Debug,
+                    ctx.event,
+                    "device:{} invalid data {}",
+                    ctx.handle.get_name(),
+                    error
+                );
+                source_error(ctx, ctx.handle.get_name(), &error);
+            }
+        },
+        Ok(data) => {
+            ctx.last_valid.set(Instant::now());
+            ctx.fault.lock().set_data();
+            outage_update(ctx, |outage| outage.set_data(epoch_ms()));
+            if ctx.stale.get() {
+                afb_log_msg!(Notice, ctx.event, "source:{} data resumed", ctx.handle.get_name());
+                ctx.stale.set(false);
+                ctx.event.broadcast("data-resumed");
+            }
+
+            for feature in ctx.features.iter() {
+                feature.dispatch(&data);
+            }
+            factor_dispatch(ctx, &data);
+            charge_dispatch(ctx, &data);
+            #[cfg(feature = "modbus")]
+            if let Some(modbus) = &ctx.modbus {
+                modbus.value.lock().set_tic(&data);
+            }
+
+            // sinks read values back from sensors once updated
+            let sampled = if ctx.has_sinks() {
+                Some(data.clone())
+            } else {
+                None
+            };
+            match data {
+                // register status
+                TicValue::ADSC(value) => ctx.adsc.updated(ctx.cycle, data, 0, value.raw as i64),
+
+                // over power
+                TicValue::ADPS(value) => ctx.adsp.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::ADIR1(value) => ctx.adsp.updated(ctx.cycle, data, 1, value.into()),
+                TicValue::ADIR2(value) => ctx.adsp.updated(ctx.cycle, data, 2, value.into()),
+                TicValue::ADIR3(value) => ctx.adsp.updated(ctx.cycle, data, 3, value.into()),
+
+                // cutting power
+                TicValue::PCOUP(value) => ctx.pcou.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::PREF(value) => ctx.pcou.updated(ctx.cycle, data, 1, value.into()),
+
+                // instant current
+                TicValue::IINST(value) => ctx.iinst.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::IINST1(value) => ctx.iinst.updated(ctx.cycle, data, 1, value.into()),
+                TicValue::IINST2(value) => ctx.iinst.updated(ctx.cycle, data, 2, value.into()),
+                TicValue::IINST3(value) => ctx.iinst.updated(ctx.cycle, data, 3, value.into()),
+
+                // historic maxima
+                TicValue::IMAX(value) => ctx.imax.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::IMAX1(value) => ctx.imax.updated(ctx.cycle, data, 1, value.into()),
+                TicValue::IMAX2(value) => ctx.imax.updated(ctx.cycle, data, 2, value.into()),
+                TicValue::IMAX3(value) => ctx.imax.updated(ctx.cycle, data, 3, value.into()),
+                TicValue::PMAX(value) => ctx.pmax.updated(ctx.cycle, data, 0, value.into()),
+
+                // instant active current
+                TicValue::SINSTS(value) => ctx.sinsts.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::SINSTS1(value) => ctx.sinsts.updated(ctx.cycle, data, 1, value.into()),
+                TicValue::SINSTS2(value) => ctx.sinsts.updated(ctx.cycle, data, 2, value.into()),
+                TicValue::SINSTS3(value) => ctx.sinsts.updated(ctx.cycle, data, 3, value.into()),
+
+                // efficient current
+                TicValue::IRMS1(value) => ctx.irms.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::IRMS2(value) => ctx.irms.updated(ctx.cycle, data, 1, value.into()),
+                TicValue::IRMS3(value) => ctx.irms.updated(ctx.cycle, data, 2, value.into()),
+
+                // efficient tension
+                TicValue::URMS1(value) => ctx.urms.updated(ctx.cycle, data, 0, value.into()),
+                TicValue::URMS2(value) => ctx.urms.updated(ctx.cycle, data, 1, value.into()),
+                TicValue::URMS3(value) => ctx.urms.updated(ctx.cycle, data, 2, value.into()),
+
+                // Index tarrifaire
+                TicValue::NTARF(value) => ctx.ntarf.updated(ctx.cycle, data, 0, value.into()),
+
+                // status register transitions
+                TicValue::STGE(value) => ctx.stge.updated(value),
+
+                // provider labels
+                TicValue::LTARF(ref value) => ctx.ltarf.updated(value, data.clone()),
+                TicValue::NGTF(ref value) => ctx.ngtf.updated(value, data.clone()),
+
+                // historic Tempo next day colour
+                TicValue::DEMAIN(value) => ctx.demain.updated(value.as_str(), data.clone()),
+
+                // energy counters
+                TicValue::EAST(value) => ctx.east.updated(ctx.cycle, data, 0, value as i64),
+                TicValue::EASF01(value) => ctx.easf.updated(ctx.cycle, data, 0, value as i64),
+                TicValue::EASF02(value) => ctx.easf.updated(ctx.cycle, data, 1, value as i64),
+                TicValue::EASF03(value) => ctx.easf.updated(ctx.cycle, data, 2, value as i64),
+                TicValue::EASF04(value) => ctx.easf.updated(ctx.cycle, data, 3, value as i64),
+                TicValue::EASF05(value) => ctx.easf.updated(ctx.cycle, data, 4, value as i64),
+                TicValue::EASF06(value) => ctx.easf.updated(ctx.cycle, data, 5, value as i64),
+                TicValue::EASF07(value) => ctx.easf.updated(ctx.cycle, data, 6, value as i64),
+                TicValue::EASF08(value) => ctx.easf.updated(ctx.cycle, data, 7, value as i64),
+                TicValue::EASF09(value) => ctx.easf.updated(ctx.cycle, data, 8, value as i64),
+                TicValue::EASF10(value) => ctx.easf.updated(ctx.cycle, data, 9, value as i64),
+                TicValue::EAIT(value) => ctx.eait.updated(ctx.cycle, data, 0, value as i64),
+
+                // producer meters, only kept when 'production' is set
+                TicValue::SINSTI(_)
+                | TicValue::SMAXIN(_)
+                | TicValue::SMAXIN1(_)
+                | TicValue::ERQ1(_)
+                | TicValue::ERQ2(_)
+                | TicValue::ERQ3(_)
+                | TicValue::ERQ4(_) => {
+                    if let Some(production) = &ctx.production {
+                        production.updated(ctx.cycle, data);
+                    }
+                }
+
+                // meter date
+                TicValue::DATE(value) => ctx.date.updated(value),
+                TicValue::SMAXSN(value) => ctx.smaxsn.updated(value),
+
+                // provider calendar
+                TicValue::PJOURF1(value) => ctx.pjourf1.updated(value),
+                TicValue::PPOINTE(value) => ctx.ppointe.updated(value),
+
+                // config declared labels
+                TicValue::CUSTOM(idx, value) => {
+                    if let Some(sensor) = ctx.customs.get(idx) {
+                        sensor.updated(ctx.cycle, data, 0, value.into())
+                    }
+                }
+
+                _ => {} // ignore any other data
+            };
+
+            if let Some(data) = sampled {
+                sample_dispatch(ctx, &data);
+            }
+        }
+    }
+}
+
+// power factor is estimated once per load curve point
+fn factor_dispatch(ctx: &EventDataCtx, data: &TicValue) {
+    match data {
+        TicValue::SINSTS(value) => ctx.factor.lock().push_apparent(*value),
+        TicValue::CCASN(value) => {
+            let factor = ctx.factor.lock().set_active(value.get_value());
+            if let Some(factor) = factor {
+                let percent = (factor * 100.0).round() as i32;
+                ctx.pf.updated(ctx.cycle, TicValue::PF(percent), 0, percent.into());
+            }
+        }
+        _ => {}
+    }
+}
+
+fn charge_dispatch(ctx: &EventDataCtx, data: &TicValue) {
+    let limit = {
+        let mut limiter = ctx.limiter.value.lock();
+        match data {
+            TicValue::ISOUSC(value) => limiter.set_subscribed(*value),
+            TicValue::PREF(value) => limiter.set_reference(*value),
+            TicValue::IINST(value) | TicValue::IINST1(value) | TicValue::IRMS1(value) => {
+                limiter.set_current(1, *value)
+            }
+            TicValue::IINST2(value) | TicValue::IRMS2(value) => limiter.set_current(2, *value),
+            TicValue::IINST3(value) | TicValue::IRMS3(value) => limiter.set_current(3, *value),
+            _ => return,
+        }
+        limiter.update(ctx.limiter.started.elapsed().as_secs_f64())
+    };
+    if let Some(value) = limit {
+        ctx.charge.updated(ctx.cycle, TicValue::CHARGE(value), 0, value.into());
+    }
+}
+
+// sensor values once updated, every value index is kept within the same sample
+fn sample_dispatch(ctx: &EventDataCtx, data: &TicValue) {
+    let sensor = |sensor: &SensorHandleCtx| {
+        let values = sensor.get_values();
+        (sensor.tic, values[0..sensor.tic.get_count()].to_vec())
+    };
+
+    let (tic, values) = match data {
+        TicValue::IINST(_) | TicValue::IINST1(_) | TicValue::IINST2(_) | TicValue::IINST3(_) => {
+            sensor(&ctx.iinst)
+        }
+        TicValue::SINSTS(_)
+        | TicValue::SINSTS1(_)
+        | TicValue::SINSTS2(_)
+        | TicValue::SINSTS3(_) => sensor(&ctx.sinsts),
+        TicValue::ADPS(_) | TicValue::ADIR1(_) | TicValue::ADIR2(_) | TicValue::ADIR3(_) => {
+            sensor(&ctx.adsp)
+        }
+        TicValue::PCOUP(_) | TicValue::PREF(_) => sensor(&ctx.pcou),
+        TicValue::IMAX(_) | TicValue::IMAX1(_) | TicValue::IMAX2(_) | TicValue::IMAX3(_) => {
+            sensor(&ctx.imax)
+        }
+        TicValue::PMAX(_) => sensor(&ctx.pmax),
+        TicValue::IRMS1(_) | TicValue::IRMS2(_) | TicValue::IRMS3(_) => sensor(&ctx.irms),
+        TicValue::URMS1(_) | TicValue::URMS2(_) | TicValue::URMS3(_) => sensor(&ctx.urms),
+        TicValue::NTARF(_) => sensor(&ctx.ntarf),
+        TicValue::CCASN(_) => sensor(&ctx.pf),
+        TicValue::CUSTOM(idx, _) => match ctx.customs.get(*idx) {
+            Some(custom) => sensor(custom),
+            None => return,
+        },
+        TicValue::EAST(_) => sensor(&ctx.east),
+        TicValue::EASF01(_)
+        | TicValue::EASF02(_)
+        | TicValue::EASF03(_)
+        | TicValue::EASF04(_)
+        | TicValue::EASF05(_)
+        | TicValue::EASF06(_)
+        | TicValue::EASF07(_)
+        | TicValue::EASF08(_)
+        | TicValue::EASF09(_)
+        | TicValue::EASF10(_) => sensor(&ctx.easf),
+        TicValue::EAIT(_) => sensor(&ctx.eait),
+        _ => return,
+    };
+
+    let uid = tic.get_uid();
+    let at = epoch_ms();
+    #[cfg(feature = "sqlite")]
+    if let Some(store) = &ctx.store {
+        let result = store.value.lock().insert(uid, at, &values);
+        if let Err(error) = result {
+            afb_log_msg!(Error, ctx.event, "fail to store sensor:{} error={}", uid, error);
+        }
+    }
+    if let Some(csv) = &ctx.csv {
+        let result = csv.value.lock().append(uid, at, &values);
+        if let Err(error) = result {
+            afb_log_msg!(Error, ctx.event, "fail to log sensor:{} error={}", uid, error);
+        }
+    }
+    #[cfg(feature = "influx")]
+    if let Some(influx) = &ctx.influx {
+        influx.value.lock().push(uid, at, &values);
+    }
+    #[cfg(feature = "redis")]
+    if let Some(redis) = &ctx.redis {
+        redis.value.lock().push(uid, at, &values);
+    }
+    #[cfg(feature = "mqtt")]
+    if let Some(sparkplug) = &ctx.sparkplug {
+        sparkplug.value.lock().set_value(tic, &values);
+    }
+    #[cfg(feature = "dbus")]
+    if let Some(dbus) = &ctx.dbus {
+        dbus.value.lock().set_value(uid, &values);
+    }
+}
+
+// close lost source and retry with exponential backoff
+fn source_lost(fd: &AfbEvtFd, ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    afb_log_msg!(
+        Warning,
+        ctx.event,
+        "source:{} lost, reconnect in {}ms",
+        ctx.handle.get_name(),
+        ctx.delay.get()
+    );
+    fd.unref();
+    ctx.evtfd.set(None);
+    ctx.handle.close();
+    ctx.event.broadcast("source-offline");
+    outage_update(ctx, |outage| outage.set_link_lost());
+    reconnect_arm(ctx)
+}
+
+fn reconnect_arm(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    AfbTimer::new("linky-reconnect")
+        .set_period(ctx.delay.get())
+        .set_decount(1)
+        .set_callback(reconnect_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    Ok(())
+}
+
+fn reconnect_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+
+    match ctx.handle.reopen() {
+        Err(error) => {
+            let delay = u32::min(ctx.delay.get().saturating_mul(2), ctx.backoff);
+            afb_log_msg!(
+                Debug,
+                ctx.event,
+                "source:{} reconnect fail {}, retry in {}ms",
+                ctx.handle.get_name(),
+                error,
+                delay
+            );
+            ctx.handle.close();
+            ctx.delay.set(delay);
+            reconnect_arm(ctx)?;
+        }
+        Ok(()) => {
+            afb_log_msg!(Info, ctx.event, "source:{} reconnected", ctx.handle.get_name());
+            ctx.delay.set(ctx.reconnect.get());
+            ctx.event.broadcast("source-online");
+            source_start(ctx)?;
+        }
+    }
+    Ok(())
+}
+
+// backup source only feeds sensors while primary is silent
+fn async_backup_cb(fd: &AfbEvtFd, revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+    let backup = match ctx.handle.get_backup() {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+
+    let status = if revent != AfbEvtFdPoll::IN.bits() {
+        Err(LinkyError::ReopenDev)
+    } else {
+        source_drain_from(ctx, backup, LinkySource::Backup)
+    };
+
+    match status {
+        Err(LinkyError::ReopenDev | LinkyError::SerialError(_)) => {
+            afb_log_msg!(Warning, ctx.event, "backup:{} lost", backup.get_name());
+            fd.unref();
+            backup.close();
+            AfbTimer::new("linky-backup")
+                .set_period(u32::max(ctx.reconnect.get(), BACKUP_RETRY))
+                .set_decount(1)
+                .set_callback(backup_reconnect_cb)
+                .set_context(ctx.clone())
+                .start()?;
+        }
+        Err(error) => {
+            afb_log_msg!(Debug, ctx.event, "backup:{} error {}", backup.get_name(), error);
+        }
+        Ok(()) => budget_resume(ctx)?,
+    }
+    Ok(())
+}
+
+fn backup_reconnect_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let data = ctx.get_ref::<Arc<EventDataCtx>>()?;
+    let backup = match data.handle.get_backup() {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+
+    match backup.reopen() {
+        Ok(()) => backup_start(data),
+        Err(_) => {
+            backup.close();
+            AfbTimer::new("linky-backup")
+                .set_period(u32::max(data.reconnect.get(), BACKUP_RETRY))
+                .set_decount(1)
+                .set_callback(backup_reconnect_cb)
+                .set_context(data.clone())
+                .start()?;
+            Ok(())
+        }
+    }
+}
+
+fn backup_start(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    if let Some(backup) = ctx.handle.get_backup() {
+        AfbEvtFd::new(backup.get_name())
+            .set_fd(backup.get_fd())
+            .set_events(AfbEvtFdPoll::IN)
+            .set_callback(async_backup_cb)
+            .set_context(ctx.clone())
+            .start()?;
+    }
+    Ok(())
+}
+
+// no reconnect policy: closed source is reopened in place, io errors keep descriptor.
+// After 'fault_max' failures without a valid line, source stops until an admin reopen
+fn source_fault(ctx: &Arc<EventDataCtx>, error: LinkyError) -> Result<(), AfbError> {
+    let reopen = matches!(error, LinkyError::ReopenDev);
+    let action = ctx.fault.lock().push(reopen);
+    match action {
+        FaultAction::Ignore => Ok(()),
+        FaultAction::Reopen => source_retry(ctx),
+        FaultAction::Fail => {
+            afb_log_msg!(
+                Error,
+                ctx.event,
+                "source:{} failed after {} faults, waiting for reopen",
+                ctx.handle.get_name(),
+                ctx.fault.lock().get_faults()
+            );
+            if let Some(evtfd) = ctx.evtfd.take() {
+                evtfd.unref();
+            }
+            ctx.handle.close();
+            ctx.event.broadcast("source-failed");
+            Ok(())
+        }
+    }
+}
+
+// failed reopen is retried later and counts as a new fault
+fn source_retry(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    if let Some(evtfd) = ctx.evtfd.take() {
+        evtfd.unref();
+    }
+    match ctx.handle.reopen() {
+        Ok(()) => {
+            afb_log_msg!(Notice, ctx.event, "source:{} reopened", ctx.handle.get_name());
+            source_start(ctx)
+        }
+        Err(error) => {
+            let name = ctx.handle.get_name();
+            afb_log_msg!(Warning, ctx.event, "source:{} reopen fail {}", name, error);
+            ctx.handle.close();
+            AfbTimer::new("linky-fault")
+                .set_period(FAULT_RETRY)
+                .set_decount(1)
+                .set_callback(fault_retry_cb)
+                .set_context(ctx.clone())
+                .start()?;
+            Ok(())
+        }
+    }
+}
+
+fn fault_retry_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+
+    // admin reopen or reconfigure already restarted source
+    if ctx.evtfd.get().is_some() {
+        return Ok(());
+    }
+    source_fault(ctx, LinkyError::ReopenDev)
+}
+
+// USB dongle removed, device is reopened from hotplug watch
+fn source_unplug(fd: &AfbEvtFd, ctx: &Arc<EventDataCtx>) {
+    afb_log_msg!(Warning, ctx.event, "device:{} unplugged", ctx.handle.get_name());
+    fd.unref();
+    ctx.evtfd.set(None);
+    ctx.handle.close();
+    ctx.online.set(false);
+    ctx.event.broadcast("unplugged");
+    outage_update(ctx, |outage| outage.set_link_lost());
+}
+
+fn hotplug_cb(_fd: &AfbEvtFd, _revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+    if let Some(watch) = &ctx.watch {
+        watch.drain();
+    }
+
+    // udev creates by-id links after device node, give it some time
+    if !ctx.online.get() && !ctx.probing.get() {
+        ctx.probing.set(true);
+        AfbTimer::new("linky-hotplug")
+            .set_period(HOTPLUG_DELAY)
+            .set_decount(1)
+            .set_callback(hotplug_timer_cb)
+            .set_context(ctx.clone())
+            .start()?;
+    }
+    Ok(())
+}
+
+fn hotplug_timer_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+    ctx.probing.set(false);
+
+    let present = match &ctx.watch {
+        Some(watch) => watch.is_present(),
+        None => false,
+    };
+
+    if !present || ctx.online.get() {
+        return Ok(());
+    }
+
+    match ctx.handle.reopen() {
+        Err(error) => {
+            afb_log_msg!(Debug, ctx.event, "device:{} reopen fail {}", ctx.handle.get_name(), error);
+        }
+        Ok(()) => {
+            afb_log_msg!(Notice, ctx.event, "device:{} plugged", ctx.handle.get_name());
+            ctx.online.set(true);
+            ctx.event.broadcast("plugged");
+            source_start(ctx)?;
+        }
+    }
+    Ok(())
+}
+
+// meter silence is reported once, 'data-resumed' is sent with next decoded line
+fn watchdog_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+
+    let silence = ctx.last_valid.get().elapsed();
+    if !ctx.stale.get() && silence >= Duration::from_secs(ctx.watchdog as u64) {
+        afb_log_msg!(
+            Warning,
+            ctx.event,
+            "source:{} no valid data since {}s",
+            ctx.handle.get_name(),
+            silence.as_secs()
+        );
+        ctx.stale.set(true);
+        ctx.event.broadcast("data-stale");
+        let since = epoch_ms().saturating_sub(silence.as_millis() as u64);
+        outage_update(ctx, |outage| outage.set_silent(since));
+    }
+    gateway_check(ctx);
+    Ok(())
+}
+
+// outage transition as 'outage-start'/'outage-end' on main event, record on 'outages' event
+fn outage_update<F>(ctx: &EventDataCtx, update: F)
+where
+    F: FnOnce(&mut OutageTracker) -> Option<OutageEvent>,
+{
+    let outage = match &ctx.outage {
+        Some(value) => value,
+        None => return,
+    };
+    let (status, record) = match update(&mut outage.value.lock()) {
+        Some(OutageEvent::START(record)) => ("outage-start", record),
+        Some(OutageEvent::END(record)) => ("outage-end", record),
+        None => return,
+    };
+    afb_log_msg!(
+        Warning,
+        ctx.event,
+        "source:{} {} after {}s",
+        ctx.handle.get_name(),
+        status,
+        record.get_duration(epoch_ms()) / 1000
+    );
+    ctx.event.broadcast(status);
+
+    let jsonc = outage_jsonc(&record).and_then(|jsonc| {
+        jsonc.add("status", status)?;
+        Ok(jsonc)
+    });
+    match jsonc {
+        Ok(jsonc) => {
+            outage.event.push(jsonc);
+        }
+        Err(error) => {
+            afb_log_msg!(Error, ctx.event, "fail to build outage error={}", error);
+        }
+    }
+}
+
+// '{"start":1699607299000,"end":1699607359000,"duration_ms":60000}' epoch ms, no end while down
+fn outage_jsonc(record: &OutageRecord) -> Result<JsoncObj, AfbError> {
+    let jsonc = JsoncObj::new();
+    jsonc.add("start", record.start)?;
+    if let Some(end) = record.end {
+        jsonc.add("end", end)?;
+    }
+    jsonc.add("duration_ms", record.get_duration(epoch_ms()))?;
+    Ok(jsonc)
+}
+
+// network bridge going quiet or moving to another address, serial sources have no gateway
+fn gateway_check(ctx: &EventDataCtx) {
+    let (addr, stamp) = match ctx.handle.get_gateway() {
+        None => return,
+        Some(value) => value,
+    };
+    let silent = stamp.elapsed() >= Duration::from_secs(ctx.watchdog as u64);
+
+    match ctx.gateway.get() {
+        Some((known, false)) if known == addr && silent => {
+            afb_log_msg!(
+                Warning,
+                ctx.event,
+                "source:{} gateway:{} silent since {}s",
+                ctx.handle.get_name(),
+                addr,
+                stamp.elapsed().as_secs()
+            );
+            ctx.gateway.set(Some((addr, true)));
+            ctx.event.broadcast("gateway-lost");
+        }
+        Some((known, lost)) if known != addr || (lost && !silent) => {
+            afb_log_msg!(
+                Notice,
+                ctx.event,
+                "source:{} gateway:{} back (was {})",
+                ctx.handle.get_name(),
+                addr,
+                known
+            );
+            ctx.gateway.set(Some((addr, silent)));
+            ctx.event.broadcast("gateway-back");
+        }
+        Some(_) => {}
+        None => ctx.gateway.set(Some((addr, silent))),
+    }
+}
+
+// consumption periods, tariff counters and cost baselines, see 'state' config
+fn state_collect(ctx: &EventDataCtx) -> LinkyState {
+    let mut state = LinkyState::default();
+    for feature in ctx.features.iter() {
+        feature.get_state(&mut state);
+    }
+    state
+}
+
+fn state_save(ctx: &EventDataCtx) {
+    if let Some(file) = &ctx.state {
+        if let Err(error) = file.save(&state_collect(ctx)) {
+            afb_log_msg!(Error, ctx.event, "fail to save state error={}", error);
+        }
+    }
+}
+
+fn state_restore(ctx: &EventDataCtx, file: &StateFile) {
+    let state = match file.load() {
+        Ok(Some(value)) => value,
+        Ok(None) => return,
+        Err(error) => {
+            afb_log_msg!(Warning, ctx.event, "state not restored error={}", error);
+            return;
+        }
+    };
+    for feature in ctx.features.iter() {
+        feature.set_state(&state);
+    }
+    afb_log_msg!(Notice, ctx.event, "state restored path:{}", file.get_path());
+}
+
+fn state_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+    state_save(ctx);
+    Ok(())
+}
+
+// restore before first frame, then save every 'interval' s and on exit
+fn state_start(ctx: &Arc<EventDataCtx>, interval: u32) -> Result<(), AfbError> {
+    let file = match &ctx.state {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    state_restore(ctx, file);
+    if interval > 0 {
+        AfbTimer::new("linky-state")
+            .set_period(interval * 1000)
+            .set_decount(0)
+            .set_callback(state_cb)
+            .set_context(ctx.clone())
+            .start()?;
+    }
+    Ok(())
+}
+
+fn watchdog_start(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    if ctx.watchdog > 0 {
+        AfbTimer::new("linky-watchdog")
+            .set_period(WATCHDOG_TICK)
+            .set_decount(0)
+            .set_callback(watchdog_cb)
+            .set_context(ctx.clone())
+            .start()?;
+    }
+    Ok(())
+}
+
+// failed push is retried on next tick, only state transitions are logged
+#[cfg(feature = "influx")]
+fn influx_flush_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<InfluxHandleCtx>>()?;
+    let mut sink = ctx.value.lock();
+
+    match sink.flush() {
+        Err(error) => {
+            if !ctx.failing.get() {
+                afb_log_msg!(Warning, None, "influx push fail error={}", error);
+                ctx.failing.set(true);
+            }
+        }
+        Ok(_) => {
+            if ctx.failing.get() {
+                afb_log_msg!(Notice, None, "influx push resumed dropped:{}", sink.get_dropped());
+                ctx.failing.set(false);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "influx")]
+fn mk_influx(config: &InfluxConfig) -> Result<Arc<InfluxHandleCtx>, AfbError> {
+    let sink = InfluxSink::new(
+        config.url,
+        config.org,
+        config.bucket,
+        config.token,
+        config.measurement,
+        config.buffer,
+        config.uids.clone(),
+    )?;
+    let ctx = Arc::new(InfluxHandleCtx {
+        failing: SyncCell::new(false),
+        value: SyncLock::new(sink),
+    });
+
+    AfbTimer::new("linky-influx")
+        .set_period(config.flush)
+        .set_decount(0)
+        .set_callback(influx_flush_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    Ok(ctx)
+}
+
+// same retry policy as influx, connection is reopened on next tick
+#[cfg(feature = "redis")]
+fn redis_flush_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<RedisHandleCtx>>()?;
+    let mut sink = ctx.value.lock();
+
+    match sink.flush() {
+        Err(error) => {
+            if !ctx.failing.get() {
+                afb_log_msg!(Warning, None, "redis push fail error={}", error);
+                ctx.failing.set(true);
+            }
+        }
+        Ok(_) => {
+            if ctx.failing.get() {
+                afb_log_msg!(Notice, None, "redis push resumed dropped:{}", sink.get_dropped());
+                ctx.failing.set(false);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "redis")]
+fn mk_redis(config: &RedisConfig) -> Result<Arc<RedisHandleCtx>, AfbError> {
+    let sink = RedisSink::new(
+        config.host,
+        config.port,
+        config.password,
+        config.stream,
+        config.maxlen,
+        config.buffer,
+        config.uids.clone(),
+    );
+    let ctx = Arc::new(RedisHandleCtx {
+        failing: SyncCell::new(false),
+        value: SyncLock::new(sink),
+    });
+
+    AfbTimer::new("linky-redis")
+        .set_period(config.flush)
+        .set_decount(0)
+        .set_callback(redis_flush_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    Ok(ctx)
+}
+
+// broker lost, NDEATH is published by broker from connect will
+#[cfg(feature = "mqtt")]
+fn sparkplug_lost(ctx: &SparkplugHandleCtx, error: LinkyFail) {
+    afb_log_msg!(Warning, None, "sparkplug broker:{} lost error={}", ctx.host, error);
+    ctx.client.replace(None);
+    if let Some(evtfd) = ctx.evtfd.take() {
+        evtfd.unref();
+    }
+}
+
+#[cfg(feature = "mqtt")]
+fn sparkplug_publish(ctx: &SparkplugHandleCtx, message: (String, Vec<u8>)) {
+    let (topic, payload) = message;
+    let result = match ctx.client.lock().as_mut() {
+        None => return,
+        Some(client) => client.publish(topic.as_str(), &payload, false),
+    };
+    if let Err(error) = result {
+        sparkplug_lost(ctx, error);
+    }
+}
+
+#[cfg(feature = "mqtt")]
+fn sparkplug_connect(ctx: &Arc<SparkplugHandleCtx>) -> Result<(), AfbError> {
+    let mut node = ctx.value.lock();
+    let (topic, death) = node.new_session();
+    let will = MqttWill {
+        topic: topic.as_str(),
+        payload: &death,
+        retain: false,
+    };
+    let client_id = node.get_topic("client").replace('/', "-");
+    let mut client = MqttClient::connect(
+        ctx.host,
+        ctx.port,
+        client_id.as_str(),
+        ctx.keepalive as u16,
+        ctx.username,
+        ctx.password,
+        Some(&will),
+    )?;
+    client.subscribe(node.get_topic("NCMD").as_str())?;
+
+    let evtfd = AfbEvtFd::new("linky-sparkplug")
+        .set_fd(client.get_raw_fd())
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(sparkplug_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    ctx.evtfd.set(Some(evtfd));
+    ctx.client.replace(Some(client));
+    ctx.last_ping.set(Instant::now());
+    afb_log_msg!(Notice, None, "sparkplug broker:{} connected as {}", ctx.host, client_id);
+    Ok(())
+}
+
+// rebirth is the only node command supported
+#[cfg(feature = "mqtt")]
+fn sparkplug_cb(_fd: &AfbEvtFd, _revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<SparkplugHandleCtx>>()?;
+
+    let result = match ctx.client.lock().as_mut() {
+        None => return Ok(()),
+        Some(client) => client.process(),
+    };
+    match result {
+        Err(error) => sparkplug_lost(ctx, error),
+        Ok(messages) => {
+            if messages.iter().any(|(_, payload)| spb_is_rebirth(payload)) {
+                afb_log_msg!(Notice, None, "sparkplug rebirth requested");
+                let birth = ctx.value.lock().birth(epoch_ms());
+                sparkplug_publish(ctx, birth);
+            }
+        }
+    }
+    Ok(())
+}
+
+// (re)connect, then birth certificate when metric set changed, then pending NDATA
+#[cfg(feature = "mqtt")]
+fn sparkplug_tick_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<SparkplugHandleCtx>>()?;
+
+    if ctx.client.lock().is_none() {
+        let retry = match ctx.last_connect.get() {
+            Some(last) => last.elapsed() >= Duration::from_secs(SPARKPLUG_RETRY),
+            None => true,
+        };
+        if !retry {
+            return Ok(());
+        }
+        ctx.last_connect.set(Some(Instant::now()));
+        if let Err(error) = sparkplug_connect(ctx) {
+            afb_log_msg!(Debug, None, "sparkplug connect fail error={}", error);
+            return Ok(());
+        }
+    }
+
+    let at = epoch_ms();
+    let mut node = ctx.value.lock();
+    let message = if node.is_born() {
+        node.data(at)
+    } else {
+        Some(node.birth(at))
+    };
+    drop(node);
+    if let Some(message) = message {
+        sparkplug_publish(ctx, message);
+    }
+
+    let keepalive = Duration::from_secs(ctx.keepalive as u64 / 2);
+    if ctx.keepalive > 0 && ctx.last_ping.get().elapsed() >= keepalive {
+        ctx.last_ping.set(Instant::now());
+        let result = match ctx.client.lock().as_mut() {
+            None => Ok(()),
+            Some(client) => client.ping(),
+        };
+        if let Err(error) = result {
+            sparkplug_lost(ctx, error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "mqtt")]
+fn mk_sparkplug(config: &SparkplugConfig) -> Result<Arc<SparkplugHandleCtx>, AfbError> {
+    let ctx = Arc::new(SparkplugHandleCtx {
+        host: config.host,
+        port: config.port,
+        username: config.username,
+        password: config.password,
+        keepalive: config.keepalive,
+        client: SyncLock::new(None),
+        evtfd: SyncCell::new(None),
+        last_connect: SyncCell::new(None),
+        last_ping: SyncCell::new(Instant::now()),
+        value: SyncLock::new(SparkplugNode::new(config.group, config.node, config.uids.clone())),
+    });
+
+    AfbTimer::new("linky-sparkplug")
+        .set_period(u32::max(config.interval, 100))
+        .set_decount(0)
+        .set_callback(sparkplug_tick_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    Ok(ctx)
+}
+
+#[cfg(feature = "modbus")]
+fn modbus_client_cb(fd: &AfbEvtFd, revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<ModbusClientCtx>()?;
+
+    let alive = revent == AfbEvtFdPoll::IN.bits()
+        && ctx.client.lock().process(&ctx.handle.value.lock());
+    if !alive {
+        afb_log_msg!(Debug, None, "modbus client left");
+        fd.unref();
+    }
+    Ok(())
+}
+
+#[cfg(feature = "modbus")]
+fn modbus_accept_cb(_fd: &AfbEvtFd, _revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<ModbusHandleCtx>>()?;
+
+    while let Some(client) = ctx.server.accept() {
+        AfbEvtFd::new("linky-modbus-client")
+            .set_fd(client.get_raw_fd())
+            .set_events(AfbEvtFdPoll::IN)
+            .set_callback(modbus_client_cb)
+            .set_context(ModbusClientCtx {
+                handle: ctx.clone(),
+                client: SyncLock::new(client),
+            })
+            .start()?;
+    }
+    Ok(())
+}
+
+// register map is documented in linky-lib modbus-server.rs
+#[cfg(feature = "modbus")]
+fn mk_modbus(addr: &'static str, port: u16) -> Result<Arc<ModbusHandleCtx>, AfbError> {
+    let ctx = Arc::new(ModbusHandleCtx {
+        server: ModbusServer::new(addr, port)?,
+        value: SyncLock::new(ModbusRegisters::new()),
+    });
+
+    AfbEvtFd::new("linky-modbus")
+        .set_fd(ctx.server.get_raw_fd())
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(modbus_accept_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    afb_log_msg!(Notice, None, "modbus server addr:{} port:{} ready", addr, port);
+    Ok(ctx)
+}
+
+#[cfg(feature = "dbus")]
+fn dbus_cb(fd: &AfbEvtFd, _revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<DbusHandleCtx>>()?;
+
+    if !ctx.value.lock().process() {
+        afb_log_msg!(Warning, None, "d-bus connection lost, service stopped");
+        fd.unref();
+    }
+    Ok(())
+}
+
+#[cfg(feature = "dbus")]
+fn mk_dbus(system: bool, name: &'static str) -> Result<Arc<DbusHandleCtx>, AfbError> {
+    let ctx = Arc::new(DbusHandleCtx {
+        value: SyncLock::new(DbusExport::new(system, name)?),
+    });
+
+    AfbEvtFd::new("linky-dbus")
+        .set_fd(ctx.value.lock().get_raw_fd())
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(dbus_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    afb_log_msg!(Notice, None, "d-bus service name:{} path:{} ready", name, DBUS_PATH);
+    Ok(ctx)
+}
+
+// polled sources have no fd and are fetched on timer ticks
+fn source_tick_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+
+    if let Err(error) = source_drain(ctx) {
+        afb_log_msg!(Debug, ctx.event, "source:{} fetch fail {}", ctx.handle.get_name(), error);
+        source_error(ctx, ctx.handle.get_name(), &error);
+    }
+    budget_resume(ctx)
+}
+
+fn source_start(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    if ctx.interval.get() > 0 {
+        let timer = AfbTimer::new(ctx.handle.get_name())
+            .set_period(ctx.interval.get())
+            .set_decount(0)
+            .set_callback(source_tick_cb)
+            .set_context(ctx.clone())
+            .start()?;
+        ctx.timer.set(Some(timer));
+        return Ok(());
+    }
+
+    // tcp client connects in background, fd is watched for write until it settles
+    if ctx.handle.is_connecting() {
+        let evtfd = AfbEvtFd::new(ctx.handle.get_name())
+            .set_fd(ctx.handle.get_fd())
+            .set_events(AfbEvtFdPoll::OUT)
+            .set_callback(async_connect_cb)
+            .set_context(ctx.clone())
+            .start()?;
+        ctx.evtfd.set(Some(evtfd));
+        return Ok(());
+    }
+
+    let evtfd = AfbEvtFd::new(ctx.handle.get_name())
+        .set_fd(ctx.handle.get_fd())
+        .set_events(AfbEvtFdPoll::IN)
+        .set_callback(async_serial_cb)
+        .set_context(ctx.clone())
+        .start()?;
+    ctx.evtfd.set(Some(evtfd));
+    Ok(())
+}
+
+// connect settled, source is watched for data or follows usual failure path
+fn async_connect_cb(fd: &AfbEvtFd, _revent: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<Arc<EventDataCtx>>()?;
+
+    if let Err(error) = ctx.handle.set_connected() {
+        afb_log_msg!(Debug, ctx.event, "source:{} {}", ctx.handle.get_name(), error);
+        if ctx.reconnect.get() > 0 {
+            return source_lost(fd, ctx);
+        }
+        return source_fault(ctx, LinkyError::ReopenDev);
+    }
+
+    afb_log_msg!(Notice, ctx.event, "source:{} connected", ctx.handle.get_name());
+    fd.unref();
+    ctx.evtfd.set(None);
+    source_start(ctx)
+}
+
+// binder unloads api, fds are unregistered from main loop before sources are closed
+fn source_shutdown(ctx: &EventDataCtx) {
+    state_save(ctx);
+    if let Some(evtfd) = ctx.evtfd.take() {
+        evtfd.unref();
+    }
+    if let Some(timer) = ctx.timer.take() {
+        timer.unref();
+    }
+    ctx.raw.recorder.lock().stop();
+    ctx.handle.close();
+    if let Some(backup) = ctx.handle.get_backup() {
+        backup.close();
+    }
+    afb_log_msg!(Notice, ctx.event, "source:{} closed", ctx.handle.get_name());
+}
+
+// api controls releasing every meter source on exit
+#[derive(Clone)]
+pub(crate) struct ApiShutdown {
+    meters: Vec<Arc<EventDataCtx>>,
+}
+
+impl ApiShutdown {
+    pub fn new() -> ApiShutdown {
+        ApiShutdown { meters: Vec::new() }
+    }
+
+    // hooks subcalls need the finalized api
+    pub fn set_api(&self, api: &'static AfbApi) {
+        for ctx in &self.meters {
+            for feature in ctx.features.iter() {
+                feature.set_api(api);
+            }
+        }
+    }
+}
+
+impl AfbApiControls for ApiShutdown {
+    fn exit(&mut self, _api: &AfbApi, code: i32) -> i32 {
+        for ctx in &self.meters {
+            source_shutdown(ctx);
+        }
+        code
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// operator forced reopen, recovers a wedged device without restarting the binder
+fn source_reopen(ctx: &Arc<EventDataCtx>) -> Result<(), AfbError> {
+    afb_log_msg!(Notice, ctx.event, "source:{} reopen requested", ctx.handle.get_name());
+    ctx.fault.lock().reset();
+
+    // polled sources keep their timer running
+    if ctx.interval.get() > 0 {
+        ctx.handle.reopen()?;
+        return Ok(());
+    }
+
+    if let Some(evtfd) = ctx.evtfd.take() {
+        evtfd.unref();
+    }
+
+    if let Err(error) = ctx.handle.reopen() {
+        ctx.handle.close();
+        ctx.online.set(false);
+        ctx.event.broadcast("source-offline");
+        if ctx.reconnect.get() > 0 {
+            reconnect_arm(ctx)?;
+        }
+        return Err(error.into());
+    }
+
+    ctx.online.set(true);
+    ctx.event.broadcast("source-online");
+    source_start(ctx)
+}
+
+// move meter to a new source (e.g. serial to udp) without restarting the binder
+fn source_reconfigure(ctx: &Arc<EventDataCtx>, config: LinkyConfig) -> Result<(), AfbError> {
+    // hotplug watch is bound to configured device
+    if ctx.watch.is_some() {
+        return afb_error!("linky-control-fail", "reconfigure not supported with hotplug");
+    }
+
+    // new source is opened first, current one keeps running on failure
+    ctx.handle.reconfigure(&config)?;
+    if let Some(evtfd) = ctx.evtfd.take() {
+        evtfd.unref();
+    }
+    if let Some(timer) = ctx.timer.take() {
+        timer.unref();
+    }
+
+    afb_log_msg!(
+        Notice,
+        ctx.event,
+        "source:{} mode:{} reconfigured",
+        ctx.handle.get_name(),
+        ctx.handle.get_mode()
+    );
+    ctx.raw.source.replace(format!(
+        "source:{} mode:{}",
+        ctx.handle.get_name(),
+        ctx.handle.get_mode()
+    ));
+    ctx.reconnect.set(config.get_reconnect());
+    ctx.delay.set(config.get_reconnect());
+    ctx.interval.set(config.get_interval());
+    ctx.fault.lock().reset();
+    ctx.online.set(true);
+    ctx.event.broadcast("source-online");
+    source_start(ctx)
+}
+
+// wrap sensor value with its unit as '{"value":..., "unit":"VA"}'
+fn unit_wrap(tic: &TicObject, value: JsoncObj) -> Result<JsoncObj, AfbError> {
+    let jsonc = JsoncObj::new();
+    jsonc.add("value", value)?;
+    jsonc.add("unit", tic.get_unit().as_str())?;
+    Ok(jsonc)
+}
+
+// phase 1-3 map to value index, 4 values sensors keep index 0 for mono/total (IINST/IINST1-3)
+fn phase_index(tic: &TicObject, phase: usize) -> Option<usize> {
+    let idx = if tic.get_count() == 4 {
+        Some(phase)
+    } else {
+        phase.checked_sub(1)
+    };
+    idx.filter(|idx| *idx < tic.get_count())
+}
+
+// phase sensor value index to object key, 4 values sensors hold mono/total at index 0
+fn phase_keys(tic: &TicObject) -> Option<&'static [&'static str]> {
+    match tic.get_count() {
+        3 => Some(&["l1", "l2", "l3"]),
+        4 => Some(&["total", "l1", "l2", "l3"]),
+        _ => None,
+    }
+}
+
+// converted value rounded to 1/1000, keeps kWh exact and hides power factor float noise
+fn unit_convert(value: i64, factor: f64) -> f64 {
+    (value as f64 * factor * 1000.0).round() / 1000.0
+}
+
+// reply shaping from sensor query, selected value indexes and unit conversion
+struct QueryView {
+    indexes: Vec<usize>,
+    factor: Option<f64>, // None: raw sensor value
+    unit: Option<String>,
+    keys: Option<&'static [&'static str]>,
+    raw: bool, // uncalibrated values
+}
+
+impl QueryView {
+    fn new(
+        tic: &TicObject,
+        query: &SensorQuery,
+        reply: &ReplyFormat,
+    ) -> Result<QueryView, AfbError> {
+        let mut indexes = Vec::new();
+        match &query.phases {
+            None => indexes.extend(0..tic.get_count()),
+            Some(phases) => {
+                for phase in phases {
+                    match phase_index(tic, *phase) {
+                        Some(idx) => indexes.push(idx),
+                        None => {
+                            return afb_error!(
+                                "linky-phase-invalid",
+                                "sensor:{} phase:{} not available",
+                                tic.get_uid(),
+                                phase
+                            )
+                        }
+                    }
+                }
+            }
+        }
+
+        // explicit query unit first, then config default target for this sensor unit
+        let base = tic.get_unit().as_str();
+        let unit = match &query.unit {
+            Some(unit) => Some(unit.clone()),
+            None => reply.convert.target(base).map(|unit| unit.to_string()),
+        };
+        let factor = match unit.as_deref() {
+            None => None,
+            Some(unit) if unit == base => None,
+            Some(unit) => match reply.convert.factor(base, unit) {
+                Some(factor) => Some(factor),
+                None => {
+                    return afb_error!(
+                        "linky-unit-invalid",
+                        "sensor:{} unit:{} not convertible from {}",
+                        tic.get_uid(),
+                        unit,
+                        base
+                    )
+                }
+            },
+        };
+
+        Ok(QueryView {
+            indexes,
+            factor,
+            unit,
+            keys: if reply.phase_keys { phase_keys(tic) } else { None },
+            raw: query.raw,
+        })
+    }
+
+    // positional array, or phase keyed object when 'phase_keys' is set
+    fn values_jsonc(&self, values: &[i64]) -> Result<JsoncObj, AfbError> {
+        let jsonc = match self.keys {
+            Some(_) => JsoncObj::new(),
+            None => JsoncObj::array(),
+        };
+        for (pos, idx) in self.indexes.iter().enumerate() {
+            let value = values[*idx];
+            match (self.keys, self.factor) {
+                (Some(keys), None) => {
+                    jsonc.add(keys[*idx], value)?;
+                }
+                (Some(keys), Some(factor)) => {
+                    jsonc.add(keys[*idx], unit_convert(value, factor))?;
+                }
+                (None, None) => {
+                    jsonc.insert(pos, value)?;
+                }
+                (None, Some(factor)) => {
+                    jsonc.insert(pos, unit_convert(value, factor))?;
+                }
+            }
+        }
+        Ok(jsonc)
+    }
+
+    fn unit_str<'a>(&'a self, tic: &'a TicObject) -> &'a str {
+        match &self.unit {
+            Some(unit) => unit.as_str(),
+            None => tic.get_unit().as_str(),
+        }
+    }
+
+    // explicit unit is always returned, sensor default unit follows 'units' config
+    fn wrap(&self, tic: &TicObject, units: bool, value: JsoncObj) -> Result<JsoncObj, AfbError> {
+        match &self.unit {
+            Some(unit) => {
+                let jsonc = JsoncObj::new();
+                jsonc.add("value", value)?;
+                jsonc.add("unit", unit.as_str())?;
+                Ok(jsonc)
+            }
+            None if units => unit_wrap(tic, value),
+            None => Ok(value),
+        }
+    }
+
+    // '[{"timestamp":1699610899123,"value":[...]}, ...]' last samples only when count is set
+    fn history_jsonc(
+        &self,
+        samples: Vec<(u64, Vec<i64>)>,
+        count: Option<usize>,
+    ) -> Result<JsoncObj, AfbError> {
+        let skip = match count {
+            Some(count) => samples.len().saturating_sub(count),
+            None => 0,
+        };
+        let jsonc = JsoncObj::array();
+        for (idx, (timestamp, values)) in samples.iter().skip(skip).enumerate() {
+            let jsample = JsoncObj::new();
+            jsample.add("timestamp", *timestamp)?;
+            jsample.add("value", self.values_jsonc(values)?)?;
+            jsonc.insert(idx, jsample)?;
+        }
+        Ok(jsonc)
+    }
+}
+
+pub(crate) fn epoch_ms() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(value) => value.as_millis() as u64,
+        Err(_) => 0,
+    }
+}
+
+// '{"reported":true,"last_update":1699610899123,"age_ms":1520}' tells zero from never received
+pub(crate) fn freshness_jsonc(last_update: Option<Instant>) -> Result<JsoncObj, AfbError> {
+    let jsonc = JsoncObj::new();
+    jsonc.add("reported", last_update.is_some())?;
+    if let Some(stamp) = last_update {
+        let age = stamp.elapsed().as_millis() as u64;
+        jsonc.add("last_update", epoch_ms().saturating_sub(age))?;
+        jsonc.add("age_ms", age)?;
+    }
+    Ok(jsonc)
+}
+
+// sensor read reply layout, v1 replies '[value, freshness]', v2 a single common envelope
+#[derive(Clone, Copy)]
+struct ReplyFormat {
+    schema: ReplySchema,
+    stale: Option<Duration>, // watchdog delay, older values are flagged stale
+    phase_keys: bool,
+    convert: UnitConvert,
+}
+
+impl ReplyFormat {
+    fn new(config: &BindingConfig) -> ReplyFormat {
+        let stale = if config.watchdog > 0 {
+            Some(Duration::from_secs(config.watchdog as u64))
+        } else {
+            None
+        };
+        ReplyFormat {
+            schema: config.schema,
+            stale,
+            phase_keys: config.phase_keys,
+            convert: config.convert,
+        }
+    }
+
+    fn is_v2(&self) -> bool {
+        self.schema == ReplySchema::V2
+    }
+
+    // '{"unit":"V","ts":1699610899123,"quality":"good"}' caller adds "value" when known,
+    // quality is 'init' until first value and 'stale' once value is older than watchdog
+    fn envelope(&self, unit: &str, last_update: Option<Instant>) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("unit", unit)?;
+        let quality = match last_update {
+            None => "init",
+            Some(stamp) => {
+                let age = stamp.elapsed();
+                jsonc.add("ts", epoch_ms().saturating_sub(age.as_millis() as u64))?;
+                match self.stale {
+                    Some(delay) if age > delay => "stale",
+                    _ => "good",
+                }
+            }
+        };
+        jsonc.add("quality", quality)?;
+        Ok(jsonc)
+    }
+}
+
+// keep last pushed samples, oldest are dropped once ring is full
+fn history_add<T>(history: &mut VecDeque<(u64, T)>, size: usize, values: T) {
+    if size == 0 {
+        return;
+    }
+    while history.len() >= size {
+        history.pop_front();
+    }
+    history.push_back((epoch_ms(), values));
+}
+
+fn history_trim<T>(history: &mut VecDeque<(u64, T)>, size: usize) {
+    while history.len() > size {
+        history.pop_front();
+    }
+}
+
+// all sensors change feed, batch mode keeps frame updates as text until frame closes
+struct ChangeFeed {
+    event: &'static AfbEvent,
+    batch: bool,
+    framed: SyncCell<bool>, // STX/ETX seen, DATE no longer delimits frames
+    pending: SyncLock<Vec<(&'static str, String)>>,
+    pushes: SyncLock<BTreeMap<&'static str, u64>>, // change count per uid, 'metrics' verb
+}
+
+// unified change feed as '{"uid":"SINSTS","value":[...],"timestamp":1699610899123}',
+// in batch mode last value per uid waits for batch_push
+fn change_push(changes: &ChangeFeed, tic: &TicObject, value: Result<JsoncObj, AfbError>) {
+    *changes.pushes.lock().entry(tic.get_uid()).or_insert(0) += 1;
+    if changes.batch {
+        match value {
+            Ok(value) => {
+                let mut pending = changes.pending.lock();
+                pending.retain(|(uid, _)| *uid != tic.get_uid());
+                pending.push((tic.get_uid(), value.to_string()));
+            }
+            Err(error) => {
+                afb_log_msg!(Error, changes.event, "fail to batch change error={}", error)
+            }
+        }
+        return;
+    }
+
+    let timestamp = epoch_ms();
+    let jchange = value.and_then(|value| {
+        let jsonc = JsoncObj::new();
+        jsonc.add("uid", tic.get_uid())?;
+        jsonc.add("value", value)?;
+        jsonc.add("timestamp", timestamp)?;
+        Ok(jsonc)
+    });
+
+    match jchange {
+        Ok(jsonc) => {
+            changes.event.push(jsonc);
+        }
+        Err(error) => afb_log_msg!(Error, changes.event, "fail to push change error={}", error),
+    }
+}
+
+// '{"uid":"batch","value":{"SINSTS":[...],"EAST":[...]},"frame":{...},"timestamp":..}'
+// frame summary is missing when DATE delimits the batch
+fn batch_jsonc(
+    pending: &[(&'static str, String)],
+    frame: Option<&TicFrame>,
+) -> Result<JsoncObj, AfbError> {
+    let jvalues = JsoncObj::new();
+    for (uid, text) in pending {
+        jvalues.add(uid, JsoncObj::parse(text.as_str())?)?;
+    }
+    let jsonc = JsoncObj::new();
+    jsonc.add("uid", "batch")?;
+    jsonc.add("value", jvalues)?;
+    if let Some(frame) = frame {
+        jsonc.add("frame", frame.to_jsonc()?)?;
+    }
+    jsonc.add("timestamp", epoch_ms())?;
+    Ok(jsonc)
+}
+
+// one event per frame carrying every update received since previous batch
+fn batch_push(changes: &ChangeFeed, frame: Option<&TicFrame>) {
+    let pending = std::mem::take(&mut *changes.pending.lock());
+    if pending.is_empty() && frame.is_none() {
+        return;
+    }
+
+    match batch_jsonc(&pending, frame) {
+        Ok(jsonc) => {
+            changes.event.push(jsonc);
+        }
+        Err(error) => afb_log_msg!(Error, changes.event, "fail to push batch error={}", error),
+    }
+}
+
+// frame summary on change feed as '{"uid":"frame","value":{"seq":12,"complete":true,...}}',
+// consumers know values received since previous summary form a partial snapshot
+fn frame_push(changes: &ChangeFeed, frame: &TicFrame) {
+    changes.framed.set(true);
+    if changes.batch {
+        batch_push(changes, Some(frame));
+        return;
+    }
+
+    let timestamp = epoch_ms();
+    let jframe = frame.to_jsonc().and_then(|value| {
+        let jsonc = JsoncObj::new();
+        jsonc.add("uid", "frame")?;
+        jsonc.add("value", value)?;
+        jsonc.add("timestamp", timestamp)?;
+        Ok(jsonc)
+    });
+
+    match jframe {
+        Ok(jsonc) => {
+            changes.event.push(jsonc);
+        }
+        Err(error) => afb_log_msg!(Error, changes.event, "fail to push frame error={}", error),
+    }
+}
+
+// push event data with or without unit depending on config
+fn unit_push(event: &AfbEvent, tic: &TicObject, units: bool, data: TicValue) {
+    if !units {
+        event.push(data);
+        return;
+    }
+
+    match serde_jsonc(&data).and_then(|value| unit_wrap(tic, value)) {
+        Ok(jsonc) => {
+            event.push(jsonc);
+        }
+        Err(error) => {
+            afb_log_msg!(Error, event, "fail to add unit error={}", error);
+            event.push(data);
+        }
+    }
+}
+
+// NTARF event with tariff period name next to raw index as '{"NTARF":2,"name":"HP Bleu"}'
+fn name_push(event: &AfbEvent, tic: &TicObject, units: bool, data: TicValue, name: &str) {
+    let jsonc = serde_jsonc(&data)
+        .and_then(|value| if units { unit_wrap(tic, value) } else { Ok(value) })
+        .and_then(|jsonc| {
+            jsonc.add("name", name)?;
+            Ok(jsonc)
+        });
+
+    match jsonc {
+        Ok(jsonc) => {
+            event.push(jsonc);
+        }
+        Err(error) => {
+            afb_log_msg!(Error, event, "fail to add tariff name error={}", error);
+            event.push(data);
+        }
+    }
+}
+
+impl SensorState {
+    fn new(config: SensorConfig) -> Self {
+        SensorState {
+            last_update: None,
+            values: [0; SENSOR_VALUES],
+            raws: [0; SENSOR_VALUES],
+            count: 0,
+            config,
+            reported: [0; SENSOR_VALUES],
+            pushed: [None; SENSOR_VALUES],
+            pending: [false; SENSOR_VALUES],
+            history: VecDeque::with_capacity(config.history),
+            window: Default::default(),
+        }
+    }
+
+    fn is_significant(&self, idx: usize, value: i64) -> bool {
+        let reported = self.reported[idx];
+        let diff = value.abs_diff(reported);
+        let relative = reported.unsigned_abs() * self.config.delta_pct as u64 / 100;
+        diff > 0 && diff >= u64::max(self.config.delta as u64, relative)
+    }
+}
+
+// if new/old value diverge send event and update value cache
+impl SensorHandleCtx {
+    fn get_event(&self, phase: Option<usize>) -> Result<&'static AfbEvent, AfbError> {
+        let phase = match phase {
+            None => return Ok(self.event),
+            Some(value) => value,
+        };
+
+        match phase_index(self.tic, phase).and_then(|idx| self.phases.get(idx)) {
+            Some(event) => Ok(event),
+            None => afb_error!(
+                "linky-phase-invalid",
+                "sensor:{} phase:{} not available",
+                self.tic.get_uid(),
+                phase
+            ),
+        }
+    }
+
+    pub fn get_values(&self) -> [i64; SENSOR_VALUES] {
+        self.state.lock().values
+    }
+
+    pub fn get_last_update(&self) -> Option<Instant> {
+        self.state.lock().last_update
+    }
+
+    // NTARF tariff period name
+    fn get_tariff_name(&self) -> Option<String> {
+        self.tariff_name(&self.get_values())
+    }
+
+    fn tariff_name(&self, values: &[i64; SENSOR_VALUES]) -> Option<String> {
+        let index = i32::try_from(values[0]).ok()?;
+        self.tariffs.as_ref()?.get_name(index)
+    }
+
+    // current values as json array, wrapped with unit when requested
+    fn values_jsonc(&self, values: &[i64; SENSOR_VALUES]) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::array();
+        for idx in 0..self.tic.get_count() {
+            jsonc.insert(idx, values[idx])?;
+        }
+        if self.units {
+            unit_wrap(self.tic, jsonc)
+        } else {
+            Ok(jsonc)
+        }
+    }
+
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        self.values_jsonc(&self.get_values())
+    }
+
+    pub fn query_values(&self, view: &QueryView) -> Result<JsoncObj, AfbError> {
+        let state = self.state.lock();
+        view.values_jsonc(if view.raw { &state.raws } else { &state.values })
+    }
+
+    pub fn query_jsonc(&self, view: &QueryView) -> Result<JsoncObj, AfbError> {
+        view.wrap(self.tic, self.units, self.query_values(view)?)
+    }
+
+    // pushed samples, see QueryView::history_jsonc
+    pub fn history_jsonc(
+        &self,
+        view: &QueryView,
+        count: Option<usize>,
+    ) -> Result<JsoncObj, AfbError> {
+        let samples = self
+            .state
+            .lock()
+            .history
+            .iter()
+            .map(|(stamp, values)| (*stamp, values.to_vec()))
+            .collect();
+        view.wrap(self.tic, self.units, view.history_jsonc(samples, count)?)
+    }
+
+    pub fn config_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let config = self.state.lock().config;
+        let jsonc = JsoncObj::new();
+        jsonc.add("units", self.units)?;
+        jsonc.add("enabled", config.enabled)?;
+        jsonc.add("min_interval_ms", config.min_interval)?;
+        jsonc.add("delta", config.delta)?;
+        jsonc.add("delta_pct", config.delta_pct)?;
+        jsonc.add("history", config.history as u32)?;
+        jsonc.add("window", config.window as u32)?;
+        jsonc.add("scale", config.scale)?;
+        jsonc.add("offset", config.offset)?;
+        Ok(jsonc)
+    }
+
+    // apply new tuning, history and statistics rings shrink to their new size
+    pub fn configure(&self, config: SensorConfig) {
+        let mut state = self.state.lock();
+        state.config = config;
+        history_trim(&mut state.history, config.history);
+        for samples in state.window.iter_mut() {
+            while samples.len() > config.window {
+                samples.pop_front();
+            }
+        }
+        if !config.enabled {
+            state.pending = [false; SENSOR_VALUES];
+        }
+    }
+
+    pub fn reset(&self) {
+        let mut state = self.state.lock();
+        let config = state.config;
+        *state = SensorState::new(config);
+    }
+
+    // rolling statistics per value index as '[{"min":..,"max":..,"avg":..,"count":..}, ...]'
+    pub fn stats_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let state = self.state.lock();
+        if state.config.window == 0 {
+            return afb_error!(
+                "linky-stats-disabled",
+                "sensor:{} statistics window is 0",
+                self.tic.get_uid()
+            );
+        }
+
+        let jsonc = JsoncObj::array();
+        for idx in 0..self.tic.get_count() {
+            let samples = &state.window[idx];
+            let jstats = JsoncObj::new();
+            jstats.add("count", samples.len() as u32)?;
+            if let (Some(min), Some(max)) = (samples.iter().min(), samples.iter().max()) {
+                let sum: i64 = samples.iter().sum();
+                jstats.add("min", *min)?;
+                jstats.add("max", *max)?;
+                jstats.add("avg", sum as f64 / samples.len() as f64)?;
+            }
+            jsonc.insert(idx, jstats)?;
+        }
+        Ok(jsonc)
+    }
+
+    pub fn updated(&self, cycle: u32, data: TicValue, idx: usize, value: i64) {
+        // sensor state moves under one guard, events are pushed once it is released
+        let mut state = self.state.lock();
+        let config = state.config;
+        if !config.enabled {
+            return;
+        }
+        state.last_update = Some(Instant::now());
+
+        // calibration happens before caching, events carry the corrected value
+        state.raws[idx] = value;
+        let (data, value) = if config.is_calibrated() {
+            let value = config.calibrate(value);
+            let value = if self.counter { value.max(0) } else { value };
+            (data.with_value(value), value)
+        } else {
+            (data, value)
+        };
+
+        // increase cycle counter and force event if needed
+        let forced = if cycle > 0 {
+            if state.count == cycle {
+                true
+            } else {
+                state.count += 1;
+                false
+            }
+        } else {
+            false
+        };
+
+        state.values[idx] = value;
+        if config.window > 0 {
+            let samples = &mut state.window[idx];
+            if samples.len() >= config.window {
+                samples.pop_front();
+            }
+            samples.push_back(value);
+        }
+
+        // small jitter is ignored, change is measured against last pushed value
+        let changed = state.is_significant(idx, value);
+        if !changed && !forced && !state.pending[idx] {
+            return;
+        }
+
+        // rate limited change is kept pending and pushed with latest value once interval elapsed
+        if let Some(stamp) = state.pushed[idx] {
+            if stamp.elapsed() < Duration::from_millis(config.min_interval as u64) {
+                state.pending[idx] = true;
+                return;
+            }
+        }
+
+        state.pending[idx] = false;
+        state.reported[idx] = value;
+        state.pushed[idx] = Some(Instant::now());
+        state.count = 0;
+        let values = state.values;
+        history_add(&mut state.history, config.history, values);
+        drop(state);
+
+        if let Some(event) = self.phases.get(idx) {
+            unit_push(event, self.tic, self.units, data.clone());
+        }
+        match self.tariff_name(&values) {
+            Some(name) => name_push(self.event, self.tic, self.units, data, &name),
+            None => unit_push(self.event, self.tic, self.units, data),
+        }
+        change_push(self.changes, self.tic, self.values_jsonc(&values));
+    }
+}
+
+impl TextHandleCtx {
+    pub fn updated(&self, value: &str, data: TicValue) {
+        self.last_update.set(Some(Instant::now()));
+        if self.value.lock().as_deref() == Some(value) {
+            return;
+        }
+        self.value.replace(Some(value.to_string()));
+        self.event.push(data);
+        change_push(self.changes, self.tic, serde_jsonc(value));
+    }
+}
+
+impl StampHandleCtx {
+    pub fn updated(&self, value: TimeStampData) {
+        self.last_update.set(Some(Instant::now()));
+        let rollover = match self.value.replace(Some(value)) {
+            Some(previous) => !previous.is_same_day(&value),
+            None => false,
+        };
+        if rollover {
+            match value.to_jsonc() {
+                Ok(jsonc) => {
+                    self.event.push(jsonc);
+                }
+                Err(error) => afb_log_msg!(Error, self.event, "fail to push date error={}", error),
+            }
+            change_push(self.changes, self.tic, value.to_jsonc());
+        }
+    }
+}
+
+impl PowerHandleCtx {
+    pub fn updated(&self, value: PowerMaxData) {
+        self.last_update.set(Some(Instant::now()));
+        if self.value.replace(Some(value)) == Some(value) {
+            return;
+        }
+        match value.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => afb_log_msg!(Error, self.event, "fail to push max power error={}", error),
+        }
+        change_push(self.changes, self.tic, value.to_jsonc());
+    }
+}
+
+impl ProductionHandleCtx {
+    pub fn updated(&self, cycle: u32, data: TicValue) {
+        match data {
+            TicValue::SINSTI(value) => self.sinsti.updated(cycle, data, 0, value.into()),
+            TicValue::SMAXIN(value) => self.smaxin.updated(value),
+            TicValue::SMAXIN1(value) => self.smaxin1.updated(value),
+            TicValue::ERQ1(value) => self.erq.updated(cycle, data, 0, value as i64),
+            TicValue::ERQ2(value) => self.erq.updated(cycle, data, 1, value as i64),
+            TicValue::ERQ3(value) => self.erq.updated(cycle, data, 2, value as i64),
+            TicValue::ERQ4(value) => self.erq.updated(cycle, data, 3, value as i64),
+            _ => {}
+        }
+    }
+
+    fn get_events(&self) -> [&'static AfbEvent; 5] {
+        [
+            self.sinsti.event,
+            self.smaxin.event,
+            self.smaxin1.event,
+            self.eait.event,
+            self.erq.event,
+        ]
+    }
+
+    // '{"SINSTI":[..],"SMAXIN":{..},"SMAXIN-1":{..},"EAIT":[..],"ERQ":[..]}' maxima once received
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add(self.sinsti.tic.get_uid(), self.sinsti.to_jsonc()?)?;
+        for power in [&self.smaxin, &self.smaxin1] {
+            if let Some(value) = power.value.get() {
+                jsonc.add(power.tic.get_uid(), value.to_jsonc()?)?;
+            }
+        }
+        jsonc.add(self.eait.tic.get_uid(), self.eait.to_jsonc()?)?;
+        jsonc.add(self.erq.tic.get_uid(), self.erq.to_jsonc()?)?;
+        Ok(jsonc)
+    }
+}
+
+impl ProfileHandleCtx {
+    pub fn updated(&self, value: ProviderProfile) {
+        self.last_update.set(Some(Instant::now()));
+        if self.value.lock().as_ref() == Some(&value) {
+            return;
+        }
+        match value.to_jsonc() {
+            Ok(jsonc) => {
+                self.event.push(jsonc);
+            }
+            Err(error) => afb_log_msg!(Error, self.event, "fail to push profile error={}", error),
+        }
+        change_push(self.changes, self.tic, value.to_jsonc());
+        self.value.replace(Some(value));
+    }
+}
+
+impl RegisterHandleCtx {
+    fn to_jsonc(status: &RegisterStatus) -> Result<JsoncObj, AfbError> {
+        serde_jsonc(status)
+    }
+
+    // first status is the reference, only later transitions are pushed
+    pub fn updated(&self, value: RegisterStatus) {
+        self.last_update.set(Some(Instant::now()));
+        let previous = match self.value.replace(Some(value)) {
+            Some(previous) => previous,
+            None => return,
+        };
+
+        let fields = value.changes(&previous);
+        if fields.is_empty() {
+            return;
+        }
+
+        let jevent = match self.transition(&previous, &value, &fields) {
+            Ok(jsonc) => jsonc,
+            Err(error) => {
+                afb_log_msg!(Error, self.event, "fail to build register event error={}", error);
+                return;
+            }
+        };
+        self.event.push(jevent);
+        change_push(self.changes, self.tic, RegisterHandleCtx::to_jsonc(&value));
+    }
+
+    // '{"uid":"STGE","changes":[{"field":"relay_open","old":false,"new":true}]}'
+    fn transition(
+        &self,
+        previous: &RegisterStatus,
+        value: &RegisterStatus,
+        fields: &[&str],
+    ) -> Result<JsoncObj, AfbError> {
+        let jold = RegisterHandleCtx::to_jsonc(previous)?;
+        let jnew = RegisterHandleCtx::to_jsonc(value)?;
+        let jchanges = JsoncObj::array();
+        for (idx, field) in fields.iter().enumerate() {
+            let jchange = JsoncObj::new();
+            jchange.add("field", *field)?;
+            jchange.add("old", jold.get::<JsoncObj>(field)?)?;
+            jchange.add("new", jnew.get::<JsoncObj>(field)?)?;
+            jchanges.insert(idx, jchange)?;
+        }
+
+        let jsonc = JsoncObj::new();
+        jsonc.add("uid", self.tic.get_uid())?;
+        jsonc.add("raw", value.raw)?;
+        jsonc.add("changes", jchanges)?;
+        Ok(jsonc)
+    }
+}
+
+impl RawFrameCtx {
+    // accumulate raw lines and keep the last STX/ETX delimited frame
+    pub fn append(&self, data: &[u8]) {
+        let mut recorder = self.recorder.lock();
+        if let Err(error) = recorder.write(epoch_ms(), data) {
+            afb_log_msg!(Error, None, "capture stopped error={}", error);
+        }
+        drop(recorder);
+
+        let mut frame = self.frame.lock();
+        let mut start = 0;
+        for (idx, byte) in data.iter().enumerate() {
+            match *byte {
+                ETX => {
+                    frame.push_str(&String::from_utf8_lossy(&data[start..idx]));
+                    let text = frame.clone();
+                    frame.clear();
+                    self.event.push(text.as_str());
+                    self.last.replace(text);
+                    start = idx + 1;
+                }
+                STX => {
+                    frame.clear();
+                    start = idx + 1;
+                }
+                _ => {}
+            }
+        }
+        frame.push_str(&String::from_utf8_lossy(&data[start..]));
+    }
+}
+
+struct RawDataCtx {
+    handle: Arc<RawFrameCtx>,
+}
+
+fn rawcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<RawDataCtx>()?;
+
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ => {
+            response.push(ctx.handle.last.lock().clone())?;
+        }
+        ApiAction::INFO => {
+            response.push("last complete raw frame before parsing")?;
+            response.push(ctx.handle.source.lock().clone())?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+struct RecordDataCtx {
+    handle: Arc<RawFrameCtx>,
+}
+
+// '{"path":"/var/log/linky/capture.tic"}' capture is readable by replay source
+fn record_startcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<RecordDataCtx>()?;
+
+    let jquery = args.get::<JsoncObj>(0)?;
+    let path = match jquery.get::<String>("path") {
+        Ok(value) => value,
+        Err(_) => return afb_error!("linky-record-path", "missing capture 'path'"),
+    };
+    ctx.handle.recorder.lock().start(path.as_str())?;
+    afb_log_msg!(Notice, rqt, "raw capture started path:{}", path);
+
+    let jsonc = JsoncObj::new();
+    jsonc.add("path", path.as_str())?;
+    rqt.reply(jsonc, 0);
+    Ok(())
+}
+
+fn record_stopcb(rqt: &AfbRequest, _args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<RecordDataCtx>()?;
+
+    let mut recorder = ctx.handle.recorder.lock();
+    if !recorder.stop() {
+        return afb_error!("linky-record-idle", "no raw capture running");
+    }
+    afb_log_msg!(Notice, rqt, "raw capture stopped path:{}", recorder.get_path());
+
+    let jsonc = JsoncObj::new();
+    jsonc.add("path", recorder.get_path())?;
+    jsonc.add("lines", recorder.get_lines())?;
+    jsonc.add("bytes", recorder.get_bytes())?;
+    rqt.reply(jsonc, 0);
+    Ok(())
+}
+
+struct HealthDataCtx {
+    ctx: Arc<EventDataCtx>,
+    event: &'static AfbEvent,
+    started: Instant,
+}
+
+// '{"source":"/dev/ttyUSB0","state":"online","active":"primary","age_ms":1200,...}'
+fn health_jsonc(health: &HealthDataCtx) -> Result<JsoncObj, AfbError> {
+    let ctx = &health.ctx;
+    let connected = ctx.evtfd.get().is_some() || ctx.timer.get().is_some();
+    let state = if ctx.fault.lock().is_failed() {
+        "failed"
+    } else if !ctx.online.get() {
+        "unplugged"
+    } else if !connected {
+        "reconnecting"
+    } else if ctx.stale.get() {
+        "stale"
+    } else {
+        "online"
+    };
+
+    let jsonc = JsoncObj::new();
+    jsonc.add("source", ctx.handle.get_name())?;
+    jsonc.add("state", state)?;
+    jsonc.add("active", ctx.handle.get_active().as_str())?;
+    jsonc.add("age_ms", ctx.last_valid.get().elapsed().as_millis() as u64)?;
+    jsonc.add("uptime_s", health.started.elapsed().as_secs())?;
+    jsonc.add("primary", stats_json(&ctx.handle)?)?;
+    if let Some(backup) = ctx.handle.get_backup() {
+        jsonc.add("backup", stats_json(backup)?)?;
+    }
+    Ok(jsonc)
+}
+
+fn health_cb(_timer: &AfbTimer, _decount: u32, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let health = ctx.get_ref::<Arc<HealthDataCtx>>()?;
+    health.event.push(health_jsonc(health)?);
+    Ok(())
+}
+
+fn healthcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let health = ctx.get_ref::<Arc<HealthDataCtx>>()?;
+
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ => {
+            response.push(health_jsonc(health)?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            health.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            health.event.unsubscribe(rqt)?;
+        }
+        _ => {
+            return afb_error!(
+                "linky-health-action",
+                "health only support read|subscribe|unsubscribe"
+            )
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+struct InjectDataCtx {
+    ctx: Arc<EventDataCtx>,
+}
+
+// '{"data":"SINSTS\t00022\tJ\r\n"}' one line or a whole frame, checksum is verified and
+// decoded lines reach sensors exactly as data read from the source
+fn injectcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = &ctx.get_ref::<InjectDataCtx>()?.ctx;
+
+    let jquery = args.get::<JsoncObj>(0)?;
+    let data = match jquery.get::<String>("data") {
+        Ok(value) => value,
+        Err(_) => return afb_error!("linky-inject-data", "missing 'data' tic line(s)"),
+    };
+
+    let mut lines = 0;
+    let mut valid = 0;
+    for line in data.split_inclusive(['\n', ETX as char]) {
+        let line = line.trim_start_matches([STX as char, '\n']);
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut raw = line.as_bytes().to_vec();
+        if !matches!(raw.last(), Some(&b'\n') | Some(&ETX)) {
+            raw.push(b'\n');
+        }
+
+        let result = ctx.handle.parse(&raw, raw.len());
+        if result.is_ok() {
+            valid += 1;
+        }
+        lines += 1;
+        ctx.raw.append(&raw);
+        line_trace(ctx, &raw, &result);
+        line_dispatch(ctx, result);
+    }
+
+    let jsonc = JsoncObj::new();
+    jsonc.add("lines", lines)?;
+    jsonc.add("valid", valid)?;
+    rqt.reply(jsonc, 0);
+    Ok(())
+}
+
+struct DebugDataCtx {
+    ctx: Arc<EventDataCtx>,
+}
+
+// '{"enable":true}' dumps every received line to afb log, no argument reads current state
+fn debugcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = &ctx.get_ref::<DebugDataCtx>()?.ctx;
+
+    if let Ok(jquery) = args.get::<JsoncObj>(0) {
+        if let Ok(enable) = jquery.get::<bool>("enable") {
+            if enable != ctx.debug.get() {
+                afb_log_msg!(Notice, rqt, "source:{} debug:{}", ctx.handle.get_name(), enable);
+            }
+            ctx.debug.set(enable);
+        }
+    }
+
+    let jsonc = JsoncObj::new();
+    jsonc.add("enable", ctx.debug.get())?;
+    rqt.reply(jsonc, 0);
+    Ok(())
+}
+
+struct OutageDataCtx {
+    handle: Arc<OutageHandleCtx>,
+}
+
+// '{"active":{"start":..,"duration_ms":..},"outages":[{"start":..,"end":..,"duration_ms":..}]}'
+fn outagescb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<OutageDataCtx>()?;
+
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ => {
+            let outages = ctx.handle.value.lock();
+            let jsonc = JsoncObj::new();
+            if let Some(record) = outages.get_active() {
+                jsonc.add("active", outage_jsonc(&record)?)?;
+            }
+            let jrecords = JsoncObj::array();
+            for (idx, record) in outages.get_records().iter().enumerate() {
+                jrecords.insert(idx, outage_jsonc(record)?)?;
+            }
+            jsonc.add("outages", jrecords)?;
+            response.push(jsonc)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+struct ProductionDataCtx {
+    handle: Arc<ProductionHandleCtx>,
+}
+
+// subscribe covers every family member event at once
+fn productioncb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<ProductionDataCtx>()?;
+
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ => {
+            response.push(ctx.handle.to_jsonc()?)?;
+            response.push(freshness_jsonc(ctx.handle.sinsti.get_last_update())?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            for event in ctx.handle.get_events() {
+                event.subscribe(rqt)?;
+            }
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            for event in ctx.handle.get_events() {
+                event.unsubscribe(rqt)?;
+            }
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+struct StoreDataCtx {
+    handle: Arc<StoreHandleCtx>,
+}
+
+// '[{"timestamp":1699610899123,"value":[...]}, ...]' oldest first
+#[cfg(feature = "sqlite")]
+fn querycb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<StoreDataCtx>()?;
+    let query = args.get::<&StoreQuery>(0)?;
+
+    let store = ctx.handle.value.lock();
+    if !store.is_selected(query.uid.as_str()) {
+        return afb_error!("linky-query-fail", "sensor:{} not stored", query.uid);
+    }
+    let samples = store.query(
+        query.uid.as_str(),
+        query.from.unwrap_or(0),
+        query.to.unwrap_or(u64::MAX),
+        query.count.unwrap_or(usize::MAX),
+    )?;
+
+    let jsonc = JsoncObj::array();
+    for (idx, (timestamp, values)) in samples.iter().enumerate() {
+        let jvalues = JsoncObj::array();
+        for (pos, value) in values.iter().enumerate() {
+            jvalues.insert(pos, *value)?;
+        }
+        let jsample = JsoncObj::new();
+        jsample.add("timestamp", *timestamp)?;
+        jsample.add("value", jvalues)?;
+        jsonc.insert(idx, jsample)?;
+    }
+
+    let mut response = AfbParams::new();
+    response.push(jsonc)?;
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+struct StatsDataCtx {
+    ctx: Arc<EventDataCtx>,
+}
+
+fn stats_json(handle: &LinkyHandle) -> Result<JsoncObj, AfbError> {
+    let jstats = serde_jsonc(&handle.get_stats())?;
+    jstats.add("source", handle.get_name())?;
+    jstats.add("mode", handle.get_mode())?;
+    jstats.add("checksum_rate", handle.get_checksum_rate())?;
+    if let Some((addr, stamp)) = handle.get_gateway() {
+        let jgateway = JsoncObj::new();
+        jgateway.add("addr", addr.to_string())?;
+        jgateway.add("idle_ms", stamp.elapsed().as_millis() as u64)?;
+        jstats.add("gateway", jgateway)?;
+    }
+    let jlabels = JsoncObj::new();
+    for (label, count) in handle.get_checksum_labels() {
+        jlabels.add(label.as_str(), count)?;
+    }
+    jstats.add("checksum_labels", jlabels)?;
+    Ok(jstats)
+}
+
+fn statscb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<StatsDataCtx>()?;
+    let handle = &ctx.ctx.handle;
+
+    let mut response = AfbParams::new();
+    match args.get::<&StatsAction>(0)? {
+        StatsAction::READ => {
+            let jsonc = JsoncObj::new();
+            jsonc.add("primary", stats_json(handle)?)?;
+            if let Some(backup) = handle.get_backup() {
+                jsonc.add("backup", stats_json(backup)?)?;
+            }
+            response.push(jsonc)?;
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+struct MetricsDataCtx {
+    ctx: Arc<EventDataCtx>,
+    started: Instant,
+    sample: SyncLock<(Instant, LinkyStats)>, // previous scrape, rates are computed since then
+}
+
+// errors over every line received, 0 before first line
+fn error_rate(count: u64, stats: &LinkyStats) -> f64 {
+    let total = stats.lines + stats.checksum + stats.encoding + stats.parsing;
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+// '{"uptime_s":3600,"frames_s":0.59,"lines_s":12.4,"parse_error_rate":0.0,
+// "checksum_error_rate":0.001,"ring_peak":214,"ring_size":512,"frame_age_ms":820,
+// "pushes":{"SINSTS":1200,...}}' rates cover the period since previous read
+fn metricscb(rqt: &AfbRequest, _args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let metrics = ctx.get_ref::<MetricsDataCtx>()?;
+    let ctx = &metrics.ctx;
+
+    let stats = ctx.handle.get_stats();
+    let (stamp, previous) = metrics.sample.replace((Instant::now(), stats));
+    let elapsed = stamp.elapsed().as_secs_f64();
+    let rate = |count: u64, previous: u64| {
+        if elapsed > 0.0 {
+            count.saturating_sub(previous) as f64 / elapsed
+        } else {
+            0.0
+        }
+    };
+    let (peak, size) = ctx.handle.get_ring_usage();
+
+    let jsonc = JsoncObj::new();
+    jsonc.add("source", ctx.handle.get_name())?;
+    jsonc.add("uptime_s", metrics.started.elapsed().as_secs())?;
+    jsonc.add("period_s", elapsed)?;
+    jsonc.add("frames", stats.frames)?;
+    jsonc.add("lines", stats.lines)?;
+    jsonc.add("frames_s", rate(stats.frames, previous.frames))?;
+    jsonc.add("lines_s", rate(stats.lines, previous.lines))?;
+    jsonc.add("parse_error_rate", error_rate(stats.parsing, &stats))?;
+    jsonc.add("checksum_error_rate", error_rate(stats.checksum, &stats))?;
+    jsonc.add("ring_peak", peak as u64)?;
+    jsonc.add("ring_size", size as u64)?;
+    if let Some(stamp) = ctx.last_frame.get() {
+        jsonc.add("frame_age_ms", stamp.elapsed().as_millis() as u64)?;
+    }
+    jsonc.add("pushes", serde_jsonc(&*ctx.changes.pushes.lock())?)?;
+    rqt.reply(jsonc, 0);
+    Ok(())
+}
+
+struct ControlDataCtx {
+    ctx: Arc<EventDataCtx>,
+}
+
+fn controlcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<ControlDataCtx>()?;
+
+    match args.get::<&ControlAction>(0)? {
+        ControlAction::REOPEN => source_reopen(&ctx.ctx)?,
+        ControlAction::RECONFIGURE {
+            network,
+            serial,
+            replay,
+        } => {
+            let jsource = JsoncObj::new();
+            if let Some(value) = network {
+                jsource.add("network", JsoncObj::parse(value.to_string().as_str())?)?;
+            }
+            if let Some(value) = serial {
+                jsource.add("serial", JsoncObj::parse(value.to_string().as_str())?)?;
+            }
+            if let Some(value) = replay {
+                jsource.add("replay", JsoncObj::parse(value.to_string().as_str())?)?;
+            }
+            source_reconfigure(&ctx.ctx, parse_source(jsource)?)?;
+        }
+        ControlAction::RESET { uids } => {
+            let uids = uids.clone().unwrap_or_default();
+            sensors_reset(&ctx.ctx, &uids);
+        }
+        ControlAction::SENSORS { sensors } => {
+            let mut configs = Vec::new();
+            for jsensor in sensors {
+                let jsensor = JsoncObj::parse(jsensor.to_string().as_str())?;
+                configs.push(parse_sensor(jsensor, ctx.ctx.history, ctx.ctx.window)?);
+            }
+            sensors_reload(&ctx.ctx, &configs)?;
+        }
+    }
+
+    rqt.reply(AfbParams::new(), 0);
+    Ok(())
+}
+
+// restart sensors from a clean state, empty uids means every sensor
+fn sensors_reset(ctx: &EventDataCtx, uids: &[String]) {
+    let wanted = |uid: &str| uids.is_empty() || uids.iter().any(|value| value == uid);
+
+    // link counters and tariffs accumulation are not attached to a sensor
+    if uids.is_empty() {
+        for feature in ctx.features.iter() {
+            feature.reset();
+        }
+        #[cfg(feature = "modbus")]
+        if let Some(modbus) = &ctx.modbus {
+            modbus.value.lock().reset();
+        }
+        #[cfg(feature = "mqtt")]
+        if let Some(sparkplug) = &ctx.sparkplug {
+            sparkplug.value.lock().reset();
+        }
+        #[cfg(feature = "dbus")]
+        if let Some(dbus) = &ctx.dbus {
+            dbus.value.lock().reset();
+        }
+        if let Some(outage) = &ctx.outage {
+            outage.value.lock().reset();
+        }
+        ctx.factor.lock().reset();
+        ctx.limiter.value.lock().reset();
+        ctx.handle.reset_stats();
+        if let Some(backup) = ctx.handle.get_backup() {
+            backup.reset_stats();
+        }
+        ctx.changes.pushes.lock().clear();
+    }
+
+    let sensors = [
+        &ctx.iinst, &ctx.sinsts, &ctx.adsp, &ctx.adsc, &ctx.pcou, &ctx.ntarf, &ctx.irms, &ctx.urms,
+        &ctx.pf, &ctx.charge, &ctx.imax, &ctx.pmax, &ctx.east, &ctx.easf, &ctx.eait,
+    ];
+    for sensor in sensors.into_iter().chain(ctx.customs.iter()) {
+        if wanted(sensor.tic.get_uid()) {
+            sensor.reset();
+        }
+    }
+    for text in [&ctx.ltarf, &ctx.ngtf, &ctx.demain] {
+        if wanted(text.tic.get_uid()) {
+            text.value.replace(None);
+            text.last_update.set(None);
+        }
+    }
+    for profile in [&ctx.pjourf1, &ctx.ppointe] {
+        if wanted(profile.tic.get_uid()) {
+            profile.value.replace(None);
+            profile.last_update.set(None);
+        }
+    }
+    if wanted(ctx.date.tic.get_uid()) {
+        ctx.date.value.set(None);
+        ctx.date.last_update.set(None);
+    }
+    if wanted(ctx.smaxsn.tic.get_uid()) {
+        ctx.smaxsn.value.set(None);
+        ctx.smaxsn.last_update.set(None);
+    }
+    if wanted(ctx.stge.tic.get_uid()) {
+        ctx.stge.value.set(None);
+        ctx.stge.last_update.set(None);
+    }
+    afb_log_msg!(Notice, ctx.event, "sensors reset uids:{:?}", uids);
+}
+
+// apply a new 'sensors' config, sensors missing from the list get back default tuning
+fn sensors_reload(ctx: &EventDataCtx, configs: &[SensorConfig]) -> Result<(), AfbError> {
+    let sensors = [
+        &ctx.iinst, &ctx.sinsts, &ctx.adsp, &ctx.adsc, &ctx.pcou, &ctx.ntarf, &ctx.irms, &ctx.urms,
+        &ctx.pf, &ctx.charge, &ctx.imax, &ctx.pmax, &ctx.east, &ctx.easf, &ctx.eait,
+    ];
+    let numerics: Vec<&Arc<SensorHandleCtx>> =
+        sensors.into_iter().chain(ctx.customs.iter()).collect();
+
+    // check every uid before touching any sensor
+    for config in configs {
+        let known = numerics.iter().any(|sensor| sensor.tic.get_uid() == config.uid);
+        if !known {
+            return afb_error!("linky-sensor-unknown", "sensor uid:{} not tunable", config.uid);
+        }
+    }
+
+    for sensor in numerics {
+        let uid = sensor.tic.get_uid();
+        sensor.configure(find_sensor(configs, uid, ctx.history, ctx.window));
+    }
+
+    let uids: Vec<&str> = configs.iter().map(|config| config.uid).collect();
+    afb_log_msg!(Notice, ctx.event, "sensors reload uids:{:?}", uids);
+    Ok(())
+}
+
+struct SensorsDataCtx {
+    prefix: &'static str,
+    preset: Option<&'static [&'static str]>,
+    ctx: Arc<EventDataCtx>,
+}
+
+// '{"uid":"SINSTS","verb":"SINSTS","name":"Instant-Power","unit":"VA","multi":true,...}'
+fn sensor_jsonc(
+    prefix: &'static str,
+    tic: &TicObject,
+    config: JsoncObj,
+) -> Result<JsoncObj, AfbError> {
+    let jsonc = JsoncObj::new();
+    jsonc.add("uid", tic.get_uid())?;
+    jsonc.add("verb", mk_name(prefix, tic.get_uid()))?;
+    jsonc.add("alias", alias_name(prefix, tic).as_str())?;
+    jsonc.add("name", tic.get_name())?;
+    jsonc.add("info", tic.get_info())?;
+    jsonc.add("unit", tic.get_unit().as_str())?;
+    jsonc.add("multi", tic.get_count() > 1)?;
+    jsonc.add("count", tic.get_count() as u32)?;
+    jsonc.add("events", true)?;
+    jsonc.add("config", config)?;
+    Ok(jsonc)
+}
+
+fn sensorscb(rqt: &AfbRequest, _args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<SensorsDataCtx>()?;
+    let prefix = ctx.prefix;
+    let preset = ctx.preset;
+    let ctx = &ctx.ctx;
+
+    let jsonc = JsoncObj::array();
+    let mut jsensors = Vec::new();
+    let sensors = [
+        &ctx.iinst, &ctx.sinsts, &ctx.adsp, &ctx.adsc, &ctx.pcou, &ctx.ntarf, &ctx.irms, &ctx.urms,
+        &ctx.pf, &ctx.charge, &ctx.imax, &ctx.pmax, &ctx.east, &ctx.easf, &ctx.eait,
+    ];
+    // sensors outside of config preset have no verb, custom labels always have one
+    let exposed = |tic: &TicObject| preset_exposed(preset, tic.get_uid());
+    for sensor in sensors.into_iter().filter(|sensor| exposed(sensor.tic)) {
+        jsensors.push(sensor_jsonc(prefix, sensor.tic, sensor.config_jsonc()?)?);
+    }
+    for sensor in ctx.customs.iter() {
+        jsensors.push(sensor_jsonc(prefix, sensor.tic, sensor.config_jsonc()?)?);
+    }
+    for text in [&ctx.ltarf, &ctx.ngtf, &ctx.demain] {
+        if exposed(text.tic) {
+            jsensors.push(sensor_jsonc(prefix, text.tic, JsoncObj::new())?);
+        }
+    }
+    for tic in [ctx.stge.tic, ctx.date.tic, ctx.smaxsn.tic] {
+        if exposed(tic) {
+            jsensors.push(sensor_jsonc(prefix, tic, JsoncObj::new())?);
+        }
+    }
+    for profile in [&ctx.pjourf1, &ctx.ppointe] {
+        if exposed(profile.tic) {
+            jsensors.push(sensor_jsonc(prefix, profile.tic, JsoncObj::new())?);
+        }
+    }
+
+    for (idx, jsensor) in jsensors.into_iter().enumerate() {
+        jsonc.insert(idx, jsensor)?;
+    }
+
+    let mut response = AfbParams::new();
+    response.push(jsonc)?;
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register sensor discovery verb for generic clients
+fn mk_sensors(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("sensors");
+    verb.set_info("list registered sensors with metadata and config");
+    verb.set_permission(acl);
+    verb.set_callback(sensorscb);
+    verb.set_context(SensorsDataCtx {
+        prefix: config.prefix,
+        preset: config.preset,
+        ctx: ctx.clone(),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(())
+}
+
+struct ConfigDataCtx {
+    jconf: String, // effective config, serialized once at registration
+}
+
+fn configcb(rqt: &AfbRequest, _args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<ConfigDataCtx>()?;
+    let mut response = AfbParams::new();
+    response.push(JsoncObj::parse(ctx.jconf.as_str())?)?;
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// effective meter config with defaults applied, secrets are masked
+fn mk_config(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("config");
+    verb.set_info("effective configuration once defaults are applied");
+    verb.set_permission(acl);
+    verb.set_callback(configcb);
+    verb.set_context(ConfigDataCtx {
+        jconf: config.to_jsonc()?.to_string(),
+    });
+    verb.finalize()?;
+
+    api.add_admin(verb);
+    Ok(())
+}
+
+struct SnapshotDataCtx {
+    ctx: Arc<EventDataCtx>,
+}
+
+// sensors keyed by uid, empty uids means all. Every uid looked at lands in 'known', not yet
+// received labels included, so callers can tell a typo from a value still missing.
+fn snapshot_jsonc(
+    ctx: &EventDataCtx,
+    uids: &[String],
+    known: &mut Vec<&'static str>,
+) -> Result<JsoncObj, AfbError> {
+    let mut wanted = |uid: &'static str| {
+        known.push(uid);
+        uids.is_empty() || uids.iter().any(|value| value == uid)
+    };
+
+    let jsonc = JsoncObj::new();
+    let sensors = [
+        &ctx.iinst, &ctx.sinsts, &ctx.adsp, &ctx.adsc, &ctx.pcou, &ctx.ntarf, &ctx.irms, &ctx.urms,
+        &ctx.pf, &ctx.charge, &ctx.imax, &ctx.pmax, &ctx.east, &ctx.easf, &ctx.eait,
+    ];
+    for sensor in sensors.into_iter().chain(ctx.customs.iter()) {
+        if wanted(sensor.tic.get_uid()) {
+            jsonc.add(sensor.tic.get_uid(), sensor.to_jsonc()?)?;
+        }
+    }
+
+    if let Some(production) = &ctx.production {
+        if wanted(production.sinsti.tic.get_uid()) {
+            jsonc.add(production.sinsti.tic.get_uid(), production.sinsti.to_jsonc()?)?;
+        }
+        if wanted(production.erq.tic.get_uid()) {
+            jsonc.add(production.erq.tic.get_uid(), production.erq.to_jsonc()?)?;
+        }
+        for power in [&production.smaxin, &production.smaxin1] {
+            if wanted(power.tic.get_uid()) {
+                if let Some(value) = power.value.get() {
+                    jsonc.add(power.tic.get_uid(), value.to_jsonc()?)?;
+                }
+            }
+        }
+    }
+
+    // labels, date and calendars are only reported once received from meter
+    for text in [&ctx.ltarf, &ctx.ngtf, &ctx.demain] {
+        if wanted(text.tic.get_uid()) {
+            if let Some(value) = &*text.value.lock() {
+                jsonc.add(text.tic.get_uid(), value.as_str())?;
+            }
+        }
+    }
+    if wanted(ctx.date.tic.get_uid()) {
+        if let Some(stamp) = ctx.date.value.get() {
+            jsonc.add(ctx.date.tic.get_uid(), stamp.to_jsonc()?)?;
+        }
+    }
+    if wanted(ctx.smaxsn.tic.get_uid()) {
+        if let Some(power) = ctx.smaxsn.value.get() {
+            jsonc.add(ctx.smaxsn.tic.get_uid(), power.to_jsonc()?)?;
+        }
+    }
+    if wanted(ctx.stge.tic.get_uid()) {
+        if let Some(status) = ctx.stge.value.get() {
+            jsonc.add(ctx.stge.tic.get_uid(), RegisterHandleCtx::to_jsonc(&status)?)?;
+        }
+    }
+    for profile in [&ctx.pjourf1, &ctx.ppointe] {
+        if wanted(profile.tic.get_uid()) {
+            if let Some(value) = &*profile.value.lock() {
+                jsonc.add(profile.tic.get_uid(), value.to_jsonc()?)?;
+            }
+        }
+    }
+    Ok(jsonc)
+}
+
+// '{"uids":[...]}' from verb argument, missing list is an empty one
+fn query_uids(args: &AfbRqtData) -> Result<Vec<String>, AfbError> {
+    let mut uids = Vec::new();
+    if let Ok(jquery) = args.get::<JsoncObj>(0) {
+        if let Ok(juids) = jquery.get::<JsoncObj>("uids") {
+            for idx in 0..juids.count()? {
+                uids.push(juids.index::<String>(idx)?);
+            }
+        }
+    }
+    Ok(uids)
+}
+
+// return every sensor within one document keyed by uid, '{"uids":["IINST","SINSTS"]}' restricts the list
+fn snapshotcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = &ctx.get_ref::<SnapshotDataCtx>()?.ctx;
+
+    let uids = query_uids(args)?;
+    let jsonc = snapshot_jsonc(ctx, &uids, &mut Vec::new())?;
+
+    let mut response = AfbParams::new();
+    response.push(jsonc)?;
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// '{"uids":["SINSTS","NTARF","ENERGY"]}' only listed sensors plus 'ENERGY' energy manager view,
+// unknown uids are rejected and values not yet received are left out
+fn readmanycb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = &ctx.get_ref::<SnapshotDataCtx>()?.ctx;
+
+    let uids = query_uids(args)?;
+    if uids.is_empty() {
+        return afb_error!("linky-readmany-fail", "mandatory 'uids' list missing or empty");
+    }
+
+    let mut known = vec!["ENERGY"];
+    let jsonc = snapshot_jsonc(ctx, &uids, &mut known)?;
+    if let Some(uid) = uids.iter().find(|uid| !known.contains(&uid.as_str())) {
+        return afb_error!("linky-readmany-fail", "uid:{} unknown", uid);
+    }
+    if uids.iter().any(|uid| uid == "ENERGY") && ctx.evse.get_last_update().is_some() {
+        jsonc.add("ENERGY", ctx.evse.to_jsonc()?)?;
+    }
+
+    let mut response = AfbParams::new();
+    response.push(jsonc)?;
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+struct ChangesDataCtx {
+    event: &'static AfbEvent,
+}
+
+fn changescb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<ChangesDataCtx>()?;
+
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.event.unsubscribe(rqt)?;
+        }
+        _ => return afb_error!("linky-changes-action", "changes only support subscribe|unsubscribe"),
+    }
+
+    rqt.reply(AfbParams::new(), 0);
+    Ok(())
+}
+
+// register single event stream carrying every accepted sensor update
+fn mk_changes(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    batch: bool,
+) -> Result<&'static ChangeFeed, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "changes"));
+    let verb = AfbVerb::new("changes");
+
+    verb.set_info("all sensors change feed");
+    verb.set_permission(acl);
+    verb.set_actions("['subscribe', 'unsubscribe']")?;
+    verb.set_callback(changescb);
+    verb.set_context(ChangesDataCtx { event });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    api.add_event(event);
+    Ok(Box::leak(Box::new(ChangeFeed {
+        event,
+        batch,
+        framed: SyncCell::new(false),
+        pending: SyncLock::new(Vec::new()),
+        pushes: SyncLock::new(BTreeMap::new()),
+    })))
+}
+
+// register all sensors snapshot verb
+fn mk_snapshot(
+    api: &mut MeterApi,
+    acl: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("snapshot");
+    verb.set_info("read all sensors at once, optional {'uids':[...]} filter");
+    verb.set_permission(acl);
+    verb.set_callback(snapshotcb);
+    verb.set_context(SnapshotDataCtx { ctx: ctx.clone() });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(())
+}
+
+// register selective multi sensors read verb
+fn mk_read_many(
+    api: &mut MeterApi,
+    acl: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("read-many");
+    verb.set_info("read listed sensors at once, {'uids':['SINSTS','NTARF','ENERGY']}");
+    verb.set_permission(acl);
+    verb.set_callback(readmanycb);
+    verb.set_context(SnapshotDataCtx { ctx: ctx.clone() });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(())
+}
+
+// register operator control verb, protected by admin permission
+fn mk_control(
+    api: &mut MeterApi,
+    admin: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("control");
+    verb.set_info("source administration");
+    verb.set_actions("['reopen', 'reconfigure', 'reset', 'sensors']")?;
+    verb.set_permission(admin);
+    verb.set_callback(controlcb);
+    verb.set_context(ControlDataCtx { ctx: ctx.clone() });
+    verb.finalize()?;
+
+    api.add_admin(verb);
+    Ok(())
+}
+
+// register binding health verb and its periodic heartbeat event, period 0 disable heartbeat
+fn mk_health(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    period: u32,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "health"));
+    let verb = AfbVerb::new("health");
+
+    let health = Arc::new(HealthDataCtx {
+        ctx: ctx.clone(),
+        event,
+        started: Instant::now(),
+    });
+
+    verb.set_info("source state, last frame age, error counters and uptime");
+    verb.set_permission(acl);
+    verb.set_actions("['read', 'subscribe', 'unsubscribe']")?;
+    verb.set_callback(healthcb);
+    verb.set_context(health.clone());
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    api.add_event(event);
+
+    if period > 0 {
+        AfbTimer::new("linky-health")
+            .set_period(period)
+            .set_decount(0)
+            .set_callback(health_cb)
+            .set_context(health)
+            .start()?;
+    }
+    Ok(())
+}
+
+// register test frame injection verb, protected by admin permission
+fn mk_inject(
+    api: &mut MeterApi,
+    admin: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("inject");
+    verb.set_info("decode test tic line(s) as if read from source");
+    verb.set_permission(admin);
+    verb.set_callback(injectcb);
+    verb.set_context(InjectDataCtx { ctx: ctx.clone() });
+    verb.finalize()?;
+
+    api.add_admin(verb);
+    Ok(())
+}
+
+// register runtime frame dump verb, protected by admin permission
+fn mk_debug(
+    api: &mut MeterApi,
+    admin: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("debug");
+    verb.set_info("log raw lines with checksum status and parse result");
+    verb.set_permission(admin);
+    verb.set_callback(debugcb);
+    verb.set_context(DebugDataCtx { ctx: ctx.clone() });
+    verb.finalize()?;
+
+    api.add_admin(verb);
+    Ok(())
+}
+
+// register raw capture verbs, protected by admin permission
+fn mk_record(
+    api: &mut MeterApi,
+    admin: &'static AfbPermission,
+    raw: &Arc<RawFrameCtx>,
+) -> Result<(), AfbError> {
+    let start = AfbVerb::new("record-start");
+    start.set_info("record raw source lines to a replay capture file");
+    start.set_permission(admin);
+    start.set_callback(record_startcb);
+    start.set_context(RecordDataCtx {
+        handle: raw.clone(),
+    });
+    start.finalize()?;
+
+    let stop = AfbVerb::new("record-stop");
+    stop.set_info("close raw capture file");
+    stop.set_permission(admin);
+    stop.set_callback(record_stopcb);
+    stop.set_context(RecordDataCtx {
+        handle: raw.clone(),
+    });
+    stop.finalize()?;
+
+    api.add_admin(start);
+    api.add_admin(stop);
+    Ok(())
+}
+
+// register outages verb, only serial sources with a watchdog can tell an outage from silence
+fn mk_outage(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+) -> Result<Option<Arc<OutageHandleCtx>>, AfbError> {
+    let serial = matches!(config.source, LinkyConfig::Serial { .. });
+    if !serial || config.watchdog == 0 || config.outages == 0 {
+        return Ok(None);
+    }
+
+    let event = AfbEvent::new(mk_name(config.prefix, "outages"));
+    let verb = AfbVerb::new("outages");
+
+    let ctx = Arc::new(OutageHandleCtx {
+        event,
+        value: SyncLock::new(OutageTracker::new(config.outages)),
+    });
+
+    verb.set_info("probable meter power outages with start, end and duration");
+    verb.set_permission(acl);
+    verb.set_actions("['read', 'subscribe', 'unsubscribe']")?;
+    verb.set_callback(outagescb);
+    verb.set_context(OutageDataCtx {
+        handle: ctx.clone(),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    api.add_event(event);
+    Ok(Some(ctx))
+}
+
+// register producer sensors and their 'production' family verb
+fn mk_production(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    eait: &Arc<SensorHandleCtx>,
+) -> Result<Arc<ProductionHandleCtx>, AfbError> {
+    let ctx = Arc::new(ProductionHandleCtx {
+        sinsti: mk_sensor(api, config, acl, changes, &TicObject::SINSTI)?,
+        smaxin: mk_power_sensor(api, config, acl, changes, &TicObject::SMAXIN)?,
+        smaxin1: mk_power_sensor(api, config, acl, changes, &TicObject::SMAXIN1)?,
+        eait: eait.clone(),
+        erq: mk_energy(api, config, acl, changes, &TicObject::ERQ)?,
+    });
+
+    let verb = AfbVerb::new("production");
+    verb.set_info("producer view: injected power, injected maxima, injected and reactive energy");
+    verb.set_permission(acl);
+    verb.set_actions("['read', 'subscribe', 'unsubscribe']")?;
+    verb.set_callback(productioncb);
+    verb.set_context(ProductionDataCtx {
+        handle: ctx.clone(),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(ctx)
+}
+
+// register sqlite persistence and its 'query' verb
+#[cfg(feature = "sqlite")]
+fn mk_store(
+    api: &mut MeterApi,
+    acl: &'static AfbPermission,
+    config: &StorageConfig,
+) -> Result<Arc<StoreHandleCtx>, AfbError> {
+    let store = SampleStore::open(
+        config.path,
+        config.retention as u64 * 24 * 3600,
+        config.interval as u64,
+        config.uids.clone(),
+    )?;
+    let ctx = Arc::new(StoreHandleCtx {
+        value: SyncLock::new(store),
+    });
+
+    let verb = AfbVerb::new("query");
+    verb.set_info("stored sensor samples within a time range");
+    verb.set_permission(acl);
+    verb.set_callback(querycb);
+    verb.set_context(StoreDataCtx {
+        handle: ctx.clone(),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(ctx)
+}
+
+// register per source link quality counters verb
+fn mk_stats(
+    api: &mut MeterApi,
+    acl: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("stats");
+    verb.set_info("source link quality counters, reset through control verb");
+    verb.set_actions("['read']")?;
+    verb.set_permission(acl);
+    verb.set_callback(statscb);
+    verb.set_context(StatsDataCtx { ctx: ctx.clone() });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(())
+}
+
+// register supervision metrics verb, rates are computed between two reads
+fn mk_metrics(
+    api: &mut MeterApi,
+    acl: &'static AfbPermission,
+    ctx: &Arc<EventDataCtx>,
+) -> Result<(), AfbError> {
+    let verb = AfbVerb::new("metrics");
+    verb.set_info("frame/line rates, error rates, event pushes and buffer usage");
+    verb.set_permission(acl);
+    verb.set_callback(metricscb);
+    verb.set_context(MetricsDataCtx {
+        ctx: ctx.clone(),
+        started: Instant::now(),
+        sample: SyncLock::new((Instant::now(), LinkyStats::default())),
+    });
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(())
+}
+
+// register raw frame inspection verb
+fn mk_raw(
+    api: &mut MeterApi,
+    prefix: &'static str,
+    acl: &'static AfbPermission,
+    handle: &LinkyHandle,
+) -> Result<Arc<RawFrameCtx>, AfbError> {
+    let event = AfbEvent::new(mk_name(prefix, "raw-frame"));
+    let verb = AfbVerb::new("raw");
+
+    let ctx = Arc::new(RawFrameCtx {
+        event,
+        source: SyncLock::new(format!("source:{} mode:{}", handle.get_name(), handle.get_mode())),
+        frame: SyncLock::new(String::new()),
+        last: SyncLock::new(String::new()),
+        recorder: SyncLock::new(FrameRecorder::new()),
+    });
+
+    verb.set_info("last raw frame (debug)");
+    verb.set_permission(acl);
+    verb.set_actions("['read', 'info', 'subscribe', 'unsubscribe']")?;
+    verb.set_callback(rawcb);
+    verb.set_context(RawDataCtx {
+        handle: ctx.clone(),
+    });
+
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    api.add_event(event);
+    Ok(ctx)
+}
+
+struct StampDataCtx {
+    reply: ReplyFormat,
+    handle: Arc<StampHandleCtx>,
+}
+
+fn stampcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<StampDataCtx>()?;
+
+    let unit = ctx.handle.tic.get_unit().as_str();
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ if ctx.reply.is_v2() => {
+            let jsonc = ctx.reply.envelope(unit, ctx.handle.last_update.get())?;
+            if let Some(stamp) = ctx.handle.value.get() {
+                jsonc.add("value", stamp.to_jsonc()?)?;
+            }
+            response.push(jsonc)?;
+        }
+        ApiAction::READ => match ctx.handle.value.get() {
+            Some(stamp) => {
+                response.push(stamp.to_jsonc()?)?;
+                response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+            }
+            None => return afb_error!("linky-stamp-unset", "no date received from meter yet"),
+        },
+        ApiAction::INFO => {
+            let info = match serde_json::to_string(ctx.handle.tic) {
+                Ok(value) => value,
+                Err(_) => "no-sensor-info".to_string(),
+            };
+            response.push(info)?;
+            response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register a read only date sensor
+fn mk_stamp_sensor(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<StampHandleCtx>, AfbError> {
+    let prefix = config.prefix;
+    let reply = ReplyFormat::new(config);
+    let event = AfbEvent::new(mk_name(prefix, tic.get_name()));
+    let verb = AfbVerb::new(tic.get_name());
+
+    let ctx = Arc::new(StampHandleCtx {
+        tic,
+        event,
+        changes,
+        last_update: SyncCell::new(None),
+        value: SyncCell::new(None),
+    });
+
+    verb.set_name(mk_name(prefix, tic.get_uid()));
+    verb.set_info(tic.get_info());
+    verb.set_permission(acl);
+    verb.set_actions(TIC_ACTIONS)?;
+    verb.set_callback(stampcb);
+    verb.set_context(StampDataCtx {
+        reply,
+        handle: ctx.clone(),
+    });
+
+    if config.is_exposed(tic) {
+        verb.finalize()?;
+        api.add_verb(verb);
+        mk_alias(api, acl, tic, TIC_ACTIONS, stampcb, StampDataCtx {
+            reply,
+            handle: ctx.clone(),
+        })?;
+    }
+    api.add_event(event);
+    Ok(ctx)
+}
+
+struct PowerDataCtx {
+    reply: ReplyFormat,
+    handle: Arc<PowerHandleCtx>,
+}
+
+fn powercb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<PowerDataCtx>()?;
+
+    let unit = ctx.handle.tic.get_unit().as_str();
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ if ctx.reply.is_v2() => {
+            let jsonc = ctx.reply.envelope(unit, ctx.handle.last_update.get())?;
+            if let Some(power) = ctx.handle.value.get() {
+                jsonc.add("value", power.to_jsonc()?)?;
+            }
+            response.push(jsonc)?;
+        }
+        ApiAction::READ => match ctx.handle.value.get() {
+            Some(power) => {
+                response.push(power.to_jsonc()?)?;
+                response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+            }
+            None => return afb_error!("linky-power-unset", "no max power received from meter yet"),
+        },
+        ApiAction::INFO => {
+            let info = match serde_json::to_string(ctx.handle.tic) {
+                Ok(value) => value,
+                Err(_) => "no-sensor-info".to_string(),
+            };
+            response.push(info)?;
+            response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register horodated max power sensor
+fn mk_power_sensor(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<PowerHandleCtx>, AfbError> {
+    let prefix = config.prefix;
+    let reply = ReplyFormat::new(config);
+    let event = AfbEvent::new(mk_name(prefix, tic.get_name()));
+    let verb = AfbVerb::new(tic.get_name());
+
+    let ctx = Arc::new(PowerHandleCtx {
+        tic,
+        event,
+        changes,
+        last_update: SyncCell::new(None),
+        value: SyncCell::new(None),
+    });
+
+    verb.set_name(mk_name(prefix, tic.get_uid()));
+    verb.set_info(tic.get_info());
+    verb.set_permission(acl);
+    verb.set_actions(TIC_ACTIONS)?;
+    verb.set_callback(powercb);
+    verb.set_context(PowerDataCtx {
+        reply,
+        handle: ctx.clone(),
+    });
+
+    if config.is_exposed(tic) {
+        verb.finalize()?;
+        api.add_verb(verb);
+        mk_alias(api, acl, tic, TIC_ACTIONS, powercb, PowerDataCtx {
+            reply,
+            handle: ctx.clone(),
+        })?;
+    }
+    api.add_event(event);
+    Ok(ctx)
 }
 
-struct EventDataCtx {
-    pub cycle: u32,
-    pub handle: LinkyHandle,
-    pub event: &'static AfbEvent,
-    pub iinst: Rc<SensorHandleCtx>,
-    pub sinsts: Rc<SensorHandleCtx>,
-    pub adsp: Rc<SensorHandleCtx>,
-    pub adsc: Rc<SensorHandleCtx>,
-    pub pcou: Rc<SensorHandleCtx>,
-    pub ntarf: Rc<SensorHandleCtx>,
-    pub irms: Rc<SensorHandleCtx>,
-    pub urms: Rc<SensorHandleCtx>,
+struct TextDataCtx {
+    reply: ReplyFormat,
+    handle: Arc<TextHandleCtx>,
 }
 
-// this method is call each time a message is waiting on session raw_socket
-//AfbEvtFdRegister!(SerialAsyncCtrl, async_serial_cb, EventDataCtx);
-fn async_serial_cb(
-    _fd: &AfbEvtFd, 
-    revent: u32, 
-    ctx: &AfbCtxData, //&mut EventDataCtx
-) -> Result<(), AfbError>{
+fn textcb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<TextDataCtx>()?;
 
-    let ctx = ctx.get_ref::<EventDataCtx>()?;
-
-    // There is no value initializing a buffer before reading operation
-    #[allow(invalid_value)]
-    let mut buffer = unsafe { MaybeUninit::<[u8; 256]>::uninit().assume_init() };
-
-    if revent == AfbEvtFdPoll::IN.bits() {
-        match ctx.handle.decode(&mut buffer) {
-            Err(error) => match error {
-                LinkyError::ChecksumError(_) => {}
-                _ => {
-                    afb_log_msg!(
-                        Debug,
-                        ctx.event,
-                        "device:{} invalid data {:?}",
-                        ctx.handle.get_name(),
-                        error
-                    );
-                    ctx.event.broadcast(format!("{:?}", error));
-                }
-            },
-            Ok(data) => {
-                match data {
-                    // register status
-                    TicValue::ADSC(value) => ctx.adsc.updated(ctx.cycle, data, 0, value.raw as i32),
-
-                    // over power
-                    TicValue::ADPS(value) => ctx.adsp.updated(ctx.cycle, data, 0, value),
-                    TicValue::ADIR1(value) => ctx.adsp.updated(ctx.cycle, data, 1, value),
-                    TicValue::ADIR2(value) => ctx.adsp.updated(ctx.cycle, data, 2, value),
-                    TicValue::ADIR3(value) => ctx.adsp.updated(ctx.cycle, data, 3, value),
-
-                    // cutting power
-                    TicValue::PCOUP(value) => ctx.pcou.updated(ctx.cycle, data, 0, value),
-                    TicValue::PREF(value) => ctx.pcou.updated(ctx.cycle, data, 1, value),
-
-                    // instant current
-                    TicValue::IINST(value) => ctx.iinst.updated(ctx.cycle, data, 0, value),
-                    TicValue::IINST1(value) => ctx.iinst.updated(ctx.cycle, data, 1, value),
-                    TicValue::IINST2(value) => ctx.iinst.updated(ctx.cycle, data, 2, value),
-                    TicValue::IINST3(value) => ctx.iinst.updated(ctx.cycle, data, 3, value),
-
-                    // instant active current
-                    TicValue::SINSTS(value) => ctx.sinsts.updated(ctx.cycle, data, 0, value),
-                    TicValue::SINSTS1(value) => ctx.sinsts.updated(ctx.cycle, data, 1, value),
-                    TicValue::SINSTS2(value) => ctx.sinsts.updated(ctx.cycle, data, 2, value),
-                    TicValue::SINSTS3(value) => ctx.sinsts.updated(ctx.cycle, data, 3, value),
-
-                    // efficient current
-                    TicValue::IRMS1(value) => ctx.irms.updated(ctx.cycle, data, 0, value),
-                    TicValue::IRMS2(value) => ctx.irms.updated(ctx.cycle, data, 1, value),
-                    TicValue::IRMS3(value) => ctx.irms.updated(ctx.cycle, data, 2, value),
-
-                    // efficient tension
-                    TicValue::URMS1(value) => ctx.urms.updated(ctx.cycle, data, 0, value),
-                    TicValue::URMS2(value) => ctx.urms.updated(ctx.cycle, data, 1, value),
-                    TicValue::URMS3(value) => ctx.urms.updated(ctx.cycle, data, 2, value),
-
-                    // Index tarrifaire
-                    TicValue::NTARF(value) => ctx.ntarf.updated(ctx.cycle, data, 1, value),
-
-                    _ => {} // ignore any other data
-                };
+    let unit = ctx.handle.tic.get_unit().as_str();
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ if ctx.reply.is_v2() => {
+            let jsonc = ctx.reply.envelope(unit, ctx.handle.last_update.get())?;
+            if let Some(label) = &*ctx.handle.value.lock() {
+                jsonc.add("value", label.as_str())?;
             }
+            response.push(jsonc)?;
+        }
+        ApiAction::READ => match &*ctx.handle.value.lock() {
+            Some(label) => {
+                response.push(label.clone())?;
+                response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+            }
+            None => return afb_error!("linky-text-unset", "no label received from meter yet"),
+        },
+        ApiAction::INFO => {
+            let info = match serde_json::to_string(ctx.handle.tic) {
+                Ok(value) => value,
+                Err(_) => "no-sensor-info".to_string(),
+            };
+            response.push(info)?;
+            response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
         }
-    } else {
-        ctx.event.broadcast("tty-error");
     }
+
+    rqt.reply(response, 0);
     Ok(())
 }
 
-// if new/old value diverge send event and update value cache
-impl SensorHandleCtx {
-    pub fn updated(&self, cycle: u32, data: TicValue, idx: usize, value: i32) {
-        let mut values = self.values.get();
+// register a provider text label sensor
+fn mk_text_sensor(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<TextHandleCtx>, AfbError> {
+    let prefix = config.prefix;
+    let reply = ReplyFormat::new(config);
+    let event = AfbEvent::new(mk_name(prefix, tic.get_name()));
+    let verb = AfbVerb::new(tic.get_name());
 
-        // increase cycle counter and force event if needed
-        let forced = if cycle > 0 {
-            let count = self.count.get();
-            if count == cycle {
-                true
-            } else {
-                self.count.set(count+1);
-                false
+    let ctx = Arc::new(TextHandleCtx {
+        tic,
+        event,
+        changes,
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(None),
+    });
+
+    verb.set_name(mk_name(prefix, tic.get_uid()));
+    verb.set_info(tic.get_info());
+    verb.set_permission(acl);
+    verb.set_actions(TIC_ACTIONS)?;
+    verb.set_callback(textcb);
+    verb.set_context(TextDataCtx {
+        reply,
+        handle: ctx.clone(),
+    });
+
+    if config.is_exposed(tic) {
+        verb.finalize()?;
+        api.add_verb(verb);
+        mk_alias(api, acl, tic, TIC_ACTIONS, textcb, TextDataCtx {
+            reply,
+            handle: ctx.clone(),
+        })?;
+    }
+    api.add_event(event);
+    Ok(ctx)
+}
+
+struct RegisterDataCtx {
+    reply: ReplyFormat,
+    handle: Arc<RegisterHandleCtx>,
+}
+
+fn registercb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<RegisterDataCtx>()?;
+
+    let unit = ctx.handle.tic.get_unit().as_str();
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ if ctx.reply.is_v2() => {
+            let jsonc = ctx.reply.envelope(unit, ctx.handle.last_update.get())?;
+            if let Some(status) = ctx.handle.value.get() {
+                jsonc.add("value", RegisterHandleCtx::to_jsonc(&status)?)?;
             }
-        } else {
-            false
-        };
+            response.push(jsonc)?;
+        }
+        ApiAction::READ => match ctx.handle.value.get() {
+            Some(status) => {
+                response.push(RegisterHandleCtx::to_jsonc(&status)?)?;
+                response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+            }
+            None => return afb_error!("linky-register-unset", "no status received from meter yet"),
+        },
+        ApiAction::INFO => {
+            let info = match serde_json::to_string(ctx.handle.tic) {
+                Ok(value) => value,
+                Err(_) => "no-sensor-info".to_string(),
+            };
+            response.push(info)?;
+            response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
+        }
+    }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register meter status register sensor
+fn mk_register_sensor(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<RegisterHandleCtx>, AfbError> {
+    let prefix = config.prefix;
+    let reply = ReplyFormat::new(config);
+    let event = AfbEvent::new(mk_name(prefix, tic.get_name()));
+    let verb = AfbVerb::new(tic.get_name());
+
+    let ctx = Arc::new(RegisterHandleCtx {
+        tic,
+        event,
+        changes,
+        last_update: SyncCell::new(None),
+        value: SyncCell::new(None),
+    });
+
+    verb.set_name(mk_name(prefix, tic.get_uid()));
+    verb.set_info(tic.get_info());
+    verb.set_permission(acl);
+    verb.set_actions(TIC_ACTIONS)?;
+    verb.set_callback(registercb);
+    verb.set_context(RegisterDataCtx {
+        reply,
+        handle: ctx.clone(),
+    });
+
+    if config.is_exposed(tic) {
+        verb.finalize()?;
+        api.add_verb(verb);
+        mk_alias(api, acl, tic, TIC_ACTIONS, registercb, RegisterDataCtx {
+            reply,
+            handle: ctx.clone(),
+        })?;
+    }
+    api.add_event(event);
+    Ok(ctx)
+}
+
+struct ProfileDataCtx {
+    reply: ReplyFormat,
+    handle: Arc<ProfileHandleCtx>,
+}
 
-        if value != values[idx] || forced {
-            values[idx] = value;
-            self.count.set(0);
-            self.values.set(values);
-            self.event.push(data);
+fn profilecb(rqt: &AfbRequest, args: &AfbRqtData, ctx: &AfbCtxData) -> Result<(), AfbError> {
+    let ctx = ctx.get_ref::<ProfileDataCtx>()?;
+
+    let unit = ctx.handle.tic.get_unit().as_str();
+    let mut response = AfbParams::new();
+    match args.get::<&ApiAction>(0)? {
+        ApiAction::READ if ctx.reply.is_v2() => {
+            let jsonc = ctx.reply.envelope(unit, ctx.handle.last_update.get())?;
+            if let Some(profile) = &*ctx.handle.value.lock() {
+                jsonc.add("value", profile.to_jsonc()?)?;
+            }
+            response.push(jsonc)?;
+        }
+        ApiAction::READ => match &*ctx.handle.value.lock() {
+            Some(profile) => {
+                response.push(profile.to_jsonc()?)?;
+                response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+            }
+            None => return afb_error!("linky-profile-unset", "no profile received from meter yet"),
+        },
+        ApiAction::INFO => {
+            let info = match serde_json::to_string(ctx.handle.tic) {
+                Ok(value) => value,
+                Err(_) => "no-sensor-info".to_string(),
+            };
+            response.push(info)?;
+            response.push(freshness_jsonc(ctx.handle.last_update.get())?)?;
+        }
+        ApiAction::SUBSCRIBE { phase: None } => {
+            ctx.handle.event.subscribe(rqt)?;
+        }
+        ApiAction::UNSUBSCRIBE { phase: None } => {
+            ctx.handle.event.unsubscribe(rqt)?;
+        }
+        action => {
+            return afb_error!("linky-action-unsupported", "action:{:?} not supported", action)
         }
     }
+
+    rqt.reply(response, 0);
+    Ok(())
+}
+
+// register a read only provider calendar sensor
+fn mk_profile_sensor(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<ProfileHandleCtx>, AfbError> {
+    let prefix = config.prefix;
+    let reply = ReplyFormat::new(config);
+    let event = AfbEvent::new(mk_name(prefix, tic.get_name()));
+    let verb = AfbVerb::new(tic.get_name());
+
+    let ctx = Arc::new(ProfileHandleCtx {
+        tic,
+        event,
+        changes,
+        last_update: SyncCell::new(None),
+        value: SyncLock::new(None),
+    });
+
+    verb.set_name(mk_name(prefix, tic.get_uid()));
+    verb.set_info(tic.get_info());
+    verb.set_permission(acl);
+    verb.set_actions(TIC_ACTIONS)?;
+    verb.set_callback(profilecb);
+    verb.set_context(ProfileDataCtx {
+        reply,
+        handle: ctx.clone(),
+    });
+
+    if config.is_exposed(tic) {
+        verb.finalize()?;
+        api.add_verb(verb);
+        mk_alias(api, acl, tic, TIC_ACTIONS, profilecb, ProfileDataCtx {
+            reply,
+            handle: ctx.clone(),
+        })?;
+    }
+    api.add_event(event);
+    Ok(ctx)
 }
 
 struct SensorDataCtx {
-    handle: Rc<SensorHandleCtx>,
+    reply: ReplyFormat,
+    handle: Arc<SensorHandleCtx>,
 }
 
 fn sensorcb(
@@ -156,28 +4166,80 @@ fn sensorcb(
 
     let ctx = ctx.get_ref::<SensorDataCtx>()?;
 
+    // missing argument is a plain read
+    let default = SensorQuery::default();
+    let query = if args.get_count() > 0 {
+        args.get::<&SensorQuery>(0)?
+    } else {
+        &default
+    };
+    let view = QueryView::new(ctx.handle.tic, query, &ctx.reply)?;
+
     let mut response = AfbParams::new();
-    match args.get::<&ApiAction>(0)? {
-        ApiAction::READ => {
-            let values = ctx.handle.values.get();
-            let jsonc= JsoncObj::array();
-            for idx in 0..ctx.handle.tic.get_count() {
-                jsonc.insert(idx,values[idx])?;
+    match query.action {
+        SensorAction::READ if ctx.reply.is_v2() => {
+            let unit = view.unit_str(ctx.handle.tic);
+            let last_update = ctx.handle.get_last_update();
+            let jsonc = ctx.reply.envelope(unit, last_update)?;
+            if last_update.is_some() {
+                jsonc.add("value", ctx.handle.query_values(&view)?)?;
+            }
+            if let Some(name) = ctx.handle.get_tariff_name() {
+                jsonc.add("name", name.as_str())?;
             }
             response.push(jsonc)?;
+            if query.history.is_some() {
+                response.push(ctx.handle.history_jsonc(&view, query.history)?)?;
+            }
         }
-        ApiAction::INFO => {
+        SensorAction::READ => {
+            response.push(ctx.handle.query_jsonc(&view)?)?;
+            response.push(freshness_jsonc(ctx.handle.get_last_update())?)?;
+            if query.history.is_some() {
+                response.push(ctx.handle.history_jsonc(&view, query.history)?)?;
+            }
+            // NTARF period name comes last, '{"name":"HP Bleu"}'
+            if let Some(name) = ctx.handle.get_tariff_name() {
+                let jname = JsoncObj::new();
+                jname.add("name", name.as_str())?;
+                response.push(jname)?;
+            }
+        }
+        SensorAction::HISTORY => {
+            response.push(ctx.handle.history_jsonc(&view, query.history)?)?;
+        }
+        SensorAction::STATS => {
+            response.push(ctx.handle.stats_jsonc()?)?;
+        }
+        SensorAction::INFO => {
             let info = match serde_json::to_string(ctx.handle.tic) {
                 Ok(value) => value,
                 Err(_) => "no-sensor-info".to_string(),
             };
             response.push(info)?;
+            response.push(freshness_jsonc(ctx.handle.get_last_update())?)?;
         }
-        ApiAction::SUBSCRIBE => {
-            ctx.handle.event.subscribe(rqt)?;
+        SensorAction::SUBSCRIBE => {
+            for phase in query_phases(query) {
+                ctx.handle.get_event(phase)?.subscribe(rqt)?;
+            }
+            // cached value comes with the reply, next event may take hours
+            if let Some(stamp) = ctx.handle.get_last_update() {
+                if ctx.reply.is_v2() {
+                    let unit = view.unit_str(ctx.handle.tic);
+                    let jsonc = ctx.reply.envelope(unit, Some(stamp))?;
+                    jsonc.add("value", ctx.handle.query_values(&view)?)?;
+                    response.push(jsonc)?;
+                } else {
+                    response.push(ctx.handle.query_jsonc(&view)?)?;
+                    response.push(freshness_jsonc(Some(stamp))?)?;
+                }
+            }
         }
-        ApiAction::UNSUBSCRIBE => {
-            ctx.handle.event.unsubscribe(rqt)?;
+        SensorAction::UNSUBSCRIBE => {
+            for phase in query_phases(query) {
+                ctx.handle.get_event(phase)?.unsubscribe(rqt)?;
+            }
         }
     }
 
@@ -185,66 +4247,466 @@ fn sensorcb(
     Ok(())
 }
 
+// subscribe targets, 'phase' and 'phases' add up, none of them means main sensor event
+fn query_phases(query: &SensorQuery) -> Vec<Option<usize>> {
+    let mut phases: Vec<Option<usize>> = query.phase.iter().map(|phase| Some(*phase)).collect();
+    if let Some(values) = &query.phases {
+        phases.extend(values.iter().map(|phase| Some(*phase)));
+    }
+    if phases.is_empty() {
+        phases.push(None);
+    }
+    phases
+}
+
+// register a new linky energy counter sensor, counters have no per index event
+fn mk_energy(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<SensorHandleCtx>, AfbError> {
+    mk_named_sensor(api, config, acl, changes, tic, None, true)
+}
+
+// register same callback under sensor readable name, e.g. 'instant-power' for SINSTS
+fn mk_alias<T: 'static>(
+    api: &mut MeterApi,
+    acl: &'static AfbPermission,
+    tic: &'static TicObject,
+    actions: &'static str,
+    callback: RqtCallback,
+    context: T,
+) -> Result<(), AfbError> {
+    if tic.get_name().to_lowercase() == tic.get_uid() {
+        return Ok(());
+    }
+
+    let verb = AfbVerb::new(to_static_str(tic.get_name().to_lowercase()));
+    verb.set_info(tic.get_info());
+    verb.set_permission(acl);
+    verb.set_actions(actions)?;
+    verb.set_callback(callback);
+    verb.set_context(context);
+    verb.finalize()?;
+
+    api.add_verb(verb);
+    Ok(())
+}
+
+fn alias_name(prefix: &str, tic: &TicObject) -> String {
+    let alias = tic.get_name().to_lowercase();
+    if prefix.is_empty() {
+        alias
+    } else {
+        format!("{}/{}", prefix, alias)
+    }
+}
+
+// meter verbs are grouped under 'prefix/' and admin ones under 'prefix/admin/', events keep
+// their 'prefix/name' and stay api wide. Single meter without prefix has no data group.
+pub(crate) struct MeterApi<'a> {
+    api: &'a mut AfbApi,
+    verbs: Option<&'static mut AfbGroup>,
+    admin: &'static mut AfbGroup,
+}
+
+impl<'a> MeterApi<'a> {
+    fn new(api: &'a mut AfbApi, prefix: &'static str, admin: &'static AfbPermission) -> Self {
+        let verbs = if prefix.is_empty() {
+            None
+        } else {
+            let group = AfbGroup::new(prefix);
+            group.set_prefix(prefix).set_separator("/").set_info("meter sensors and reports");
+            Some(group)
+        };
+        let group = AfbGroup::new(mk_name(prefix, "admin"));
+        group
+            .set_prefix(mk_name(prefix, "admin"))
+            .set_separator("/")
+            .set_info("meter administration")
+            .set_permission(admin);
+        MeterApi {
+            api,
+            verbs,
+            admin: group,
+        }
+    }
+
+    pub(crate) fn add_verb(&mut self, verb: &AfbVerb) {
+        match &mut self.verbs {
+            Some(group) => {
+                group.add_verb(verb);
+            }
+            None => {
+                self.api.add_verb(verb);
+            }
+        }
+    }
+
+    fn add_admin(&mut self, verb: &AfbVerb) {
+        self.admin.add_verb(verb);
+    }
+
+    pub(crate) fn add_event(&mut self, event: &AfbEvent) {
+        self.api.add_event(event);
+    }
+
+    fn finalize(self) -> Result<(), AfbError> {
+        if let Some(group) = self.verbs {
+            self.api.add_group(group.finalize()?);
+        }
+        self.api.add_group(self.admin.finalize()?);
+        Ok(())
+    }
+}
+
+// prefix event names when binding serves more than one meter
+pub(crate) fn mk_name(prefix: &'static str, name: &'static str) -> &'static str {
+    if prefix.is_empty() {
+        name
+    } else {
+        to_static_str(format!("{}/{}", prefix, name))
+    }
+}
+
 // register a new linky sensor
 fn mk_sensor(
-    api: &mut AfbApi, 
-    tic: &'static TicObject
-) -> Result<Rc<SensorHandleCtx>, AfbError> {
-    
-    let uid = tic.get_uid();
-    let name = tic.get_name();
-    let event = AfbEvent::new(name);
-    let verb = AfbVerb::new(name);
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+) -> Result<Arc<SensorHandleCtx>, AfbError> {
+    mk_named_sensor(api, config, acl, changes, tic, None, false)
+}
 
-    let ctx = Rc::new(SensorHandleCtx {
+// sensor with tariff period name (NTARF)
+fn mk_named_sensor(
+    api: &mut MeterApi,
+    config: &BindingConfig,
+    acl: &'static AfbPermission,
+    changes: &'static ChangeFeed,
+    tic: &'static TicObject,
+    tariffs: Option<Arc<TariffHandleCtx>>,
+    counter: bool,
+) -> Result<Arc<SensorHandleCtx>, AfbError> {
+    let prefix = config.prefix;
+    let reply = ReplyFormat::new(config);
+    let sensor = config.get_sensor(tic.get_uid());
+
+    let uid = mk_name(prefix, tic.get_uid());
+    let event = AfbEvent::new(mk_name(prefix, tic.get_name()));
+    let verb = AfbVerb::new(tic.get_name());
+
+    let mut phases: Vec<&'static AfbEvent> = Vec::new();
+    if tic.get_count() > 1 && !counter {
+        for idx in 0..tic.get_count() {
+            let label = to_static_str(format!("{}-{}", tic.get_name(), idx));
+            let phase = AfbEvent::new(mk_name(prefix, label));
+            api.add_event(phase);
+            phases.push(phase);
+        }
+    }
+
+    let ctx = Arc::new(SensorHandleCtx {
         tic,
         event,
-        values: Cell::new([0; 4]),
-        count: Cell::new(0),
+        changes,
+        phases,
+        units: config.units,
+        counter,
+        state: SyncLock::new(SensorState::new(sensor)),
+        tariffs,
     });
 
     verb.set_name(uid);
     verb.set_info(tic.get_info());
-    verb.set_actions("['read', 'info', 'subscribe', 'unsubscribe']")?;
+    verb.set_permission(acl);
+    verb.set_actions(SENSOR_ACTIONS)?;
     verb.set_callback(sensorcb);    //
-    verb.set_context(SensorDataCtx{ 
+    verb.set_context(SensorDataCtx {
+        reply,
         handle: ctx.clone(),
     });
 
-    verb.finalize()?;
-
-    api.add_verb(verb);
+    // preset excluded sensors keep decoding for derived values, only their verbs are skipped
+    if config.is_exposed(tic) {
+        verb.finalize()?;
+        api.add_verb(verb);
+        mk_alias(api, acl, tic, SENSOR_ACTIONS, sensorcb, SensorDataCtx {
+            reply,
+            handle: ctx.clone(),
+        })?;
+    }
     api.add_event(event);
     Ok(ctx)
 }
 
-pub(crate) fn register_verbs(api: &mut AfbApi, config: LinkyConfig) -> Result<(), AfbError> {
-    // register custom parser afb-v4 type within binder
-    linky::prelude::tic_register_type()?;
-    let event = AfbEvent::new("Serial");
+pub(crate) fn register_verbs(
+    api: &mut AfbApi,
+    config: BindingConfig,
+    acls: BindingAcls,
+    shutdown: &mut ApiShutdown,
+) -> Result<(), AfbError> {
+    let mut groups = MeterApi::new(api, config.prefix, acls.admin);
+    let api = &mut groups;
+    let event = AfbEvent::new(mk_name(config.prefix, "Serial"));
+    let unknown = AfbEvent::new(mk_name(config.prefix, "unknown-label"));
+    let diag = AfbEvent::new(mk_name(config.prefix, "diagnostics"));
+    let changes = mk_changes(api, config.prefix, acls.read, config.batch)?;
+
+    let mut handle = LinkyHandle::new(&config.source)?;
+    handle.set_customs(config.labels.clone());
+    handle.set_strict(config.strict);
+    handle.set_buffer(config.source.get_buffer(config.buffer));
+    handle.set_quality(config.checksum_alarm);
+    if let Some(source) = &config.backup {
+        let mut backup = LinkyHandle::new(source)?;
+        backup.set_customs(config.labels.clone());
+        backup.set_strict(config.strict);
+        backup.set_buffer(source.get_buffer(config.buffer));
+        backup.set_quality(config.checksum_alarm);
+        handle.set_backup(backup, config.failover);
+    }
+    afb_log_msg!(
+        Notice,
+        None,
+        "source:{} mode:{} ready",
+        handle.get_name(),
+        handle.get_mode()
+    );
+
+    let raw = mk_raw(api, config.prefix, acls.read, &handle)?;
+    mk_config(api, &config, acls.admin)?;
+    let mut customs = Vec::new();
+    for tic in &config.labels {
+        customs.push(mk_sensor(api, &config, acls.read, changes, tic)?);
+    }
+
+    let reconnect = config.source.get_reconnect();
+
+    let interval = config.source.get_interval();
+
+    let watch = match (&config.source, config.hotplug) {
+        (LinkyConfig::Serial { device, .. }, true) => Some(DeviceWatch::new(device)?),
+        _ => None,
+    };
+
+    // dispatch order matters, cost and shedding read tariffs and headroom once updated
+    let tariffs = mk_tariffs(api, &config, acls.read)?;
+    let tempo = mk_tempo(api, config.prefix, acls.read)?;
+    let headroom = mk_headroom(api, config.prefix, acls.read, config.margin)?;
+    let evse = mk_evse(api, &config, acls.read)?;
+    let mut features: Vec<Arc<dyn TicFeature>> = vec![
+        // meter trips a few seconds after ADPS, alarm goes first
+        mk_overpower(api, &config, acls.read)?,
+        tariffs.clone(),
+        mk_consumption(api, &config, acls.read)?,
+        tempo.clone(),
+        mk_phases(api, config.prefix, acls.read)?,
+        mk_ejp(api, config.prefix, acls.read)?,
+        mk_charging(api, &config, acls.read, &tempo)?,
+        headroom.clone(),
+        mk_overload(api, &config, acls.read, &headroom)?,
+        mk_imbalance(api, config.prefix, acls.read, config.imbalance)?,
+        mk_voltage(api, &config, acls.read)?,
+        mk_ramp(api, &config, acls.read)?,
+        mk_injection(api, &config, acls.read)?,
+    ];
+    if let Some(value) = &config.shedding {
+        features.push(mk_shedding(api, config.prefix, acls.read, value, &headroom)?);
+    }
+    if !config.hooks.is_empty() {
+        features.push(mk_hooks(api, config.prefix, acls.read, &config.hooks)?);
+    }
+    features.push(mk_selfconso(api, config.prefix, acls.read, config.selfconso)?);
+    features.push(mk_ocpp(api, &config, acls.read)?);
+    features.push(evse.clone());
+    if let Some(value) = &config.cost {
+        features.push(mk_cost(api, config.prefix, acls.read, value, &tariffs)?);
+    }
+
+    #[cfg(feature = "sqlite")]
+    let store = match &config.storage {
+        Some(value) => Some(mk_store(api, acls.read, value)?),
+        None => None,
+    };
+
+    let csv = config.csv.as_ref().map(|value| {
+        let sink = CsvSink::new(
+            value.path,
+            value.uids.clone(),
+            value.size as u64 * 1024,
+            value.daily,
+        );
+        Arc::new(CsvHandleCtx {
+            value: SyncLock::new(sink),
+        })
+    });
+
+    #[cfg(feature = "influx")]
+    let influx = match &config.influx {
+        Some(value) => Some(mk_influx(value)?),
+        None => None,
+    };
+
+    #[cfg(feature = "redis")]
+    let redis = match &config.redis {
+        Some(value) => Some(mk_redis(value)?),
+        None => None,
+    };
+
+    #[cfg(feature = "mqtt")]
+    let sparkplug = match &config.sparkplug {
+        Some(value) => Some(mk_sparkplug(value)?),
+        None => None,
+    };
+
+    #[cfg(feature = "modbus")]
+    let modbus = match config.modbus {
+        Some((addr, port)) => Some(mk_modbus(addr, port)?),
+        None => None,
+    };
+
+    #[cfg(feature = "dbus")]
+    let dbus = match config.dbus {
+        Some((system, name)) => Some(mk_dbus(system, name)?),
+        None => None,
+    };
+
+    let eait = mk_energy(api, &config, acls.read, changes, &TicObject::EAIT)?;
+    let production = if config.production {
+        Some(mk_production(api, &config, acls.read, changes, &eait)?)
+    } else {
+        None
+    };
 
-    let event_ctx = EventDataCtx {
+    let event_ctx = Arc::new(EventDataCtx {
         cycle: config.cycle,
-        handle: LinkyHandle::new(config.device, config.speed, config.parity)?,
+        reconnect: SyncCell::new(reconnect),
+        backoff: u32::max(config.backoff, reconnect),
+        delay: SyncCell::new(reconnect),
+        interval: SyncCell::new(interval),
+        timer: SyncCell::new(None),
+        watch,
+        online: SyncCell::new(true),
+        fault: SyncLock::new(FaultTracker::new(config.fault_max)),
+        probing: SyncCell::new(false),
+        watchdog: config.watchdog,
+        last_valid: SyncCell::new(Instant::now()),
+        stale: SyncCell::new(false),
+        debug: SyncCell::new(false),
+        last_frame: SyncCell::new(None),
+        gateway: SyncCell::new(None),
+        handle,
+        buffer: SyncLock::new(vec![0; config.line_max]),
+        lines: SyncLock::new(TicLines::new()),
+        budget: config.line_budget,
+        backlog: [SyncCell::new(false), SyncCell::new(false)],
+        resume: SyncCell::new(false),
+        evtfd: SyncCell::new(None),
         event: event,
-        iinst: mk_sensor(api, &TicObject::IINST)?,
-        sinsts: mk_sensor(api, &TicObject::SINSTS)?,
-        adsp: mk_sensor(api, &TicObject::ADPS)?,
-        adsc: mk_sensor(api, &TicObject::ADSC)?,
-        pcou: mk_sensor(api, &TicObject::PCOUP)?,
-        ntarf: mk_sensor(api, &TicObject::NTARF)?,
-        irms: mk_sensor(api, &TicObject::IRMS)?,
-        urms: mk_sensor(api, &TicObject::URMS)?,
-    };
+        unknown,
+        diag,
+        changes,
+        raw,
+        iinst: mk_sensor(api, &config, acls.read, changes, &TicObject::IINST)?,
+        sinsts: mk_sensor(api, &config, acls.read, changes, &TicObject::SINSTS)?,
+        adsp: mk_sensor(api, &config, acls.read, changes, &TicObject::ADPS)?,
+        adsc: mk_sensor(api, &config, acls.read, changes, &TicObject::ADSC)?,
+        pcou: mk_sensor(api, &config, acls.read, changes, &TicObject::PCOUP)?,
+        ntarf: mk_named_sensor(
+            api,
+            &config,
+            acls.read,
+            changes,
+            &TicObject::NTARF,
+            Some(tariffs.clone()),
+            false,
+        )?,
+        irms: mk_sensor(api, &config, acls.read, changes, &TicObject::IRMS)?,
+        urms: mk_sensor(api, &config, acls.read, changes, &TicObject::URMS)?,
+        pf: mk_sensor(api, &config, acls.read, changes, &TicObject::PF)?,
+        factor: SyncLock::new(PowerFactor::new()),
+        charge: mk_sensor(api, &config, acls.read, changes, &TicObject::CHARGE)?,
+        imax: mk_sensor(api, &config, acls.read, changes, &TicObject::IMAX)?,
+        pmax: mk_sensor(api, &config, acls.read, changes, &TicObject::PMAX)?,
+        limiter: ChargeLimitCtx {
+            started: Instant::now(),
+            value: SyncLock::new(ChargeLimit::new(
+                config.charge.margin,
+                config.charge.min,
+                config.charge.max,
+                config.charge.hysteresis,
+                config.charge.ramp,
+            )),
+        },
+        ltarf: mk_text_sensor(api, &config, acls.read, changes, &TicObject::LTARF)?,
+        ngtf: mk_text_sensor(api, &config, acls.read, changes, &TicObject::NGTF)?,
+        demain: mk_text_sensor(api, &config, acls.read, changes, &TicObject::DEMAIN)?,
+        stge: mk_register_sensor(api, &config, acls.read, changes, &TicObject::STGE)?,
+        east: mk_energy(api, &config, acls.read, changes, &TicObject::EAST)?,
+        easf: mk_energy(api, &config, acls.read, changes, &TicObject::EASF)?,
+        eait,
+        production,
+        customs,
+        features,
+        evse,
+        #[cfg(feature = "sqlite")]
+        store,
+        state: config.state.as_ref().map(|state| StateFile::new(state.path)),
+        csv,
+        #[cfg(feature = "influx")]
+        influx,
+        #[cfg(feature = "redis")]
+        redis,
+        #[cfg(feature = "mqtt")]
+        sparkplug,
+        #[cfg(feature = "modbus")]
+        modbus,
+        #[cfg(feature = "dbus")]
+        dbus,
+        outage: mk_outage(api, &config, acls.read)?,
+        history: config.history,
+        window: config.window,
+        date: mk_stamp_sensor(api, &config, acls.read, changes, &TicObject::DATE)?,
+        smaxsn: mk_power_sensor(api, &config, acls.read, changes, &TicObject::SMAXSN)?,
+        pjourf1: mk_profile_sensor(api, &config, acls.read, changes, &TicObject::PJOURF1)?,
+        ppointe: mk_profile_sensor(api, &config, acls.read, changes, &TicObject::PPOINTE)?,
+    });
 
+    mk_stats(api, acls.read, &event_ctx)?;
+    mk_metrics(api, acls.read, &event_ctx)?;
+    mk_health(api, config.prefix, acls.read, config.health, &event_ctx)?;
+    mk_snapshot(api, acls.read, &event_ctx)?;
+    mk_read_many(api, acls.read, &event_ctx)?;
+    mk_sensors(api, &config, acls.read, &event_ctx)?;
+    mk_control(api, acls.admin, &event_ctx)?;
+    mk_inject(api, acls.admin, &event_ctx)?;
+    mk_debug(api, acls.admin, &event_ctx)?;
+    mk_record(api, acls.admin, &event_ctx.raw)?;
     api.add_event(event);
+    api.add_event(unknown);
+    api.add_event(diag);
+    groups.finalize()?;
+    if let Some(state) = &config.state {
+        state_start(&event_ctx, state.interval)?;
+    }
+    source_start(&event_ctx)?;
+    backup_start(&event_ctx)?;
+    watchdog_start(&event_ctx)?;
 
-    AfbEvtFd::new(config.device)
-        .set_fd(event_ctx.handle.get_fd())
-        .set_events(AfbEvtFdPoll::IN)
-        .set_callback(async_serial_cb)
-        .set_context(event_ctx)
-        .start()?;
+    if let Some(watch) = &event_ctx.watch {
+        AfbEvtFd::new("linky-hotplug")
+            .set_fd(watch.get_raw_fd())
+            .set_events(AfbEvtFdPoll::IN)
+            .set_callback(hotplug_cb)
+            .set_context(event_ctx.clone())
+            .start()?;
+    }
 
+    shutdown.meters.push(event_ctx);
     Ok(())
 }
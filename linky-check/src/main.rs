@@ -0,0 +1,189 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// qualify a gateway firmware against the parser from one of its captures,
+// 'linky-check capture.tic' exits with 1 when any anomaly was found
+
+use std::collections::BTreeMap;
+use std::process::exit;
+
+use linky::prelude::*;
+
+const USAGE: &str = "usage: linky-check [--json] CAPTURE...
+  validate checksums, labels and frame boundaries of plain, gzip or zstd captures";
+
+// anomalies found within one capture
+#[derive(Default)]
+struct CheckReport {
+    groups: u64,
+    frames: u64,
+    complete: u64,
+    truncated: u64,  // frame closed by next STX before its ETX
+    incomplete: u64, // frame missing groups or holding rejected ones
+    too_long: u64,
+    errors: BTreeMap<&'static str, u64>, // error code count
+    checksum: BTreeMap<String, u64>,     // checksum failure per label
+    unknown: BTreeMap<String, u64>,      // valid checksum, label not in grammar
+    missing: BTreeMap<String, u64>,      // label dropped within a frame
+}
+
+impl CheckReport {
+    fn push(&mut self, line: &TicLine) {
+        let raw = String::from_utf8_lossy(&line.raw);
+        let label = tic_label(&raw).to_string();
+        match &line.value {
+            Ok(_) => self.groups += 1,
+            Err(LinkyError::RetryLater) => {}
+            Err(error) => {
+                *self.errors.entry(error.get_code()).or_insert(0) += 1;
+                match error {
+                    LinkyError::ChecksumError(_) | LinkyError::EncodingError(_) => {
+                        *self.checksum.entry(label).or_insert(0) += 1
+                    }
+                    LinkyError::UnknownLabel(_) => {
+                        *self.unknown.entry(label).or_insert(0) += 1
+                    }
+                    LinkyError::TooLong(_) => self.too_long += 1,
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(frame) = &line.frame {
+            self.frames += 1;
+            if frame.is_complete() {
+                self.complete += 1;
+            } else if frame.started && !frame.ended {
+                self.truncated += 1;
+            } else if frame.started {
+                self.incomplete += 1;
+            }
+            for label in &frame.missing {
+                *self.missing.entry(label.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    // first frame is usually cut by capture start and is not an anomaly
+    fn is_clean(&self) -> bool {
+        self.errors.is_empty() && self.truncated == 0 && self.incomplete == 0 && self.frames > 0
+    }
+
+    fn to_json(&self, path: &str) -> serde_json::Value {
+        serde_json::json!({
+            "capture": path,
+            "clean": self.is_clean(),
+            "groups": self.groups,
+            "frames": self.frames,
+            "complete": self.complete,
+            "truncated": self.truncated,
+            "incomplete": self.incomplete,
+            "too_long": self.too_long,
+            "errors": self.errors,
+            "checksum": self.checksum,
+            "unknown": self.unknown,
+            "missing": self.missing,
+        })
+    }
+
+    fn print(&self, path: &str) {
+        println!("{}: {}", path, if self.is_clean() { "clean" } else { "anomalies found" });
+        println!("  groups decoded   {}", self.groups);
+        println!("  frames           {} (complete:{})", self.frames, self.complete);
+        println!("  frames truncated {}", self.truncated);
+        println!("  frames partial   {}", self.incomplete);
+        for (code, count) in &self.errors {
+            println!("  {:16} {}", code, count);
+        }
+        for (title, labels) in [
+            ("checksum", &self.checksum),
+            ("unknown", &self.unknown),
+            ("missing", &self.missing),
+        ] {
+            if !labels.is_empty() {
+                let list: Vec<String> =
+                    labels.iter().map(|(label, count)| format!("{}:{}", label, count)).collect();
+                println!("  {:16} {}", title, list.join(" "));
+            }
+        }
+    }
+}
+
+fn check_capture(path: &'static str) -> Result<CheckReport, String> {
+    let config = LinkyConfig::Replay {
+        path,
+        speed: 0,
+        interval: 0,
+        repeat: false,
+    };
+    let mut handle = match LinkyHandle::new(&config) {
+        Ok(value) => value,
+        Err(error) => return Err(format!("{}: {}", path, error)),
+    };
+    handle.set_strict(true);
+
+    // capture is replayed without pacing, nothing left once a pass is empty
+    let mut report = CheckReport::default();
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    loop {
+        if let Err(error) = handle.decode_all(&mut buffer, &mut lines) {
+            return Err(format!("{}: {}", path, error));
+        }
+        if lines.is_empty() {
+            break;
+        }
+        for line in lines.iter() {
+            report.push(line);
+        }
+        lines.clear();
+    }
+    Ok(report)
+}
+
+fn main() {
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            _ if arg.starts_with("--") => {
+                eprintln!("unknown option:{}\n{}", arg, USAGE);
+                exit(2);
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        exit(2);
+    }
+
+    let mut clean = true;
+    for path in paths {
+        let path: &'static str = Box::leak(path.into_boxed_str());
+        match check_capture(path) {
+            Ok(report) => {
+                clean &= report.is_clean();
+                match json {
+                    true => println!("{}", report.to_json(path)),
+                    false => report.print(path),
+                }
+            }
+            Err(error) => {
+                eprintln!("linky-check: {}", error);
+                clean = false;
+            }
+        }
+    }
+    exit(if clean { 0 } else { 1 });
+}
@@ -0,0 +1,202 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// decode a TIC source and print one json line per group, installer sanity check
+// before writing binder config: 'linky-dump --serial /dev/ttyUSB0 --label SINSTS,EAST'
+
+use std::process::exit;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use linky::prelude::*;
+
+const DUMP_POLL: Duration = Duration::from_millis(50);
+const DUMP_RECONNECT: Duration = Duration::from_secs(2);
+
+const USAGE: &str = "usage: linky-dump <source> [options]
+source:
+  --serial DEVICE    serial device, speed is auto detected unless --speed is set
+  --udp ADDR:PORT    bind udp socket and decode received datagrams
+  --tcp HOST:PORT    connect a tcp gateway
+  --file PATH        decode a plain, gzip or zstd capture, then exit
+options:
+  --speed BAUD       serial speed (1200|9600|19200|57600|115200)
+  --parity even|odd  serial parity, default even
+  --label A,B,...    only print groups with these labels
+  --errors           also print rejected groups";
+
+struct DumpArgs {
+    config: LinkyConfig,
+    labels: Vec<String>,
+    errors: bool,
+}
+
+fn to_static_str(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+fn split_addr(value: &str) -> Result<(&'static str, u16), String> {
+    let (host, port) = match value.rsplit_once(':') {
+        Some(value) => value,
+        None => return Err(format!("invalid address:{} should be 'host:port'", value)),
+    };
+    match port.parse::<u16>() {
+        Ok(port) => Ok((to_static_str(host.to_string()), port)),
+        Err(_) => Err(format!("invalid port:{}", port)),
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<DumpArgs, String> {
+    let mut source = None;
+    let mut speed = None;
+    let mut parity = "even";
+    let mut labels = Vec::new();
+    let mut errors = false;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--errors" {
+            errors = true;
+            continue;
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None => return Err(format!("missing value for {}", arg)),
+        };
+        match arg.as_str() {
+            "--serial" | "--udp" | "--tcp" | "--file" => source = Some((arg, value)),
+            "--speed" => match value.parse::<u32>() {
+                Ok(value) => speed = Some(value),
+                Err(_) => return Err(format!("invalid speed:{}", value)),
+            },
+            "--parity" => match value.as_str() {
+                "even" | "odd" => parity = to_static_str(value),
+                _ => return Err(format!("invalid parity:{} should be even|odd", value)),
+            },
+            "--label" => labels = value.split(',').map(|label| label.to_uppercase()).collect(),
+            _ => return Err(format!("unknown option:{}", arg)),
+        }
+    }
+
+    let config = match source {
+        Some((kind, value)) => match kind.as_str() {
+            "--serial" => LinkyConfig::Serial {
+                device: to_static_str(value),
+                speed: speed.unwrap_or(0),
+                parity,
+                reconnect: 0,
+                canonical: false,
+                buffered: false,
+                flags: SerialFlags::default(),
+            },
+            "--udp" => {
+                let (addr, port) = split_addr(&value)?;
+                LinkyConfig::Udp {
+                    addr,
+                    port,
+                    group: None,
+                    iface: None,
+                    psk: None,
+                    reconnect: 0,
+                }
+            }
+            "--tcp" => {
+                let (host, port) = split_addr(&value)?;
+                LinkyConfig::Tcp {
+                    host,
+                    port,
+                    reconnect: 0,
+                }
+            }
+            _ => LinkyConfig::Replay {
+                path: to_static_str(value),
+                speed: 0,
+                interval: 0,
+                repeat: false,
+            },
+        },
+        None => return Err("missing source".to_string()),
+    };
+
+    Ok(DumpArgs {
+        config,
+        labels,
+        errors,
+    })
+}
+
+fn dump_line(args: &DumpArgs, line: &TicLine) {
+    let raw = String::from_utf8_lossy(&line.raw);
+    let label = tic_label(&raw);
+    if !args.labels.is_empty() && !args.labels.iter().any(|value| value == label) {
+        return;
+    }
+
+    let stamp = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(value) => value.as_millis() as u64,
+        Err(_) => 0,
+    };
+    let jline = match &line.value {
+        Ok(TicValue::UNSET) | Err(LinkyError::RetryLater) => return,
+        Ok(value) => serde_json::json!({"timestamp": stamp, "label": label, "value": value}),
+        Err(error) if args.errors => serde_json::json!({
+            "timestamp": stamp,
+            "label": label,
+            "error": error.get_code(),
+            "info": error.get_info().trim_end(),
+        }),
+        Err(_) => return,
+    };
+    println!("{}", jline);
+}
+
+fn main() {
+    let args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            exit(1);
+        }
+    };
+
+    let handle = match LinkyHandle::new(&args.config) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("linky-dump: fail to open source: {}", error);
+            exit(1);
+        }
+    };
+    eprintln!("linky-dump: {} mode:{}", handle.get_name(), handle.get_mode());
+
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    loop {
+        let status = handle.decode_all(&mut buffer, &mut lines);
+        let idle = lines.is_empty();
+        for line in lines.iter() {
+            dump_line(&args, line);
+        }
+        lines.clear();
+
+        match status {
+            // capture is replayed without pacing, nothing left once a pass is empty
+            Ok(()) if idle && args.config.get_protocol() == "replay" => break,
+            Ok(()) => std::thread::sleep(DUMP_POLL),
+            Err(error) => {
+                eprintln!("linky-dump: {}, reopening source", error);
+                std::thread::sleep(DUMP_RECONNECT);
+                if let Err(error) = handle.reopen() {
+                    eprintln!("linky-dump: fail to reopen source: {}", error);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// run with 'cargo bench -p liblinky', compare against a saved baseline with
+// 'cargo bench -p liblinky -- --save-baseline main' then '--baseline main'
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use linky::prelude::*;
+
+const FRAMES: [(&str, &[u8]); 3] = [
+    ("standard", include_bytes!("../test/frames/standard-mono.tic")),
+    ("producer", include_bytes!("../test/frames/standard-producer.tic")),
+    ("historic", include_bytes!("../test/frames/historic-tri.tic")),
+];
+
+// frame groups as the ring hands them to the parser
+fn frame_lines(frame: &[u8]) -> Vec<Vec<u8>> {
+    let mut ring = BufferRing::new();
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    ring.push(frame);
+    let mut lines = Vec::new();
+    while let Some(count) = ring.get_one_line(&mut buffer) {
+        lines.push(buffer[0..count].to_vec());
+    }
+    lines
+}
+
+// checksum + grammar, lines/second
+fn bench_parse(bench: &mut Criterion) {
+    let mut group = bench.benchmark_group("parse");
+    for (name, frame) in FRAMES {
+        let lines = frame_lines(frame);
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        group.bench_function(name, |bencher| {
+            bencher.iter(|| {
+                for line in &lines {
+                    if let Ok(data) = tic_checksum(line, line.len()) {
+                        let _ = black_box(tic_from_str(data));
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+// line splitting of a whole frame, lines/second
+fn bench_ring(bench: &mut Criterion) {
+    let mut group = bench.benchmark_group("ring");
+    for (name, frame) in FRAMES {
+        let count = frame_lines(frame).len();
+        let mut ring = BufferRing::new();
+        let mut buffer = [0u8; TIC_LINE_MAX];
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_function(name, |bencher| {
+            bencher.iter(|| {
+                ring.push(black_box(frame));
+                while let Some(count) = ring.get_one_line(&mut buffer) {
+                    black_box(&buffer[0..count]);
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_ring);
+criterion_main!(benches);
@@ -9,6 +9,13 @@
 use std::env;
 
 fn main() {
+    // pure Rust backend does not need any C glue
+    #[cfg(feature = "capi")]
+    capi_bindgen();
+}
+
+#[cfg(feature = "capi")]
+fn capi_bindgen() {
     // invalidate the built crate whenever the wrapper changes
     println!("cargo:rerun-if-changed=capi/capi-map.h");
     println!("cargo:rustc-link-search=/usr/local/lib64");
@@ -43,10 +50,14 @@ fn main() {
         .allowlist_function("tcflush")
         .allowlist_function("cfsetispeed")
         .allowlist_function("cfsetospeed")
+        .allowlist_function("poll")
+        .allowlist_function("inotify_init1")
+        .allowlist_function("inotify_add_watch")
         .allowlist_var("TIO_.*")
         .allowlist_var("TCF_.*")
         .allowlist_var("TIF_.*")
         .allowlist_var("TTY_O_.*")
+        .allowlist_var("TIN_.*")
         .allowlist_function("__errno_location")
         .allowlist_function("errno")
         .allowlist_function("strerror_r")
@@ -20,6 +20,8 @@
 #include <sys/types.h>
 #include <sys/stat.h>
 #include <fcntl.h>
+#include <poll.h>
+#include <sys/inotify.h>
 #include <termios.h>
 #include <unistd.h>
 
@@ -30,6 +32,7 @@ const int TTY_O_NDELAY= O_NDELAY;
 const int TTY_O_RDWR= O_RDWR;
 const int TTY_O_RDONLY= O_RDONLY;
 const int TTY_O_SYNC= O_SYNC;
+const int TTY_O_CLOEXEC= O_CLOEXEC;
 
 // control flags (termio c_iflag)
 const uint TIO_ICRNL= ICRNL;
@@ -47,6 +50,8 @@ const uint TIO_B2400= B2400;
 const uint TIO_B9600= B9600;
 const uint TIO_B19200= B19200;
 const uint TIO_B38400= B38400;
+const uint TIO_B57600= B57600;
+const uint TIO_B115200= B115200;
 
 // control bits  (termio c_cflags)
 const uint TCF_CS7= CS7;
@@ -77,3 +82,19 @@ const uint TIO_TCSANOW= TCSANOW; // change attribute now
 // line control (tcflush)
 const int TIO_TCIOFLUSH= TCIOFLUSH; // flush pending input/oputput
 const uint TIO_VMIN= VMIN; // Minimum number of characters for non canonical read (MIN).
+
+// read errors (errno)
+const int TIO_EINTR= EINTR; // interrupted by signal, retry
+const int TIO_EAGAIN= EAGAIN; // nothing to read on non blocking fd
+const int TIO_EIO= EIO; // tty hangup
+const int TIO_ENXIO= ENXIO; // device gone
+const int TIO_ENODEV= ENODEV; // device gone
+
+// wait for data (poll)
+const short TIO_POLLIN= POLLIN; // data ready to read
+
+// device hotplug watch (inotify)
+const int TIN_NONBLOCK= IN_NONBLOCK;
+const int TIN_CLOEXEC= IN_CLOEXEC;
+const uint32_t TIN_CREATE= IN_CREATE; // device node or link created
+const uint32_t TIN_DELETE= IN_DELETE; // device node or link removed
@@ -21,7 +21,7 @@ use std::ffi::CStr;
 use std::ffi::CString;
 use std::mem;
 
-use afbv4::prelude::*;
+use crate::prelude::*;
 
 const MAX_ERROR_LEN: usize = 256;
 pub mod cglue {
@@ -62,6 +62,9 @@ pub struct SerialRaw {
 pub enum SerialSpeed {
     B1200 = cglue::TIO_B1200,
     B9600 = cglue::TIO_B9600,
+    B19200 = cglue::TIO_B19200,
+    B57600 = cglue::TIO_B57600,
+    B115200 = cglue::TIO_B115200,
 }
 
 #[repr(u32)]
@@ -122,10 +125,10 @@ impl SerialRaw {
         iflags: &[SerialIflag],
         cflags: &[SerialCflag],
         lflags: &[SerialLflag],
-    ) -> Result<SerialRaw, AfbError> {
+    ) -> Result<SerialRaw, LinkyFail> {
         let devname = match CString::new(device) {
             Err(_) => {
-                return afb_error!("serial-invalid-devname", "fail to convert name to UTF8")
+                return linky_fail!("serial-invalid-devname", "fail to convert name to UTF8")
             }
             Ok(value) => value,
         };
@@ -151,7 +154,7 @@ impl SerialRaw {
 
         let handle= SerialRaw {
             devname,
-            raw_fd:Cell::new(0),
+            raw_fd:Cell::new(-1),
             speed,
             pflags: tty_pflags,
             iflags: tty_iflags,
@@ -166,11 +169,12 @@ impl SerialRaw {
     }
 
     #[track_caller]
-    pub fn open(&self) -> Result<(), AfbError> {
-        // open tty device
-        let raw_fd = unsafe { cglue::open(self.devname.as_ptr(), self.pflags, 0) };
+    pub fn open(&self) -> Result<(), LinkyFail> {
+        // open tty device, fd is not inherited by spawned processes
+        let pflags = self.pflags | cglue::TTY_O_CLOEXEC;
+        let raw_fd = unsafe { cglue::open(self.devname.as_ptr(), pflags, 0) };
         if raw_fd < 0 {
-            return afb_error!("serial-open-fail", get_perror())
+            return linky_fail!("serial-open-fail", get_perror())
         }
 
         // set attributes useless but ttyios.c_cc[6]= 1 require
@@ -179,10 +183,10 @@ impl SerialRaw {
 
         // Fulup warning cfsetspeed does not seems working as expected with ICANON
         if unsafe { cglue::cfsetispeed(&mut termios, self.speed as u32) } < 0 {
-            return afb_error!("serial-speed-setting", get_perror())
+            return linky_fail!("serial-speed-setting", get_perror())
         }
         if unsafe { cglue::cfsetospeed(&mut termios, self.speed as u32) } < 0 {
-            return afb_error!("serial-speed-setting", get_perror())
+            return linky_fail!("serial-speed-setting", get_perror())
         }
 
         termios.c_cflag= termios.c_cflag| self.cflags;
@@ -190,13 +194,15 @@ impl SerialRaw {
         termios.c_iflag= termios.c_iflag| self.iflags;
 
         if unsafe { cglue::tcsetattr(raw_fd, cglue::TIO_TCSANOW as i32, &mut termios) } < 0 {
-            return afb_error!("serial-flags-setting", get_perror())
+            return linky_fail!("serial-flags-setting", get_perror())
         }
 
         // update fd cell within immutable handle
         self.raw_fd.set(raw_fd);
 
-        afb_log_msg!(Debug, None, "Open port={:?} speed={:?}", self.devname, self.speed);
+        // by-id links are resolved to kernel tty name for log
+        let target = std::fs::canonicalize(self.devname.to_string_lossy().as_ref());
+        linky_log!(Debug, "Open port={:?} tty={:?} speed={:?}", self.devname, target, self.speed);
 
         Ok(())
     }
@@ -205,29 +211,155 @@ impl SerialRaw {
         self.raw_fd.get()
     }
 
-    #[track_caller]
-    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, AfbError> {
-        let count = unsafe {
-            cglue::read(
-                self.raw_fd.get(),
-                buffer as *const _ as *mut raw::c_void,
-                buffer.len(),
-            )
+    // non blocking read, unplugged dongle ends as EOF or hangup and asks for reopen
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        loop {
+            let count = unsafe {
+                cglue::read(
+                    self.raw_fd.get(),
+                    buffer as *const _ as *mut raw::c_void,
+                    buffer.len(),
+                )
+            };
+            if count > 0 {
+                return Ok(count as usize);
+            }
+            if count == 0 {
+                return Err(LinkyError::ReopenDev);
+            }
+
+            match unsafe { *cglue::__errno_location() } {
+                cglue::TIO_EINTR => continue,
+                cglue::TIO_EAGAIN => return Err(LinkyError::RetryLater),
+                cglue::TIO_EIO | cglue::TIO_ENXIO | cglue::TIO_ENODEV => {
+                    return Err(LinkyError::ReopenDev)
+                }
+                _ => return Err(LinkyError::SerialError(get_perror().into())),
+            }
+        }
+    }
+
+    // wait up to timeout(ms) for incoming data, false when nothing arrived
+    pub fn wait(&self, timeout: i32) -> Result<bool, LinkyFail> {
+        let mut pollfd = cglue::pollfd {
+            fd: self.raw_fd.get(),
+            events: cglue::TIO_POLLIN,
+            revents: 0,
         };
 
-        if count <= 0 {
-            afb_error!("SerialRaw-read-fail", get_perror())
-        } else {
-            Ok(count as usize)
+        let count = unsafe { cglue::poll(&mut pollfd, 1, timeout) };
+        if count < 0 {
+            return linky_fail!("SerialRaw-wait-fail", get_perror());
         }
+        Ok(count > 0)
     }
 
-    #[allow(dead_code)]
     pub fn flush(&self) {
         unsafe{cglue::tcflush(self.raw_fd.get(), cglue::TIO_TCIOFLUSH)};
     }
 
+    // pending tty data is discarded, fd is invalidated to prevent a double close
     pub fn close(&self) {
-        unsafe{cglue::close(self.raw_fd.get())};
+        if self.raw_fd.get() >= 0 {
+            self.flush();
+            unsafe{cglue::close(self.raw_fd.replace(-1))};
+        }
+    }
+}
+
+// watch device directory to detect USB dongle replug, also valid for /dev/serial/by-id links
+pub struct DeviceWatch {
+    pub(crate) device: &'static str,
+    pub(crate) raw_fd: raw::c_int,
+}
+
+impl DeviceWatch {
+    #[track_caller]
+    pub fn new(device: &'static str) -> Result<DeviceWatch, LinkyFail> {
+        let dirname = match std::path::Path::new(device).parent() {
+            Some(value) if !value.as_os_str().is_empty() => value,
+            _ => std::path::Path::new("/dev"),
+        };
+
+        // by-id directory vanishes with last dongle, then watch /dev itself
+        let dirname = if dirname.starts_with("/dev/serial") {
+            std::path::Path::new("/dev")
+        } else {
+            dirname
+        };
+
+        let dirname = match CString::new(dirname.to_string_lossy().as_bytes()) {
+            Err(_) => return linky_fail!("watch-invalid-devname", "fail to convert name to UTF8"),
+            Ok(value) => value,
+        };
+
+        let raw_fd = unsafe { cglue::inotify_init1(cglue::TIN_NONBLOCK | cglue::TIN_CLOEXEC) };
+        if raw_fd < 0 {
+            return linky_fail!("watch-init-fail", get_perror());
+        }
+
+        let status = unsafe {
+            cglue::inotify_add_watch(
+                raw_fd,
+                dirname.as_ptr(),
+                cglue::TIN_CREATE | cglue::TIN_DELETE,
+            )
+        };
+        if status < 0 {
+            let error = get_perror();
+            unsafe { cglue::close(raw_fd) };
+            return linky_fail!("watch-add-fail", "dir:{:?} error:{}", dirname, error);
+        }
+
+        Ok(DeviceWatch { device, raw_fd })
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        self.raw_fd
+    }
+
+    // flush pending inotify events, caller only cares about device presence
+    pub fn drain(&self) {
+        let mut buffer = [0u8; 1024];
+        loop {
+            let count = unsafe {
+                cglue::read(
+                    self.raw_fd,
+                    &mut buffer as *mut _ as *mut raw::c_void,
+                    buffer.len(),
+                )
+            };
+            if count <= 0 {
+                break;
+            }
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        std::path::Path::new(self.device).exists()
+    }
+}
+
+impl Drop for DeviceWatch {
+    fn drop(&mut self) {
+        unsafe { cglue::close(self.raw_fd) };
+    }
+}
+
+impl SourceHandle for SerialRaw {
+    fn open(&self) -> Result<(), LinkyFail> {
+        SerialRaw::open(self)
+    }
+
+    fn close(&self) {
+        SerialRaw::close(self)
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        SerialRaw::read(self, buffer)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        SerialRaw::get_raw_fd(self)
     }
 }
@@ -0,0 +1,301 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+// pure Rust replacement for capi-mod.rs, no bindgen/libclang and no unsafe fd handling
+use ::std::os::raw;
+use std::cell::RefCell;
+use std::os::fd::{AsRawFd, OwnedFd};
+
+use rustix::event::{poll, PollFd, PollFlags, Timespec};
+use rustix::fs::{inotify, Mode, OFlags};
+use rustix::io::Errno;
+use rustix::termios::{
+    tcflush, tcgetattr, tcsetattr, ControlModes, InputModes, LocalModes, OptionalActions,
+    OutputModes, QueueSelector, SpecialCodeIndex,
+};
+
+use crate::prelude::*;
+
+pub struct SerialRaw {
+    pub(crate) fd: RefCell<Option<OwnedFd>>,
+    pub(crate) devname: &'static str,
+    pub(crate) speed: SerialSpeed,
+    pub(crate) pflags: u32, // device open flags
+    pub(crate) iflags: u32, // input stream mask
+    pub(crate) cflags: u32, // control stream mask
+    pub(crate) lflags: u32, // local control mask
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+pub enum SerialSpeed {
+    B1200 = 1200,
+    B9600 = 9600,
+    B19200 = 19200,
+    B57600 = 57600,
+    B115200 = 115200,
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+pub enum SerialCflag {
+    CS7 = ControlModes::CS7.bits(),
+    CS8 = ControlModes::CS8.bits(),
+    PARENB = ControlModes::PARENB.bits(),
+    PARODD = ControlModes::PARODD.bits(),
+    CSTOPB = ControlModes::CSTOPB.bits(),
+    CRTSCTS = ControlModes::CRTSCTS.bits(),
+    CLOCAL = ControlModes::CLOCAL.bits(),
+    PAREVN = 0, // C default value
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+pub enum SerialIflag {
+    IGNBRK = InputModes::IGNBRK.bits(),
+    IGNPAR = InputModes::IGNPAR.bits(),
+    INLCR = InputModes::INLCR.bits(),
+    IGNCR = InputModes::IGNCR.bits(),
+    IUCLC = InputModes::IUCLC.bits(),
+    IUTF8 = InputModes::IUTF8.bits(),
+    ICRNL = InputModes::ICRNL.bits(),
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+pub enum SerialLflag {
+    ICANON = LocalModes::ICANON.bits(),
+    XCASE = LocalModes::XCASE.bits(),
+    ISIG = LocalModes::ISIG.bits(),
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code)]
+pub enum PortFlag {
+    NOCTTY = OFlags::NOCTTY.bits(),
+    NDELAY = OFlags::NONBLOCK.bits(),
+    RDWRITE = OFlags::RDWR.bits(),
+    RDONLY = OFlags::RDONLY.bits(),
+    OSYNC = OFlags::SYNC.bits(),
+}
+
+impl SerialRaw {
+    // prepare handle for open operation
+    #[track_caller]
+    pub fn new(
+        device: &'static str,
+        speed: SerialSpeed,
+        pflags: &[PortFlag],
+        iflags: &[SerialIflag],
+        cflags: &[SerialCflag],
+        lflags: &[SerialLflag],
+    ) -> Result<SerialRaw, LinkyFail> {
+        let handle = SerialRaw {
+            fd: RefCell::new(None),
+            devname: device,
+            speed,
+            pflags: pflags.iter().fold(0, |mask, flag| mask | *flag as u32),
+            iflags: iflags.iter().fold(0, |mask, flag| mask | *flag as u32),
+            cflags: cflags.iter().fold(0, |mask, flag| mask | *flag as u32),
+            lflags: lflags.iter().fold(0, |mask, flag| mask | *flag as u32),
+        };
+
+        // open the line before returning the handle
+        handle.open()?;
+
+        Ok(handle)
+    }
+
+    #[track_caller]
+    pub fn open(&self) -> Result<(), LinkyFail> {
+        let fd = match rustix::fs::open(
+            self.devname,
+            OFlags::from_bits_retain(self.pflags) | OFlags::CLOEXEC,
+            Mode::empty(),
+        ) {
+            Err(error) => return linky_fail!("serial-open-fail", error.to_string()),
+            Ok(value) => value,
+        };
+
+        // start from a blank config as C backend does with a zeroed termios
+        let mut termios = match tcgetattr(&fd) {
+            Err(error) => return linky_fail!("serial-flags-setting", error.to_string()),
+            Ok(value) => value,
+        };
+        termios.input_modes = InputModes::from_bits_retain(self.iflags);
+        termios.output_modes = OutputModes::empty();
+        termios.control_modes = ControlModes::from_bits_retain(self.cflags);
+        termios.local_modes = LocalModes::from_bits_retain(self.lflags);
+        termios.special_codes[SpecialCodeIndex::VTIME] = 0;
+        termios.special_codes[SpecialCodeIndex::VMIN] = 1; // read at least one character when not in canonical mode
+
+        if let Err(error) = termios.set_speed(self.speed as u32) {
+            return linky_fail!("serial-speed-setting", error.to_string());
+        }
+
+        if let Err(error) = tcsetattr(&fd, OptionalActions::Now, &termios) {
+            return linky_fail!("serial-flags-setting", error.to_string());
+        }
+
+        // update fd cell within immutable handle
+        self.fd.replace(Some(fd));
+
+        // by-id links are resolved to kernel tty name for log
+        let target = std::fs::canonicalize(self.devname);
+        linky_log!(Debug, "Open port={:?} tty={:?} speed={:?}", self.devname, target, self.speed);
+
+        Ok(())
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        match self.fd.borrow().as_ref() {
+            Some(fd) => fd.as_raw_fd(),
+            None => -1,
+        }
+    }
+
+    // non blocking read, unplugged dongle ends as EOF or hangup and asks for reopen
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let guard = self.fd.borrow();
+        let fd = match guard.as_ref() {
+            None => return Err(LinkyError::ReopenDev),
+            Some(value) => value,
+        };
+
+        loop {
+            return match rustix::io::read(fd, &mut *buffer) {
+                Ok(0) => Err(LinkyError::ReopenDev),
+                Ok(count) => Ok(count),
+                Err(Errno::INTR) => continue,
+                Err(Errno::AGAIN) => Err(LinkyError::RetryLater),
+                Err(Errno::IO | Errno::NXIO | Errno::NODEV) => Err(LinkyError::ReopenDev),
+                Err(error) => Err(LinkyError::SerialError(error.to_string().into())),
+            };
+        }
+    }
+
+    // wait up to timeout(ms) for incoming data, false when nothing arrived
+    pub fn wait(&self, timeout: i32) -> Result<bool, LinkyFail> {
+        let guard = self.fd.borrow();
+        let fd = match guard.as_ref() {
+            None => return linky_fail!("SerialRaw-wait-fail", "device not open"),
+            Some(value) => value,
+        };
+
+        let timeout = Timespec {
+            tv_sec: (timeout / 1000) as i64,
+            tv_nsec: ((timeout % 1000) * 1_000_000) as i64,
+        };
+        let mut pollfd = [PollFd::new(fd, PollFlags::IN)];
+        match poll(&mut pollfd, Some(&timeout)) {
+            Err(error) => linky_fail!("SerialRaw-wait-fail", error.to_string()),
+            Ok(count) => Ok(count > 0),
+        }
+    }
+
+    pub fn flush(&self) {
+        if let Some(fd) = self.fd.borrow().as_ref() {
+            let _ = tcflush(fd, QueueSelector::IOFlush);
+        }
+    }
+
+    pub fn close(&self) {
+        // pending tty data is discarded, dropping OwnedFd closes the device
+        self.flush();
+        self.fd.replace(None);
+    }
+}
+
+// watch device directory to detect USB dongle replug, also valid for /dev/serial/by-id links
+pub struct DeviceWatch {
+    pub(crate) device: &'static str,
+    pub(crate) fd: OwnedFd,
+}
+
+impl DeviceWatch {
+    #[track_caller]
+    pub fn new(device: &'static str) -> Result<DeviceWatch, LinkyFail> {
+        let dirname = match std::path::Path::new(device).parent() {
+            Some(value) if !value.as_os_str().is_empty() => value,
+            _ => std::path::Path::new("/dev"),
+        };
+
+        // by-id directory vanishes with last dongle, then watch /dev itself
+        let dirname = if dirname.starts_with("/dev/serial") {
+            std::path::Path::new("/dev")
+        } else {
+            dirname
+        };
+
+        let fd = match inotify::init(inotify::CreateFlags::NONBLOCK | inotify::CreateFlags::CLOEXEC)
+        {
+            Err(error) => return linky_fail!("watch-init-fail", error.to_string()),
+            Ok(value) => value,
+        };
+
+        if let Err(error) = inotify::add_watch(
+            &fd,
+            dirname,
+            inotify::WatchFlags::CREATE | inotify::WatchFlags::DELETE,
+        ) {
+            return linky_fail!("watch-add-fail", "dir:{:?} error:{}", dirname, error);
+        }
+
+        Ok(DeviceWatch { device, fd })
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        self.fd.as_raw_fd()
+    }
+
+    // flush pending inotify events, caller only cares about device presence
+    pub fn drain(&self) {
+        let mut buffer = [0u8; 1024];
+        while let Ok(count) = rustix::io::read(&self.fd, &mut buffer) {
+            if count == 0 {
+                break;
+            }
+        }
+    }
+
+    pub fn is_present(&self) -> bool {
+        std::path::Path::new(self.device).exists()
+    }
+}
+
+impl SourceHandle for SerialRaw {
+    fn open(&self) -> Result<(), LinkyFail> {
+        SerialRaw::open(self)
+    }
+
+    fn close(&self) {
+        SerialRaw::close(self)
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        SerialRaw::read(self, buffer)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        SerialRaw::get_raw_fd(self)
+    }
+}
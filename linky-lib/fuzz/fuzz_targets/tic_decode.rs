@@ -0,0 +1,30 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use linky::prelude::*;
+
+// raw line as received from the meter, count may be past checksum position or buffer end
+fuzz_target!(|data: &[u8]| {
+    for count in [data.len(), data.len().saturating_sub(1), data.len() + 1] {
+        if let Ok(line) = tic_checksum(data, count) {
+            let _ = tic_from_str(line);
+        }
+    }
+
+    // grammar alone, checksum is most often wrong on random input
+    if let Ok(line) = std::str::from_utf8(data) {
+        let _ = tic_from_str(line);
+    }
+});
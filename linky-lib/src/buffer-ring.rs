@@ -0,0 +1,163 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+pub const RING_BUFFER_SZ: usize = 512; // default read chunk
+pub const RING_BUFFER_MAX: usize = 8 * RING_BUFFER_SZ; // growth limit before dropping garbage
+pub const RING_ETX: u8 = 0x03; // end of frame
+
+// stream sources deliver random chunks, the ring keeps partial lines until '\n' is received
+pub struct BufferRing {
+    data: Vec<u8>,
+    chunk: usize,
+    limit: usize,
+    start: usize,
+    stop: usize,
+    overflow: usize,
+    peak: usize, // pending bytes high-water mark
+    resync: bool,
+}
+
+impl BufferRing {
+    pub fn new() -> BufferRing {
+        BufferRing::with_size(RING_BUFFER_SZ)
+    }
+
+    // size is the room guaranteed to each read, a full datagram should fit within it
+    pub fn with_size(size: usize) -> BufferRing {
+        let chunk = usize::max(size, 1);
+        BufferRing {
+            data: vec![0; chunk],
+            chunk,
+            limit: usize::max(chunk, RING_BUFFER_MAX),
+            start: 0,
+            stop: 0,
+            overflow: 0,
+            peak: 0,
+            resync: false,
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.chunk
+    }
+
+    // move pending data at buffer head and return free space for next read
+    pub fn get_free(&mut self) -> &mut [u8] {
+        if self.start > 0 {
+            self.data.copy_within(self.start..self.stop, 0);
+            self.stop -= self.start;
+            self.start = 0;
+        }
+
+        // keep room for a full chunk, grow up to limit before giving up
+        if self.data.len() - self.stop < self.chunk {
+            if self.data.len() < self.limit {
+                let size = usize::min(self.stop + self.chunk, self.limit);
+                self.data.resize(size, 0);
+            } else if self.stop == self.data.len() {
+                // full buffer without line ending, drop garbage and skip its tail on next line
+                self.overflow += 1;
+                self.resync = true;
+                self.stop = 0;
+            }
+        }
+        &mut self.data[self.stop..]
+    }
+
+    // account bytes written within get_free() slice
+    pub fn commit(&mut self, count: usize) {
+        self.stop = usize::min(self.stop + count, self.data.len());
+        self.peak = usize::max(self.peak, self.stop - self.start);
+    }
+
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        let mut count = 0;
+        while count < data.len() {
+            let free = self.get_free();
+            let len = usize::min(free.len(), data.len() - count);
+            free[0..len].copy_from_slice(&data[count..count + len]);
+            self.commit(len);
+            count += len;
+        }
+        count
+    }
+
+    // copy next complete line (including '\n') into buffer and return its size.
+    // ETX also closes a line, so frame last group keeps its checksum at count-3
+    pub fn get_one_line(&mut self, buffer: &mut [u8]) -> Option<usize> {
+        if self.resync {
+            match self.data[self.start..self.stop]
+                .iter()
+                .position(|byte| *byte == b'\n' || *byte == RING_ETX)
+            {
+                None => {
+                    self.start = 0;
+                    self.stop = 0;
+                    return None;
+                }
+                Some(eol) => {
+                    self.start += eol + 1;
+                    self.resync = false;
+                }
+            }
+        }
+
+        let pending = &self.data[self.start..self.stop];
+        let eol = pending
+            .iter()
+            .position(|byte| *byte == b'\n' || *byte == RING_ETX)?;
+
+        let count = usize::min(eol + 1, buffer.len());
+        buffer[0..count].copy_from_slice(&pending[0..count]);
+        self.start += eol + 1;
+
+        if self.start == self.stop {
+            self.start = 0;
+            self.stop = 0;
+        }
+        Some(count)
+    }
+
+    pub fn get_pending(&self) -> usize {
+        self.stop - self.start
+    }
+
+    // oversized payload was rejected by the source, pending partial line cannot be completed
+    pub fn set_overflow(&mut self) {
+        self.overflow += 1;
+        self.reset();
+    }
+
+    pub fn get_overflow(&self) -> usize {
+        self.overflow
+    }
+
+    pub fn get_peak(&self) -> usize {
+        self.peak
+    }
+
+    pub fn reset_peak(&mut self) {
+        self.peak = 0;
+    }
+
+    pub fn reset(&mut self) {
+        self.start = 0;
+        self.stop = 0;
+        self.resync = false;
+    }
+}
+
+impl Default for BufferRing {
+    fn default() -> Self {
+        Self::new()
+    }
+}
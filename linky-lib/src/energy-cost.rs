@@ -0,0 +1,175 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Tempo contracts publish blue/white/red off-peak/peak energy on EASF01-EASF06
+pub const TEMPO_COLOURS: [&str; 3] = ["blue", "white", "red"];
+
+// price per kWh for each tariff index, index without price costs nothing
+#[derive(Debug, Clone, Copy)]
+pub struct PriceTable {
+    prices: [Option<f64>; TARIFF_INDEX_MAX],
+}
+
+impl PriceTable {
+    pub fn new() -> PriceTable {
+        PriceTable {
+            prices: [None; TARIFF_INDEX_MAX],
+        }
+    }
+
+    // tariff index 1-10, return false when out of range
+    pub fn set_price(&mut self, index: usize, price: f64) -> bool {
+        if !(1..=TARIFF_INDEX_MAX).contains(&index) {
+            return false;
+        }
+        self.prices[index - 1] = Some(price);
+        true
+    }
+
+    // Tempo colour blue|white|red mapped on its off-peak/peak tariff indexes
+    pub fn set_tempo(&mut self, colour: &str, offpeak: f64, peak: f64) -> bool {
+        match TEMPO_COLOURS.iter().position(|value| *value == colour) {
+            Some(idx) => self.set_price(2 * idx + 1, offpeak) && self.set_price(2 * idx + 2, peak),
+            None => false,
+        }
+    }
+
+    pub fn get_price(&self, index: usize) -> Option<f64> {
+        match index {
+            1..=TARIFF_INDEX_MAX => self.prices[index - 1],
+            _ => None,
+        }
+    }
+
+    // energy in Wh per tariff index position 0-9
+    pub fn get_cost(&self, energy: &[u64; TARIFF_INDEX_MAX]) -> f64 {
+        self.prices
+            .iter()
+            .zip(energy.iter())
+            .map(|(price, wh)| price.unwrap_or(0.0) * *wh as f64 / 1000.0)
+            .sum()
+    }
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// cost of a closed day, month cost is only set when the month closes with it
+#[derive(Debug, Clone, Copy)]
+pub struct CostRollover {
+    pub day: f64,
+    pub month: Option<f64>,
+}
+
+// day and month baselines saved across restarts, prices always come from config
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CostState {
+    pub totals: [u64; TARIFF_INDEX_MAX],
+    pub day_start: [u64; TARIFF_INDEX_MAX],
+    pub month_start: [u64; TARIFF_INDEX_MAX],
+    pub date: Option<TimeStampData>,
+}
+
+// running daily/monthly cost computed from per tariff index totals (TariffAccumulator)
+pub struct CostEstimator {
+    prices: PriceTable,
+    totals: [u64; TARIFF_INDEX_MAX],
+    day_start: [u64; TARIFF_INDEX_MAX],
+    month_start: [u64; TARIFF_INDEX_MAX],
+    date: Option<TimeStampData>,
+}
+
+impl CostEstimator {
+    pub fn new(prices: PriceTable) -> CostEstimator {
+        CostEstimator {
+            prices,
+            totals: [0; TARIFF_INDEX_MAX],
+            day_start: [0; TARIFF_INDEX_MAX],
+            month_start: [0; TARIFF_INDEX_MAX],
+            date: None,
+        }
+    }
+
+    pub fn get_prices(&self) -> &PriceTable {
+        &self.prices
+    }
+
+    pub fn set_totals(&mut self, totals: &[u64; TARIFF_INDEX_MAX]) {
+        self.totals = *totals;
+    }
+
+    fn since(&self, start: &[u64; TARIFF_INDEX_MAX]) -> f64 {
+        let mut energy = [0; TARIFF_INDEX_MAX];
+        for (idx, value) in energy.iter_mut().enumerate() {
+            *value = self.totals[idx].saturating_sub(start[idx]);
+        }
+        self.prices.get_cost(&energy)
+    }
+
+    // meter date drives rollover, return closed day (and month) cost
+    pub fn set_date(&mut self, stamp: &TimeStampData) -> Option<CostRollover> {
+        let previous = self.date.replace(*stamp)?;
+        if previous.is_same_day(stamp) {
+            return None;
+        }
+
+        let rollover = CostRollover {
+            day: self.get_today(),
+            month: if previous.is_same_month(stamp) {
+                None
+            } else {
+                Some(self.get_month())
+            },
+        };
+
+        self.day_start = self.totals;
+        if rollover.month.is_some() {
+            self.month_start = self.totals;
+        }
+        Some(rollover)
+    }
+
+    pub fn get_today(&self) -> f64 {
+        self.since(&self.day_start)
+    }
+
+    pub fn get_month(&self) -> f64 {
+        self.since(&self.month_start)
+    }
+
+    pub fn get_state(&self) -> CostState {
+        CostState {
+            totals: self.totals,
+            day_start: self.day_start,
+            month_start: self.month_start,
+            date: self.date,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &CostState) {
+        self.totals = state.totals;
+        self.day_start = state.day_start;
+        self.month_start = state.month_start;
+        self.date = state.date;
+    }
+
+    // totals restart from zero when tariff accumulator is reset
+    pub fn reset(&mut self) {
+        *self = CostEstimator::new(self.prices);
+    }
+}
@@ -0,0 +1,82 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// detect when site exports to grid. SINSTI (VA) gives an immediate answer on meters
+// publishing it, otherwise EAIT (Wh) progression is used and production is considered
+// stopped when counter did not move during timeout seconds.
+pub struct InjectionDetector {
+    threshold: i32,
+    timeout: f64,
+    power: Option<i32>,
+    eait: Option<u64>,
+    increased: Option<f64>,
+    producing: Option<bool>,
+}
+
+impl InjectionDetector {
+    // threshold in VA above which SINSTI means producing
+    pub fn new(threshold: u32, timeout: f64) -> InjectionDetector {
+        InjectionDetector {
+            threshold: threshold as i32,
+            timeout,
+            power: None,
+            eait: None,
+            increased: None,
+            producing: None,
+        }
+    }
+
+    // return new state when it changes, first state is always returned
+    fn set_state(&mut self, producing: bool) -> Option<bool> {
+        if self.producing == Some(producing) {
+            return None;
+        }
+        self.producing = Some(producing);
+        Some(producing)
+    }
+
+    pub fn set_power(&mut self, value: i32) -> Option<bool> {
+        self.power = Some(value);
+        self.set_state(value > self.threshold)
+    }
+
+    // at is sample time in seconds (any origin), ignored when SINSTI is available
+    pub fn set_energy(&mut self, at: f64, value: u64) -> Option<bool> {
+        let previous = self.eait.replace(value);
+        if self.power.is_some() {
+            return None;
+        }
+        match previous {
+            Some(previous) if value > previous => {
+                self.increased = Some(at);
+                self.set_state(true)
+            }
+            Some(_) => match self.increased {
+                Some(stamp) if at - stamp < self.timeout => None,
+                _ => self.set_state(false),
+            },
+            None => None,
+        }
+    }
+
+    pub fn is_producing(&self) -> Option<bool> {
+        self.producing
+    }
+
+    pub fn get_power(&self) -> Option<i32> {
+        self.power
+    }
+
+    pub fn reset(&mut self) {
+        *self = InjectionDetector::new(self.threshold as u32, self.timeout);
+    }
+}
@@ -0,0 +1,210 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnergyPeriod {
+    DAY,
+    WEEK,
+    MONTH,
+}
+
+impl EnergyPeriod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnergyPeriod::DAY => "day",
+            EnergyPeriod::WEEK => "week",
+            EnergyPeriod::MONTH => "month",
+        }
+    }
+
+    fn is_same(&self, start: &TimeStampData, stamp: &TimeStampData) -> bool {
+        match self {
+            EnergyPeriod::DAY => start.is_same_day(stamp),
+            EnergyPeriod::WEEK => start.is_same_week(stamp),
+            EnergyPeriod::MONTH => start.is_same_month(stamp),
+        }
+    }
+}
+
+// energy withdrawn (EAST) and injected (EAIT) in Wh during one period. First period
+// since startup is partial, it starts with the first received meter date
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PeriodRecord {
+    pub start: TimeStampData,
+    pub withdrawn: u64,
+    pub injected: u64,
+    pub partial: bool,
+}
+
+impl PeriodRecord {
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("start", self.start.to_rfc3339())?;
+        jsonc.add("epoch", self.start.get_epoch())?;
+        jsonc.add("withdrawn", self.withdrawn)?;
+        jsonc.add("injected", self.injected)?;
+        jsonc.add("partial", self.partial)?;
+        Ok(jsonc)
+    }
+}
+
+// aggregator counters and baselines saved across restarts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PeriodState {
+    pub period: EnergyPeriod,
+    pub start: Option<TimeStampData>,
+    pub partial: bool,
+    pub east: Option<u64>,
+    pub eait: Option<u64>,
+    pub east_start: Option<u64>,
+    pub eait_start: Option<u64>,
+    pub history: Vec<PeriodRecord>,
+}
+
+// snapshot energy counters at period boundaries using meter local date (DATE label)
+pub struct PeriodAggregator {
+    period: EnergyPeriod,
+    size: usize,
+    start: Option<TimeStampData>,
+    partial: bool,
+    east: Option<u64>,
+    eait: Option<u64>,
+    east_start: Option<u64>,
+    eait_start: Option<u64>,
+    history: VecDeque<PeriodRecord>,
+}
+
+impl PeriodAggregator {
+    // size is the number of closed periods kept
+    pub fn new(period: EnergyPeriod, size: usize) -> PeriodAggregator {
+        PeriodAggregator {
+            period,
+            size,
+            start: None,
+            partial: true,
+            east: None,
+            eait: None,
+            east_start: None,
+            eait_start: None,
+            history: VecDeque::with_capacity(size),
+        }
+    }
+
+    pub fn get_period(&self) -> EnergyPeriod {
+        self.period
+    }
+
+    pub fn set_withdrawn(&mut self, value: u64) {
+        self.east = Some(value);
+        self.east_start.get_or_insert(value);
+    }
+
+    pub fn set_injected(&mut self, value: u64) {
+        self.eait = Some(value);
+        self.eait_start.get_or_insert(value);
+    }
+
+    // return closed period when stamp crosses a boundary
+    pub fn set_date(&mut self, stamp: &TimeStampData) -> Option<PeriodRecord> {
+        let start = match &self.start {
+            None => {
+                self.start = Some(*stamp);
+                return None;
+            }
+            Some(value) if self.period.is_same(value, stamp) => return None,
+            Some(value) => *value,
+        };
+
+        let record = PeriodRecord {
+            start,
+            withdrawn: delta(self.east_start, self.east),
+            injected: delta(self.eait_start, self.eait),
+            partial: self.partial,
+        };
+
+        if self.size > 0 {
+            while self.history.len() >= self.size {
+                self.history.pop_front();
+            }
+            self.history.push_back(record);
+        }
+
+        self.start = Some(*stamp);
+        self.partial = false;
+        self.east_start = self.east;
+        self.eait_start = self.eait;
+        Some(record)
+    }
+
+    // running period, none until meter date is received
+    pub fn get_current(&self) -> Option<PeriodRecord> {
+        self.start.map(|start| PeriodRecord {
+            start,
+            withdrawn: delta(self.east_start, self.east),
+            injected: delta(self.eait_start, self.eait),
+            partial: self.partial,
+        })
+    }
+
+    pub fn get_history(&self) -> &VecDeque<PeriodRecord> {
+        &self.history
+    }
+
+    pub fn get_state(&self) -> PeriodState {
+        PeriodState {
+            period: self.period,
+            start: self.start,
+            partial: self.partial,
+            east: self.east,
+            eait: self.eait,
+            east_start: self.east_start,
+            eait_start: self.eait_start,
+            history: self.history.iter().copied().collect(),
+        }
+    }
+
+    // restored running period continues if next meter date falls within it
+    pub fn set_state(&mut self, state: &PeriodState) -> bool {
+        if state.period != self.period {
+            return false;
+        }
+        let skip = state.history.len().saturating_sub(self.size);
+        self.start = state.start;
+        self.partial = state.partial;
+        self.east = state.east;
+        self.eait = state.eait;
+        self.east_start = state.east_start;
+        self.eait_start = state.eait_start;
+        self.history = state.history.iter().skip(skip).copied().collect();
+        true
+    }
+
+    pub fn reset(&mut self) {
+        *self = PeriodAggregator::new(self.period, self.size);
+    }
+}
+
+// counters going backward (meter replaced) count as no consumption
+fn delta(start: Option<u64>, value: Option<u64>) -> u64 {
+    match (start, value) {
+        (Some(start), Some(value)) => value.saturating_sub(start),
+        _ => 0,
+    }
+}
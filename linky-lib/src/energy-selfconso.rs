@@ -0,0 +1,133 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use std::collections::VecDeque;
+
+// daily grid energy with production when known, ratio is the share of production
+// consumed on site: (produced - injected) / produced
+#[derive(Debug, Clone, Copy)]
+pub struct SelfConsumptionDay {
+    pub grid: PeriodRecord,
+    pub produced: Option<u64>,
+}
+
+impl SelfConsumptionDay {
+    pub fn get_ratio(&self) -> Option<f64> {
+        match self.produced {
+            Some(produced) if produced > 0 => {
+                let injected = u64::min(self.grid.injected, produced);
+                Some((produced - injected) as f64 / produced as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+// meter only sees grid flows, production (Wh counter and W power) is pushed by the
+// inverter side. Without production only grid energy is reported.
+pub struct SelfConsumption {
+    daily: PeriodAggregator,
+    size: usize,
+    produced: Option<u64>,
+    produced_start: Option<u64>,
+    history: VecDeque<SelfConsumptionDay>,
+    power: Option<i32>,
+    injecting: Option<i32>,
+}
+
+impl SelfConsumption {
+    // size is the number of closed days kept
+    pub fn new(size: usize) -> SelfConsumption {
+        SelfConsumption {
+            daily: PeriodAggregator::new(EnergyPeriod::DAY, 0),
+            size,
+            produced: None,
+            produced_start: None,
+            history: VecDeque::with_capacity(size),
+            power: None,
+            injecting: None,
+        }
+    }
+
+    // EAST
+    pub fn set_withdrawn(&mut self, value: u64) {
+        self.daily.set_withdrawn(value);
+    }
+
+    // EAIT
+    pub fn set_injected(&mut self, value: u64) {
+        self.daily.set_injected(value);
+    }
+
+    // SINSTI
+    pub fn set_injected_power(&mut self, value: i32) {
+        self.injecting = Some(value);
+    }
+
+    // inverter cumulative production counter (Wh)
+    pub fn set_production(&mut self, value: u64) {
+        self.produced = Some(value);
+        self.produced_start.get_or_insert(value);
+    }
+
+    // inverter instant production power (W)
+    pub fn set_production_power(&mut self, value: i32) {
+        self.power = Some(value);
+    }
+
+    fn get_produced(&self) -> Option<u64> {
+        match (self.produced_start, self.produced) {
+            (Some(start), Some(value)) => Some(value.saturating_sub(start)),
+            _ => None,
+        }
+    }
+
+    // return closed day on meter midnight
+    pub fn set_date(&mut self, stamp: &TimeStampData) -> Option<SelfConsumptionDay> {
+        let grid = self.daily.set_date(stamp)?;
+        let day = SelfConsumptionDay {
+            grid,
+            produced: self.get_produced(),
+        };
+        self.produced_start = self.produced;
+        if self.size > 0 {
+            while self.history.len() >= self.size {
+                self.history.pop_front();
+            }
+            self.history.push_back(day);
+        }
+        Some(day)
+    }
+
+    pub fn get_today(&self) -> Option<SelfConsumptionDay> {
+        self.daily.get_current().map(|grid| SelfConsumptionDay {
+            grid,
+            produced: self.get_produced(),
+        })
+    }
+
+    // share of production power not exported, none without production
+    pub fn get_instant(&self) -> Option<f64> {
+        let power = self.power.filter(|value| *value > 0)? as f64;
+        let injecting = self.injecting.unwrap_or(0) as f64;
+        Some(((power - injecting) / power).clamp(0.0, 1.0))
+    }
+
+    pub fn get_history(&self) -> &VecDeque<SelfConsumptionDay> {
+        &self.history
+    }
+
+    pub fn reset(&mut self) {
+        *self = SelfConsumption::new(self.size);
+    }
+}
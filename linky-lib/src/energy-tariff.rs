@@ -0,0 +1,221 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+pub const TARIFF_INDEX_MAX: usize = 10; // EASF01-EASF10
+
+// accumulator counters and meter baselines saved across restarts, energy consumed while
+// the binder was down is credited to the restored tariff
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TariffState {
+    pub tariff: Option<usize>,
+    pub east: Option<u64>,
+    pub easf: [Option<u64>; TARIFF_INDEX_MAX],
+    pub indexed: bool,
+    pub total: [u64; TARIFF_INDEX_MAX],
+    pub today: [u64; TARIFF_INDEX_MAX],
+    pub day: Option<TimeStampData>,
+}
+
+// energy consumed per tariff index (Wh) since startup and since meter midnight.
+// EASFxx deltas are credited to their own index, meters without EASF fall back on
+// EAST deltas credited to current NTARF index.
+pub struct TariffAccumulator {
+    tariff: Option<usize>,
+    east: Option<u64>,
+    easf: [Option<u64>; TARIFF_INDEX_MAX],
+    indexed: bool, // EASF seen, EAST is not used anymore
+    total: [u64; TARIFF_INDEX_MAX],
+    today: [u64; TARIFF_INDEX_MAX],
+    day: Option<TimeStampData>,
+}
+
+impl TariffAccumulator {
+    pub fn new() -> TariffAccumulator {
+        TariffAccumulator {
+            tariff: None,
+            east: None,
+            easf: [None; TARIFF_INDEX_MAX],
+            indexed: false,
+            total: [0; TARIFF_INDEX_MAX],
+            today: [0; TARIFF_INDEX_MAX],
+            day: None,
+        }
+    }
+
+    // first value is the baseline, a counter going backward restarts from it
+    fn delta(last: &mut Option<u64>, value: u64) -> u64 {
+        let delta = match *last {
+            Some(previous) if value >= previous => value - previous,
+            _ => 0,
+        };
+        *last = Some(value);
+        delta
+    }
+
+    fn credit(&mut self, idx: usize, delta: u64) -> bool {
+        if delta == 0 || idx >= TARIFF_INDEX_MAX {
+            return false;
+        }
+        self.total[idx] += delta;
+        self.today[idx] += delta;
+        true
+    }
+
+    // NTARF index 1-10
+    pub fn set_tariff(&mut self, index: usize) {
+        if (1..=TARIFF_INDEX_MAX).contains(&index) {
+            self.tariff = Some(index - 1);
+        }
+    }
+
+    pub fn get_tariff(&self) -> Option<usize> {
+        self.tariff.map(|idx| idx + 1)
+    }
+
+    // EAST total withdrawn energy, return true when a tariff was credited
+    pub fn set_total(&mut self, value: u64) -> bool {
+        let delta = TariffAccumulator::delta(&mut self.east, value);
+        match self.tariff {
+            Some(idx) if !self.indexed => self.credit(idx, delta),
+            _ => false,
+        }
+    }
+
+    // EASFxx withdrawn energy on index idx (0-9), return true when credited
+    pub fn set_index(&mut self, idx: usize, value: u64) -> bool {
+        if idx >= TARIFF_INDEX_MAX {
+            return false;
+        }
+        self.indexed = true;
+        let delta = TariffAccumulator::delta(&mut self.easf[idx], value);
+        self.credit(idx, delta)
+    }
+
+    // meter date drives midnight rollover, return true when daily counters were cleared
+    pub fn set_date(&mut self, stamp: &TimeStampData) -> bool {
+        let rollover = match &self.day {
+            Some(day) => !day.is_same_day(stamp),
+            None => false,
+        };
+        if rollover {
+            self.today = [0; TARIFF_INDEX_MAX];
+        }
+        self.day = Some(*stamp);
+        rollover
+    }
+
+    pub fn get_total(&self) -> &[u64; TARIFF_INDEX_MAX] {
+        &self.total
+    }
+
+    pub fn get_today(&self) -> &[u64; TARIFF_INDEX_MAX] {
+        &self.today
+    }
+
+    pub fn get_state(&self) -> TariffState {
+        TariffState {
+            tariff: self.tariff,
+            east: self.east,
+            easf: self.easf,
+            indexed: self.indexed,
+            total: self.total,
+            today: self.today,
+            day: self.day,
+        }
+    }
+
+    pub fn set_state(&mut self, state: &TariffState) {
+        self.tariff = state.tariff.filter(|idx| *idx < TARIFF_INDEX_MAX);
+        self.east = state.east;
+        self.easf = state.easf;
+        self.indexed = state.indexed;
+        self.total = state.total;
+        self.today = state.today;
+        self.day = state.day;
+    }
+
+    pub fn reset(&mut self) {
+        *self = TariffAccumulator::new();
+    }
+}
+
+impl Default for TariffAccumulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Tempo NTARF indexes 1-6 as [off-peak, peak] per colour
+const TEMPO_NAMES: [&str; 6] =
+    ["HC Bleu", "HP Bleu", "HC Blanc", "HP Blanc", "HC Rouge", "HP Rouge"];
+
+// human readable NTARF period: config names first, then Tempo calendar (NGTF) periods, then
+// LTARF labels learned while their index was current. Frames send LTARF before NTARF.
+pub struct TariffNames {
+    names: Vec<String>, // tariff index 1-n
+    calendar: Option<String>,
+    label: Option<String>,
+    learned: [Option<String>; TARIFF_INDEX_MAX],
+}
+
+impl TariffNames {
+    pub fn new(names: Vec<String>) -> TariffNames {
+        TariffNames {
+            names,
+            calendar: None,
+            label: None,
+            learned: Default::default(),
+        }
+    }
+
+    // NGTF, learned labels belong to previous calendar
+    pub fn set_calendar(&mut self, calendar: &str) {
+        if self.calendar.as_deref() != Some(calendar) {
+            self.calendar = Some(calendar.to_string());
+            self.learned = Default::default();
+        }
+    }
+
+    // LTARF
+    pub fn set_label(&mut self, label: &str) {
+        self.label = Some(label.to_string());
+    }
+
+    // NTARF index 1-10
+    pub fn set_tariff(&mut self, index: usize) {
+        if let (Some(label), true) = (&self.label, (1..=TARIFF_INDEX_MAX).contains(&index)) {
+            self.learned[index - 1] = Some(label.clone());
+        }
+    }
+
+    pub fn get_name(&self, index: usize) -> Option<String> {
+        let idx = index.checked_sub(1)?;
+        if let Some(name) = self.names.get(idx) {
+            return Some(name.clone());
+        }
+        let tempo =
+            matches!(self.calendar.as_deref(), Some(calendar) if calendar.contains("TEMPO"));
+        if tempo && idx < TEMPO_NAMES.len() {
+            return Some(TEMPO_NAMES[idx].to_string());
+        }
+        self.learned.get(idx)?.clone()
+    }
+
+    pub fn reset(&mut self) {
+        self.calendar = None;
+        self.label = None;
+        self.learned = Default::default();
+    }
+}
@@ -0,0 +1,129 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+const NOMINAL_TENSION: i32 = 230; // V
+
+// EV charging current (A) left by the household on its most loaded phase. Meter currents
+// include the EV itself, headroom is therefore added to the granted limit. Lower limits
+// apply at once, higher ones wait for 'hysteresis' A of headroom then grow by 'ramp' A/s.
+// Below 'min' charging is paused (0), IEC 61851 forbids less than 6A.
+pub struct ChargeLimit {
+    margin: i32,
+    min: i32,
+    max: i32,
+    hysteresis: i32,
+    ramp: f64,
+    subscribed: Option<i32>, // A per phase (ISOUSC)
+    reference: Option<i32>,  // VA (PREF)
+    currents: [Option<i32>; 3],
+    limit: Option<i32>,
+    stamp: f64, // s, ramp reference
+}
+
+impl ChargeLimit {
+    pub fn new(margin: u32, min: u32, max: u32, hysteresis: u32, ramp: u32) -> ChargeLimit {
+        ChargeLimit {
+            margin: margin as i32,
+            min: min as i32,
+            max: max as i32,
+            hysteresis: hysteresis as i32,
+            ramp: ramp as f64,
+            subscribed: None,
+            reference: None,
+            currents: [None; 3],
+            limit: None,
+            stamp: 0.0,
+        }
+    }
+
+    // ISOUSC (A)
+    pub fn set_subscribed(&mut self, value: i32) {
+        self.subscribed = Some(value);
+    }
+
+    // PREF (kVA)
+    pub fn set_reference(&mut self, kva: i32) {
+        self.reference = Some(kva * 1000);
+    }
+
+    // IINST, IINST1-3 or IRMS1-3 (A)
+    pub fn set_current(&mut self, phase: usize, value: i32) {
+        if (1..=3).contains(&phase) {
+            self.currents[phase - 1] = Some(value);
+        }
+    }
+
+    // ISOUSC when present, PREF split on phases otherwise
+    pub fn get_subscribed(&self) -> Option<i32> {
+        if self.subscribed.is_some() {
+            return self.subscribed;
+        }
+        let phases = if self.currents[1..].iter().any(|value| value.is_some()) {
+            3
+        } else {
+            1
+        };
+        Some(self.reference? / (NOMINAL_TENSION * phases))
+    }
+
+    pub fn get_limit(&self) -> Option<i32> {
+        self.limit
+    }
+
+    // new limit when it changed, none until subscription and currents are known
+    pub fn update(&mut self, at: f64) -> Option<i32> {
+        let load = self.currents.iter().flatten().max()?;
+        let headroom = self.get_subscribed()? - self.margin - load;
+
+        // first value assumes the EV is not charging yet
+        let target = i32::min(self.limit.unwrap_or(0) + headroom, self.max);
+        let target = if target < self.min { 0 } else { target };
+
+        let limit = match self.limit {
+            None => target,
+            Some(limit) if target <= limit => {
+                self.stamp = at;
+                target
+            }
+            // resuming from pause also needs hysteresis above min
+            Some(limit) if target < i32::max(limit, self.min) + self.hysteresis => {
+                self.stamp = at;
+                limit
+            }
+            Some(limit) => {
+                let step = ((at - self.stamp) * self.ramp) as i32;
+                if step == 0 && limit > 0 {
+                    return None;
+                }
+                self.stamp = at;
+                i32::max(i32::min(target, limit + step), self.min)
+            }
+        };
+
+        if self.limit == Some(limit) {
+            return None;
+        }
+        if self.limit.is_none() {
+            self.stamp = at;
+        }
+        self.limit = Some(limit);
+        Some(limit)
+    }
+
+    pub fn reset(&mut self) {
+        self.subscribed = None;
+        self.reference = None;
+        self.currents = [None; 3];
+        self.limit = None;
+        self.stamp = 0.0;
+    }
+}
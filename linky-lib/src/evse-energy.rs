@@ -0,0 +1,142 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+
+const NOMINAL_TENSION: i32 = 230; // V
+
+// tux-evse energy manager view of the meter: tension (V) and current (A) per phase,
+// apparent and subscribed power (VA), over current flags from ADPS (phase 1) or ADIR1-3
+pub struct EvseEnergy {
+    tensions: [Option<i32>; 3],
+    currents: [Option<i32>; 3],
+    power: Option<i32>,
+    subscribed: Option<i32>,
+    over_currents: [bool; 3],
+}
+
+impl EvseEnergy {
+    pub fn new() -> EvseEnergy {
+        EvseEnergy {
+            tensions: [None; 3],
+            currents: [None; 3],
+            power: None,
+            subscribed: None,
+            over_currents: [false; 3],
+        }
+    }
+
+    // URMS1-3 (V)
+    pub fn set_tension(&mut self, phase: usize, value: i32) {
+        if (1..=3).contains(&phase) {
+            self.tensions[phase - 1] = Some(value);
+        }
+    }
+
+    // IINST, IINST1-3 or IRMS1-3 (A)
+    pub fn set_current(&mut self, phase: usize, value: i32) {
+        if (1..=3).contains(&phase) {
+            self.currents[phase - 1] = Some(value);
+        }
+    }
+
+    // SINSTS (VA)
+    pub fn set_power(&mut self, value: i32) {
+        self.power = Some(value);
+    }
+
+    // PREF (kVA)
+    pub fn set_subscribed(&mut self, kva: i32) {
+        self.subscribed = Some(kva * 1000);
+    }
+
+    // return true when phase flag changed
+    pub fn set_over_current(&mut self, phase: usize, alarm: bool) -> bool {
+        if !(1..=3).contains(&phase) || self.over_currents[phase - 1] == alarm {
+            return false;
+        }
+        self.over_currents[phase - 1] = alarm;
+        true
+    }
+
+    pub fn get_over_currents(&self) -> [bool; 3] {
+        self.over_currents
+    }
+
+    // single phase meters only report phase 1
+    pub fn get_phases(&self) -> usize {
+        let three = self.tensions[1..]
+            .iter()
+            .chain(self.currents[1..].iter())
+            .any(|value| value.is_some());
+        if three {
+            3
+        } else {
+            1
+        }
+    }
+
+    // subscribed current per phase (A), from subscribed power on nominal tension
+    pub fn get_subscribed_current(&self) -> Option<i32> {
+        let subscribed = self.subscribed?;
+        Some(subscribed / (NOMINAL_TENSION * self.get_phases() as i32))
+    }
+
+    // '{"phases":1,"tension":[231],"current":[12],"power":2760,"subscribed":9000,
+    // "subscribed_current":39,"over_current":[false]}' unknown values are not reported
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let phases = self.get_phases();
+        let jsonc = JsoncObj::new();
+        jsonc.add("phases", phases as u32)?;
+        if self.tensions.iter().any(|value| value.is_some()) {
+            let jtensions = JsoncObj::array();
+            for (idx, value) in self.tensions[0..phases].iter().enumerate() {
+                jtensions.insert(idx, value.unwrap_or(0))?;
+            }
+            jsonc.add("tension", jtensions)?;
+        }
+        if self.currents.iter().any(|value| value.is_some()) {
+            let jcurrents = JsoncObj::array();
+            for (idx, value) in self.currents[0..phases].iter().enumerate() {
+                jcurrents.insert(idx, value.unwrap_or(0))?;
+            }
+            jsonc.add("current", jcurrents)?;
+        }
+        if let Some(value) = self.power {
+            jsonc.add("power", value)?;
+        }
+        if let Some(value) = self.subscribed {
+            jsonc.add("subscribed", value)?;
+        }
+        if let Some(value) = self.get_subscribed_current() {
+            jsonc.add("subscribed_current", value)?;
+        }
+        let jflags = JsoncObj::array();
+        for (idx, flag) in self.over_currents[0..phases].iter().enumerate() {
+            jflags.insert(idx, *flag)?;
+        }
+        jsonc.add("over_current", jflags)?;
+        Ok(jsonc)
+    }
+
+    pub fn reset(&mut self) {
+        *self = EvseEnergy::new();
+    }
+}
+
+impl Default for EvseEnergy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,170 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+
+// one schedule period, start in s from midnight and power limit in W
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChargingPeriod {
+    pub start: u32,
+    pub index: usize,
+    pub limit: i32,
+}
+
+// next day power-limit schedule from provider calendar, PPOINTE replaces PJOURF+1 when
+// next day is a peak day. Reduced tariff indexes are either listed or priced above threshold.
+pub struct ChargingPlanner {
+    max: Option<i32>, // W, subscribed power when none
+    reduced: i32,
+    indexes: Vec<usize>,
+    prices: Option<(PriceTable, f64)>,
+    subscribed: Option<i32>,
+    profile: Option<ProviderProfile>,
+    peak: Option<ProviderProfile>,
+    peak_day: bool,
+}
+
+impl ChargingPlanner {
+    // power in W, max 0 follows subscribed power
+    pub fn new(max: u32, reduced: u32, indexes: Vec<usize>) -> ChargingPlanner {
+        ChargingPlanner {
+            max: if max > 0 { Some(max as i32) } else { None },
+            reduced: reduced as i32,
+            indexes,
+            prices: None,
+            subscribed: None,
+            profile: None,
+            peak: None,
+            peak_day: false,
+        }
+    }
+
+    // tariff indexes priced above threshold (per kWh) are reduced
+    pub fn set_prices(&mut self, prices: PriceTable, threshold: f64) {
+        self.prices = Some((prices, threshold));
+    }
+
+    // PREF (kVA)
+    pub fn set_subscribed(&mut self, kva: i32) {
+        self.subscribed = Some(kva * 1000);
+    }
+
+    // PJOURF+1
+    pub fn set_profile(&mut self, profile: ProviderProfile) {
+        self.profile = Some(profile);
+    }
+
+    // PPOINTE
+    pub fn set_peak_profile(&mut self, profile: ProviderProfile) {
+        self.peak = Some(profile);
+    }
+
+    pub fn set_peak_day(&mut self, peak_day: bool) {
+        self.peak_day = peak_day;
+    }
+
+    pub fn get_source(&self) -> &'static str {
+        if self.peak_day && self.peak.is_some() {
+            "PPOINTE"
+        } else {
+            "PJOURF1"
+        }
+    }
+
+    pub fn is_reduced(&self, index: usize) -> bool {
+        if self.indexes.contains(&index) {
+            return true;
+        }
+        match &self.prices {
+            Some((prices, threshold)) => match prices.get_price(index) {
+                Some(price) => price > *threshold,
+                None => false,
+            },
+            None => false,
+        }
+    }
+
+    // none until calendar and max power are known. Slot index 0 keeps previous index,
+    // midnight inherits last slot index as calendar wraps from previous day.
+    pub fn get_schedule(&self) -> Option<Vec<ChargingPeriod>> {
+        let max = self.max.or(self.subscribed)?;
+        let profile = match (self.peak_day, &self.peak) {
+            (true, Some(peak)) => peak,
+            _ => self.profile.as_ref()?,
+        };
+
+        let mut slots: Vec<&ProviderInfo> =
+            profile.get_slots().iter().filter(|slot| slot.is_used()).collect();
+        slots.sort_by_key(|slot| slot.get_start());
+
+        let mut index = slots
+            .iter()
+            .rev()
+            .map(|slot| slot.get_index() as usize)
+            .find(|index| *index > 0)
+            .unwrap_or(0);
+
+        let mut periods: Vec<ChargingPeriod> = Vec::new();
+        let mut period = |start: u32, index: usize| {
+            let limit = if self.is_reduced(index) { self.reduced } else { max };
+            match periods.last() {
+                Some(last) if last.index == index && last.limit == limit => {}
+                Some(last) if last.start == start => {
+                    periods.pop();
+                    periods.push(ChargingPeriod { start, index, limit });
+                }
+                _ => periods.push(ChargingPeriod { start, index, limit }),
+            }
+        };
+
+        period(0, index);
+        for slot in slots {
+            if slot.get_index() > 0 {
+                index = slot.get_index() as usize;
+            }
+            period(slot.get_start() * 60, index);
+        }
+        Some(periods)
+    }
+
+    // ISO 15118 / OCPP SmartCharging schedule layout, 'index' is the tariff index
+    // '{"source":"PJOURF1","duration":86400,"chargingRateUnit":"W",
+    // "chargingSchedulePeriod":[{"startPeriod":0,"limit":7400,"index":1}]}'
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("source", self.get_source())?;
+        jsonc.add("duration", 86400)?;
+        jsonc.add("chargingRateUnit", "W")?;
+        let jperiods = JsoncObj::array();
+        if let Some(periods) = self.get_schedule() {
+            for (idx, period) in periods.iter().enumerate() {
+                let jperiod = JsoncObj::new();
+                jperiod.add("startPeriod", period.start)?;
+                jperiod.add("limit", period.limit)?;
+                jperiod.add("index", period.index as u32)?;
+                jperiods.insert(idx, jperiod)?;
+            }
+        }
+        jsonc.add("chargingSchedulePeriod", jperiods)?;
+        Ok(jsonc)
+    }
+
+    pub fn reset(&mut self) {
+        self.subscribed = None;
+        self.profile = None;
+        self.peak = None;
+        self.peak_day = false;
+    }
+}
@@ -0,0 +1,161 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CString;
+use std::time::Duration;
+
+use dbus::channel::{BusType, Channel};
+use dbus::message::MessageType;
+use dbus::strings::ErrorName;
+use dbus::Message;
+
+use crate::prelude::*;
+
+pub const DBUS_NAME: &str = "org.tuxevse.Linky";
+pub const DBUS_PATH: &str = "/org/tuxevse/Linky";
+pub const DBUS_IFACE: &str = "org.tuxevse.Linky";
+
+const DBUS_TIMEOUT: Duration = Duration::from_secs(5);
+const DBUS_INTROSPECT: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.tuxevse.Linky">
+    <method name="ListSensors"><arg name="uids" type="as" direction="out"/></method>
+    <method name="GetValue">
+      <arg name="uid" type="s" direction="in"/>
+      <arg name="values" type="ax" direction="out"/>
+    </method>
+    <method name="GetAll"><arg name="values" type="a{sax}" direction="out"/></method>
+    <signal name="Changed"><arg name="uid" type="s"/><arg name="values" type="ax"/></signal>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect"><arg name="xml" type="s" direction="out"/></method>
+  </interface>
+</node>
+"#;
+
+fn dbus_error(msg: &Message, name: &'static str, text: &str) -> Message {
+    let text = CString::new(text).unwrap_or_default();
+    msg.error(&ErrorName::from(name), &text)
+}
+
+// answer method calls from cached values, none for anything else than a method call
+pub fn dbus_reply(values: &BTreeMap<String, Vec<i64>>, msg: &Message) -> Option<Message> {
+    if msg.msg_type() != MessageType::MethodCall {
+        return None;
+    }
+    let iface = msg.interface();
+    let member = msg.member();
+    let reply = match (iface.as_deref(), member.as_deref()) {
+        (Some("org.freedesktop.DBus.Introspectable"), Some("Introspect")) => {
+            msg.method_return().append1(DBUS_INTROSPECT)
+        }
+        (Some(DBUS_IFACE) | None, Some("ListSensors")) => {
+            msg.method_return().append1(values.keys().cloned().collect::<Vec<String>>())
+        }
+        (Some(DBUS_IFACE) | None, Some("GetAll")) => {
+            let all: HashMap<String, Vec<i64>> =
+                values.iter().map(|(uid, value)| (uid.clone(), value.clone())).collect();
+            msg.method_return().append1(all)
+        }
+        (Some(DBUS_IFACE) | None, Some("GetValue")) => match msg.read1::<&str>() {
+            Err(_) => dbus_error(msg, "org.freedesktop.DBus.Error.InvalidArgs", "uid expected"),
+            Ok(uid) => match values.get(uid) {
+                Some(value) => msg.method_return().append1(value.clone()),
+                None => dbus_error(msg, "org.tuxevse.Linky.Error.UnknownSensor", uid),
+            },
+        },
+        _ => dbus_error(msg, "org.freedesktop.DBus.Error.UnknownMethod", "unknown method"),
+    };
+    Some(reply)
+}
+
+// D-Bus service publishing sensor values, driven by caller main loop through its fd
+pub struct DbusExport {
+    channel: Channel,
+    values: BTreeMap<String, Vec<i64>>,
+}
+
+impl DbusExport {
+    pub fn new(system: bool, name: &str) -> Result<DbusExport, LinkyFail> {
+        let bus = if system {
+            BusType::System
+        } else {
+            BusType::Session
+        };
+        let mut channel = match Channel::get_private(bus) {
+            Ok(value) => value,
+            Err(error) => return linky_fail!("dbus-connect-fail", "bus:{:?} error:{}", bus, error),
+        };
+        channel.set_watch_enabled(true);
+
+        // DO_NOT_QUEUE, a second binding instance should fail instead of waiting
+        let request = match Message::new_method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+        ) {
+            Ok(value) => value.append2(name, 4u32),
+            Err(error) => return linky_fail!("dbus-name-fail", "name:{} error:{}", name, error),
+        };
+        let status = channel
+            .send_with_reply_and_block(request, DBUS_TIMEOUT)
+            .map(|reply| reply.read1::<u32>());
+        match status {
+            Ok(Ok(1)) => {}
+            Ok(_) => return linky_fail!("dbus-name-fail", "name:{} already owned", name),
+            Err(error) => return linky_fail!("dbus-name-fail", "name:{} error:{}", name, error),
+        }
+
+        Ok(DbusExport {
+            channel,
+            values: BTreeMap::new(),
+        })
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        self.channel.watch().fd
+    }
+
+    // cache value and emit 'Changed' signal when it differs from previous one
+    pub fn set_value(&mut self, uid: &str, values: &[i64]) {
+        if self.values.get(uid).map(|value| value.as_slice()) == Some(values) {
+            return;
+        }
+        self.values.insert(uid.to_string(), values.to_vec());
+        if let Ok(signal) = Message::new_signal(DBUS_PATH, DBUS_IFACE, "Changed") {
+            let _ = self.channel.send(signal.append2(uid, values.to_vec()));
+            self.channel.flush();
+        }
+    }
+
+    // drain incoming messages, false when bus connection is lost
+    pub fn process(&mut self) -> bool {
+        if self.channel.read_write(Some(Duration::ZERO)).is_err() {
+            return false;
+        }
+        while let Some(msg) = self.channel.pop_message() {
+            if let Some(reply) = dbus_reply(&self.values, &msg) {
+                let _ = self.channel.send(reply);
+            }
+        }
+        self.channel.flush();
+        self.channel.is_connected()
+    }
+
+    pub fn reset(&mut self) {
+        self.values.clear();
+    }
+}
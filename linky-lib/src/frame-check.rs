@@ -0,0 +1,144 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+use std::collections::BTreeMap;
+
+const FRAME_STX: u8 = 0x02;
+const FRAME_ETX: u8 = 0x03;
+
+// closed frame summary, complete when delimited by STX/ETX with every group accepted
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TicFrame {
+    pub seq: u64,
+    pub groups: u32,          // groups with a valid checksum
+    pub rejected: u32,        // groups lost to checksum, encoding or overflow
+    pub missing: Vec<String>, // labels of previous delimited frame not received
+    pub started: bool,        // STX received
+    pub ended: bool,          // ETX received, false when next STX truncated the frame
+}
+
+impl TicFrame {
+    pub fn is_complete(&self) -> bool {
+        self.started && self.ended && self.rejected == 0 && self.missing.is_empty()
+    }
+
+    // '{"seq":12,"complete":false,"groups":17,"rejected":1,"missing":["EAST"],...}'
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("seq", self.seq)?;
+        jsonc.add("complete", self.is_complete())?;
+        jsonc.add("groups", self.groups)?;
+        jsonc.add("rejected", self.rejected)?;
+        let jmissing = JsoncObj::array();
+        for (idx, label) in self.missing.iter().enumerate() {
+            jmissing.insert(idx, label.as_str())?;
+        }
+        jsonc.add("missing", jmissing)?;
+        jsonc.add("started", self.started)?;
+        jsonc.add("ended", self.ended)?;
+        Ok(jsonc)
+    }
+}
+
+// frame boundaries from raw lines. Expected labels come from last delimited frame
+// without rejected group, a label dropped by the meter is only reported once.
+// Labels keep the seq of the last frame they were seen in, steady frames don't allocate.
+pub struct FrameCheck {
+    seq: u64,
+    open: bool,
+    current: TicFrame,
+    seen: BTreeMap<String, (u64, bool)>, // last frame seq, expected label
+}
+
+impl FrameCheck {
+    pub fn new() -> FrameCheck {
+        FrameCheck {
+            seq: 0,
+            open: false,
+            current: TicFrame::default(),
+            seen: BTreeMap::new(),
+        }
+    }
+
+    // label is none when group was rejected and empty for STX only lines,
+    // return frame closed by this line
+    pub fn push(&mut self, raw: &[u8], label: Option<&str>) -> Option<TicFrame> {
+        let mut closed = None;
+        if raw.contains(&FRAME_STX) {
+            if self.open {
+                closed = Some(self.close(false));
+            }
+            self.current.started = true;
+            self.open = true;
+        }
+
+        match label {
+            None => {
+                self.current.rejected += 1;
+                self.open = true;
+            }
+            Some("") => {}
+            Some(label) => {
+                self.current.groups += 1;
+                match self.seen.get_mut(label) {
+                    Some((last, _)) => *last = self.seq,
+                    None => {
+                        self.seen.insert(label.to_string(), (self.seq, false));
+                    }
+                }
+                self.open = true;
+            }
+        }
+
+        if raw.last() == Some(&FRAME_ETX) {
+            closed = Some(self.close(true));
+        }
+        closed
+    }
+
+    fn close(&mut self, ended: bool) -> TicFrame {
+        let mut frame = std::mem::take(&mut self.current);
+        frame.seq = self.seq;
+        frame.ended = ended;
+        let seq = self.seq;
+        frame.missing = self
+            .seen
+            .iter()
+            .filter(|(_, (last, expected))| *expected && *last != seq)
+            .map(|(label, _)| label.clone())
+            .collect();
+        if frame.started && frame.ended && frame.rejected == 0 {
+            self.seen.retain(|_, (last, _)| *last == seq);
+            for (_, expected) in self.seen.values_mut() {
+                *expected = true;
+            }
+        }
+        self.seq += 1;
+        self.open = false;
+        frame
+    }
+
+    pub fn reset(&mut self) {
+        self.current = TicFrame::default();
+        self.seen.clear();
+        self.open = false;
+    }
+}
+
+impl Default for FrameCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -15,24 +15,221 @@
     html_favicon_url = "https://iot.bzh/images/defaults/favicon.ico"
 )]
 
-#[cfg(not(afbv4))]
+#[cfg(feature = "afb")]
 extern crate afbv4;
 
 #[cfg(test)]
 #[path = "../test/parser-test.rs"]
 mod test;
 
+#[cfg(test)]
+#[path = "../test/frames-test.rs"]
+mod frames;
+
+#[cfg(not(feature = "rustix"))]
 #[path = "../capi/capi-mod.rs"]
 mod capi;
 
+#[cfg(feature = "rustix")]
+#[path = "../capi/capi-rustix.rs"]
+mod capi;
+
+#[path = "source-memory.rs"]
+mod memory;
+
+#[path = "source-net.rs"]
+mod network;
+
+#[cfg(feature = "dtls")]
+#[path = "source-dtls.rs"]
+mod dtls;
+
+#[cfg(feature = "tls")]
+#[path = "source-tls.rs"]
+mod tls;
+
+#[cfg(feature = "websocket")]
+#[path = "source-ws.rs"]
+mod websocket;
+
+#[path = "source-record.rs"]
+mod record;
+
+#[path = "source-replay.rs"]
+mod replay;
+
+#[path = "frame-check.rs"]
+mod frame;
+
+#[path = "link-fault.rs"]
+mod fault;
+
+#[path = "link-quality.rs"]
+mod quality;
+
+#[path = "linky-error.rs"]
+mod error;
+
+#[path = "linky-log.rs"]
+mod log;
+
+#[cfg(feature = "stream")]
+#[path = "linky-stream.rs"]
+mod stream;
+
 #[path = "serial-read.rs"]
 mod serial;
 
+#[cfg(feature = "mqtt")]
+#[path = "mqtt-client.rs"]
+mod mqtt;
+
 #[path = "parser-tic.rs"]
 mod parser;
 
+#[path = "buffer-ring.rs"]
+mod ring;
+
+#[path = "energy-tariff.rs"]
+mod tariff;
+
+#[path = "energy-period.rs"]
+mod period;
+
+#[path = "energy-cost.rs"]
+mod cost;
+
+#[path = "energy-injection.rs"]
+mod injection;
+
+#[path = "energy-selfconso.rs"]
+mod selfconso;
+
+#[path = "evse-charge.rs"]
+mod charge;
+
+#[path = "evse-energy.rs"]
+mod evse;
+
+#[path = "evse-profile.rs"]
+mod profile;
+
+#[cfg(feature = "dbus")]
+#[path = "export-dbus.rs"]
+mod export;
+
+#[cfg(feature = "modbus")]
+#[path = "modbus-server.rs"]
+mod modbus;
+
+#[path = "ocpp-meter.rs"]
+mod ocpp;
+
+#[path = "power-headroom.rs"]
+mod headroom;
+
+#[path = "power-overload.rs"]
+mod overload;
+
+#[path = "power-factor.rs"]
+mod factor;
+
+#[path = "power-imbalance.rs"]
+mod imbalance;
+
+#[path = "power-outage.rs"]
+mod outage;
+
+#[path = "power-ramp.rs"]
+mod ramp;
+
+#[path = "power-shedding.rs"]
+mod shedding;
+
+#[path = "sink-csv.rs"]
+mod csv;
+
+#[cfg(feature = "influx")]
+#[path = "sink-influx.rs"]
+mod influx;
+
+#[cfg(feature = "redis")]
+#[path = "sink-redis.rs"]
+mod redis;
+
+#[cfg(feature = "mqtt")]
+#[path = "sink-sparkplug.rs"]
+mod sparkplug;
+
+#[path = "state-file.rs"]
+mod state;
+
+#[cfg(feature = "sqlite")]
+#[path = "store-sqlite.rs"]
+mod store;
+
+#[path = "sync-cell.rs"]
+mod sync;
+
+#[path = "voltage-quality.rs"]
+mod voltage;
+
 pub mod prelude {
+    pub use crate::capi::{DeviceWatch, PortFlag, SerialCflag, SerialIflag, SerialLflag};
     pub(crate) use crate::capi::*;
+    pub use crate::charge::*;
+    pub use crate::cost::*;
+    pub use crate::csv::*;
+    #[cfg(feature = "dtls")]
+    pub use crate::dtls::*;
+    pub use crate::error::*;
+    pub use crate::evse::*;
+    #[cfg(feature = "dbus")]
+    pub use crate::export::*;
+    pub use crate::factor::*;
+    pub use crate::fault::*;
+    pub use crate::frame::*;
+    pub use crate::headroom::*;
+    pub use crate::imbalance::*;
+    #[cfg(feature = "influx")]
+    pub use crate::influx::*;
+    pub use crate::injection::*;
+    pub use crate::log::*;
+    pub use crate::memory::*;
+    #[cfg(feature = "modbus")]
+    pub use crate::modbus::*;
+    #[cfg(feature = "mqtt")]
+    pub use crate::mqtt::*;
+    pub use crate::network::*;
+    pub use crate::ocpp::*;
+    pub use crate::outage::*;
+    pub use crate::overload::*;
+    pub use crate::record::*;
+    pub use crate::replay::*;
     pub use crate::parser::*;
+    pub use crate::period::*;
+    pub use crate::profile::*;
+    pub use crate::quality::*;
+    pub use crate::ramp::*;
+    #[cfg(feature = "redis")]
+    pub use crate::redis::*;
+    pub use crate::ring::*;
+    pub use crate::selfconso::*;
     pub use crate::serial::*;
+    pub use crate::shedding::*;
+    #[cfg(feature = "mqtt")]
+    pub use crate::sparkplug::*;
+    pub use crate::state::*;
+    #[cfg(feature = "sqlite")]
+    pub use crate::store::*;
+    #[cfg(feature = "stream")]
+    pub use crate::stream::*;
+    pub use crate::sync::*;
+    pub use crate::tariff::*;
+    #[cfg(feature = "tls")]
+    pub use crate::tls::*;
+    pub use crate::voltage::*;
+    #[cfg(feature = "websocket")]
+    pub use crate::websocket::*;
+    pub use crate::{linky_fail, linky_log};
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultAction {
+    Ignore, // descriptor kept, e.g. transient io error
+    Reopen, // close and reopen source, failed reopen is pushed as a new fault
+    Fail,   // give up until an admin reopen
+}
+
+// source failures without any valid line in between, 0 max never gives up
+pub struct FaultTracker {
+    max: u32,
+    faults: u32,
+    failed: bool,
+}
+
+impl FaultTracker {
+    pub fn new(max: u32) -> FaultTracker {
+        FaultTracker {
+            max,
+            faults: 0,
+            failed: false,
+        }
+    }
+
+    // 'reopen' when descriptor is dead (EOF, hangup, failed reopen)
+    pub fn push(&mut self, reopen: bool) -> FaultAction {
+        if self.failed {
+            return FaultAction::Ignore;
+        }
+        self.faults += 1;
+        if self.max > 0 && self.faults >= self.max {
+            self.failed = true;
+            return FaultAction::Fail;
+        }
+        if reopen {
+            FaultAction::Reopen
+        } else {
+            FaultAction::Ignore
+        }
+    }
+
+    // valid line decoded
+    pub fn set_data(&mut self) {
+        self.faults = 0;
+    }
+
+    pub fn get_faults(&self) -> u32 {
+        self.faults
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn reset(&mut self) {
+        self.faults = 0;
+        self.failed = false;
+    }
+}
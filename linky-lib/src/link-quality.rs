@@ -0,0 +1,102 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+const QUALITY_WINDOW: Duration = Duration::from_secs(60);
+
+// label of a rejected line, '?' when it cannot be recovered
+pub fn tic_label(line: &str) -> &str {
+    let line = line.trim_start_matches(['\x02', '\n', '\r']);
+    match line.split(['\t', ' ']).next() {
+        Some(label) if !label.is_empty() && label.len() < line.len() => label,
+        _ => "?",
+    }
+}
+
+// checksum failures per label and over the last minute. State changes when the
+// per-minute count reaches threshold and again when it drops below, 0 disables alarm.
+pub struct LinkQuality {
+    threshold: u32,
+    labels: BTreeMap<String, u64>,
+    recent: VecDeque<Instant>,
+    degraded: bool,
+}
+
+impl LinkQuality {
+    pub fn new(threshold: u32) -> LinkQuality {
+        LinkQuality {
+            threshold,
+            labels: BTreeMap::new(),
+            recent: VecDeque::new(),
+            degraded: false,
+        }
+    }
+
+    pub fn set_threshold(&mut self, threshold: u32) {
+        self.threshold = threshold;
+    }
+
+    pub fn get_threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn push(&mut self, label: &str, now: Instant) {
+        *self.labels.entry(label.to_string()).or_insert(0) += 1;
+        self.recent.push_back(now);
+    }
+
+    // failures within last minute
+    pub fn get_rate(&mut self, now: Instant) -> u32 {
+        while let Some(oldest) = self.recent.front() {
+            if now.duration_since(*oldest) < QUALITY_WINDOW {
+                break;
+            }
+            self.recent.pop_front();
+        }
+        self.recent.len() as u32
+    }
+
+    pub fn get_labels(&self) -> &BTreeMap<String, u64> {
+        &self.labels
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    // Some(true) when link became degraded, Some(false) when it recovered
+    pub fn check(&mut self, now: Instant) -> Option<bool> {
+        if self.threshold == 0 {
+            return None;
+        }
+        let degraded = self.get_rate(now) >= self.threshold;
+        if degraded == self.degraded {
+            return None;
+        }
+        self.degraded = degraded;
+        Some(degraded)
+    }
+
+    pub fn reset(&mut self) {
+        self.labels.clear();
+        self.recent.clear();
+        self.degraded = false;
+    }
+}
+
+impl Default for LinkQuality {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
@@ -0,0 +1,145 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+use std::borrow::Cow;
+use std::fmt;
+
+// setup error (open, bind, config, io) as a stable uid plus context, it replaces AfbError so
+// the library does not need a binder. 'afb' feature converts it into AfbError.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkyFail {
+    uid: String,
+    info: String,
+}
+
+impl LinkyFail {
+    pub fn new(uid: &str, info: impl Into<String>) -> LinkyFail {
+        LinkyFail {
+            uid: uid.to_string(),
+            info: info.into(),
+        }
+    }
+
+    pub fn get_uid(&self) -> &str {
+        self.uid.as_str()
+    }
+
+    pub fn get_info(&self) -> &str {
+        self.info.as_str()
+    }
+}
+
+impl fmt::Display for LinkyFail {
+    fn fmt(&self, format: &mut fmt::Formatter) -> fmt::Result {
+        write!(format, "{}:{}", self.uid, self.info)
+    }
+}
+
+impl std::error::Error for LinkyFail {}
+
+// same syntax as afb_error!, 'linky_fail!("uid", "format {}", args)' or 'linky_fail!("uid", info)'
+#[macro_export]
+macro_rules! linky_fail {
+    ($uid:expr, $format:literal $(, $args:expr)* $(,)?) => {
+        Err($crate::prelude::LinkyFail::new($uid, format!($format $(, $args)*)))
+    };
+    ($uid:expr, $info:expr $(,)?) => {
+        Err($crate::prelude::LinkyFail::new($uid, $info))
+    };
+}
+
+// crate wide source/parser error. Codes are stable, clients may match on them,
+// variants payload holds the context (offending line, io error, size limit). Constant
+// payloads are borrowed, only errors quoting runtime data allocate.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LinkyError {
+    RetryLater,
+    ReopenDev,
+    TooLong(Cow<'static, str>),
+    ParsingError(Cow<'static, str>),
+    UnknownLabel(Cow<'static, str>),
+    SerialError(Cow<'static, str>),
+    ChecksumError(Cow<'static, str>),
+    EncodingError(Cow<'static, str>),
+}
+
+impl LinkyError {
+    pub fn get_code(&self) -> &'static str {
+        match self {
+            LinkyError::RetryLater => "linky-retry-later",
+            LinkyError::ReopenDev => "linky-source-closed",
+            LinkyError::TooLong(_) => "linky-too-long",
+            LinkyError::ParsingError(_) => "linky-parsing-fail",
+            LinkyError::UnknownLabel(_) => "linky-unknown-label",
+            LinkyError::SerialError(_) => "linky-source-fail",
+            LinkyError::ChecksumError(_) => "linky-checksum-fail",
+            LinkyError::EncodingError(_) => "linky-encoding-fail",
+        }
+    }
+
+    pub fn get_info(&self) -> &str {
+        match self {
+            LinkyError::RetryLater | LinkyError::ReopenDev => "",
+            LinkyError::TooLong(info)
+            | LinkyError::ParsingError(info)
+            | LinkyError::UnknownLabel(info)
+            | LinkyError::SerialError(info)
+            | LinkyError::ChecksumError(info)
+            | LinkyError::EncodingError(info) => info,
+        }
+    }
+
+    // '{"error":"linky-checksum-fail","info":"SINSTS\t00022\tX"}'
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("error", self.get_code())?;
+        jsonc.add("info", self.get_info().trim_end())?;
+        Ok(jsonc)
+    }
+}
+
+impl fmt::Display for LinkyError {
+    fn fmt(&self, format: &mut fmt::Formatter) -> fmt::Result {
+        match self.get_info().trim_end() {
+            "" => write!(format, "{}", self.get_code()),
+            info => write!(format, "{} {}", self.get_code(), info),
+        }
+    }
+}
+
+// error code becomes setup error uid
+impl From<LinkyError> for LinkyFail {
+    fn from(error: LinkyError) -> LinkyFail {
+        LinkyFail::new(error.get_code(), error.get_info().trim_end())
+    }
+}
+
+#[cfg(feature = "afb")]
+impl From<LinkyFail> for AfbError {
+    fn from(error: LinkyFail) -> AfbError {
+        let result: Result<(), AfbError> = afb_error!(error.get_uid(), error.get_info());
+        result.unwrap_err()
+    }
+}
+
+// error code becomes afb error uid
+#[cfg(feature = "afb")]
+impl From<LinkyError> for AfbError {
+    fn from(error: LinkyError) -> AfbError {
+        let info = error.get_info().trim_end();
+        let result: Result<(), AfbError> = afb_error!(error.get_code(), info);
+        result.unwrap_err()
+    }
+}
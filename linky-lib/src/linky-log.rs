@@ -0,0 +1,66 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkyLogLevel {
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+pub type LinkyLogHook = fn(LinkyLogLevel, &str);
+
+static LOG_HOOK: OnceLock<LinkyLogHook> = OnceLock::new();
+
+// plain library users route messages to their own logger, first hook wins
+pub fn set_log_hook(hook: LinkyLogHook) -> bool {
+    LOG_HOOK.set(hook).is_ok()
+}
+
+// hook first, then binder log with 'afb' feature, stderr for errors and warnings otherwise
+pub fn log_msg(level: LinkyLogLevel, message: &str) {
+    if let Some(hook) = LOG_HOOK.get() {
+        hook(level, message);
+        return;
+    }
+
+    #[cfg(feature = "afb")]
+    match level {
+        LinkyLogLevel::Error => afb_log_msg!(Error, None, "{}", message),
+        LinkyLogLevel::Warning => afb_log_msg!(Warning, None, "{}", message),
+        LinkyLogLevel::Notice => afb_log_msg!(Notice, None, "{}", message),
+        LinkyLogLevel::Info => afb_log_msg!(Info, None, "{}", message),
+        LinkyLogLevel::Debug => afb_log_msg!(Debug, None, "{}", message),
+    }
+
+    #[cfg(not(feature = "afb"))]
+    if let LinkyLogLevel::Error | LinkyLogLevel::Warning = level {
+        eprintln!("linky {:?}: {}", level, message);
+    }
+}
+
+// afb_log_msg! without target, 'linky_log!(Warning, "source:{} overflow", name)'
+#[macro_export]
+macro_rules! linky_log {
+    ($level:ident, $format:literal $(, $args:expr)* $(,)?) => {
+        $crate::prelude::log_msg(
+            $crate::prelude::LinkyLogLevel::$level,
+            format!($format $(, $args)*).as_str(),
+        )
+    };
+}
@@ -0,0 +1,144 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use futures_core::Stream;
+use std::os::fd::RawFd;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+// what wakes the stream, fd driven sources register their descriptor, polled ones tick
+enum StreamWake {
+    Fd(AsyncFd<RawFd>),
+    Tick(Interval),
+    Closed,
+}
+
+// tokio consumer of any LinkyConfig source, same ring/parser as the binder event loop.
+// Must be created within a runtime with io and time drivers enabled
+pub struct LinkyStream {
+    handle: LinkyHandle,
+    wake: StreamWake,
+    interval: u32,
+    buffer: Vec<u8>,
+    idle: u32, // consecutive RetryLater, the second one means source is drained
+}
+
+impl LinkyStream {
+    pub fn new(config: &LinkyConfig) -> Result<LinkyStream, LinkyFail> {
+        let handle = LinkyHandle::new(config)?;
+        LinkyStream::from_handle(handle, config.get_interval())
+    }
+
+    // interval in ms for sources without fd (http, replay), 0 for fd driven ones
+    pub fn from_handle(handle: LinkyHandle, interval: u32) -> Result<LinkyStream, LinkyFail> {
+        let mut stream = LinkyStream {
+            handle,
+            wake: StreamWake::Closed,
+            interval,
+            buffer: vec![0; TIC_LINE_MAX],
+            idle: 0,
+        };
+        stream.register()?;
+        Ok(stream)
+    }
+
+    fn register(&mut self) -> Result<(), LinkyFail> {
+        self.wake = if self.interval > 0 {
+            let mut ticks = interval(Duration::from_millis(self.interval as u64));
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            StreamWake::Tick(ticks)
+        } else {
+            match self.handle.get_fd() {
+                fd if fd < 0 => StreamWake::Closed,
+                fd => match AsyncFd::new(fd) {
+                    Ok(value) => StreamWake::Fd(value),
+                    Err(error) => {
+                        let name = self.handle.get_name();
+                        return linky_fail!("linky-stream-fail", "source:{} error:{}", name, error);
+                    }
+                },
+            }
+        };
+        self.idle = 0;
+        Ok(())
+    }
+
+    // after ReopenDev, stream ends when source has no descriptor and is not reopened
+    pub fn reopen(&mut self) -> Result<(), LinkyFail> {
+        self.handle.reopen()?;
+        self.register()
+    }
+
+    // stats, quality and gateway stay available while streaming
+    pub fn get_handle(&self) -> &LinkyHandle {
+        &self.handle
+    }
+}
+
+impl Stream for LinkyStream {
+    type Item = Result<TicValue, LinkyError>;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            // tcp-listen swaps its listener for the accepted client
+            if let StreamWake::Fd(wake) = &this.wake {
+                if *wake.get_ref() != this.handle.get_fd() {
+                    if let Err(error) = this.register() {
+                        return Poll::Ready(Some(Err(LinkyError::SerialError(
+                            error.to_string().into(),
+                        ))));
+                    }
+                }
+            }
+
+            match this.handle.read(&mut this.buffer) {
+                Ok(count) => {
+                    this.idle = 0;
+                    match this.handle.parse(&this.buffer, count) {
+                        Err(LinkyError::RetryLater) => continue,
+                        result => return Poll::Ready(Some(result)),
+                    }
+                }
+                Err(LinkyError::RetryLater) => this.idle += 1,
+                Err(error) => {
+                    this.idle = 0;
+                    return Poll::Ready(Some(Err(error)));
+                }
+            }
+
+            // first RetryLater only closes the read sequence, second one hit an empty source
+            if this.idle < 2 {
+                continue;
+            }
+            this.idle = 0;
+            match &mut this.wake {
+                StreamWake::Fd(wake) => match ready!(wake.poll_read_ready(context)) {
+                    Ok(mut guard) => guard.clear_ready(),
+                    Err(error) => {
+                        return Poll::Ready(Some(Err(LinkyError::SerialError(
+                            error.to_string().into(),
+                        ))))
+                    }
+                },
+                StreamWake::Tick(ticks) => {
+                    ready!(ticks.poll_tick(context));
+                }
+                StreamWake::Closed => return Poll::Ready(None),
+            }
+        }
+    }
+}
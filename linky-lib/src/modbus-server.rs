@@ -0,0 +1,242 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::io::AsRawFd;
+
+use crate::prelude::*;
+
+// SunSpec style register map, same content for holding (0x03) and input (0x04) registers.
+// 32 bits values are big endian register pairs, values not received yet read as 0xFFFF.
+//
+//  addr  size  content
+//     0     2  'SunS' marker
+//     2     1  model id (64900, vendor range)
+//     3     1  model length (registers after this one up to end marker)
+//     4     1  map version
+//     5     2  EAST   total energy withdrawn (Wh)
+//     7     2  EAIT   total energy injected (Wh)
+//     9     2  SINSTS apparent power withdrawn (VA)
+//    11     2  SINSTI apparent power injected (VA)
+//    13     3  IRMS1-3 current per phase (A)
+//    16     3  URMS1-3 voltage per phase (V)
+//    19     2  PCOUP  cutting power (VA)
+//    21     1  NTARF  tariff index
+//    22     2  end marker (0xFFFF, 0)
+pub const MODBUS_MODEL: u16 = 64900;
+pub const MODBUS_VERSION: u16 = 1;
+pub const MODBUS_EAST: usize = 5;
+pub const MODBUS_EAIT: usize = 7;
+pub const MODBUS_SINSTS: usize = 9;
+pub const MODBUS_SINSTI: usize = 11;
+pub const MODBUS_IRMS: usize = 13;
+pub const MODBUS_URMS: usize = 16;
+pub const MODBUS_PCOUP: usize = 19;
+pub const MODBUS_NTARF: usize = 21;
+pub const MODBUS_SIZE: usize = 24;
+
+const MBAP_SIZE: usize = 7;
+const MBAP_LENGTH_MAX: usize = 254; // unit id + 253 bytes PDU
+const MBAP_FRAME_MAX: usize = 6 + MBAP_LENGTH_MAX;
+const MODBUS_MAX_READ: usize = 125;
+
+pub struct ModbusRegisters {
+    regs: [u16; MODBUS_SIZE],
+}
+
+impl ModbusRegisters {
+    pub fn new() -> ModbusRegisters {
+        let mut regs = [0xFFFF; MODBUS_SIZE];
+        regs[0] = 0x5375; // 'Su'
+        regs[1] = 0x6e53; // 'nS'
+        regs[2] = MODBUS_MODEL;
+        regs[3] = (MODBUS_SIZE - 6) as u16;
+        regs[4] = MODBUS_VERSION;
+        regs[MODBUS_SIZE - 1] = 0;
+        ModbusRegisters { regs }
+    }
+
+    fn set_u32(&mut self, addr: usize, value: u32) {
+        self.regs[addr] = (value >> 16) as u16;
+        self.regs[addr + 1] = value as u16;
+    }
+
+    // negative values are clamped, registers are unsigned
+    pub fn set_tic(&mut self, data: &TicValue) {
+        match data {
+            TicValue::EAST(value) => self.set_u32(MODBUS_EAST, *value as u32),
+            TicValue::EAIT(value) => self.set_u32(MODBUS_EAIT, *value as u32),
+            TicValue::SINSTS(value) => self.set_u32(MODBUS_SINSTS, (*value).max(0) as u32),
+            TicValue::SINSTI(value) => self.set_u32(MODBUS_SINSTI, (*value).max(0) as u32),
+            TicValue::IRMS1(value) => self.regs[MODBUS_IRMS] = (*value).max(0) as u16,
+            TicValue::IRMS2(value) => self.regs[MODBUS_IRMS + 1] = (*value).max(0) as u16,
+            TicValue::IRMS3(value) => self.regs[MODBUS_IRMS + 2] = (*value).max(0) as u16,
+            TicValue::URMS1(value) => self.regs[MODBUS_URMS] = (*value).max(0) as u16,
+            TicValue::URMS2(value) => self.regs[MODBUS_URMS + 1] = (*value).max(0) as u16,
+            TicValue::URMS3(value) => self.regs[MODBUS_URMS + 2] = (*value).max(0) as u16,
+            TicValue::PCOUP(value) => self.set_u32(MODBUS_PCOUP, (*value).max(0) as u32 * 1000),
+            TicValue::NTARF(value) => self.regs[MODBUS_NTARF] = (*value).max(0) as u16,
+            _ => {}
+        }
+    }
+
+    pub fn get(&self, addr: usize, count: usize) -> Option<&[u16]> {
+        self.regs.get(addr..addr.checked_add(count)?)
+    }
+
+    pub fn reset(&mut self) {
+        *self = ModbusRegisters::new();
+    }
+}
+
+impl Default for ModbusRegisters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// reply to first request within buffer, none while request is incomplete.
+// Returns consumed bytes with the reply, unit id is echoed (gateway semantic).
+pub fn modbus_reply(regs: &ModbusRegisters, request: &[u8]) -> Option<(usize, Vec<u8>)> {
+    if request.len() < MBAP_SIZE + 1 {
+        return None;
+    }
+    // malformed or out of spec header, empty reply tells caller to drop the client
+    let length = u16::from_be_bytes([request[4], request[5]]) as usize;
+    if !(2..=MBAP_LENGTH_MAX).contains(&length) {
+        return Some((request.len(), Vec::new()));
+    }
+    let size = 6 + length;
+    if request.len() < size {
+        return None;
+    }
+
+    let pdu = &request[MBAP_SIZE..size];
+    let function = pdu[0];
+    let exception = |code: u8| vec![function | 0x80, code];
+    let response = match function {
+        0x03 | 0x04 if pdu.len() == 5 => {
+            let addr = u16::from_be_bytes([pdu[1], pdu[2]]) as usize;
+            let count = u16::from_be_bytes([pdu[3], pdu[4]]) as usize;
+            if count == 0 || count > MODBUS_MAX_READ {
+                exception(0x03)
+            } else {
+                match regs.get(addr, count) {
+                    None => exception(0x02),
+                    Some(values) => {
+                        let mut response = vec![function, (count * 2) as u8];
+                        for value in values {
+                            response.extend_from_slice(&value.to_be_bytes());
+                        }
+                        response
+                    }
+                }
+            }
+        }
+        0x03 | 0x04 => exception(0x03),
+        _ => exception(0x01),
+    };
+
+    // MBAP: transaction and protocol id, length, unit id
+    let mut reply = Vec::with_capacity(MBAP_SIZE + response.len());
+    reply.extend_from_slice(&request[0..4]);
+    reply.extend_from_slice(&((response.len() + 1) as u16).to_be_bytes());
+    reply.push(request[6]);
+    reply.extend_from_slice(&response);
+    Some((size, reply))
+}
+
+// non blocking listener, accepted clients are polled by caller main loop
+pub struct ModbusServer {
+    port: u16,
+    listener: TcpListener,
+}
+
+impl ModbusServer {
+    pub fn new(addr: &str, port: u16) -> Result<ModbusServer, LinkyFail> {
+        let listener = match TcpListener::bind((addr, port)) {
+            Err(error) => {
+                let info = format!("addr:{} port:{} error:{}", addr, port, error);
+                return linky_fail!("modbus-listen-fail", info);
+            }
+            Ok(value) => value,
+        };
+        if let Err(error) = listener.set_nonblocking(true) {
+            return linky_fail!("modbus-listen-fail", "port:{} error:{}", port, error);
+        }
+        Ok(ModbusServer { port, listener })
+    }
+
+    pub fn accept(&self) -> Option<ModbusClient> {
+        match self.listener.accept() {
+            Ok((stream, remote)) => {
+                linky_log!(Debug, "Modbus accept port={} remote={}", self.port, remote);
+                if stream.set_nonblocking(true).is_err() {
+                    return None;
+                }
+                Some(ModbusClient {
+                    stream,
+                    pending: Vec::new(),
+                })
+            }
+            Err(_) => None,
+        }
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        self.listener.as_raw_fd()
+    }
+}
+
+pub struct ModbusClient {
+    stream: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl ModbusClient {
+    // answer every complete request, false when client left or sent a malformed request
+    pub fn process(&mut self, regs: &ModbusRegisters) -> bool {
+        let mut buffer = [0u8; MBAP_FRAME_MAX];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return false,
+                Ok(count) => self.pending.extend_from_slice(&buffer[0..count]),
+                Err(error) => match error.kind() {
+                    ErrorKind::Interrupted => continue,
+                    ErrorKind::WouldBlock => return true,
+                    _ => return false,
+                },
+            }
+
+            while let Some((consumed, reply)) = modbus_reply(regs, &self.pending) {
+                if reply.is_empty() {
+                    return false;
+                }
+                self.pending.drain(0..consumed);
+                if self.stream.write_all(&reply).is_err() {
+                    return false;
+                }
+            }
+
+            // complete requests are answered, at most one partial frame may be left
+            if self.pending.len() >= MBAP_FRAME_MAX {
+                return false;
+            }
+        }
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        self.stream.as_raw_fd()
+    }
+}
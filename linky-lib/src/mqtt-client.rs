@@ -0,0 +1,256 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use crate::prelude::*;
+
+const MQTT_TIMEOUT: u64 = 5; // seconds
+const MQTT_CONNECT: u8 = 0x10;
+const MQTT_CONNACK: u8 = 0x20;
+const MQTT_PUBLISH: u8 = 0x30;
+const MQTT_SUBSCRIBE: u8 = 0x82;
+const MQTT_PINGREQ: u8 = 0xC0;
+const MQTT_DISCONNECT: u8 = 0xE0;
+
+// last will published by broker when connection is lost, sent with QoS 1
+pub struct MqttWill<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub retain: bool,
+}
+
+fn mqtt_push_str(buffer: &mut Vec<u8>, data: &[u8]) {
+    buffer.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(data);
+}
+
+// fixed header with variable length remaining size
+fn mqtt_packet(header: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![header];
+    let mut len = body.len();
+    loop {
+        let mut byte = (len % 128) as u8;
+        len /= 128;
+        if len > 0 {
+            byte |= 0x80;
+        }
+        packet.push(byte);
+        if len == 0 {
+            break;
+        }
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+// MQTT 3.1.1 clean session connect
+pub fn mqtt_connect_packet(
+    client_id: &str,
+    keepalive: u16,
+    username: &str,
+    password: &str,
+    will: Option<&MqttWill>,
+) -> Vec<u8> {
+    let mut flags = 0x02;
+    if let Some(will) = will {
+        flags |= 0x04 | 0x08;
+        if will.retain {
+            flags |= 0x20;
+        }
+    }
+    if !username.is_empty() {
+        flags |= 0x80;
+        if !password.is_empty() {
+            flags |= 0x40;
+        }
+    }
+
+    let mut body = Vec::new();
+    mqtt_push_str(&mut body, b"MQTT");
+    body.push(4);
+    body.push(flags);
+    body.extend_from_slice(&keepalive.to_be_bytes());
+    mqtt_push_str(&mut body, client_id.as_bytes());
+    if let Some(will) = will {
+        mqtt_push_str(&mut body, will.topic.as_bytes());
+        mqtt_push_str(&mut body, will.payload);
+    }
+    if !username.is_empty() {
+        mqtt_push_str(&mut body, username.as_bytes());
+        if !password.is_empty() {
+            mqtt_push_str(&mut body, password.as_bytes());
+        }
+    }
+    mqtt_packet(MQTT_CONNECT, &body)
+}
+
+// QoS 0 publish
+pub fn mqtt_publish_packet(topic: &str, payload: &[u8], retain: bool) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 2);
+    mqtt_push_str(&mut body, topic.as_bytes());
+    body.extend_from_slice(payload);
+    mqtt_packet(MQTT_PUBLISH | retain as u8, &body)
+}
+
+// QoS 0 subscription, wildcards are left to the broker
+pub fn mqtt_subscribe_packet(id: u16, topic: &str) -> Vec<u8> {
+    let mut body = id.to_be_bytes().to_vec();
+    mqtt_push_str(&mut body, topic.as_bytes());
+    body.push(0);
+    mqtt_packet(MQTT_SUBSCRIBE, &body)
+}
+
+// first packet within buffer as consumed bytes, header and body, none while incomplete
+pub fn mqtt_decode(buffer: &[u8]) -> Option<(usize, u8, &[u8])> {
+    let mut len = 0usize;
+    for idx in 1..5 {
+        let byte = *buffer.get(idx)?;
+        len += ((byte & 0x7F) as usize) << (7 * (idx - 1));
+        if byte & 0x80 == 0 {
+            let start = idx + 1;
+            let body = buffer.get(start..start + len)?;
+            return Some((start + len, buffer[0], body));
+        }
+    }
+    // more than 4 length bytes, drop whole buffer
+    Some((buffer.len(), 0, &[]))
+}
+
+// publish topic and payload, QoS 1/2 packet id is skipped
+pub fn mqtt_decode_publish(header: u8, body: &[u8]) -> Option<(String, Vec<u8>)> {
+    if header & 0xF0 != MQTT_PUBLISH || body.len() < 2 {
+        return None;
+    }
+    let len = u16::from_be_bytes([body[0], body[1]]) as usize;
+    let topic = String::from_utf8_lossy(body.get(2..2 + len)?).to_string();
+    let start = if header & 0x06 != 0 { 4 + len } else { 2 + len };
+    Some((topic, body.get(start..)?.to_vec()))
+}
+
+// minimal QoS 0 MQTT client, incoming data is polled by caller main loop
+pub struct MqttClient {
+    host: &'static str,
+    stream: TcpStream,
+    pending: Vec<u8>,
+    packet_id: u16,
+}
+
+impl MqttClient {
+    // blocking until CONNACK, socket is then switched to non blocking
+    pub fn connect(
+        host: &'static str,
+        port: u16,
+        client_id: &str,
+        keepalive: u16,
+        username: &str,
+        password: &str,
+        will: Option<&MqttWill>,
+    ) -> Result<MqttClient, LinkyFail> {
+        let mut stream = tcp_connect(host, port)?;
+        let request = mqtt_connect_packet(client_id, keepalive, username, password, will);
+        let mut connack = [0u8; 4];
+        let status = stream
+            .set_read_timeout(Some(Duration::from_secs(MQTT_TIMEOUT)))
+            .and_then(|_| stream.write_all(&request))
+            .and_then(|_| stream.read_exact(&mut connack))
+            .and_then(|_| stream.set_nonblocking(true));
+        if let Err(error) = status {
+            return linky_fail!("mqtt-connect-fail", "host:{} error:{}", host, error);
+        }
+        if connack[0] != MQTT_CONNACK || connack[3] != 0 {
+            return linky_fail!("mqtt-connect-fail", "host:{} refused code:{}", host, connack[3]);
+        }
+
+        Ok(MqttClient {
+            host,
+            stream,
+            pending: Vec::new(),
+            packet_id: 0,
+        })
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<(), LinkyFail> {
+        // non blocking socket, small packets only wait on a full send buffer
+        let mut sent = 0;
+        while sent < packet.len() {
+            match self.stream.write(&packet[sent..]) {
+                Ok(0) => {
+                    return linky_fail!("mqtt-send-fail", "host:{} connection closed", self.host)
+                }
+                Ok(count) => sent += count,
+                Err(error) => match error.kind() {
+                    ErrorKind::Interrupted | ErrorKind::WouldBlock => continue,
+                    _ => {
+                        return linky_fail!("mqtt-send-fail", "host:{} error:{}", self.host, error)
+                    }
+                },
+            }
+        }
+        Ok(())
+    }
+
+    pub fn publish(&mut self, topic: &str, payload: &[u8], retain: bool) -> Result<(), LinkyFail> {
+        self.send(&mqtt_publish_packet(topic, payload, retain))
+    }
+
+    pub fn subscribe(&mut self, topic: &str) -> Result<(), LinkyFail> {
+        self.packet_id = self.packet_id.wrapping_add(1).max(1);
+        self.send(&mqtt_subscribe_packet(self.packet_id, topic))
+    }
+
+    pub fn ping(&mut self) -> Result<(), LinkyFail> {
+        self.send(&[MQTT_PINGREQ, 0])
+    }
+
+    pub fn disconnect(&mut self) {
+        let _ = self.send(&[MQTT_DISCONNECT, 0]);
+    }
+
+    // drain socket and return received publications, error when broker left
+    pub fn process(&mut self) -> Result<Vec<(String, Vec<u8>)>, LinkyFail> {
+        let mut buffer = [0u8; 512];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    return linky_fail!("mqtt-read-fail", "host:{} connection closed", self.host)
+                }
+                Ok(count) => self.pending.extend_from_slice(&buffer[0..count]),
+                Err(error) => match error.kind() {
+                    ErrorKind::Interrupted => continue,
+                    ErrorKind::WouldBlock => break,
+                    _ => {
+                        return linky_fail!("mqtt-read-fail", "host:{} error:{}", self.host, error)
+                    }
+                },
+            }
+        }
+
+        // acks and ping responses are only consumed
+        let mut messages = Vec::new();
+        while let Some((consumed, header, body)) = mqtt_decode(&self.pending) {
+            if let Some(message) = mqtt_decode_publish(header, body) {
+                messages.push(message);
+            }
+            self.pending.drain(0..consumed);
+        }
+        Ok(messages)
+    }
+
+    pub fn get_raw_fd(&self) -> raw::c_int {
+        self.stream.as_raw_fd()
+    }
+}
@@ -0,0 +1,186 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[cfg(feature = "afb")]
+use afbv4::prelude::*;
+
+const OCPP_PHASES: [&str; 3] = ["L1", "L2", "L3"];
+
+// 1.6 sends values as strings with flat unit, 2.0.1 numbers with 'unitOfMeasure'
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OcppVersion {
+    V16,
+    V201,
+}
+
+impl OcppVersion {
+    pub fn from_label(label: &str) -> Option<OcppVersion> {
+        match label {
+            "1.6" => Some(OcppVersion::V16),
+            "2.0.1" => Some(OcppVersion::V201),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OcppVersion::V16 => "1.6",
+            OcppVersion::V201 => "2.0.1",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OcppSample {
+    pub measurand: &'static str,
+    pub phase: Option<&'static str>,
+    pub unit: &'static str,
+    pub value: f64,
+}
+
+// epoch ms as UTC '2023-11-10T10:08:19.123Z'
+pub fn epoch_rfc3339(epoch_ms: u64) -> String {
+    let secs = epoch_ms / 1000;
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    // civil date from days since 1970-01-01
+    let shifted = days + 719468;
+    let era = shifted.div_euclid(146097);
+    let doe = shifted - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        epoch_ms % 1000
+    )
+}
+
+// Linky readings as OCPP MeterValues measurands. Power comes from SINSTS apparent
+// power (VA) as the meter does not report instant active power.
+pub struct OcppMeter {
+    energy: Option<u64>,
+    power: Option<i32>,
+    currents: [Option<i32>; 3],
+}
+
+impl OcppMeter {
+    pub fn new() -> OcppMeter {
+        OcppMeter {
+            energy: None,
+            power: None,
+            currents: [None; 3],
+        }
+    }
+
+    // EAST (Wh)
+    pub fn set_energy(&mut self, value: u64) {
+        self.energy = Some(value);
+    }
+
+    // SINSTS (VA)
+    pub fn set_power(&mut self, value: i32) {
+        self.power = Some(value);
+    }
+
+    // phase 1-3 (A)
+    pub fn set_current(&mut self, phase: usize, value: i32) {
+        if (1..=3).contains(&phase) {
+            self.currents[phase - 1] = Some(value);
+        }
+    }
+
+    // only received values are reported
+    pub fn get_samples(&self) -> Vec<OcppSample> {
+        let mut samples = Vec::new();
+        if let Some(value) = self.energy {
+            samples.push(OcppSample {
+                measurand: "Energy.Active.Import.Register",
+                phase: None,
+                unit: "Wh",
+                value: value as f64,
+            });
+        }
+        if let Some(value) = self.power {
+            samples.push(OcppSample {
+                measurand: "Power.Active.Import",
+                phase: None,
+                unit: "W",
+                value: value as f64,
+            });
+        }
+        for (idx, current) in self.currents.iter().enumerate() {
+            if let Some(value) = current {
+                samples.push(OcppSample {
+                    measurand: "Current.Import",
+                    phase: Some(OCPP_PHASES[idx]),
+                    unit: "A",
+                    value: *value as f64,
+                });
+            }
+        }
+        samples
+    }
+
+    // MeterValue as '{"timestamp":"...","sampledValue":[{"measurand":..., "value":...}]}'
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self, version: OcppVersion, timestamp: &str) -> Result<JsoncObj, AfbError> {
+        let jsamples = JsoncObj::array();
+        for (idx, sample) in self.get_samples().iter().enumerate() {
+            let jsample = JsoncObj::new();
+            jsample.add("measurand", sample.measurand)?;
+            jsample.add("context", "Sample.Periodic")?;
+            jsample.add("location", "Inlet")?;
+            if let Some(phase) = sample.phase {
+                jsample.add("phase", phase)?;
+            }
+            match version {
+                OcppVersion::V16 => {
+                    jsample.add("value", format!("{}", sample.value).as_str())?;
+                    jsample.add("unit", sample.unit)?;
+                }
+                OcppVersion::V201 => {
+                    let junit = JsoncObj::new();
+                    junit.add("unit", sample.unit)?;
+                    jsample.add("value", sample.value)?;
+                    jsample.add("unitOfMeasure", junit)?;
+                }
+            }
+            jsamples.insert(idx, jsample)?;
+        }
+
+        let jsonc = JsoncObj::new();
+        jsonc.add("timestamp", timestamp)?;
+        jsonc.add("sampledValue", jsamples)?;
+        Ok(jsonc)
+    }
+
+    pub fn reset(&mut self) {
+        *self = OcppMeter::new();
+    }
+}
+
+impl Default for OcppMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -13,23 +13,46 @@
  */
 
 use crate::prelude::*;
+#[cfg(feature = "afb")]
 use afbv4::prelude::*;
 use nom::{
-    branch::alt,
-    bytes::complete::{tag, take_while},
+    bytes::complete::{tag, take, take_while},
     character::complete::anychar,
-    character::complete::{char, i32, line_ending, not_line_ending},
+    character::complete::{char, i32, line_ending, not_line_ending, one_of, u64},
     number::complete::hex_u32,
+    sequence::preceded,
     IResult,
 };
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+
+// single json conversion for event payloads and verb replies
+#[cfg(feature = "afb")]
+pub fn serde_jsonc<T: Serialize + ?Sized>(value: &T) -> Result<JsoncObj, AfbError> {
+    match serde_json::to_string(value) {
+        Ok(text) => JsoncObj::parse(text.as_str()),
+        Err(error) => afb_error!("linky-serde-fail", error.to_string()),
+    }
+}
+
+macro_rules! _energy_data {
+    ($label:ident) => {
+        #[allow(non_snake_case)]
+        fn $label(s: &str) -> IResult<&str, TicValue> {
+            let (s, value) = label_to_u64(s, stringify!($label))?;
+            Ok((s, TicValue::$label(value)))
+        }
+    };
+}
 
-macro_rules! _ignore_data {
+macro_rules! _text_data {
     ($label:ident) => {
         #[allow(non_snake_case)]
-        fn $label(s: &str) -> IResult<&str, ()> {
-            let (s, _) = label_to_ignore(s, stringify!($label))?;
-            Ok((s, ()))
+        fn $label(s: &str) -> IResult<&str, TicValue> {
+            let (s, value) = label_to_str(s, stringify!($label))?;
+            Ok((s, TicValue::$label(tic_text(value.trim()))))
         }
     };
 }
@@ -50,12 +73,41 @@ pub enum TicUnit {
     Ampere,
     Volt,
     Watt,
+    WattHour,
     VoltAmpere,
+    VarHour,
     None,
 }
 
+impl TicUnit {
+    pub fn from_label(label: &str) -> Result<TicUnit, LinkyError> {
+        let unit = match label {
+            "A" => TicUnit::Ampere,
+            "V" => TicUnit::Volt,
+            "W" => TicUnit::Watt,
+            "Wh" => TicUnit::WattHour,
+            "VA" => TicUnit::VoltAmpere,
+            "VArh" => TicUnit::VarHour,
+            "" => TicUnit::None,
+            _ => return Err(LinkyError::ParsingError(format!("invalid unit:{}", label).into())),
+        };
+        Ok(unit)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TicUnit::Ampere => "A",
+            TicUnit::Volt => "V",
+            TicUnit::Watt => "W",
+            TicUnit::WattHour => "Wh",
+            TicUnit::VoltAmpere => "VA",
+            TicUnit::VarHour => "VArh",
+            TicUnit::None => "",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
-#[serde(untagged)]
 enum RegisterCut {
     CLOSE,
     SURTENTION,
@@ -68,7 +120,6 @@ enum RegisterCut {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
-#[serde(untagged)]
 enum RegisterMod {
     PROVIDER,
     CONSUMER,
@@ -80,6 +131,35 @@ enum RegisterEnergy {
     NEGATIVE,
 }
 
+// historic three phase PPOT, bit 1-3 is set when phase 1-3 potential is missing
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PhasePresence {
+    #[serde(skip_serializing)]
+    pub raw: u32,
+    present: [bool; 3],
+}
+
+impl PhasePresence {
+    pub fn from_bits(value: u32) -> PhasePresence {
+        PhasePresence {
+            raw: value,
+            present: [value & 0x02 == 0, value & 0x04 == 0, value & 0x08 == 0],
+        }
+    }
+
+    pub fn get_present(&self) -> [bool; 3] {
+        self.present
+    }
+
+    // phases (1-3) present in previous status and missing now
+    pub fn lost(&self, previous: &PhasePresence) -> Vec<usize> {
+        (0..3)
+            .filter(|idx| previous.present[*idx] && !self.present[*idx])
+            .map(|idx| idx + 1)
+            .collect()
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct RegisterStatus {
     #[serde(skip_serializing)]
@@ -91,10 +171,421 @@ pub struct RegisterStatus {
     over_power: bool,
     mode: RegisterMod,
     energy: RegisterEnergy,
+    supplier_index: u32,    // supplier contract tariff index 1-16 (bits 10-13)
+    distributor_index: u32, // distributor contract tariff index 1-4 (bits 14-15)
+}
+
+impl RegisterStatus {
+    // name of decoded fields that differ from previous status
+    pub fn changes(&self, previous: &RegisterStatus) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.relay_open != previous.relay_open {
+            fields.push("relay_open");
+        }
+        if self.cut != previous.cut {
+            fields.push("cut");
+        }
+        if self.door_open != previous.door_open {
+            fields.push("door_open");
+        }
+        if self.over_tension != previous.over_tension {
+            fields.push("over_tension");
+        }
+        if self.over_power != previous.over_power {
+            fields.push("over_power");
+        }
+        if self.mode != previous.mode {
+            fields.push("mode");
+        }
+        if self.energy != previous.energy {
+            fields.push("energy");
+        }
+        if self.supplier_index != previous.supplier_index {
+            fields.push("supplier_index");
+        }
+        if self.distributor_index != previous.distributor_index {
+            fields.push("distributor_index");
+        }
+        fields
+    }
+
+    pub fn get_supplier_index(&self) -> u32 {
+        self.supplier_index
+    }
+
+    pub fn get_distributor_index(&self) -> u32 {
+        self.distributor_index
+    }
+
+    // Tempo colour of current day (bits 24-25)
+    pub fn get_tempo_today(&self) -> TempoColour {
+        TempoColour::from_bits(self.raw >> 24)
+    }
+
+    // Tempo colour announced for next day (bits 26-27)
+    pub fn get_tempo_tomorrow(&self) -> TempoColour {
+        TempoColour::from_bits(self.raw >> 26)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TempoColour {
+    UNKNOWN,
+    BLUE,
+    WHITE,
+    RED,
+}
+
+impl TempoColour {
+    fn from_bits(value: u32) -> TempoColour {
+        match value & 0x03 {
+            1 => TempoColour::BLUE,
+            2 => TempoColour::WHITE,
+            3 => TempoColour::RED,
+            _ => TempoColour::UNKNOWN,
+        }
+    }
+
+    // historic DEMAIN 'BLEU|BLAN|ROUG', '----' until colour is published
+    pub fn from_label(label: &str) -> TempoColour {
+        match label {
+            "BLEU" => TempoColour::BLUE,
+            "BLAN" => TempoColour::WHITE,
+            "ROUG" => TempoColour::RED,
+            _ => TempoColour::UNKNOWN,
+        }
+    }
+
+    // historic PTEC 'HCJB|HPJB|HCJW|HPJW|HCJR|HPJR', other options have no colour
+    pub fn from_period(label: &str) -> TempoColour {
+        match label {
+            "HCJB" | "HPJB" => TempoColour::BLUE,
+            "HCJW" | "HPJW" => TempoColour::WHITE,
+            "HCJR" | "HPJR" => TempoColour::RED,
+            _ => TempoColour::UNKNOWN,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TempoColour::UNKNOWN => "unknown",
+            TempoColour::BLUE => "blue",
+            TempoColour::WHITE => "white",
+            TempoColour::RED => "red",
+        }
+    }
 }
 
-AfbDataConverter!(tic_value, TicValue);
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TicSeason {
+    WINTER, // 'H' hiver UTC+1
+    SUMMER, // 'E' ete UTC+2
+    NONE,   // ' ' meter without season
+}
+
+// serialized as '{"date":"2023-11-10T11:08:19+01:00","epoch":..,"summer":false,"degraded":false}'
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "TimeStampJson")]
+pub struct TimeStampData {
+    season: TicSeason,
+    degraded: bool, // lower case season means meter clock is degraded
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+impl TimeStampData {
+    // Linky meter time is Europe/Paris, season marker gives DST offset
+    pub fn get_offset(&self) -> i32 {
+        match self.season {
+            TicSeason::SUMMER => 2 * 3600,
+            _ => 3600,
+        }
+    }
+
+    // meter local civil date to days since 1970-01-01 (proleptic gregorian)
+    pub fn get_days(&self) -> i64 {
+        let (month, day) = (self.month as i64, self.day as i64);
+        let year = if month <= 2 {
+            self.year as i64 - 1
+        } else {
+            self.year as i64
+        };
+        let era = if year >= 0 { year } else { year - 399 } / 400;
+        let yoe = year - era * 400;
+        let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    pub fn get_epoch(&self) -> i64 {
+        self.get_days() * 86400
+            + self.hour as i64 * 3600
+            + self.minute as i64 * 60
+            + self.second as i64
+            - self.get_offset() as i64
+    }
+
+    pub fn to_rfc3339(&self) -> String {
+        let offset = self.get_offset() / 60;
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}+{:02}:{:02}",
+            self.year,
+            self.month,
+            self.day,
+            self.hour,
+            self.minute,
+            self.second,
+            offset / 60,
+            offset % 60
+        )
+    }
+
+    pub fn is_degraded(&self) -> bool {
+        self.degraded
+    }
+
+    pub fn is_same_day(&self, other: &TimeStampData) -> bool {
+        self.year == other.year && self.month == other.month && self.day == other.day
+    }
+
+    // weeks start on monday, 1970-01-01 was a thursday
+    pub fn is_same_week(&self, other: &TimeStampData) -> bool {
+        (self.get_days() + 3).div_euclid(7) == (other.get_days() + 3).div_euclid(7)
+    }
+
+    pub fn is_same_month(&self, other: &TimeStampData) -> bool {
+        self.year == other.year && self.month == other.month
+    }
+
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        serde_jsonc(self)
+    }
+}
+
+impl Serialize for TimeStampData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TimeStampData", 4)?;
+        state.serialize_field("date", &self.to_rfc3339())?;
+        state.serialize_field("epoch", &self.get_epoch())?;
+        state.serialize_field("summer", &(self.season == TicSeason::SUMMER))?;
+        state.serialize_field("degraded", &self.degraded)?;
+        state.end()
+    }
+}
+
+// serialized layout read back, epoch is recomputed from local date
+#[derive(Deserialize)]
+struct TimeStampJson {
+    date: String,
+    summer: bool,
+    degraded: bool,
+}
+
+impl TryFrom<TimeStampJson> for TimeStampData {
+    type Error = String;
+    fn try_from(value: TimeStampJson) -> Result<Self, Self::Error> {
+        let field = |start: usize, end: usize| match value.date.get(start..end) {
+            Some(text) => text.parse::<u16>().map_err(|_| format!("invalid date:{}", value.date)),
+            None => Err(format!("invalid date:{}", value.date)),
+        };
+        Ok(TimeStampData {
+            season: if value.summer {
+                TicSeason::SUMMER
+            } else {
+                TicSeason::WINTER
+            },
+            degraded: value.degraded,
+            year: field(0, 4)?,
+            month: field(5, 7)? as u8,
+            day: field(8, 10)? as u8,
+            hour: field(11, 13)? as u8,
+            minute: field(14, 16)? as u8,
+            second: field(17, 19)? as u8,
+        })
+    }
+}
+
+// horodated power (daily apparent maximum, load curve point) with its meter timestamp,
+// serialized as '{"value":7250,"date":"..","epoch":..}'
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PowerMaxData {
+    stamp: TimeStampData,
+    value: i32,
+}
+
+impl PowerMaxData {
+    pub fn get_stamp(&self) -> &TimeStampData {
+        &self.stamp
+    }
+
+    pub fn get_value(&self) -> i32 {
+        self.value
+    }
+
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        serde_jsonc(self)
+    }
+}
+
+impl Serialize for PowerMaxData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PowerMaxData", 3)?;
+        state.serialize_field("value", &self.value)?;
+        state.serialize_field("date", &self.stamp.to_rfc3339())?;
+        state.serialize_field("epoch", &self.stamp.get_epoch())?;
+        state.end()
+    }
+}
+
+// real relay action from provider calendar (selector bits 14-15)
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RelayAction {
+    NOCHANGE,
+    TEMPO,
+    OPEN,
+    CLOSE,
+}
+
+// one calendar slot 'HHMMSSSS' start time + 16bit hexa selector, 'NONUTILE' when unused.
+// Serialized as '{"used":true,"start":"06:00","index":2,"relay":"nochange","virtual":[..]}'
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct ProviderInfo {
+    used: bool,
+    hour: u8,
+    minute: u8,
+    selector: u16,
+}
+
+impl ProviderInfo {
+    pub fn is_used(&self) -> bool {
+        self.used
+    }
+
+    // slot start in minutes from midnight
+    pub fn get_start(&self) -> u32 {
+        self.hour as u32 * 60 + self.minute as u32
+    }
+
+    // supplier tariff index to apply (selector bits 0-3), 0 means no change
+    pub fn get_index(&self) -> u8 {
+        (self.selector & 0x0F) as u8
+    }
+
+    pub fn get_relay(&self) -> RelayAction {
+        match self.selector >> 14 & 0x03 {
+            1 => RelayAction::TEMPO,
+            2 => RelayAction::OPEN,
+            3 => RelayAction::CLOSE,
+            _ => RelayAction::NOCHANGE,
+        }
+    }
+
+    // virtual relays 2-7 (selector bits 4-9), true when closed
+    pub fn get_virtual(&self, relay: usize) -> bool {
+        match relay {
+            2..=7 => self.selector >> (relay + 2) & 0x01 == 1,
+            _ => false,
+        }
+    }
+
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        serde_jsonc(self)
+    }
+}
+
+impl Serialize for ProviderInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let count = if self.used { 5 } else { 1 };
+        let mut state = serializer.serialize_struct("ProviderInfo", count)?;
+        state.serialize_field("used", &self.used)?;
+        if self.used {
+            let virtuals: Vec<bool> = (2..=7).map(|relay| self.get_virtual(relay)).collect();
+            state.serialize_field("start", &format!("{:02}:{:02}", self.hour, self.minute))?;
+            state.serialize_field("index", &self.get_index())?;
+            state.serialize_field("relay", &format!("{:?}", self.get_relay()).to_lowercase())?;
+            state.serialize_field("virtual", &virtuals)?;
+        }
+        state.end()
+    }
+}
+
+// calendar size depends on supplier configuration, standard meters send 11 slots.
+// Slots are held inline so decoding PJOURF+1 on every frame does not allocate,
+// serialized as a bare slot array
+pub const PROFILE_SLOTS_MAX: usize = 16;
+
+#[derive(Deserialize, Default, Clone)]
+#[serde(try_from = "Vec<ProviderInfo>")]
+pub struct ProviderProfile {
+    slots: [ProviderInfo; PROFILE_SLOTS_MAX],
+    count: usize,
+}
+
+impl ProviderProfile {
+    fn push(&mut self, info: ProviderInfo) -> bool {
+        match self.slots.get_mut(self.count) {
+            Some(slot) => {
+                *slot = info;
+                self.count += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_slots(&self) -> &[ProviderInfo] {
+        &self.slots[0..self.count]
+    }
+
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        serde_jsonc(self)
+    }
+}
+
+impl TryFrom<Vec<ProviderInfo>> for ProviderProfile {
+    type Error = String;
+    fn try_from(slots: Vec<ProviderInfo>) -> Result<Self, Self::Error> {
+        let mut profile = ProviderProfile::default();
+        for info in slots {
+            if !profile.push(info) {
+                return Err(format!("profile > {} slots", PROFILE_SLOTS_MAX));
+            }
+        }
+        Ok(profile)
+    }
+}
+
+impl Serialize for ProviderProfile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.get_slots())
+    }
+}
+
+impl PartialEq for ProviderProfile {
+    fn eq(&self, other: &Self) -> bool {
+        self.get_slots() == other.get_slots()
+    }
+}
+
+// same output as a slot Vec, golden frames print it
+impl fmt::Debug for ProviderProfile {
+    fn fmt(&self, format: &mut fmt::Formatter) -> fmt::Result {
+        format.debug_struct("ProviderProfile").field("slots", &self.get_slots()).finish()
+    }
+}
+
+#[cfg(feature = "afb")]
+AfbDataConverter!(tic_value, TicValue);
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum TicValue {
     // instant current
     IINST(i32),
@@ -107,6 +598,7 @@ pub enum TicValue {
     SINSTS1(i32),
     SINSTS2(i32),
     SINSTS3(i32),
+    SINSTI(i32), // instant apparent power injected
 
     // courrant efficace
     IRMS1(i32),
@@ -127,12 +619,71 @@ pub enum TicValue {
     // allowed power
     PREF(i32), // preference power
     PCOUP(i32), // cutting power
+    ISOUSC(i32), // subscribed current (A), historic meters
 
     //misc
     ADSC(RegisterStatus),
+    STGE(RegisterStatus), // status register
     RELAIS(i32),
     NTARF(i32), // index tarrification
 
+    // provider labels
+    LTARF(Cow<'static, str>), // current tariff label
+    NGTF(Cow<'static, str>),  // provider calendar name
+
+    // historic mode Tempo
+    PTEC(Cow<'static, str>), // current tariff period
+    DEMAIN(TempoColour), // next day colour
+
+    // historic three phase
+    PPOT(PhasePresence), // phase potential presence
+
+    // historic EJP
+    PEJP(i32), // mobile peak notice (min), only sent during the notice
+
+    // historic maxima
+    IMAX(i32),  // max current (A)
+    IMAX1(i32), // max current ph1
+    IMAX2(i32), // max current ph2
+    IMAX3(i32), // max current ph3
+    PMAX(i32),  // three phase max power (W)
+
+    // energy counters (Wh)
+    EAST(u64),   // total active energy withdrawn
+    EASF01(u64), // active energy withdrawn per supplier index
+    EASF02(u64),
+    EASF03(u64),
+    EASF04(u64),
+    EASF05(u64),
+    EASF06(u64),
+    EASF07(u64),
+    EASF08(u64),
+    EASF09(u64),
+    EASF10(u64),
+    EAIT(u64), // total active energy injected
+    ERQ1(u64), // total reactive energy per quadrant (VArh)
+    ERQ2(u64),
+    ERQ3(u64),
+    ERQ4(u64),
+
+    // meter horodate
+    DATE(TimeStampData),
+    SMAXSN(PowerMaxData), // today max withdrawn power (VA)
+    SMAXIN(PowerMaxData),  // today max injected power (VA)
+    SMAXIN1(PowerMaxData), // yesterday max injected power 'SMAXIN-1'
+    CCASN(PowerMaxData),  // last load curve point active power (W)
+
+    // provider calendar
+    PJOURF1(ProviderProfile), // next day profile 'PJOURF+1'
+    PPOINTE(ProviderProfile), // next peak day profile
+
+    // runtime declared label (registry index, value)
+    CUSTOM(usize, i32),
+
+    // derived values
+    PF(i32),     // power factor in %
+    CHARGE(i32), // EV charging current limit (A)
+
     UNSET,
 }
 
@@ -218,6 +769,200 @@ impl TicObject {
         count: 4,
     };
 
+    pub const EAST: TicObject = TicObject {
+        uid: "EAST",
+        name: "Energy-Withdrawn",
+        info: "Total active energy withdrawn (Wh)",
+        unit: TicUnit::WattHour,
+        count: 1,
+    };
+
+    pub const EASF: TicObject = TicObject {
+        uid: "EASF",
+        name: "Energy-Supplier",
+        info: "Active energy withdrawn per supplier index (Wh)",
+        unit: TicUnit::WattHour,
+        count: 10,
+    };
+
+    pub const EAIT: TicObject = TicObject {
+        uid: "EAIT",
+        name: "Energy-Injected",
+        info: "Total active energy injected (Wh)",
+        unit: TicUnit::WattHour,
+        count: 1,
+    };
+
+    pub const ERQ: TicObject = TicObject {
+        uid: "ERQ",
+        name: "Reactive-Energy",
+        info: "Total reactive energy per quadrant Q1-Q4 (VArh)",
+        unit: TicUnit::VarHour,
+        count: 4,
+    };
+
+    pub const DATE: TicObject = TicObject {
+        uid: "DATE",
+        name: "Date-Time",
+        info: "Meter current date and time",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const IMAX: TicObject = TicObject {
+        uid: "IMAX",
+        name: "Max-Current",
+        info: "Historic max current intensity (A)",
+        unit: TicUnit::Ampere,
+        count: 4,
+    };
+
+    pub const PMAX: TicObject = TicObject {
+        uid: "PMAX",
+        name: "Max-Power-Tri",
+        info: "Historic three phase max power (W)",
+        unit: TicUnit::Watt,
+        count: 1,
+    };
+
+    pub const SMAXSN: TicObject = TicObject {
+        uid: "SMAXSN",
+        name: "Max-Power",
+        info: "Today max withdrawn power (VA)",
+        unit: TicUnit::VoltAmpere,
+        count: 1,
+    };
+
+    pub const SMAXIN: TicObject = TicObject {
+        uid: "SMAXIN",
+        name: "Max-Injected",
+        info: "Today max injected power (VA)",
+        unit: TicUnit::VoltAmpere,
+        count: 1,
+    };
+
+    pub const SMAXIN1: TicObject = TicObject {
+        uid: "SMAXIN-1",
+        name: "Max-Injected-Yesterday",
+        info: "Yesterday max injected power (VA)",
+        unit: TicUnit::VoltAmpere,
+        count: 1,
+    };
+
+    pub const SINSTI: TicObject = TicObject {
+        uid: "SINSTI",
+        name: "Injected-Power",
+        info: "Instant injected power (VA)",
+        unit: TicUnit::VoltAmpere,
+        count: 1,
+    };
+
+    pub const CCASN: TicObject = TicObject {
+        uid: "CCASN",
+        name: "Load-Curve",
+        info: "Last load curve point active power (W)",
+        unit: TicUnit::Watt,
+        count: 1,
+    };
+
+    // derived from CCASN and SINSTS, not sent by meter
+    pub const PF: TicObject = TicObject {
+        uid: "PF",
+        name: "Power-Factor",
+        info: "Estimated power factor (%)",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const ISOUSC: TicObject = TicObject {
+        uid: "ISOUSC",
+        name: "Subscribed-Current",
+        info: "Intensite souscrite (A)",
+        unit: TicUnit::Ampere,
+        count: 1,
+    };
+
+    // derived from ISOUSC/PREF and phase currents, not sent by meter
+    pub const CHARGE: TicObject = TicObject {
+        uid: "CHARGE",
+        name: "Charge-Limit",
+        info: "EV charging current limit left by household load (A)",
+        unit: TicUnit::Ampere,
+        count: 1,
+    };
+
+    pub const PJOURF1: TicObject = TicObject {
+        uid: "PJOURF1",
+        name: "Next-Day-Profile",
+        info: "Provider calendar next day profile",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const PPOINTE: TicObject = TicObject {
+        uid: "PPOINTE",
+        name: "Peak-Day-Profile",
+        info: "Provider calendar next peak day profile",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const LTARF: TicObject = TicObject {
+        uid: "LTARF",
+        name: "Tariff-Label",
+        info: "Provider current tariff label",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const NGTF: TicObject = TicObject {
+        uid: "NGTF",
+        name: "Tariff-Calendar",
+        info: "Provider tariff calendar name",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const PTEC: TicObject = TicObject {
+        uid: "PTEC",
+        name: "Tariff-Period",
+        info: "Historic current tariff period",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const DEMAIN: TicObject = TicObject {
+        uid: "DEMAIN",
+        name: "Tempo-Tomorrow",
+        info: "Historic next day Tempo colour",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const PPOT: TicObject = TicObject {
+        uid: "PPOT",
+        name: "Phase-Presence",
+        info: "Historic phase potential presence",
+        unit: TicUnit::None,
+        count: 3,
+    };
+
+    pub const PEJP: TicObject = TicObject {
+        uid: "PEJP",
+        name: "EJP-Notice",
+        info: "Historic EJP mobile peak notice (min)",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
+    pub const STGE: TicObject = TicObject {
+        uid: "STGE",
+        name: "Status-Flags",
+        info: "Meter status register (relay, cut reason, door, ...)",
+        unit: TicUnit::None,
+        count: 1,
+    };
+
     pub const IGNORED: TicObject = TicObject {
         uid: "IGNORED",
         name: "Ignored",
@@ -226,6 +971,23 @@ impl TicObject {
         count: 0,
     };
 
+    // custom labels are declared at runtime from binding config
+    pub fn new(
+        uid: &'static str,
+        name: &'static str,
+        info: &'static str,
+        unit: TicUnit,
+        count: usize,
+    ) -> TicObject {
+        TicObject {
+            uid,
+            name,
+            info,
+            unit,
+            count,
+        }
+    }
+
     pub fn get_uid(&self) -> &'static str {
         self.uid
     }
@@ -256,6 +1018,7 @@ impl TicValue {
             TicValue::SINSTS1(_) => &TicObject::IINST,
             TicValue::SINSTS2(_) => &TicObject::IINST,
             TicValue::SINSTS3(_) => &TicObject::IINST,
+            TicValue::SINSTI(_) => &TicObject::SINSTI,
 
             TicValue::ADPS(_) => &TicObject::ADPS,
             TicValue::ADIR1(_) => &TicObject::ADPS,
@@ -264,16 +1027,113 @@ impl TicValue {
 
             TicValue::PCOUP(_) => &TicObject::PCOUP,
             TicValue::PREF(_) => &TicObject::PCOUP,
+            TicValue::ISOUSC(_) => &TicObject::ISOUSC,
 
             TicValue::NTARF(_) => &TicObject::NTARF,
+            TicValue::STGE(_) => &TicObject::STGE,
+            TicValue::LTARF(_) => &TicObject::LTARF,
+            TicValue::NGTF(_) => &TicObject::NGTF,
+            TicValue::PTEC(_) => &TicObject::PTEC,
+            TicValue::DEMAIN(_) => &TicObject::DEMAIN,
+            TicValue::PPOT(_) => &TicObject::PPOT,
+            TicValue::PEJP(_) => &TicObject::PEJP,
+            TicValue::IMAX(_) => &TicObject::IMAX,
+            TicValue::IMAX1(_) => &TicObject::IMAX,
+            TicValue::IMAX2(_) => &TicObject::IMAX,
+            TicValue::IMAX3(_) => &TicObject::IMAX,
+            TicValue::PMAX(_) => &TicObject::PMAX,
+
+            TicValue::EAST(_) => &TicObject::EAST,
+            TicValue::EASF01(_) => &TicObject::EASF,
+            TicValue::EASF02(_) => &TicObject::EASF,
+            TicValue::EASF03(_) => &TicObject::EASF,
+            TicValue::EASF04(_) => &TicObject::EASF,
+            TicValue::EASF05(_) => &TicObject::EASF,
+            TicValue::EASF06(_) => &TicObject::EASF,
+            TicValue::EASF07(_) => &TicObject::EASF,
+            TicValue::EASF08(_) => &TicObject::EASF,
+            TicValue::EASF09(_) => &TicObject::EASF,
+            TicValue::EASF10(_) => &TicObject::EASF,
+            TicValue::EAIT(_) => &TicObject::EAIT,
+            TicValue::ERQ1(_) => &TicObject::ERQ,
+            TicValue::ERQ2(_) => &TicObject::ERQ,
+            TicValue::ERQ3(_) => &TicObject::ERQ,
+            TicValue::ERQ4(_) => &TicObject::ERQ,
+
+            TicValue::DATE(_) => &TicObject::DATE,
+            TicValue::SMAXSN(_) => &TicObject::SMAXSN,
+            TicValue::SMAXIN(_) => &TicObject::SMAXIN,
+            TicValue::SMAXIN1(_) => &TicObject::SMAXIN1,
+            TicValue::CCASN(_) => &TicObject::CCASN,
+            TicValue::PF(_) => &TicObject::PF,
+            TicValue::CHARGE(_) => &TicObject::CHARGE,
+            TicValue::PJOURF1(_) => &TicObject::PJOURF1,
+            TicValue::PPOINTE(_) => &TicObject::PPOINTE,
 
             _ => &TicObject::IGNORED,
         }
     }
+
+    // same label carrying a corrected value (sensor calibration), non numeric values are unchanged
+    pub fn with_value(&self, value: i64) -> TicValue {
+        match self {
+            TicValue::IINST(_) => TicValue::IINST(value as i32),
+            TicValue::IINST1(_) => TicValue::IINST1(value as i32),
+            TicValue::IINST2(_) => TicValue::IINST2(value as i32),
+            TicValue::IINST3(_) => TicValue::IINST3(value as i32),
+            TicValue::SINSTS(_) => TicValue::SINSTS(value as i32),
+            TicValue::SINSTS1(_) => TicValue::SINSTS1(value as i32),
+            TicValue::SINSTS2(_) => TicValue::SINSTS2(value as i32),
+            TicValue::SINSTS3(_) => TicValue::SINSTS3(value as i32),
+            TicValue::SINSTI(_) => TicValue::SINSTI(value as i32),
+            TicValue::IRMS1(_) => TicValue::IRMS1(value as i32),
+            TicValue::IRMS2(_) => TicValue::IRMS2(value as i32),
+            TicValue::IRMS3(_) => TicValue::IRMS3(value as i32),
+            TicValue::URMS1(_) => TicValue::URMS1(value as i32),
+            TicValue::URMS2(_) => TicValue::URMS2(value as i32),
+            TicValue::URMS3(_) => TicValue::URMS3(value as i32),
+            TicValue::ADPS(_) => TicValue::ADPS(value as i32),
+            TicValue::ADIR1(_) => TicValue::ADIR1(value as i32),
+            TicValue::ADIR2(_) => TicValue::ADIR2(value as i32),
+            TicValue::ADIR3(_) => TicValue::ADIR3(value as i32),
+            TicValue::PREF(_) => TicValue::PREF(value as i32),
+            TicValue::PCOUP(_) => TicValue::PCOUP(value as i32),
+            TicValue::ISOUSC(_) => TicValue::ISOUSC(value as i32),
+            TicValue::RELAIS(_) => TicValue::RELAIS(value as i32),
+            TicValue::NTARF(_) => TicValue::NTARF(value as i32),
+            TicValue::PEJP(_) => TicValue::PEJP(value as i32),
+            TicValue::IMAX(_) => TicValue::IMAX(value as i32),
+            TicValue::IMAX1(_) => TicValue::IMAX1(value as i32),
+            TicValue::IMAX2(_) => TicValue::IMAX2(value as i32),
+            TicValue::IMAX3(_) => TicValue::IMAX3(value as i32),
+            TicValue::PMAX(_) => TicValue::PMAX(value as i32),
+            TicValue::PF(_) => TicValue::PF(value as i32),
+            TicValue::CHARGE(_) => TicValue::CHARGE(value as i32),
+            TicValue::EAST(_) => TicValue::EAST(value.max(0) as u64),
+            TicValue::EASF01(_) => TicValue::EASF01(value.max(0) as u64),
+            TicValue::EASF02(_) => TicValue::EASF02(value.max(0) as u64),
+            TicValue::EASF03(_) => TicValue::EASF03(value.max(0) as u64),
+            TicValue::EASF04(_) => TicValue::EASF04(value.max(0) as u64),
+            TicValue::EASF05(_) => TicValue::EASF05(value.max(0) as u64),
+            TicValue::EASF06(_) => TicValue::EASF06(value.max(0) as u64),
+            TicValue::EASF07(_) => TicValue::EASF07(value.max(0) as u64),
+            TicValue::EASF08(_) => TicValue::EASF08(value.max(0) as u64),
+            TicValue::EASF09(_) => TicValue::EASF09(value.max(0) as u64),
+            TicValue::EASF10(_) => TicValue::EASF10(value.max(0) as u64),
+            TicValue::EAIT(_) => TicValue::EAIT(value.max(0) as u64),
+            TicValue::ERQ1(_) => TicValue::ERQ1(value.max(0) as u64),
+            TicValue::ERQ2(_) => TicValue::ERQ2(value.max(0) as u64),
+            TicValue::ERQ3(_) => TicValue::ERQ3(value.max(0) as u64),
+            TicValue::ERQ4(_) => TicValue::ERQ4(value.max(0) as u64),
+            TicValue::CUSTOM(idx, _) => TicValue::CUSTOM(*idx, value as i32),
+            _ => self.clone(),
+        }
+    }
 }
 
+// standard mode uses HT, historic mode SP
 fn separator(input: &str) -> IResult<&str, char> {
-    char(0x09 as char)(input)
+    one_of("\t ")(input)
 }
 
 fn not_separator(chr: char) -> bool {
@@ -309,7 +1169,7 @@ fn label_to_register<'a>(s: &'a str, label: &str) -> IResult<&'a str, RegisterSt
     let (s, _) = checksum(s)?;
 
     let relay = value & 0x01 == 1;
-    let cut = match value >> 1 & 0x111 {
+    let cut = match value >> 1 & 0x07 {
         0 => RegisterCut::CLOSE,
         1 => RegisterCut::OVERPOWER,
         2 => RegisterCut::SURTENTION,
@@ -343,6 +1203,8 @@ fn label_to_register<'a>(s: &'a str, label: &str) -> IResult<&'a str, RegisterSt
         over_power: power,
         mode: mode,
         energy: active,
+        supplier_index: (value >> 10 & 0x0F) + 1,
+        distributor_index: (value >> 14 & 0x03) + 1,
     };
     Ok((s, register))
 }
@@ -355,14 +1217,43 @@ fn label_to_int<'a>(s: &'a str, label: &str) -> IResult<&'a str, i32> {
     Ok((s, value))
 }
 
-fn _label_to_str<'a>(s: &'a str, label: &str) -> IResult<&'a str, &'a str> {
+// energy counters are 9 digits Wh and should never wrap
+fn label_to_u64<'a>(s: &'a str, label: &str) -> IResult<&'a str, u64> {
     let (s, _) = tag(label)(s)?;
     let (s, _) = separator(s)?;
-    let (s, value) = take_while(not_separator)(s)?;
+    let (s, value) = u64(s)?;
     let (s, _) = checksum(s)?;
     Ok((s, value))
 }
 
+fn label_to_str<'a>(s: &'a str, label: &str) -> IResult<&'a str, &'a str> {
+    let (s, _) = tag(label)(s)?;
+    // standard text may hold spaces, value ends with the separator that opened it
+    let (s, delimiter) = separator(s)?;
+    let (s, value) = take_while(|chr| chr != delimiter)(s)?;
+    let (s, _) = checksum(s)?;
+    Ok((s, value))
+}
+
+// provider text values repeat on every frame. Each distinct short value is leaked once and
+// handed out borrowed, past TEXT_POOL_MAX values new text is copied instead.
+const TEXT_POOL_MAX: usize = 64;
+const TEXT_POOL_LEN: usize = 32;
+static TEXT_POOL: SyncLock<Vec<&'static str>> = SyncLock::new(Vec::new());
+
+fn tic_text(value: &str) -> Cow<'static, str> {
+    let mut pool = TEXT_POOL.lock();
+    if let Some(text) = pool.iter().find(|text| **text == value) {
+        return Cow::Borrowed(text);
+    }
+    if value.len() > TEXT_POOL_LEN || pool.len() >= TEXT_POOL_MAX {
+        return Cow::Owned(value.to_string());
+    }
+    let text: &'static str = Box::leak(value.to_string().into_boxed_str());
+    pool.push(text);
+    Cow::Borrowed(text)
+}
+
 fn label_to_ignore<'a>(s: &'a str, label: &str) -> IResult<&'a str, ()> {
     let (s, _) = tag(label)(s)?;
     let (s, _) = not_line_ending(s)?;
@@ -370,12 +1261,167 @@ fn label_to_ignore<'a>(s: &'a str, label: &str) -> IResult<&'a str, ()> {
     Ok((s, ()))
 }
 
+// horodate 'SAAMMJJhhmmss' where S is season, date field may be followed by an empty value
+fn two_digits(s: &str) -> IResult<&str, u8> {
+    let (s, value) = take(2usize)(s)?;
+    match value.parse::<u8>() {
+        Ok(value) => Ok((s, value)),
+        Err(_) => Err(nom::Err::Error(nom::error::Error {
+            input: s,
+            code: nom::error::ErrorKind::Digit,
+        })),
+    }
+}
+
+fn horodate(s: &str) -> IResult<&str, TimeStampData> {
+    let (s, marker) = anychar(s)?;
+    let (s, year) = two_digits(s)?;
+    let (s, month) = two_digits(s)?;
+    let (s, day) = two_digits(s)?;
+    let (s, hour) = two_digits(s)?;
+    let (s, minute) = two_digits(s)?;
+    let (s, second) = two_digits(s)?;
+
+    let season = match marker {
+        'E' | 'e' => TicSeason::SUMMER,
+        'H' | 'h' => TicSeason::WINTER,
+        _ => TicSeason::NONE,
+    };
+
+    let stamp = TimeStampData {
+        season,
+        degraded: marker.is_ascii_lowercase(),
+        year: 2000 + year as u16,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    };
+    Ok((s, stamp))
+}
+
+fn label_to_stamp<'a>(s: &'a str, label: &str) -> IResult<&'a str, TimeStampData> {
+    let (s, _) = tag(label)(s)?;
+    let (s, _) = separator(s)?;
+    let (s, stamp) = horodate(s)?;
+    let (s, _) = take_while(|chr| chr == 0x09 as char)(s)?;
+    let (s, _) = anychar(s)?;
+    let (s, _) = line_ending(s)?;
+    Ok((s, stamp))
+}
+
+// horodated value as 'SMAXSN\tH231110083212\t07250\tZ'
+fn label_to_power<'a>(s: &'a str, label: &str) -> IResult<&'a str, PowerMaxData> {
+    let (s, _) = tag(label)(s)?;
+    let (s, _) = separator(s)?;
+    let (s, stamp) = horodate(s)?;
+    let (s, _) = separator(s)?;
+    let (s, value) = i32(s)?;
+    let (s, _) = checksum(s)?;
+    Ok((s, PowerMaxData { stamp, value }))
+}
+
+fn smaxsn(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_power(s, "SMAXSN")?;
+    Ok((s, TicValue::SMAXSN(value)))
+}
+
+fn smaxin(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_power(s, "SMAXIN")?;
+    Ok((s, TicValue::SMAXIN(value)))
+}
+
+fn smaxin1(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_power(s, "SMAXIN-1")?;
+    Ok((s, TicValue::SMAXIN1(value)))
+}
+
+// 'CCASN-1' previous point is ignored
+fn ccasn(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_power(s, "CCASN")?;
+    Ok((s, TicValue::CCASN(value)))
+}
+
+fn date(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_stamp(s, "DATE")?;
+    Ok((s, TicValue::DATE(value)))
+}
+
+fn provider_info(s: &str) -> IResult<&str, ProviderInfo> {
+    let (s, slot) = take(8usize)(s)?;
+    if slot == "NONUTILE" {
+        let info = ProviderInfo {
+            used: false,
+            hour: 0,
+            minute: 0,
+            selector: 0,
+        };
+        return Ok((s, info));
+    }
+
+    let (selector, hour) = two_digits(slot)?;
+    let (selector, minute) = two_digits(selector)?;
+    let selector = match u16::from_str_radix(selector, 16) {
+        Ok(value) => value,
+        Err(_) => {
+            return Err(nom::Err::Error(nom::error::Error {
+                input: s,
+                code: nom::error::ErrorKind::HexDigit,
+            }))
+        }
+    };
+
+    let info = ProviderInfo {
+        used: true,
+        hour,
+        minute,
+        selector,
+    };
+    Ok((s, info))
+}
+
+fn label_to_profile<'a>(s: &'a str, label: &str) -> IResult<&'a str, ProviderProfile> {
+    let (s, _) = tag(label)(s)?;
+    let (s, _) = separator(s)?;
+    let (mut s, first) = provider_info(s)?;
+    let mut profile = ProviderProfile::default();
+    profile.push(first);
+    // space separated slots, a trailing separator is left to checksum as separated_list1 did
+    while let Ok((next, info)) = preceded(char(' '), provider_info)(s) {
+        if !profile.push(info) {
+            return Err(nom::Err::Error(nom::error::Error {
+                input: s,
+                code: nom::error::ErrorKind::TooLarge,
+            }));
+        }
+        s = next;
+    }
+    let (s, _) = checksum(s)?;
+    Ok((s, profile))
+}
+
+fn pjourf1(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_profile(s, "PJOURF+1")?;
+    Ok((s, TicValue::PJOURF1(value)))
+}
+
+fn ppointe(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_profile(s, "PPOINTE")?;
+    Ok((s, TicValue::PPOINTE(value)))
+}
+
 // register status
 fn adsc(s: &str) -> IResult<&str, TicValue> {
     let (s, value) = label_to_register(s, "ADSC")?;
     Ok((s, TicValue::ADSC(value)))
 }
 
+fn stge(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_register(s, "STGE")?;
+    Ok((s, TicValue::STGE(value)))
+}
+
 // i32 message data
 _numeric_data!(ADPS);
 _numeric_data!(ADIR1);
@@ -385,14 +1431,22 @@ _numeric_data!(IINST);
 _numeric_data!(IINST1);
 _numeric_data!(IINST2);
 _numeric_data!(IINST3);
+_numeric_data!(IMAX);
+_numeric_data!(IMAX1);
+_numeric_data!(IMAX2);
+_numeric_data!(IMAX3);
 _numeric_data!(NTARF);
 _numeric_data!(PREF);
+_numeric_data!(ISOUSC);
 _numeric_data!(PCOUP);
+_numeric_data!(PEJP);
+_numeric_data!(PMAX);
 _numeric_data!(RELAIS);
 _numeric_data!(SINSTS);
 _numeric_data!(SINSTS1);
 _numeric_data!(SINSTS2);
 _numeric_data!(SINSTS3);
+_numeric_data!(SINSTI);
 _numeric_data!(URMS1);
 _numeric_data!(URMS2);
 _numeric_data!(URMS3);
@@ -400,131 +1454,203 @@ _numeric_data!(IRMS1);
 _numeric_data!(IRMS2);
 _numeric_data!(IRMS3);
 
-fn numeric_data_a(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = char('A')(s)?;
-    let (s, value) = alt((adsc, ADPS, ADIR1, ADIR2, ADIR3))(s)?;
-    Ok((s, value))
-}
-
-fn numeric_data_i(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = char('I')(s)?;
-    let (s, value) = alt((
-       IINST, IINST, IINST1, IINST2, IINST3, IRMS1, IRMS2, IRMS3,
-    ))(s)?;
-    Ok((s, value))
-}
-
-fn numeric_data_p(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = char('P')(s)?;
-    let (s, value) = alt((PCOUP, PREF))(s)?;
-    Ok((s, value))
+// u64 energy counters
+_energy_data!(EAST);
+_energy_data!(EASF01);
+_energy_data!(EASF02);
+_energy_data!(EASF03);
+_energy_data!(EASF04);
+_energy_data!(EASF05);
+_energy_data!(EASF06);
+_energy_data!(EASF07);
+_energy_data!(EASF08);
+_energy_data!(EASF09);
+_energy_data!(EASF10);
+_energy_data!(EAIT);
+_energy_data!(ERQ1);
+_energy_data!(ERQ2);
+_energy_data!(ERQ3);
+_energy_data!(ERQ4);
+
+// 2 hexa digits
+#[allow(non_snake_case)]
+fn PPOT(s: &str) -> IResult<&str, TicValue> {
+    let (s, _) = tag("PPOT")(s)?;
+    let (s, _) = separator(s)?;
+    let (s, value) = hexa_to_value(s)?;
+    // historic mode separator is a space, not_separator would also swallow checksum
+    let (s, _) = take_while(|chr: char| chr.is_ascii_hexdigit())(s)?;
+    let (s, _) = checksum(s)?;
+    Ok((s, TicValue::PPOT(PhasePresence::from_bits(value))))
 }
 
-fn numeric_data_s(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = char('S')(s)?;
-    let (s, value) = alt((SINSTS, SINSTS1, SINSTS2, SINSTS3))(s)?;
-    Ok((s, value))
-}
+// text labels
+_text_data!(LTARF);
+_text_data!(NGTF);
 
-fn numeric_data_x(s: &str) -> IResult<&str, TicValue> {
-    let (s, value) = alt((RELAIS, NTARF, URMS1, URMS2, URMS3))(s)?;
-    Ok((s, value))
-}
+// historic Tempo labels
+_text_data!(PTEC);
 
-// --- ignored messages ---
-_ignore_data!(BASE);
-_ignore_data!(BBRH);
-_ignore_data!(CCAIN);
-_ignore_data!(DATE);
-_ignore_data!(DEMAIN);
-_ignore_data!(DPM);
-_ignore_data!(EAS);
-_ignore_data!(EAIT);
-_ignore_data!(EJPH);
-_ignore_data!(FPM);
-_ignore_data!(HC);
-_ignore_data!(HHPHC);
-_ignore_data!(IRMS);
-_ignore_data!(IMAX);
-_ignore_data!(ISOUSC);
-_ignore_data!(LTARF);
-_ignore_data!(MOTDETAT);
-_ignore_data!(MSG);
-_ignore_data!(NGTF);
-_ignore_data!(NJOURF);
-_ignore_data!(OPTARIF);
-_ignore_data!(PAPP);
-_ignore_data!(PEJP);
-_ignore_data!(PMAX);
-_ignore_data!(PJOURF);
-_ignore_data!(PPOINTE);
-_ignore_data!(PPOT);
-_ignore_data!(PRM);
-_ignore_data!(PTEC);
-_ignore_data!(STGE);
-_ignore_data!(SMAX);
-_ignore_data!(UMOY);
-_ignore_data!(VTIC);
-
-fn ignore_data_b_c_d(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = alt((char('B'), char('C'), char('D')))(s)?;
-    let (s, _) = alt((BASE, BBRH, CCAIN, DATE, DEMAIN, DPM))(s)?;
-    Ok((s, TicValue::UNSET))
+#[allow(non_snake_case)]
+fn DEMAIN(s: &str) -> IResult<&str, TicValue> {
+    let (s, value) = label_to_str(s, "DEMAIN")?;
+    Ok((s, TicValue::DEMAIN(TempoColour::from_label(value.trim()))))
 }
 
-fn ignore_data_e_f_h_i(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = alt((char('E'), char('H'), char('I'), char('F')))(s)?;
-    let (s, _) = alt((EAS, EAIT, FPM, EJPH, HC, HHPHC, IRMS, IMAX, ISOUSC))(s)?;
-    Ok((s, TicValue::UNSET))
+// decoded labels, nom only parses the value once the label is known
+type TicParser = fn(&str) -> IResult<&str, TicValue>;
+
+fn label_parser(label: &str) -> Option<TicParser> {
+    let parser: TicParser = match label {
+        "ADSC" => adsc,
+        "ADPS" => ADPS,
+        "ADIR1" => ADIR1,
+        "ADIR2" => ADIR2,
+        "ADIR3" => ADIR3,
+        "CCASN" => ccasn,
+        "DATE" => date,
+        "DEMAIN" => DEMAIN,
+        "EAST" => EAST,
+        "EASF01" => EASF01,
+        "EASF02" => EASF02,
+        "EASF03" => EASF03,
+        "EASF04" => EASF04,
+        "EASF05" => EASF05,
+        "EASF06" => EASF06,
+        "EASF07" => EASF07,
+        "EASF08" => EASF08,
+        "EASF09" => EASF09,
+        "EASF10" => EASF10,
+        "EAIT" => EAIT,
+        "ERQ1" => ERQ1,
+        "ERQ2" => ERQ2,
+        "ERQ3" => ERQ3,
+        "ERQ4" => ERQ4,
+        "IINST" => IINST,
+        "IINST1" => IINST1,
+        "IINST2" => IINST2,
+        "IINST3" => IINST3,
+        "IMAX" => IMAX,
+        "IMAX1" => IMAX1,
+        "IMAX2" => IMAX2,
+        "IMAX3" => IMAX3,
+        "IRMS1" => IRMS1,
+        "IRMS2" => IRMS2,
+        "IRMS3" => IRMS3,
+        "ISOUSC" => ISOUSC,
+        "LTARF" => LTARF,
+        "NGTF" => NGTF,
+        "NTARF" => NTARF,
+        "PCOUP" => PCOUP,
+        "PEJP" => PEJP,
+        "PJOURF+1" => pjourf1,
+        "PMAX" => PMAX,
+        "PPOINTE" => ppointe,
+        "PPOT" => PPOT,
+        "PREF" => PREF,
+        "PTEC" => PTEC,
+        "RELAIS" => RELAIS,
+        "SINSTI" => SINSTI,
+        "SINSTS" => SINSTS,
+        "SINSTS1" => SINSTS1,
+        "SINSTS2" => SINSTS2,
+        "SINSTS3" => SINSTS3,
+        "SMAXIN" => smaxin,
+        "SMAXIN-1" => smaxin1,
+        "SMAXSN" => smaxsn,
+        "STGE" => stge,
+        "URMS1" => URMS1,
+        "URMS2" => URMS2,
+        "URMS3" => URMS3,
+        _ => return None,
+    };
+    Some(parser)
 }
 
-fn ignore_data_l_m_n(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = alt((char('L'), char('M'), char('N')))(s)?;
-    let (s, _) = alt((LTARF, MOTDETAT, MSG, NGTF, NJOURF))(s)?;
-    Ok((s, TicValue::UNSET))
+// known but ignored labels, matched on the exact label. A decoded label listed here with an
+// unexpected value also falls back to ignored.
+const IGNORED_LABELS: &[&str] = &[
+    "ADCO", "BASE", "BBRHCJB", "BBRHCJR", "BBRHCJW", "BBRHPJB", "BBRHPJR", "BBRHPJW", "CCAIN",
+    "CCAIN-1", "CCASN", "CCASN-1", "DPM1", "DPM2", "DPM3", "EAIT", "EAIT1", "EAIT2", "EAIT3",
+    "EASD01", "EASD02", "EASD03", "EASD04", "EASF01", "EASF02", "EASF03", "EASF04", "EASF05",
+    "EASF06", "EASF07", "EASF08", "EASF09", "EASF10", "EAST", "EJPHN", "EJPHPM", "ERQ1", "ERQ2",
+    "ERQ3", "ERQ4", "FPM1", "FPM2", "FPM3", "HCHC", "HCHP", "HHPHC", "IRMS1", "IRMS2", "IRMS3",
+    "MOTDETAT", "MSG1", "MSG2", "NJOURF", "NJOURF+1", "OPTARIF", "PAPP", "PRM", "SMAXIN", "SMAXIN-1", "SMAXSN", "SMAXSN-1",
+    "SMAXSN1", "SMAXSN1-1", "SMAXSN2", "SMAXSN2-1", "SMAXSN3", "SMAXSN3-1", "UMOY1", "UMOY2",
+    "UMOY3", "VTIC",
+];
+
+// label is the text up to first separator
+fn tic_str_label(tic_str: &str) -> &str {
+    match tic_str.find(['\t', ' ']) {
+        Some(end) => &tic_str[0..end],
+        None => tic_str,
+    }
 }
 
-fn ignore_data_o_p_s(s: &str) -> IResult<&str, TicValue> {
-    let (_, _) = alt((char('O'), char('P'), char('S')))(s)?;
-    let (s, _) = alt((OPTARIF, PAPP, PEJP, PMAX, PPOINTE, PJOURF, PPOT, PRM, PTEC, STGE, SMAX))(s)?;
-    Ok((s, TicValue::UNSET))
-}
+// lookup replaces trying every label grammar
+fn tic_data(s: &str) -> IResult<&str, TicValue> {
+    let label = tic_str_label(s);
+
+    let decoded = match label_parser(label) {
+        Some(parser) => parser(s),
+        None => Err(nom::Err::Error(nom::error::Error {
+            input: s,
+            code: nom::error::ErrorKind::Tag,
+        })),
+    };
+    if decoded.is_ok() {
+        return decoded;
+    }
 
-fn ignore_data_x(s: &str) -> IResult<&str, TicValue> {
-    let (s, _) = alt((UMOY, VTIC))(s)?;
+    if !IGNORED_LABELS.contains(&label) {
+        return decoded;
+    }
+    let (s, _) = label_to_ignore(s, label)?;
     Ok((s, TicValue::UNSET))
 }
 
-
-// Fulup note: size of nom 'alt' is limited which impose to split labels grammar
-fn tic_data(s: &str) -> IResult<&str, TicValue> {
-    let (s, data) = alt((
-        numeric_data_a,
-        numeric_data_i,
-        numeric_data_p,
-        numeric_data_s,
-        numeric_data_x,
-        ignore_data_b_c_d,
-        ignore_data_e_f_h_i,
-        ignore_data_l_m_n,
-        ignore_data_o_p_s,
-        ignore_data_x,
-    ))(s)?;
-    Ok((s, data))
-}
-
+// label outside grammar is UnknownLabel(line), known label with a bad value ParsingError
 pub fn tic_from_str(tic_str: &str) -> Result<TicValue, LinkyError> {
     match tic_data(tic_str) {
         Ok((remaining, data)) => {
             if remaining.len() > 3 {
-                return Err(LinkyError::ParsingError(remaining.to_string()));
+                return Err(LinkyError::ParsingError(remaining.to_string().into()));
             }
             Ok(data)
         }
-        Err(error) => Err(LinkyError::ParsingError(error.to_string())),
+        Err(error) => {
+            if !tic_has_builtin_label(tic_str) {
+                return Err(LinkyError::UnknownLabel(tic_str.to_string().into()));
+            }
+            Err(LinkyError::ParsingError(error.to_string().into()))
+        }
+    }
+}
+
+// false when tic_from_str would return UnknownLabel, permissive callers skip such lines
+// without building the error
+pub fn tic_has_builtin_label(tic_str: &str) -> bool {
+    tic_is_builtin(tic_str_label(tic_str))
+}
+
+// label decoded or ignored by builtin grammar, custom labels may not reuse it
+pub fn tic_is_builtin(label: &str) -> bool {
+    label_parser(label).is_some() || IGNORED_LABELS.contains(&label)
+}
+
+// custom labels are tried first, config rejects those colliding with builtin grammar
+pub fn tic_from_custom(tic_str: &str, customs: &[&'static TicObject]) -> Option<TicValue> {
+    for (idx, custom) in customs.iter().enumerate() {
+        // label, separator, value and checksum group must cover the whole line
+        if let Ok(("", value)) = label_to_int(tic_str, custom.get_uid()) {
+            return Some(TicValue::CUSTOM(idx, value));
+        }
     }
+    None
 }
 
+#[cfg(feature = "afb")]
 pub fn tic_register_type() -> Result<(), AfbError> {
     tic_value::register()?;
     Ok(())
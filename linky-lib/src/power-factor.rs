@@ -0,0 +1,65 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// CCASN is the mean active power (W) of last load curve period, apparent power (SINSTS)
+// is averaged over the same period to estimate power factor P/S
+pub struct PowerFactor {
+    sum: i64,
+    count: u32,
+    value: Option<f64>,
+}
+
+impl PowerFactor {
+    pub fn new() -> PowerFactor {
+        PowerFactor {
+            sum: 0,
+            count: 0,
+            value: None,
+        }
+    }
+
+    pub fn push_apparent(&mut self, va: i32) {
+        self.sum += va as i64;
+        self.count += 1;
+    }
+
+    // close period with its active power, none when no apparent power was received.
+    // Factor is capped to 1.0 as both values are not sampled at the same time
+    pub fn set_active(&mut self, watt: i32) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        let apparent = self.sum as f64 / self.count as f64;
+        self.sum = 0;
+        self.count = 0;
+        if apparent <= 0.0 {
+            return None;
+        }
+        let factor = (watt as f64 / apparent).clamp(0.0, 1.0);
+        self.value = Some(factor);
+        self.value
+    }
+
+    pub fn get_value(&self) -> Option<f64> {
+        self.value
+    }
+
+    pub fn reset(&mut self) {
+        *self = PowerFactor::new();
+    }
+}
+
+impl Default for PowerFactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
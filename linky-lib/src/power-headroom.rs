@@ -0,0 +1,75 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// power still available before reaching subscription (VA), index 0 total, 1-3 per phase.
+// PCOUP (cutting power) is used when present, PREF (subscribed power) otherwise, both in kVA.
+pub struct PowerHeadroom {
+    margin: i32,
+    reference: Option<i32>,
+    cutting: Option<i32>,
+    power: [Option<i32>; 4],
+}
+
+impl PowerHeadroom {
+    // margin in VA is kept as safety on total, split evenly on phases
+    pub fn new(margin: u32) -> PowerHeadroom {
+        PowerHeadroom {
+            margin: margin as i32,
+            reference: None,
+            cutting: None,
+            power: [None; 4],
+        }
+    }
+
+    pub fn get_margin(&self) -> i32 {
+        self.margin
+    }
+
+    pub fn set_reference(&mut self, kva: i32) {
+        self.reference = Some(kva * 1000);
+    }
+
+    pub fn set_cutting(&mut self, kva: i32) {
+        self.cutting = Some(kva * 1000);
+    }
+
+    // SINSTS (idx 0) or SINSTS1-3 (idx 1-3) in VA
+    pub fn set_power(&mut self, idx: usize, value: i32) {
+        if let Some(power) = self.power.get_mut(idx) {
+            *power = Some(value);
+        }
+    }
+
+    pub fn get_limit(&self) -> Option<i32> {
+        self.cutting.or(self.reference)
+    }
+
+    // three phase meters send SINSTS1-3, single phase only SINSTS
+    pub fn get_phases(&self) -> usize {
+        self.power[1..].iter().filter(|value| value.is_some()).count()
+    }
+
+    // none until both limit and instant power are known, negative when over limit
+    pub fn get_available(&self, idx: usize) -> Option<i32> {
+        let limit = self.get_limit()?;
+        let power = (*self.power.get(idx)?)?;
+        if idx == 0 {
+            return Some(limit - self.margin - power);
+        }
+        let phases = self.get_phases() as i32;
+        Some((limit - self.margin) / phases - power)
+    }
+
+    pub fn reset(&mut self) {
+        *self = PowerHeadroom::new(self.margin as u32);
+    }
+}
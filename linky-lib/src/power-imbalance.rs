@@ -0,0 +1,78 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// three phase load imbalance as max deviation from mean in % of mean (NEMA definition),
+// fed with IINST1-3 (historic) or SINSTS1-3 (standard)
+pub struct PhaseImbalance {
+    threshold: u32,
+    values: [Option<i32>; 3],
+    alarm: bool,
+}
+
+impl PhaseImbalance {
+    // threshold in %, alarm is raised above it
+    pub fn new(threshold: u32) -> PhaseImbalance {
+        PhaseImbalance {
+            threshold,
+            values: [None; 3],
+            alarm: false,
+        }
+    }
+
+    // phase 1-3
+    pub fn set_phase(&mut self, phase: usize, value: i32) {
+        if (1..=3).contains(&phase) {
+            self.values[phase - 1] = Some(value);
+        }
+    }
+
+    pub fn get_values(&self) -> [Option<i32>; 3] {
+        self.values
+    }
+
+    // none until the three phases are known, 0 without load
+    pub fn get_imbalance(&self) -> Option<u32> {
+        let mut values = [0i64; 3];
+        for (idx, value) in self.values.iter().enumerate() {
+            values[idx] = (*value)? as i64;
+        }
+        let sum: i64 = values.iter().sum();
+        if sum <= 0 {
+            return Some(0);
+        }
+        let deviation = values.iter().map(|value| (value * 3 - sum).abs()).max()?;
+        Some((deviation * 100 / sum) as u32)
+    }
+
+    pub fn is_alarm(&self) -> bool {
+        self.alarm
+    }
+
+    pub fn get_threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    // return new alarm state when threshold is crossed
+    pub fn update(&mut self) -> Option<bool> {
+        let alarm = self.get_imbalance()? > self.threshold;
+        if alarm == self.alarm {
+            return None;
+        }
+        self.alarm = alarm;
+        Some(alarm)
+    }
+
+    pub fn reset(&mut self) {
+        self.values = [None; 3];
+        self.alarm = false;
+    }
+}
@@ -0,0 +1,110 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::VecDeque;
+
+// outage boundaries as epoch ms, end is none while meter is still down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutageRecord {
+    pub start: u64,
+    pub end: Option<u64>,
+}
+
+impl OutageRecord {
+    // elapsed ms, ongoing outage is measured up to now
+    pub fn get_duration(&self, now: u64) -> u64 {
+        self.end.unwrap_or(now).saturating_sub(self.start)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutageEvent {
+    START(OutageRecord),
+    END(OutageRecord),
+}
+
+// a TIC adapter powered by the meter bus vanishes (ENXIO/EOF) when the meter loses power.
+// Silence alone may be a loose cable, link failure alone a flaky dongle, both make a
+// probable outage. It starts with last valid data and ends with the first decoded line
+pub struct OutageTracker {
+    size: usize,
+    silent: Option<u64>, // last valid data when watchdog reported silence
+    link_lost: bool,
+    active: Option<OutageRecord>,
+    records: VecDeque<OutageRecord>,
+}
+
+impl OutageTracker {
+    pub fn new(size: usize) -> OutageTracker {
+        OutageTracker {
+            size,
+            silent: None,
+            link_lost: false,
+            active: None,
+            records: VecDeque::with_capacity(size),
+        }
+    }
+
+    // watchdog silence, 'since' is last valid data
+    pub fn set_silent(&mut self, since: u64) -> Option<OutageEvent> {
+        self.silent = Some(since);
+        self.check()
+    }
+
+    // serial source failed (ENXIO, EOF, hangup)
+    pub fn set_link_lost(&mut self) -> Option<OutageEvent> {
+        self.link_lost = true;
+        self.check()
+    }
+
+    fn check(&mut self) -> Option<OutageEvent> {
+        match (self.silent, self.link_lost, self.active) {
+            (Some(start), true, None) => {
+                let record = OutageRecord { start, end: None };
+                self.active = Some(record);
+                Some(OutageEvent::START(record))
+            }
+            _ => None,
+        }
+    }
+
+    // valid data decoded, closes ongoing outage
+    pub fn set_data(&mut self, now: u64) -> Option<OutageEvent> {
+        self.silent = None;
+        self.link_lost = false;
+        let mut record = self.active.take()?;
+        record.end = Some(now);
+        if self.size > 0 {
+            if self.records.len() >= self.size {
+                self.records.pop_front();
+            }
+            self.records.push_back(record);
+        }
+        Some(OutageEvent::END(record))
+    }
+
+    pub fn get_active(&self) -> Option<OutageRecord> {
+        self.active
+    }
+
+    // closed outages, oldest first
+    pub fn get_records(&self) -> &VecDeque<OutageRecord> {
+        &self.records
+    }
+
+    pub fn reset(&mut self) {
+        self.silent = None;
+        self.link_lost = false;
+        self.active = None;
+        self.records.clear();
+    }
+}
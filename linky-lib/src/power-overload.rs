@@ -0,0 +1,105 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::VecDeque;
+
+const OVERLOAD_SAMPLES_MIN: usize = 3; // below this a trend is only noise
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OverloadEvent {
+    WARNING(f64), // estimated seconds before reaching limit
+    CLEARED,
+}
+
+// extrapolate instant power trend (least squares over last samples) against cutting
+// power and raise a warning when limit should be reached within horizon seconds
+pub struct OverloadPredictor {
+    horizon: f64,
+    size: usize,
+    samples: VecDeque<(f64, i32)>,
+    warning: Option<f64>,
+}
+
+impl OverloadPredictor {
+    pub fn new(horizon: u32, size: usize) -> OverloadPredictor {
+        let size = usize::max(size, OVERLOAD_SAMPLES_MIN);
+        OverloadPredictor {
+            horizon: horizon as f64,
+            size,
+            samples: VecDeque::with_capacity(size),
+            warning: None,
+        }
+    }
+
+    // power increase in VA per second, none without enough samples
+    pub fn get_slope(&self) -> Option<f64> {
+        if self.samples.len() < OVERLOAD_SAMPLES_MIN {
+            return None;
+        }
+        let count = self.samples.len() as f64;
+        let mean_t = self.samples.iter().map(|(at, _)| at).sum::<f64>() / count;
+        let mean_p = self.samples.iter().map(|(_, power)| *power as f64).sum::<f64>() / count;
+        let mut covar = 0.0;
+        let mut var = 0.0;
+        for (at, power) in &self.samples {
+            covar += (at - mean_t) * (*power as f64 - mean_p);
+            var += (at - mean_t) * (at - mean_t);
+        }
+        if var == 0.0 {
+            return None;
+        }
+        Some(covar / var)
+    }
+
+    // at is sample time in seconds (any origin), power and limit in VA
+    pub fn push(&mut self, at: f64, power: i32, limit: i32) -> Option<OverloadEvent> {
+        if self.samples.len() >= self.size {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((at, power));
+
+        let seconds = if power >= limit {
+            Some(0.0)
+        } else {
+            match self.get_slope() {
+                Some(slope) if slope > 0.0 => Some((limit - power) as f64 / slope),
+                _ => None,
+            }
+        };
+
+        match (seconds, self.warning) {
+            (Some(value), None) if value <= self.horizon => {
+                self.warning = Some(value);
+                Some(OverloadEvent::WARNING(value))
+            }
+            (Some(value), Some(_)) if value <= self.horizon => {
+                self.warning = Some(value);
+                None
+            }
+            (_, Some(_)) => {
+                self.warning = None;
+                Some(OverloadEvent::CLEARED)
+            }
+            _ => None,
+        }
+    }
+
+    // seconds to trip while warning is active
+    pub fn get_warning(&self) -> Option<f64> {
+        self.warning
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.warning = None;
+    }
+}
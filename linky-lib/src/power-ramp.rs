@@ -0,0 +1,76 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::VecDeque;
+
+// SINSTS change rate (VA/s) between oldest and newest sample of a sliding time window,
+// a ramp above threshold (either direction) is reported once until it falls back
+pub struct RampRate {
+    window: f64,
+    threshold: f64,
+    samples: VecDeque<(f64, i32)>,
+    alarm: bool,
+}
+
+impl RampRate {
+    // window in seconds, threshold in VA/s
+    pub fn new(window: f64, threshold: u32) -> RampRate {
+        RampRate {
+            window,
+            threshold: threshold as f64,
+            samples: VecDeque::new(),
+            alarm: false,
+        }
+    }
+
+    pub fn get_threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    // none until window holds two samples with distinct stamps
+    pub fn get_ramp(&self) -> Option<f64> {
+        let (first_at, first) = self.samples.front()?;
+        let (last_at, last) = self.samples.back()?;
+        if last_at <= first_at {
+            return None;
+        }
+        Some((last - first) as f64 / (last_at - first_at))
+    }
+
+    // at is sample time in seconds (any origin), return ramp when threshold is crossed
+    pub fn push(&mut self, at: f64, power: i32) -> Option<f64> {
+        self.samples.push_back((at, power));
+        while let Some((first_at, _)) = self.samples.front() {
+            if at - first_at <= self.window {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        let ramp = self.get_ramp()?;
+        let alarm = ramp.abs() > self.threshold;
+        if alarm == self.alarm {
+            return None;
+        }
+        self.alarm = alarm;
+        if alarm {
+            Some(ramp)
+        } else {
+            None
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.samples.clear();
+        self.alarm = false;
+    }
+}
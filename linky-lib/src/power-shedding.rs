@@ -0,0 +1,66 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// controllable load, lowest priority is shed first
+#[derive(Debug, Clone)]
+pub struct SheddingLoad {
+    pub uid: String,
+    pub priority: u32,
+    pub power: u32, // nominal VA
+}
+
+// ordered list of loads to shed to recover deficit (VA)
+#[derive(Debug, Clone, PartialEq)]
+pub struct SheddingPlan {
+    pub deficit: u32,
+    pub shed: u32,
+    pub loads: Vec<String>,
+}
+
+impl SheddingPlan {
+    // false when every load is shed and deficit is still not recovered
+    pub fn is_covered(&self) -> bool {
+        self.shed >= self.deficit
+    }
+}
+
+pub struct SheddingAdvisor {
+    loads: Vec<SheddingLoad>,
+}
+
+impl SheddingAdvisor {
+    pub fn new(mut loads: Vec<SheddingLoad>) -> SheddingAdvisor {
+        loads.sort_by_key(|load| load.priority);
+        SheddingAdvisor { loads }
+    }
+
+    pub fn get_loads(&self) -> &[SheddingLoad] {
+        &self.loads
+    }
+
+    // shed by ascending priority until deficit is recovered, empty plan without deficit
+    pub fn get_plan(&self, deficit: u32) -> SheddingPlan {
+        let mut plan = SheddingPlan {
+            deficit,
+            shed: 0,
+            loads: Vec::new(),
+        };
+        for load in &self.loads {
+            if plan.is_covered() {
+                break;
+            }
+            plan.shed += load.power;
+            plan.loads.push(load.uid.clone());
+        }
+        plan
+    }
+}
@@ -11,70 +11,824 @@
  */
 
 use crate::prelude::*;
+#[cfg(feature = "afb")]
 use afbv4::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 use std::str;
+use std::time::{Duration, Instant};
 
-#[derive(Debug)]
-pub enum LinkyError {
-    RetryLater,
-    ReopenDev,
-    FatalError,
-    TooLong(String),
-    ParsingError(String),
-    InvalidEncoding,
-    SerialError(String),
-    ChecksumError(String),
+// data source selected from binding config
+pub enum LinkyConfig {
+    Serial {
+        device: &'static str,
+        speed: u32,
+        parity: &'static str,
+        reconnect: u32,
+        canonical: bool, // false: raw bytes split into lines by BufferRing
+        buffered: bool,  // raw bulk reads of SERIAL_BULK_SZ, canonical is ignored
+        flags: SerialFlags,
+    },
+    Tcp {
+        host: &'static str,
+        port: u16,
+        reconnect: u32, // ms, 0=disable
+    },
+    TcpListen {
+        addr: &'static str,
+        port: u16,
+    },
+    Tls {
+        host: &'static str,
+        port: u16,
+        reconnect: u32,
+        ca: &'static str,
+        cert: Option<&'static str>,
+        key: Option<&'static str>,
+    },
+    WebSocket {
+        url: &'static str,
+        reconnect: u32,
+    },
+    Http {
+        url: &'static str,
+        interval: u32, // ms between two fetches
+    },
+    Udp {
+        addr: &'static str,
+        port: u16,
+        group: Option<&'static str>, // multicast group to join
+        iface: Option<&'static str>,
+        psk: Option<(&'static str, &'static str)>, // pre-shared key identity, hex key
+        reconnect: u32,
+    },
+    Replay {
+        path: &'static str, // plain, gzip or zstd capture
+        speed: u32,         // replay time multiplier, 0=no pacing
+        interval: u32,      // ms between two replay ticks
+        repeat: bool,       // restart from beginning at end of capture
+    },
+}
+
+// tty flags overriding open_serial defaults, None keeps the default set. Parity flag
+// from config is always added to cflags, lflags replaces the 'canonical' setting.
+#[derive(Default)]
+pub struct SerialFlags {
+    pub pflags: Option<Vec<PortFlag>>,
+    pub iflags: Option<Vec<SerialIflag>>,
+    pub cflags: Option<Vec<SerialCflag>>,
+    pub lflags: Option<Vec<SerialLflag>>,
+}
+
+impl SerialFlags {
+    // flag symbolic name follows enum variant, e.g. 'iflags' 'IGNPAR' or 'cflags' 'CRTSCTS'
+    pub fn push(&mut self, kind: &str, label: &str) -> Result<&mut Self, LinkyFail> {
+        match kind {
+            "pflags" => {
+                let flag = match label {
+                    "NOCTTY" => PortFlag::NOCTTY,
+                    "NDELAY" => PortFlag::NDELAY,
+                    "RDWRITE" => PortFlag::RDWRITE,
+                    "RDONLY" => PortFlag::RDONLY,
+                    "OSYNC" => PortFlag::OSYNC,
+                    _ => return linky_fail!("tty-flag-invalid", "unknown {} flag:{}", kind, label),
+                };
+                self.pflags.get_or_insert_with(Vec::new).push(flag);
+            }
+            "iflags" => {
+                let flag = match label {
+                    "IGNBRK" => SerialIflag::IGNBRK,
+                    "IGNPAR" => SerialIflag::IGNPAR,
+                    "INLCR" => SerialIflag::INLCR,
+                    "IGNCR" => SerialIflag::IGNCR,
+                    "IUCLC" => SerialIflag::IUCLC,
+                    "IUTF8" => SerialIflag::IUTF8,
+                    "ICRNL" => SerialIflag::ICRNL,
+                    _ => return linky_fail!("tty-flag-invalid", "unknown {} flag:{}", kind, label),
+                };
+                self.iflags.get_or_insert_with(Vec::new).push(flag);
+            }
+            "cflags" => {
+                let flag = match label {
+                    "CS7" => SerialCflag::CS7,
+                    "CS8" => SerialCflag::CS8,
+                    "PARENB" => SerialCflag::PARENB,
+                    "PARODD" => SerialCflag::PARODD,
+                    "CSTOPB" => SerialCflag::CSTOPB,
+                    "CRTSCTS" => SerialCflag::CRTSCTS,
+                    "CLOCAL" => SerialCflag::CLOCAL,
+                    _ => return linky_fail!("tty-flag-invalid", "unknown {} flag:{}", kind, label),
+                };
+                self.cflags.get_or_insert_with(Vec::new).push(flag);
+            }
+            "lflags" => {
+                let flag = match label {
+                    "ICANON" => SerialLflag::ICANON,
+                    "XCASE" => SerialLflag::XCASE,
+                    "ISIG" => SerialLflag::ISIG,
+                    _ => return linky_fail!("tty-flag-invalid", "unknown {} flag:{}", kind, label),
+                };
+                self.lflags.get_or_insert_with(Vec::new).push(flag);
+            }
+            _ => {
+                return linky_fail!(
+                    "tty-flag-invalid",
+                    "unknown flag set:{} (pflags|iflags|cflags|lflags)",
+                    kind
+                )
+            }
+        }
+        Ok(self)
+    }
+
+    // '{"iflags":["IGNBRK"],"cflags":["CS7","CLOCAL","PARENB"]}', default sets are omitted
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        let sets = [
+            ("pflags", self.pflags.as_ref().map(|flags| flag_labels(flags))),
+            ("iflags", self.iflags.as_ref().map(|flags| flag_labels(flags))),
+            ("cflags", self.cflags.as_ref().map(|flags| flag_labels(flags))),
+            ("lflags", self.lflags.as_ref().map(|flags| flag_labels(flags))),
+        ];
+        for (kind, labels) in sets {
+            if let Some(labels) = labels {
+                let jflags = JsoncObj::array();
+                for (idx, label) in labels.iter().enumerate() {
+                    jflags.insert(idx, label.as_str())?;
+                }
+                jsonc.add(kind, jflags)?;
+            }
+        }
+        Ok(jsonc)
+    }
+}
+
+#[cfg(feature = "afb")]
+fn flag_labels<T: std::fmt::Debug>(flags: &[T]) -> Vec<String> {
+    flags.iter().map(|flag| format!("{:?}", flag)).collect()
+}
+
+pub fn tic_checksum(buffer: &[u8], count: usize) -> Result<&str, LinkyError> {
+    // short read or count beyond buffer, checksum byte is not where expected
+    let data = match buffer.get(0..count) {
+        Some(value) if count > 3 => value,
+        _ => {
+            let data = &buffer[0..usize::min(count, buffer.len())];
+            let lossy = String::from_utf8_lossy(data).into_owned();
+            return Err(LinkyError::ParsingError(lossy.into()));
+        }
+    };
+
+    // verify checksum take all data from 'etiquette" to last 'delimiteur' (standard HT),
+    // historic mode excludes its last SP delimiteur
+    let end = match data[count - 4] {
+        b' ' => count - 4,
+        _ => count - 3,
+    };
+    let mut sum: u64 = 0;
+    for byte in &data[0..end] {
+        sum += *byte as u64;
+    }
+
+    // corrupted bytes are reported apart, lossy text keeps label and value readable
+    let checksum = (sum & 0x3f) as u8 + 0x20;
+    let line = match str::from_utf8(data) {
+        Err(_) => {
+            let lossy = String::from_utf8_lossy(data).into_owned();
+            return Err(LinkyError::EncodingError(lossy.into()));
+        }
+        Ok(data) => data,
+    };
+
+    // finally check
+    if checksum != data[count - 3] {
+        Err(LinkyError::ChecksumError(line.to_string().into()))
+    } else {
+        Ok(line)
+    }
+}
+
+impl LinkyConfig {
+    // initial reconnection delay in ms, 0 when source does not reconnect
+    pub fn get_reconnect(&self) -> u32 {
+        match self {
+            LinkyConfig::Serial { reconnect, .. } => *reconnect,
+            LinkyConfig::Tcp { reconnect, .. } => *reconnect,
+            LinkyConfig::Tls { reconnect, .. } => *reconnect,
+            LinkyConfig::WebSocket { reconnect, .. } => *reconnect,
+            LinkyConfig::Udp { reconnect, .. } => *reconnect,
+            LinkyConfig::TcpListen { .. } | LinkyConfig::Http { .. } | LinkyConfig::Replay { .. } => 0,
+        }
+    }
+
+    pub fn get_protocol(&self) -> &'static str {
+        match self {
+            LinkyConfig::Serial { .. } => "serial",
+            LinkyConfig::Tcp { .. } => "tcp",
+            LinkyConfig::TcpListen { .. } => "tcp-listen",
+            LinkyConfig::Tls { .. } => "tls",
+            LinkyConfig::WebSocket { .. } => "ws",
+            LinkyConfig::Http { .. } => "http",
+            LinkyConfig::Udp { .. } => "udp",
+            LinkyConfig::Replay { .. } => "replay",
+        }
+    }
+
+    // read chunk in bytes, buffered serial reads whatever kernel holds in one syscall
+    pub fn get_buffer(&self, size: usize) -> usize {
+        match self {
+            LinkyConfig::Serial { buffered: true, .. } => usize::max(size, SERIAL_BULK_SZ),
+            _ => size,
+        }
+    }
+
+    // polled sources have no fd and are read on timer ticks, 0 for fd driven sources
+    pub fn get_interval(&self) -> u32 {
+        match self {
+            LinkyConfig::Http { interval, .. } => *interval,
+            LinkyConfig::Replay { interval, .. } => *interval,
+            _ => 0,
+        }
+    }
+
+    // effective source with defaults applied, keys follow binding config syntax
+    // '{"protocol":"serial","device":"/dev/ttyUSB0","speed":"auto","parity":"even",...}'
+    #[cfg(feature = "afb")]
+    pub fn to_jsonc(&self) -> Result<JsoncObj, AfbError> {
+        let jsonc = JsoncObj::new();
+        jsonc.add("protocol", self.get_protocol())?;
+        match self {
+            LinkyConfig::Serial {
+                device,
+                speed,
+                parity,
+                reconnect,
+                canonical,
+                buffered,
+                flags,
+            } => {
+                jsonc.add("device", *device)?;
+                match speed {
+                    0 => jsonc.add("speed", "auto")?,
+                    _ => jsonc.add("speed", *speed)?,
+                };
+                jsonc.add("parity", *parity)?;
+                jsonc.add("reconnect", *reconnect)?;
+                jsonc.add("canonical", *canonical)?;
+                jsonc.add("buffered", *buffered)?;
+                jsonc.add("flags", flags.to_jsonc()?)?;
+            }
+            LinkyConfig::Tcp {
+                host,
+                port,
+                reconnect,
+            } => {
+                jsonc.add("host", *host)?;
+                jsonc.add("port", *port as u32)?;
+                jsonc.add("reconnect", *reconnect)?;
+            }
+            LinkyConfig::TcpListen { addr, port } => {
+                jsonc.add("host", *addr)?;
+                jsonc.add("port", *port as u32)?;
+            }
+            LinkyConfig::Tls {
+                host,
+                port,
+                reconnect,
+                ca,
+                cert,
+                key,
+            } => {
+                jsonc.add("host", *host)?;
+                jsonc.add("port", *port as u32)?;
+                jsonc.add("reconnect", *reconnect)?;
+                jsonc.add("ca", *ca)?;
+                if let Some(value) = cert {
+                    jsonc.add("cert", *value)?;
+                }
+                if let Some(value) = key {
+                    jsonc.add("key", *value)?;
+                }
+            }
+            LinkyConfig::WebSocket { url, reconnect } => {
+                jsonc.add("url", *url)?;
+                jsonc.add("reconnect", *reconnect)?;
+            }
+            LinkyConfig::Http { url, interval } => {
+                jsonc.add("url", *url)?;
+                jsonc.add("interval", *interval)?;
+            }
+            LinkyConfig::Udp {
+                addr,
+                port,
+                group,
+                iface,
+                psk,
+                reconnect,
+            } => {
+                jsonc.add("host", *addr)?;
+                jsonc.add("port", *port as u32)?;
+                if let Some(value) = group {
+                    jsonc.add("multicast", *value)?;
+                }
+                if let Some(value) = iface {
+                    jsonc.add("iface", *value)?;
+                }
+                // key itself is never dumped
+                if let Some((identity, _)) = psk {
+                    jsonc.add("psk_identity", *identity)?;
+                }
+                jsonc.add("reconnect", *reconnect)?;
+            }
+            LinkyConfig::Replay {
+                path,
+                speed,
+                interval,
+                repeat,
+            } => {
+                jsonc.add("path", *path)?;
+                jsonc.add("speed", *speed)?;
+                jsonc.add("interval", *interval)?;
+                jsonc.add("loop", *repeat)?;
+            }
+        }
+        Ok(jsonc)
+    }
+}
+
+const AUTODETECT_LINES: usize = 3; // valid lines required to lock serial speed
+const AUTODETECT_TIMEOUT: i32 = 2500; // ms without any line before trying next speed
+pub const TIC_LINE_MAX: usize = 256; // default line buffer, longest TIC group is far below
+pub const SERIAL_BULK_SZ: usize = 4096; // buffered serial read chunk, ~4s of standard mode
+
+// origin of received lines when a backup source is configured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkySource {
+    Primary,
+    Backup,
+}
+
+impl LinkySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkySource::Primary => "primary",
+            LinkySource::Backup => "backup",
+        }
+    }
+}
+
+// decoded line with its raw bytes for frame inspection
+pub struct TicLine {
+    pub raw: Vec<u8>,
+    pub value: Result<TicValue, LinkyError>,
+    pub frame: Option<TicFrame>, // frame closed by this line
+}
+
+// lines filled by decode_all/decode_budget, derefs to the decoded ones. clear() keeps every
+// slot with its raw buffer, steady state decoding reuses them instead of allocating
+#[derive(Default)]
+pub struct TicLines {
+    slots: Vec<TicLine>,
+    count: usize,
+}
+
+impl TicLines {
+    pub fn new() -> TicLines {
+        TicLines::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    // raw buffers are sized for the longest line once, later lines never grow them
+    fn push(
+        &mut self,
+        raw: &[u8],
+        capacity: usize,
+        value: Result<TicValue, LinkyError>,
+        frame: Option<TicFrame>,
+    ) {
+        if self.count == self.slots.len() {
+            self.slots.push(TicLine {
+                raw: Vec::with_capacity(capacity),
+                value: Err(LinkyError::RetryLater),
+                frame: None,
+            });
+        }
+        let slot = &mut self.slots[self.count];
+        slot.raw.clear();
+        slot.raw.extend_from_slice(raw);
+        slot.value = value;
+        slot.frame = frame;
+        self.count += 1;
+    }
+}
+
+impl std::ops::Deref for TicLines {
+    type Target = [TicLine];
+    fn deref(&self) -> &[TicLine] {
+        &self.slots[0..self.count]
+    }
+}
+
+impl std::ops::DerefMut for TicLines {
+    fn deref_mut(&mut self) -> &mut [TicLine] {
+        &mut self.slots[0..self.count]
+    }
+}
+
+// link quality counters, reset on operator request
+#[derive(Default, Clone, Copy, Debug, Serialize)]
+pub struct LinkyStats {
+    pub bytes: u64,     // raw bytes read from source
+    pub lines: u64,     // lines successfully decoded
+    pub frames: u64,    // ETX terminated frames
+    pub partial: u64,   // frames truncated or missing groups
+    pub checksum: u64,  // lines rejected by checksum
+    pub encoding: u64,  // lines holding non UTF-8 bytes
+    pub parsing: u64,   // lines with valid checksum but not decodable
+    pub reconnect: u64, // source reopen count
 }
 
 pub struct LinkyHandle {
-    pub(crate) portname: &'static str,
-    pub(crate) handle: SerialRaw,
+    pub(crate) portname: SyncCell<&'static str>,
+    pub(crate) mode: SyncLock<String>,
+    pub(crate) handle: SyncLock<Box<dyn SourceHandle>>,
+    pub(crate) ring: SyncLock<BufferRing>,
+    pub(crate) polled: SyncCell<bool>,
+    pub(crate) customs: Vec<&'static TicObject>,
+    pub(crate) strict: bool,
+    pub(crate) backup: Option<Box<LinkyHandle>>,
+    pub(crate) silence: Duration,
+    pub(crate) last_seen: SyncCell<Instant>,
+    pub(crate) gateway: SyncCell<Option<(SocketAddr, Instant)>>, // last network sender
+    pub(crate) active: SyncCell<LinkySource>,
+    pub(crate) stats: SyncLock<LinkyStats>,
+    pub(crate) quality: SyncLock<LinkQuality>,
+    pub(crate) frame: SyncLock<FrameCheck>,
+    pub(crate) scratch: SyncLock<String>, // ETX terminated line rewritten with LF
 }
 
 impl LinkyHandle {
-    pub fn new(
+    pub fn new(config: &LinkyConfig) -> Result<LinkyHandle, LinkyFail> {
+        let (portname, handle): (&'static str, Box<dyn SourceHandle>) = match config {
+            LinkyConfig::Serial {
+                device,
+                speed,
+                parity,
+                canonical,
+                buffered,
+                flags,
+                ..
+            } => {
+                // one line per read() in canonical mode, bulk reads need raw bytes
+                let canonical = *canonical && !*buffered;
+                let (serial, speed) = match speed {
+                    0 => LinkyHandle::detect_serial(device, parity, canonical, flags)?,
+                    _ => {
+                        let serial =
+                            LinkyHandle::open_serial(device, *speed, parity, canonical, flags)?;
+                        (serial, *speed)
+                    }
+                };
+                let mode = match *parity {
+                    "odd" => format!("{}-7O1", speed),
+                    _ => format!("{}-7E1", speed),
+                };
+                return Ok(LinkyHandle::from_source(device, mode, Box::new(serial)));
+            }
+            LinkyConfig::Tcp { host, port, .. } => (host, Box::new(TcpHandle::new(host, *port)?)),
+            LinkyConfig::TcpListen { addr, port } => {
+                (addr, Box::new(TcpListenHandle::new(addr, *port)?))
+            }
+            #[cfg(feature = "tls")]
+            LinkyConfig::Tls {
+                host,
+                port,
+                ca,
+                cert,
+                key,
+                ..
+            } => (host, Box::new(TlsHandle::new(host, *port, ca, *cert, *key)?)),
+            #[cfg(not(feature = "tls"))]
+            LinkyConfig::Tls { host, .. } => {
+                return linky_fail!("tls-not-built", "host:{} requires 'tls' feature", host)
+            }
+            #[cfg(feature = "websocket")]
+            LinkyConfig::WebSocket { url, .. } => (url, Box::new(WsHandle::new(url)?)),
+            #[cfg(not(feature = "websocket"))]
+            LinkyConfig::WebSocket { url, .. } => {
+                return linky_fail!("ws-not-built", "url:{} requires 'websocket' feature", url)
+            }
+            LinkyConfig::Http { url, .. } => (url, Box::new(HttpHandle::new(url)?)),
+            LinkyConfig::Udp {
+                addr,
+                port,
+                group,
+                iface,
+                psk,
+                ..
+            } => {
+                let mut handle = NetworkHandle::new(addr, *port, *group, *iface)?;
+                if let Some((identity, key)) = psk {
+                    handle.set_dtls(identity, key)?;
+                }
+                (addr, Box::new(handle))
+            }
+            LinkyConfig::Replay {
+                path,
+                speed,
+                repeat,
+                ..
+            } => (path, Box::new(ReplayHandle::new(path, *speed, *repeat)?)),
+        };
+
+        Ok(LinkyHandle::from_source(portname, config.get_protocol().to_string(), handle))
+    }
+
+    // any source, MemorySource lets tests drive a handle without device
+    pub fn from_source(
+        portname: &'static str,
+        mode: String,
+        handle: Box<dyn SourceHandle>,
+    ) -> LinkyHandle {
+        LinkyHandle {
+            portname: SyncCell::new(portname),
+            mode: SyncLock::new(mode),
+            handle: SyncLock::new(handle),
+            ring: SyncLock::new(BufferRing::new()),
+            polled: SyncCell::new(false),
+            customs: Vec::new(),
+            strict: false,
+            backup: None,
+            silence: Duration::ZERO,
+            last_seen: SyncCell::new(Instant::now()),
+            gateway: SyncCell::new(None),
+            active: SyncCell::new(LinkySource::Primary),
+            stats: SyncLock::new(LinkyStats::default()),
+            quality: SyncLock::new(LinkQuality::default()),
+            frame: SyncLock::new(FrameCheck::new()),
+            scratch: SyncLock::new(String::new()),
+        }
+    }
+
+    // probe historic (1200) then standard (9600) speed until enough lines pass checksum
+    fn detect_serial(
+        portname: &'static str,
+        parity: &'static str,
+        canonical: bool,
+        flags: &SerialFlags,
+    ) -> Result<(SerialRaw, u32), LinkyFail> {
+        for speed in [1200, 9600] {
+            let serial = LinkyHandle::open_serial(portname, speed, parity, canonical, flags)?;
+            if LinkyHandle::probe_serial(&serial) {
+                linky_log!(Notice, "device:{} detected speed:{}", portname, speed);
+                return Ok((serial, speed));
+            }
+            serial.close();
+        }
+        linky_fail!(
+            "tty-autodetect-fail",
+            "device:{} no valid TIC line at 1200|9600",
+            portname
+        )
+    }
+
+    fn probe_serial(serial: &SerialRaw) -> bool {
+        let mut ring = BufferRing::new();
+        let mut buffer = [0u8; 256];
+        let mut valid = 0;
+
+        // wrong speed garbage may still hold line feeds, bound attempts
+        for _ in 0..AUTODETECT_LINES * 4 {
+            match serial.wait(AUTODETECT_TIMEOUT) {
+                Ok(true) => {}
+                _ => return false,
+            }
+
+            match serial.read(ring.get_free()) {
+                Ok(count) => ring.commit(count),
+                Err(_) => return false,
+            };
+
+            while let Some(count) = ring.get_one_line(&mut buffer) {
+                if count > 3 && tic_checksum(&buffer, count).is_ok() {
+                    valid += 1;
+                    if valid == AUTODETECT_LINES {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn open_serial(
         portname: &'static str,
         speed: u32,
         parity: &'static str,
-    ) -> Result<LinkyHandle, AfbError> {
+        canonical: bool,
+        flags: &SerialFlags,
+    ) -> Result<SerialRaw, LinkyFail> {
         let parity = match parity {
             "even" => SerialCflag::PAREVN,
             "odd" => SerialCflag::PARODD,
-            _ => return afb_error!("tty-parity-invalid", "Linky only support even|odd",),
+            _ => return linky_fail!("tty-parity-invalid", "Linky only support even|odd",),
         };
 
         let speed = match speed {
             1200 => SerialSpeed::B1200,
             9600 => SerialSpeed::B9600,
-            _ => return afb_error!("tty-speed-invalid", "Linky only support 1200|9600",),
+            // re-emitting gateways may run faster than the meter itself
+            19200 => SerialSpeed::B19200,
+            57600 => SerialSpeed::B57600,
+            115200 => SerialSpeed::B115200,
+            _ => {
+                return linky_fail!(
+                    "tty-speed-invalid",
+                    "speed:{} not in 1200|9600|19200|57600|115200",
+                    speed
+                )
+            }
         };
 
-        let pflags = [PortFlag::NOCTTY, PortFlag::RDONLY];
-        let iflags = [SerialIflag::IGNBRK];
-        let cflags = [
-            SerialCflag::CS7,
-            SerialCflag::CLOCAL,
-            SerialCflag::PARENB,
-            parity, /*dlt=even*/
-        ];
-        let lflags = [SerialLflag::ICANON];
+        // O_NDELAY is O_NONBLOCK on Linux, empty tty reads return EAGAIN
+        let pflags = match &flags.pflags {
+            Some(value) => value.clone(),
+            None => vec![PortFlag::NOCTTY, PortFlag::RDONLY, PortFlag::NDELAY],
+        };
+        let iflags = match &flags.iflags {
+            Some(value) => value.clone(),
+            None => vec![SerialIflag::IGNBRK],
+        };
+        let mut cflags = match &flags.cflags {
+            Some(value) => value.clone(),
+            None => vec![SerialCflag::CS7, SerialCflag::CLOCAL, SerialCflag::PARENB],
+        };
+        cflags.push(parity); /*dlt=even*/
+
+        // without ICANON kernel returns raw bytes as soon as available (VMIN=1)
+        let lflags = match (&flags.lflags, canonical) {
+            (Some(value), _) => value.clone(),
+            (None, true) => vec![SerialLflag::ICANON],
+            (None, false) => Vec::new(),
+        };
+
+        SerialRaw::new(portname, speed, &pflags, &iflags, &cflags, &lflags)
+    }
+
+    pub fn reopen(&self) -> Result<(), LinkyFail> {
+        self.close();
+        self.update_stats(|stats| stats.reconnect += 1);
+        self.handle.lock().open()
+    }
+
+    // swap data source at runtime, current source stays untouched when new one fails to open
+    pub fn reconfigure(&self, config: &LinkyConfig) -> Result<(), LinkyFail> {
+        let fresh = LinkyHandle::new(config)?;
+        self.close();
+        self.portname.set(fresh.portname.get());
+        self.mode.replace(fresh.mode.into_inner());
+        self.handle.replace(fresh.handle.into_inner());
+        let size = self.ring.lock().get_size();
+        if config.get_buffer(size) != size {
+            self.ring.replace(BufferRing::with_size(config.get_buffer(size)));
+        }
+        self.last_seen.set(Instant::now());
+        self.gateway.set(None);
+        Ok(())
+    }
+
+    // address and reception time of last network payload, kept across reconnects
+    pub fn get_gateway(&self) -> Option<(SocketAddr, Instant)> {
+        self.gateway.get()
+    }
+
+    pub fn get_stats(&self) -> LinkyStats {
+        *self.stats.lock()
+    }
+
+    pub fn reset_stats(&self) {
+        *self.stats.lock() = LinkyStats::default();
+        self.quality.lock().reset();
+        self.ring.lock().reset_peak();
+    }
+
+    // checksum failures per minute raising a link quality alarm, 0 disables it
+    pub fn set_quality(&mut self, threshold: u32) -> &mut Self {
+        self.quality.lock().set_threshold(threshold);
+        self
+    }
+
+    // checksum failures within last minute
+    pub fn get_checksum_rate(&self) -> u32 {
+        self.quality.lock().get_rate(Instant::now())
+    }
+
+    // checksum failures per label since last stats reset
+    pub fn get_checksum_labels(&self) -> BTreeMap<String, u64> {
+        self.quality.lock().get_labels().clone()
+    }
+
+    // Some(true) when link just became degraded, Some(false) when it recovered
+    pub fn check_quality(&self) -> Option<bool> {
+        self.quality.lock().check(Instant::now())
+    }
 
-        let handle = SerialRaw::new(portname, speed, &pflags, &iflags, &cflags, &lflags)?;
+    fn update_stats(&self, update: impl FnOnce(&mut LinkyStats)) {
+        update(&mut self.stats.lock());
+    }
+
+    pub fn close(&self) {
+        self.ring.lock().reset();
+        self.frame.lock().reset();
+        self.polled.set(false);
+        self.handle.lock().close();
+    }
+
+    // register runtime labels, TicValue::CUSTOM index follows vector order
+    pub fn set_customs(&mut self, customs: Vec<&'static TicObject>) -> &mut Self {
+        self.customs = customs;
+        self
+    }
+
+    // datagram sources need room for a whole frame within a single read
+    pub fn set_buffer(&mut self, size: usize) -> &mut Self {
+        self.ring.replace(BufferRing::with_size(size));
+        self
+    }
 
-        Ok(LinkyHandle { portname, handle })
+    // (pending bytes high-water mark, read chunk) since last stats reset
+    pub fn get_ring_usage(&self) -> (usize, usize) {
+        let ring = self.ring.lock();
+        (ring.get_peak(), ring.get_size())
     }
 
-    pub fn reopen(&self) -> Result<(), AfbError> {
-        self.handle.close();
-        self.handle.open()
+    // count of payloads dropped because they did not fit within read buffer
+    pub fn get_overflow(&self) -> usize {
+        self.ring.lock().get_overflow()
+    }
+
+    // strict mode returns lines with a label outside grammar as UnknownLabel(raw line)
+    pub fn set_strict(&mut self, strict: bool) -> &mut Self {
+        self.strict = strict;
+        self
+    }
+
+    // backup source takes over after primary stayed silent for 'silence' ms
+    pub fn set_backup(&mut self, backup: LinkyHandle, silence: u32) -> &mut Self {
+        self.backup = Some(Box::new(backup));
+        self.silence = Duration::from_millis(silence as u64);
+        self
+    }
+
+    pub fn get_backup(&self) -> Option<&LinkyHandle> {
+        self.backup.as_deref()
+    }
+
+    pub fn get_active(&self) -> LinkySource {
+        self.active.get()
+    }
+
+    // arbitrate a line received from primary or backup. None means line should be
+    // ignored (primary still alive), Some(true) reports an active source switch
+    pub fn select(&self, origin: LinkySource) -> Option<bool> {
+        match origin {
+            LinkySource::Primary => {
+                self.last_seen.set(Instant::now());
+            }
+            LinkySource::Backup => {
+                if self.last_seen.get().elapsed() < self.silence {
+                    return None;
+                }
+            }
+        }
+        Some(self.active.replace(origin) != origin)
     }
 
     pub fn get_fd(&self) -> i32 {
-        self.handle.get_raw_fd()
+        self.handle.lock().get_raw_fd()
+    }
+
+    // source fd should be polled for write until connect settles
+    pub fn is_connecting(&self) -> bool {
+        self.handle.lock().is_connecting()
+    }
+
+    pub fn set_connected(&self) -> Result<(), LinkyFail> {
+        self.handle.lock().set_connected()
     }
 
     pub fn get_name(&self) -> &'static str {
-        self.portname
+        self.portname.get()
+    }
+
+    // source mode as '9600-7E1' for serial or protocol name for network sources
+    pub fn get_mode(&self) -> String {
+        self.mode.lock().clone()
     }
 
     pub(crate) fn checksum<'a>(
@@ -82,53 +836,178 @@ impl LinkyHandle {
         buffer: &'a [u8],
         count: usize,
     ) -> Result<&'a str, LinkyError> {
-        // verify checksum take all data from 'etiquette" to last 'delimiteur'
-        let mut sum: u64 = 0;
-        for idx in 0..(count - 3) {
-            sum = sum + buffer[idx] as u64;
+        tic_checksum(buffer, count)
+    }
+
+    // return next pending line, device is read at most once per call sequence.
+    // RetryLater tells caller to wait for next poll event
+    pub fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut ring = self.ring.lock();
+        if let Some(count) = ring.get_one_line(buffer) {
+            return LinkyHandle::line_fit(buffer, count);
         }
 
-        // reduce line to effective size
-        let data = match buffer.get(0..count) {
-            Some(value) => value,
-            None => b"invalid-count",
-        };
+        let handle = self.handle.lock();
+        if self.polled.get() && !handle.has_pending() {
+            self.polled.set(false);
+            return Err(LinkyError::RetryLater);
+        }
 
-        // move byte buffer to printable string
-        let line = match str::from_utf8(data) {
-            Err(_) => return Err(LinkyError::ChecksumError("not uft".to_string())),
-            Ok(data) => data,
+        let overflow = ring.get_overflow();
+        let count = match handle.read(ring.get_free()) {
+            Err(LinkyError::TooLong(info)) => {
+                // payload was not committed, drop it with any pending partial line
+                linky_log!(Warning, "source:{} overflow {}", self.get_name(), info);
+                ring.set_overflow();
+                self.polled.set(true);
+                return Err(LinkyError::TooLong(info));
+            }
+            Err(error) => {
+                if let LinkyError::SerialError(_) = error {
+                    linky_log!(Error, "Fail to read error={}", error);
+                }
+                return Err(error);
+            }
+            Ok(count) => count,
         };
+        ring.commit(count);
+        self.update_stats(|stats| stats.bytes += count as u64);
+        self.polled.set(true);
+        if let Some(peer) = handle.get_peer() {
+            self.gateway.set(Some((peer, Instant::now())));
+        }
 
-        // finally check
-        let checksum = (sum & 0x3f) as u8 + 0x20;
-        if checksum != buffer[count - 3] {
-            Err(LinkyError::ChecksumError(line.to_string()))
-        } else {
-            Ok(line)
+        // ring was full without line ending, garbage dropped before this read
+        if ring.get_overflow() != overflow {
+            return Err(LinkyError::TooLong(format!("line > {} bytes", RING_BUFFER_MAX).into()));
+        }
+
+        match ring.get_one_line(buffer) {
+            Some(count) => LinkyHandle::line_fit(buffer, count),
+            None => {
+                self.polled.set(false);
+                Err(LinkyError::RetryLater)
+            }
         }
     }
 
-    pub fn decode(&self, buffer: &mut [u8]) -> Result<TicValue, LinkyError> {
-        let count = match self.handle.read(buffer) {
-            Err(error) => {
-                afb_log_msg!(Error, None, "Fail to read error={}", (error.to_string()));
-                return Err(LinkyError::SerialError(error.to_string()));
-            }
-            Ok(count) => {
-                if count <= 3 {
-                    afb_log_msg!(Error, None, "Fail to read buffer={:?}", buffer);
-                    return Err(LinkyError::RetryLater);
-                } else {
-                    count
-                }
+    // ring truncates lines longer than caller buffer, they are reported instead of parsed
+    fn line_fit(buffer: &[u8], count: usize) -> Result<usize, LinkyError> {
+        match buffer[0..count].last() {
+            Some(b'\n') | Some(0x03) => Ok(count),
+            _ if count < buffer.len() => Ok(count),
+            _ => Err(LinkyError::TooLong(format!("line > {} bytes", buffer.len()).into())),
+        }
+    }
+
+    // verify checksum and parse a raw line
+    pub fn parse(&self, buffer: &[u8], count: usize) -> Result<TicValue, LinkyError> {
+        let result = self.parse_line(buffer, count);
+        if let Err(LinkyError::ChecksumError(line) | LinkyError::EncodingError(line)) = &result {
+            self.quality.lock().push(tic_label(line), Instant::now());
+        }
+        self.update_stats(|stats| {
+            match &result {
+                Ok(_) => stats.lines += 1,
+                Err(LinkyError::ChecksumError(_)) => stats.checksum += 1,
+                Err(LinkyError::EncodingError(_)) => stats.encoding += 1,
+                Err(LinkyError::RetryLater) => {}
+                Err(_) => stats.parsing += 1,
             }
-        };
+            if count > 0 && buffer[count - 1] == 0x03 {
+                stats.frames += 1;
+            }
+        });
+        result
+    }
 
+    fn parse_line(&self, buffer: &[u8], count: usize) -> Result<TicValue, LinkyError> {
+        // STX comes alone on its line once per frame, only other short lines are logged
+        if count <= 3 {
+            let delimiter = |byte: &u8| matches!(byte, 0x02 | 0x03 | b'\r' | b'\n');
+            if !buffer[0..count].iter().all(delimiter) {
+                linky_log!(Error, "Fail to read buffer={:?}", &buffer[0..count]);
+            }
+            return Err(LinkyError::RetryLater);
+        }
 
         let data = self.checksum(buffer, count)?;
-        let value = tic_from_str(data)?;
+
+        // frame last group is closed by ETX instead of LF, scratch keeps its capacity
+        let mut line = self.scratch.lock();
+        let data = match data.strip_suffix('\x03') {
+            Some(value) => {
+                line.clear();
+                line.push_str(value);
+                line.push('\n');
+                line.as_str()
+            }
+            None => data,
+        };
+
+        let value = match tic_from_custom(data, &self.customs) {
+            Some(value) => value,
+            // permissive mode skips labels outside grammar as ignored ones
+            None if !self.strict && !tic_has_builtin_label(data) => TicValue::UNSET,
+            None => tic_from_str(data)?,
+        };
 
         Ok(value)
     }
+
+    pub fn decode(&self, buffer: &mut [u8]) -> Result<TicValue, LinkyError> {
+        let count = self.read(buffer)?;
+        self.parse(buffer, count)
+    }
+
+    // decode every line available on this wakeup in one pass. Lines decoded before
+    // a source failure stay within 'lines' so caller can dispatch them before recovery
+    pub fn decode_all(&self, buffer: &mut [u8], lines: &mut TicLines) -> Result<(), LinkyError> {
+        self.decode_budget(buffer, lines, 0).map(|_| ())
+    }
+
+    // same as decode_all but stops after 'budget' lines (0 no limit). Returns true when
+    // budget was exhausted, remaining lines stay in ring until next call
+    pub fn decode_budget(
+        &self,
+        buffer: &mut [u8],
+        lines: &mut TicLines,
+        budget: usize,
+    ) -> Result<bool, LinkyError> {
+        let start = lines.len();
+        loop {
+            if budget > 0 && lines.len() - start >= budget {
+                return Ok(true);
+            }
+            match self.read(buffer) {
+                Err(LinkyError::RetryLater) => return Ok(false),
+                Err(LinkyError::TooLong(info)) => {
+                    let frame = self.check_frame(&[], None);
+                    lines.push(&[], buffer.len(), Err(LinkyError::TooLong(info)), frame)
+                }
+                Err(error) => return Err(error),
+                Ok(count) => {
+                    let value = self.parse(buffer, count);
+                    let raw = &buffer[0..count];
+                    let frame = match &value {
+                        Err(LinkyError::ChecksumError(_) | LinkyError::EncodingError(_)) => {
+                            self.check_frame(raw, None)
+                        }
+                        Err(LinkyError::RetryLater) => self.check_frame(raw, Some("")),
+                        _ => self.check_frame(raw, Some(tic_label(&String::from_utf8_lossy(raw)))),
+                    };
+                    lines.push(raw, buffer.len(), value, frame)
+                }
+            }
+        }
+    }
+
+    // track STX/ETX boundaries, closed frames missing groups are counted as partial
+    fn check_frame(&self, raw: &[u8], label: Option<&str>) -> Option<TicFrame> {
+        let frame = self.frame.lock().push(raw, label)?;
+        if !frame.is_complete() {
+            self.update_stats(|stats| stats.partial += 1);
+        }
+        Some(frame)
+    }
 }
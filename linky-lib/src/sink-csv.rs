@@ -0,0 +1,113 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+
+const CSV_HEADER: &str = "timestamp,uid,index,value\n";
+const DAY_MS: u64 = 24 * 3600 * 1000;
+
+// sensor samples appended as 'timestamp,uid,index,value' rows (epoch ms). Active file is
+// renamed as 'path.<epoch_ms>' when it reaches max size or on UTC day change.
+pub struct CsvSink {
+    path: String,
+    uids: Vec<String>, // empty: every sensor
+    max_size: u64,     // bytes, 0: no size rotation
+    daily: bool,
+    file: Option<File>,
+    size: u64,
+    day: u64,
+}
+
+impl CsvSink {
+    pub fn new(path: &str, uids: Vec<String>, max_size: u64, daily: bool) -> CsvSink {
+        CsvSink {
+            path: path.to_string(),
+            uids,
+            max_size,
+            daily,
+            file: None,
+            size: 0,
+            day: 0,
+        }
+    }
+
+    pub fn is_selected(&self, uid: &str) -> bool {
+        self.uids.is_empty() || self.uids.iter().any(|value| value == uid)
+    }
+
+    fn open(&mut self, at: u64) -> Result<(), LinkyFail> {
+        let mut file = match OpenOptions::new().create(true).append(true).open(&self.path) {
+            Ok(value) => value,
+            Err(error) => {
+                return linky_fail!("linky-csv-fail", "path:{} error:{}", self.path, error)
+            }
+        };
+        self.size = match file.metadata() {
+            Ok(value) => value.len(),
+            Err(_) => 0,
+        };
+        if self.size == 0 {
+            if let Err(error) = file.write_all(CSV_HEADER.as_bytes()) {
+                return linky_fail!("linky-csv-fail", "path:{} error:{}", self.path, error);
+            }
+            self.size = CSV_HEADER.len() as u64;
+        }
+        self.day = at / DAY_MS;
+        self.file = Some(file);
+        Ok(())
+    }
+
+    // close active file and move it aside, next append creates a new one
+    pub fn rotate(&mut self, at: u64) -> Result<(), LinkyFail> {
+        self.file = None;
+        self.size = 0;
+        let target = format!("{}.{}", self.path, at);
+        match fs::rename(&self.path, &target) {
+            Ok(()) => Ok(()),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(error) => linky_fail!("linky-csv-fail", "path:{} error:{}", target, error),
+        }
+    }
+
+    // at is epoch ms, return false when sensor is not selected
+    pub fn append(&mut self, uid: &str, at: u64, values: &[i64]) -> Result<bool, LinkyFail> {
+        if !self.is_selected(uid) {
+            return Ok(false);
+        }
+
+        if self.file.is_some() {
+            let full = self.max_size > 0 && self.size >= self.max_size;
+            let newday = self.daily && at / DAY_MS != self.day;
+            if full || newday {
+                self.rotate(at)?;
+            }
+        }
+        if self.file.is_none() {
+            self.open(at)?;
+        }
+
+        let mut rows = String::new();
+        for (idx, value) in values.iter().enumerate() {
+            rows.push_str(format!("{},{},{},{}\n", at, uid, idx, value).as_str());
+        }
+        if let Some(file) = &mut self.file {
+            if let Err(error) = file.write_all(rows.as_bytes()) {
+                self.file = None;
+                return linky_fail!("linky-csv-fail", "path:{} error:{}", self.path, error);
+            }
+        }
+        self.size += rows.len() as u64;
+        Ok(true)
+    }
+}
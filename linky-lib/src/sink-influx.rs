@@ -0,0 +1,140 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::prelude::*;
+
+const INFLUX_TIMEOUT: u64 = 5; // seconds
+
+// sensor samples pushed as InfluxDB v2 line protocol 'linky,uid=SINSTS,index=0 value=4000i <ms>'.
+// Lines are buffered until next flush, oldest ones are dropped when buffer is full.
+pub struct InfluxSink {
+    url: &'static str,
+    host: String,
+    port: u16,
+    path: String,
+    token: &'static str,
+    measurement: &'static str,
+    uids: Vec<String>, // empty: every sensor
+    capacity: usize,
+    pending: VecDeque<String>,
+    dropped: u64,
+}
+
+impl InfluxSink {
+    pub fn new(
+        url: &'static str,
+        org: &str,
+        bucket: &str,
+        token: &'static str,
+        measurement: &'static str,
+        capacity: usize,
+        uids: Vec<String>,
+    ) -> Result<InfluxSink, LinkyFail> {
+        let (host, port, path) = url_split("influx-invalid-url", url, "http", 8086)?;
+        let path = format!(
+            "{}/api/v2/write?org={}&bucket={}&precision=ms",
+            path.trim_end_matches('/'),
+            org,
+            bucket
+        );
+
+        Ok(InfluxSink {
+            url,
+            host,
+            port,
+            path,
+            token,
+            measurement,
+            uids,
+            capacity,
+            pending: VecDeque::new(),
+            dropped: 0,
+        })
+    }
+
+    pub fn is_selected(&self, uid: &str) -> bool {
+        self.uids.is_empty() || self.uids.iter().any(|value| value == uid)
+    }
+
+    // at is epoch ms, return false when sensor is not selected
+    pub fn push(&mut self, uid: &str, at: u64, values: &[i64]) -> bool {
+        if !self.is_selected(uid) || self.capacity == 0 {
+            return false;
+        }
+        for (idx, value) in values.iter().enumerate() {
+            while self.pending.len() >= self.capacity {
+                self.pending.pop_front();
+                self.dropped += 1;
+            }
+            self.pending.push_back(format!(
+                "{},uid={},index={} value={}i {}",
+                self.measurement, uid, idx, value, at
+            ));
+        }
+        true
+    }
+
+    pub fn get_pending(&self) -> &VecDeque<String> {
+        &self.pending
+    }
+
+    // lines lost while database was unreachable
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    // post every pending line, they are kept for next flush on failure
+    pub fn flush(&mut self) -> Result<usize, LinkyFail> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        let body = Vec::from(self.pending.clone()).join("\n");
+        let mut stream = tcp_connect(&self.host, self.port)?;
+        let mut request = format!(
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: text/plain; charset=utf-8\r\n",
+            self.path, self.host
+        );
+        if !self.token.is_empty() {
+            request.push_str(format!("Authorization: Token {}\r\n", self.token).as_str());
+        }
+        request.push_str(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_str());
+
+        let mut response = Vec::new();
+        let status = stream
+            .set_read_timeout(Some(Duration::from_secs(INFLUX_TIMEOUT)))
+            .and_then(|_| stream.write_all(request.as_bytes()))
+            .and_then(|_| stream.read_to_end(&mut response));
+
+        if let Err(error) = status {
+            return linky_fail!("influx-push-fail", "url:{} error:{}", self.url, error);
+        }
+
+        let status = String::from_utf8_lossy(&response);
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => {
+                let count = self.pending.len();
+                self.pending.clear();
+                Ok(count)
+            }
+            _ => linky_fail!(
+                "influx-push-fail",
+                "url:{} status:{}",
+                self.url,
+                status.lines().next().unwrap_or("")
+            ),
+        }
+    }
+}
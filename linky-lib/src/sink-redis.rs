@@ -0,0 +1,215 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::prelude::*;
+
+const REDIS_TIMEOUT: u64 = 5; // seconds
+const REDIS_BULK_MAX: usize = 64 * 1024; // XADD and AUTH replies are a few bytes
+
+// RESP array of bulk strings, the only request form redis needs
+pub fn redis_command(args: &[&str]) -> Vec<u8> {
+    let mut command = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        command.extend_from_slice(format!("${}\r\n{}\r\n", arg.len(), arg).as_bytes());
+    }
+    command
+}
+
+// first reply within buffer as consumed bytes with its text, none while incomplete.
+// Server errors ('-ERR ...') are returned as Err, nested arrays are not supported.
+pub fn redis_reply(buffer: &[u8]) -> Option<(usize, Result<String, String>)> {
+    let end = buffer.windows(2).position(|crlf| crlf == b"\r\n")?;
+    if end == 0 {
+        return Some((2, Err("empty reply".to_string())));
+    }
+    let line = String::from_utf8_lossy(&buffer[1..end]).to_string();
+    match buffer[0] {
+        b'+' | b':' => Some((end + 2, Ok(line))),
+        b'-' => Some((end + 2, Err(line))),
+        b'$' => {
+            let len = match line.parse::<i64>() {
+                Ok(value) if value < 0 => return Some((end + 2, Ok(String::new()))),
+                Ok(value) => match usize::try_from(value) {
+                    Ok(len) if len <= REDIS_BULK_MAX => len,
+                    _ => return Some((buffer.len(), Err(format!("bulk too long:{}", line)))),
+                },
+                Err(_) => return Some((buffer.len(), Err(format!("invalid reply:{}", line)))),
+            };
+            let start = end + 2;
+            let stop = start.checked_add(len)?;
+            if buffer.len() < stop.checked_add(2)? {
+                return None;
+            }
+            let data = String::from_utf8_lossy(&buffer[start..stop]).to_string();
+            Some((stop + 2, Ok(data)))
+        }
+        _ => Some((buffer.len(), Err(format!("unsupported reply:{}", line)))),
+    }
+}
+
+// sensor samples appended to a redis stream as 'XADD linky MAXLEN ~ 10000 * uid SINSTS ts <ms>
+// values 4000,1000'. Entries are buffered until next flush, oldest ones are dropped when
+// buffer is full. Connection is kept between flushes and reopened after a failure.
+pub struct RedisSink {
+    host: &'static str,
+    port: u16,
+    password: &'static str,
+    stream: &'static str,
+    maxlen: usize,     // 0: uncapped stream
+    uids: Vec<String>, // empty: every sensor
+    capacity: usize,
+    pending: VecDeque<Vec<String>>,
+    dropped: u64,
+    socket: Option<TcpStream>,
+}
+
+impl RedisSink {
+    pub fn new(
+        host: &'static str,
+        port: u16,
+        password: &'static str,
+        stream: &'static str,
+        maxlen: usize,
+        capacity: usize,
+        uids: Vec<String>,
+    ) -> RedisSink {
+        RedisSink {
+            host,
+            port,
+            password,
+            stream,
+            maxlen,
+            uids,
+            capacity,
+            pending: VecDeque::new(),
+            dropped: 0,
+            socket: None,
+        }
+    }
+
+    pub fn is_selected(&self, uid: &str) -> bool {
+        self.uids.is_empty() || self.uids.iter().any(|value| value == uid)
+    }
+
+    // at is epoch ms, return false when sensor is not selected
+    pub fn push(&mut self, uid: &str, at: u64, values: &[i64]) -> bool {
+        if !self.is_selected(uid) || self.capacity == 0 {
+            return false;
+        }
+        while self.pending.len() >= self.capacity {
+            self.pending.pop_front();
+            self.dropped += 1;
+        }
+
+        let mut args = vec!["XADD".to_string(), self.stream.to_string()];
+        if self.maxlen > 0 {
+            args.push("MAXLEN".to_string());
+            args.push("~".to_string());
+            args.push(self.maxlen.to_string());
+        }
+        let values = values.iter().map(|value| value.to_string()).collect::<Vec<String>>();
+        args.push("*".to_string());
+        args.push("uid".to_string());
+        args.push(uid.to_string());
+        args.push("ts".to_string());
+        args.push(at.to_string());
+        args.push("values".to_string());
+        args.push(values.join(","));
+        self.pending.push_back(args);
+        true
+    }
+
+    pub fn get_pending(&self) -> &VecDeque<Vec<String>> {
+        &self.pending
+    }
+
+    // entries lost while server was unreachable
+    pub fn get_dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn connect(&self) -> Result<TcpStream, LinkyFail> {
+        let mut socket = tcp_connect(self.host, self.port)?;
+        if let Err(error) = socket.set_read_timeout(Some(Duration::from_secs(REDIS_TIMEOUT))) {
+            return linky_fail!("redis-connect-fail", "host:{} error:{}", self.host, error);
+        }
+        if !self.password.is_empty() {
+            let command = redis_command(&["AUTH", self.password]);
+            let replies = redis_exchange(&mut socket, &command, 1)?;
+            if let Err(error) = &replies[0] {
+                return linky_fail!("redis-auth-fail", "host:{} error:{}", self.host, error);
+            }
+        }
+        Ok(socket)
+    }
+
+    // pipeline every pending entry, they are kept for next flush on failure
+    pub fn flush(&mut self) -> Result<usize, LinkyFail> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+        let mut socket = match self.socket.take() {
+            Some(value) => value,
+            None => self.connect()?,
+        };
+
+        let mut request = Vec::new();
+        for args in &self.pending {
+            let args = args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>();
+            request.extend_from_slice(&redis_command(&args));
+        }
+        let replies = redis_exchange(&mut socket, &request, self.pending.len())?;
+        if let Some(Err(error)) = replies.iter().find(|reply| reply.is_err()) {
+            return linky_fail!("redis-push-fail", "stream:{} error:{}", self.stream, error);
+        }
+
+        let count = self.pending.len();
+        self.pending.clear();
+        self.socket = Some(socket);
+        Ok(count)
+    }
+}
+
+// send request and wait for expected reply count
+fn redis_exchange(
+    socket: &mut TcpStream,
+    request: &[u8],
+    count: usize,
+) -> Result<Vec<Result<String, String>>, LinkyFail> {
+    if let Err(error) = socket.write_all(request) {
+        return linky_fail!("redis-push-fail", "write error:{}", error);
+    }
+
+    let mut replies = Vec::new();
+    let mut pending = Vec::new();
+    let mut buffer = [0u8; 512];
+    while replies.len() < count {
+        while let Some((consumed, reply)) = redis_reply(&pending) {
+            pending.drain(0..consumed);
+            replies.push(reply);
+        }
+        if replies.len() >= count {
+            break;
+        }
+        match socket.read(&mut buffer) {
+            Ok(0) => return linky_fail!("redis-push-fail", "connection closed by server"),
+            Ok(len) => pending.extend_from_slice(&buffer[0..len]),
+            Err(error) => return linky_fail!("redis-push-fail", "read error:{}", error),
+        }
+    }
+    Ok(replies)
+}
@@ -0,0 +1,297 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::collections::BTreeMap;
+
+use crate::prelude::*;
+
+pub const SPARKPLUG_NAMESPACE: &str = "spBv1.0";
+pub const SPARKPLUG_REBIRTH: &str = "Node Control/Rebirth";
+
+// sparkplug_b.proto DataType
+const SPB_INT64: u64 = 4;
+const SPB_UINT64: u64 = 8;
+const SPB_BOOLEAN: u64 = 11;
+const SPB_STRING: u64 = 12;
+
+// minimal protobuf writer, only the wire types used by Sparkplug B payloads
+fn pb_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn pb_uint(buffer: &mut Vec<u8>, field: u64, value: u64) {
+    pb_varint(buffer, field << 3);
+    pb_varint(buffer, value);
+}
+
+fn pb_bytes(buffer: &mut Vec<u8>, field: u64, value: &[u8]) {
+    pb_varint(buffer, (field << 3) | 2);
+    pb_varint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value);
+}
+
+fn pb_read_varint(buffer: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *buffer.get(*pos)?;
+        *pos += 1;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+// iterate (field, wire type, varint value or bytes) of one protobuf message. Lengths come
+// from broker NCMD payloads, any offset past buffer end rejects the whole message.
+fn pb_fields(buffer: &[u8]) -> Option<Vec<(u64, u64, &[u8])>> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < buffer.len() {
+        let key = pb_read_varint(buffer, &mut pos)?;
+        let (field, wire) = (key >> 3, key & 0x07);
+        match wire {
+            0 => {
+                let value = pb_read_varint(buffer, &mut pos)?;
+                fields.push((field, value, &buffer[0..0]));
+            }
+            1 => pos = pos.checked_add(8)?,
+            2 => {
+                let len = usize::try_from(pb_read_varint(buffer, &mut pos)?).ok()?;
+                let end = pos.checked_add(len)?;
+                fields.push((field, len as u64, buffer.get(pos..end)?));
+                pos = end;
+            }
+            5 => pos = pos.checked_add(4)?,
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
+
+struct SparkplugMetric {
+    name: String,
+    unit: &'static str,
+    value: Option<i64>,
+}
+
+// Metric message, name is only sent in birth certificates
+fn spb_metric(name: Option<&str>, alias: u64, at: u64, value: Option<i64>, unit: &str) -> Vec<u8> {
+    let mut metric = Vec::new();
+    if let Some(name) = name {
+        pb_bytes(&mut metric, 1, name.as_bytes());
+    }
+    pb_uint(&mut metric, 2, alias);
+    pb_uint(&mut metric, 3, at);
+    pb_uint(&mut metric, 4, SPB_INT64);
+    match value {
+        None => pb_uint(&mut metric, 7, 1),
+        Some(value) => pb_uint(&mut metric, 11, value as u64),
+    }
+
+    // PropertySet { keys:["engUnit"], values:[{type:String, string_value}] }
+    if name.is_some() && !unit.is_empty() {
+        let mut property = Vec::new();
+        pb_uint(&mut property, 1, SPB_STRING);
+        pb_bytes(&mut property, 8, unit.as_bytes());
+        let mut properties = Vec::new();
+        pb_bytes(&mut properties, 1, b"engUnit");
+        pb_bytes(&mut properties, 2, &property);
+        pb_bytes(&mut metric, 9, &properties);
+    }
+    metric
+}
+
+// stable alias from sensor uid (FNV-1a) and value index, SCADA keeps it across restarts
+pub fn spb_alias(uid: &str, index: usize) -> u64 {
+    let mut hash: u32 = 0x811c9dc5;
+    for byte in uid.bytes() {
+        hash ^= byte as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    ((hash as u64) << 8) | (index as u64 & 0xFF)
+}
+
+// true when NCMD payload holds 'Node Control/Rebirth' set to true
+pub fn spb_is_rebirth(payload: &[u8]) -> bool {
+    let fields = match pb_fields(payload) {
+        Some(value) => value,
+        None => return false,
+    };
+    let rebirth = spb_alias(SPARKPLUG_REBIRTH, 0);
+    fields.iter().filter(|(field, _, _)| *field == 2).any(|(_, _, metric)| {
+        let metric = match pb_fields(metric) {
+            Some(value) => value,
+            None => return false,
+        };
+        let named = metric.iter().any(|(field, _, name)| {
+            *field == 1 && *name == SPARKPLUG_REBIRTH.as_bytes()
+        });
+        let aliased = metric.iter().any(|(field, value, _)| *field == 2 && *value == rebirth);
+        let enabled = metric.iter().any(|(field, value, _)| *field == 14 && *value == 1);
+        (named || aliased) && enabled
+    })
+}
+
+// Sparkplug B edge node state. Metrics are registered when first seen, a new metric
+// requires a new birth certificate before NDATA can reference it by alias.
+pub struct SparkplugNode {
+    group: &'static str,
+    node: &'static str,
+    bdseq: u64,
+    seq: u64,
+    born: bool,
+    uids: Vec<String>, // empty: every sensor
+    metrics: BTreeMap<u64, SparkplugMetric>,
+    changed: Vec<u64>,
+}
+
+impl SparkplugNode {
+    pub fn new(group: &'static str, node: &'static str, uids: Vec<String>) -> SparkplugNode {
+        SparkplugNode {
+            group,
+            node,
+            bdseq: 0,
+            seq: 0,
+            born: false,
+            uids,
+            metrics: BTreeMap::new(),
+            changed: Vec::new(),
+        }
+    }
+
+    pub fn get_topic(&self, kind: &str) -> String {
+        format!("{}/{}/{}/{}", SPARKPLUG_NAMESPACE, self.group, kind, self.node)
+    }
+
+    pub fn is_selected(&self, uid: &str) -> bool {
+        self.uids.is_empty() || self.uids.iter().any(|value| value == uid)
+    }
+
+    pub fn is_born(&self) -> bool {
+        self.born
+    }
+
+    // new MQTT session, death certificate must be registered as will before connecting
+    pub fn new_session(&mut self) -> (String, Vec<u8>) {
+        self.bdseq = (self.bdseq + 1) % 256;
+        self.born = false;
+        (self.get_topic("NDEATH"), self.death())
+    }
+
+    pub fn death(&self) -> Vec<u8> {
+        let mut metric = Vec::new();
+        pb_bytes(&mut metric, 1, b"bdSeq");
+        pb_uint(&mut metric, 4, SPB_UINT64);
+        pb_uint(&mut metric, 11, self.bdseq);
+        let mut payload = Vec::new();
+        pb_bytes(&mut payload, 2, &metric);
+        payload
+    }
+
+    // 'SINSTS' single value, 'IRMS/1' for multi-phase objects (index 0 based),
+    // return false when sensor is not selected
+    pub fn set_value(&mut self, tic: &TicObject, values: &[i64]) -> bool {
+        if !self.is_selected(tic.get_uid()) {
+            return false;
+        }
+        for (idx, value) in values.iter().enumerate() {
+            let alias = spb_alias(tic.get_uid(), idx);
+            match self.metrics.get_mut(&alias) {
+                Some(metric) => {
+                    if metric.value == Some(*value) {
+                        continue;
+                    }
+                    metric.value = Some(*value);
+                }
+                None => {
+                    let name = if tic.get_count() > 1 {
+                        format!("{}/{}", tic.get_uid(), idx)
+                    } else {
+                        tic.get_uid().to_string()
+                    };
+                    let metric = SparkplugMetric {
+                        name,
+                        unit: tic.get_unit().as_str(),
+                        value: Some(*value),
+                    };
+                    self.metrics.insert(alias, metric);
+                    self.born = false;
+                }
+            }
+            if !self.changed.contains(&alias) {
+                self.changed.push(alias);
+            }
+        }
+        true
+    }
+
+    // NBIRTH with every known metric, sequence restarts at 0
+    pub fn birth(&mut self, at: u64) -> (String, Vec<u8>) {
+        let mut payload = Vec::new();
+        pb_uint(&mut payload, 1, at);
+
+        let mut bdseq = Vec::new();
+        pb_bytes(&mut bdseq, 1, b"bdSeq");
+        pb_uint(&mut bdseq, 3, at);
+        pb_uint(&mut bdseq, 4, SPB_UINT64);
+        pb_uint(&mut bdseq, 11, self.bdseq);
+        pb_bytes(&mut payload, 2, &bdseq);
+
+        let mut rebirth = Vec::new();
+        pb_bytes(&mut rebirth, 1, SPARKPLUG_REBIRTH.as_bytes());
+        pb_uint(&mut rebirth, 2, spb_alias(SPARKPLUG_REBIRTH, 0));
+        pb_uint(&mut rebirth, 3, at);
+        pb_uint(&mut rebirth, 4, SPB_BOOLEAN);
+        pb_uint(&mut rebirth, 14, 0);
+        pb_bytes(&mut payload, 2, &rebirth);
+
+        for (alias, metric) in &self.metrics {
+            let name = Some(metric.name.as_str());
+            pb_bytes(&mut payload, 2, &spb_metric(name, *alias, at, metric.value, metric.unit));
+        }
+        pb_uint(&mut payload, 3, 0);
+
+        self.seq = 1;
+        self.born = true;
+        self.changed.clear();
+        (self.get_topic("NBIRTH"), payload)
+    }
+
+    // NDATA with metrics changed since last publication, none when nothing changed
+    pub fn data(&mut self, at: u64) -> Option<(String, Vec<u8>)> {
+        if !self.born || self.changed.is_empty() {
+            return None;
+        }
+        let mut payload = Vec::new();
+        pb_uint(&mut payload, 1, at);
+        for alias in self.changed.drain(..) {
+            if let Some(metric) = self.metrics.get(&alias) {
+                pb_bytes(&mut payload, 2, &spb_metric(None, alias, at, metric.value, ""));
+            }
+        }
+        pb_uint(&mut payload, 3, self.seq);
+        self.seq = (self.seq + 1) % 256;
+        Some((self.get_topic("NDATA"), payload))
+    }
+
+    pub fn reset(&mut self) {
+        self.metrics.clear();
+        self.changed.clear();
+        self.born = false;
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+use openssl::ssl::{ErrorCode, Ssl, SslContext, SslMethod, SslOptions, SslStream, SslVersion};
+
+use crate::network::TCP_CONNECT_TIMEOUT;
+use crate::prelude::*;
+
+pub const DTLS_PSK_KEY_MIN: usize = 16;
+pub const DTLS_PSK_KEY_MAX: usize = 64;
+const DTLS_PSK_IDENTITY_MAX: usize = 128;
+const DTLS_CIPHERS: &str = "PSK-AES128-GCM-SHA256:PSK-CHACHA20-POLY1305";
+const DTLS_MTU: u32 = 1200;
+const DTLS_DATAGRAM_MAX: usize = 2048;
+const DTLS_PEERS_MAX: usize = 16;
+const DTLS_CLIENT_REKEY: Duration = Duration::from_secs(60);
+
+// DTLS 1.2 pre-shared key, gateway and listener share the same identity and key
+pub struct DtlsPsk {
+    identity: String,
+    key: Vec<u8>,
+}
+
+impl DtlsPsk {
+    // key is 16-64 bytes as hex characters
+    pub fn new(identity: &str, key: &str) -> Result<DtlsPsk, LinkyFail> {
+        if identity.is_empty() || identity.len() > DTLS_PSK_IDENTITY_MAX {
+            return linky_fail!(
+                "dtls-psk-invalid",
+                "identity:{} should be 1-{} bytes",
+                identity,
+                DTLS_PSK_IDENTITY_MAX
+            );
+        }
+
+        let mut bytes = Vec::new();
+        for idx in (0..key.len()).step_by(2) {
+            match key.get(idx..idx + 2).map(|hex| u8::from_str_radix(hex, 16)) {
+                Some(Ok(value)) => bytes.push(value),
+                _ => return linky_fail!("dtls-psk-invalid", "psk_key should be hexadecimal"),
+            }
+        }
+        if bytes.len() < DTLS_PSK_KEY_MIN || bytes.len() > DTLS_PSK_KEY_MAX {
+            return linky_fail!(
+                "dtls-psk-invalid",
+                "psk_key should be {}-{} bytes ({}-{} hex characters)",
+                DTLS_PSK_KEY_MIN,
+                DTLS_PSK_KEY_MAX,
+                DTLS_PSK_KEY_MIN * 2,
+                DTLS_PSK_KEY_MAX * 2
+            );
+        }
+
+        Ok(DtlsPsk {
+            identity: identity.to_string(),
+            key: bytes,
+        })
+    }
+
+    fn get_context(&self, server: bool) -> Result<SslContext, LinkyFail> {
+        let mut builder = match SslContext::builder(SslMethod::dtls()) {
+            Err(error) => return linky_fail!("dtls-context-fail", "error:{}", error),
+            Ok(value) => value,
+        };
+        if let Err(error) = builder
+            .set_min_proto_version(Some(SslVersion::DTLS1_2))
+            .and_then(|_| builder.set_cipher_list(DTLS_CIPHERS))
+        {
+            return linky_fail!("dtls-context-fail", "error:{}", error);
+        }
+        // datagram size is fixed by DTLS_MTU, custom io has no path mtu to query
+        builder.set_options(SslOptions::NO_QUERY_MTU);
+
+        let identity = self.identity.clone();
+        let key = self.key.clone();
+        if server {
+            // unknown identity returns an empty key and fails the handshake
+            builder.set_psk_server_callback(move |_, client, psk| {
+                if client != Some(identity.as_bytes()) || psk.len() < key.len() {
+                    return Ok(0);
+                }
+                psk[0..key.len()].copy_from_slice(&key);
+                Ok(key.len())
+            });
+        } else {
+            // identity is sent as a nul terminated string
+            builder.set_psk_client_callback(move |_, _hint, client, psk| {
+                if client.len() <= identity.len() || psk.len() < key.len() {
+                    return Ok(0);
+                }
+                client[0..identity.len()].copy_from_slice(identity.as_bytes());
+                client[identity.len()] = 0;
+                psk[0..key.len()].copy_from_slice(&key);
+                Ok(key.len())
+            });
+        }
+        Ok(builder.build())
+    }
+
+    fn new_ssl(context: &SslContext) -> Result<Ssl, LinkyFail> {
+        match Ssl::new(context).and_then(|mut ssl| ssl.set_mtu(DTLS_MTU).map(|_| ssl)) {
+            Err(error) => linky_fail!("dtls-context-fail", "error:{}", error),
+            Ok(value) => Ok(value),
+        }
+    }
+}
+
+// listener side io, openssl reads the datagram just received and replies to its sender
+struct DtlsDatagram {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    incoming: Option<Vec<u8>>,
+}
+
+impl Read for DtlsDatagram {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.incoming.take() {
+            None => Err(ErrorKind::WouldBlock.into()),
+            Some(data) => {
+                let count = usize::min(data.len(), buffer.len());
+                buffer[0..count].copy_from_slice(&data[0..count]);
+                Ok(count)
+            }
+        }
+    }
+}
+
+impl Write for DtlsDatagram {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.socket.send_to(buffer, self.peer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+struct DtlsSession {
+    stream: SslStream<DtlsDatagram>,
+    stamp: Instant,
+}
+
+// a client hello (handshake record, epoch 0, msg type 1) opens or restarts a peer session
+fn dtls_client_hello(data: &[u8]) -> bool {
+    data.len() > 13 && data[0] == 22 && data[3..5] == [0, 0] && data[13] == 1
+}
+
+// listener side, one DTLS session per gateway address. Each session has its own record
+// sequence and replay window, sessions are dropped with the socket on reopen.
+pub struct DtlsServer {
+    context: SslContext,
+    sessions: RefCell<HashMap<SocketAddr, DtlsSession>>,
+}
+
+impl DtlsServer {
+    pub fn new(psk: &DtlsPsk) -> Result<DtlsServer, LinkyFail> {
+        Ok(DtlsServer {
+            context: psk.get_context(true)?,
+            sessions: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub(crate) fn clear(&self) {
+        self.sessions.borrow_mut().clear();
+    }
+
+    fn accept(&self, socket: &UdpSocket, peer: SocketAddr) -> Result<DtlsSession, LinkyError> {
+        let io = DtlsDatagram {
+            socket: socket
+                .try_clone()
+                .map_err(|error| LinkyError::SerialError(error.to_string().into()))?,
+            peer,
+            incoming: None,
+        };
+        let ssl = DtlsPsk::new_ssl(&self.context)
+            .map_err(|error| LinkyError::SerialError(error.to_string().into()))?;
+        match SslStream::new(ssl, io) {
+            Err(error) => Err(LinkyError::SerialError(error.to_string().into())),
+            Ok(stream) => Ok(DtlsSession {
+                stream,
+                stamp: Instant::now(),
+            }),
+        }
+    }
+
+    // receive one datagram, application data is decrypted in buffer. Handshake records and
+    // datagrams failing authentication or replay check return RetryLater.
+    pub(crate) fn recv_from(
+        &self,
+        socket: &UdpSocket,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr), LinkyError> {
+        let mut datagram = [0u8; DTLS_DATAGRAM_MAX];
+        let (count, peer) = match socket.recv_from(&mut datagram) {
+            Ok(value) => value,
+            Err(error) => {
+                return match error.kind() {
+                    ErrorKind::Interrupted | ErrorKind::WouldBlock => Err(LinkyError::RetryLater),
+                    _ => Err(LinkyError::SerialError(error.to_string().into())),
+                }
+            }
+        };
+
+        match self.session_read(socket, peer, &datagram[0..count], buffer) {
+            Ok(count) => Ok((count, peer)),
+            Err(LinkyError::RetryLater) => Err(LinkyError::RetryLater),
+            Err(error) => {
+                linky_log!(Warning, "udp peer:{} dropped {}", peer, error);
+                Err(LinkyError::RetryLater)
+            }
+        }
+    }
+
+    fn session_read(
+        &self,
+        socket: &UdpSocket,
+        peer: SocketAddr,
+        datagram: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<usize, LinkyError> {
+        let mut sessions = self.sessions.borrow_mut();
+
+        // gateway restarted or new gateway, oldest session leaves room when table is full
+        if dtls_client_hello(datagram) {
+            if !sessions.contains_key(&peer) && sessions.len() >= DTLS_PEERS_MAX {
+                let oldest = sessions.iter().min_by_key(|(_, session)| session.stamp);
+                if let Some(addr) = oldest.map(|(addr, _)| *addr) {
+                    sessions.remove(&addr);
+                }
+            }
+            let session = self.accept(socket, peer)?;
+            sessions.insert(peer, session);
+        }
+
+        let session = match sessions.get_mut(&peer) {
+            None => return Err(LinkyError::ParsingError("dtls no session".into())),
+            Some(value) => value,
+        };
+        session.stamp = Instant::now();
+        session.stream.get_mut().incoming = Some(datagram.to_vec());
+
+        if !session.stream.ssl().is_init_finished() {
+            return match session.stream.accept() {
+                Ok(()) => {
+                    linky_log!(Notice, "udp peer:{} dtls session established", peer);
+                    Err(LinkyError::RetryLater)
+                }
+                Err(error) if error.code() == ErrorCode::WANT_READ => Err(LinkyError::RetryLater),
+                Err(error) => {
+                    sessions.remove(&peer);
+                    Err(LinkyError::ParsingError(format!("dtls handshake {}", error).into()))
+                }
+            };
+        }
+
+        // openssl silently discards replayed or forged records
+        let mut payload = [0u8; DTLS_DATAGRAM_MAX];
+        match session.stream.ssl_read(&mut payload) {
+            Ok(count) => {
+                let count = usize::min(count, buffer.len());
+                buffer[0..count].copy_from_slice(&payload[0..count]);
+                Ok(count)
+            }
+            Err(error) if error.code() == ErrorCode::WANT_READ => Err(LinkyError::RetryLater),
+            Err(error) if error.code() == ErrorCode::ZERO_RETURN => {
+                sessions.remove(&peer);
+                linky_log!(Notice, "udp peer:{} dtls session closed", peer);
+                Err(LinkyError::RetryLater)
+            }
+            Err(error) => {
+                sessions.remove(&peer);
+                Err(LinkyError::ParsingError(format!("dtls record {}", error).into()))
+            }
+        }
+    }
+}
+
+// gateway side io, connected socket only exchanges datagrams with the listener
+struct DtlsSocket(UdpSocket);
+
+impl Read for DtlsSocket {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.0.recv(buffer)
+    }
+}
+
+impl Write for DtlsSocket {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.send(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// gateway side, each send is one DTLS record. Session is renewed from the same address every
+// DTLS_CLIENT_REKEY, so a restarted listener gets its peer back without gateway restart.
+pub struct DtlsClient {
+    context: SslContext,
+    stream: SslStream<DtlsSocket>,
+    stamp: Instant,
+}
+
+impl DtlsClient {
+    pub fn connect(addr: &str, psk: &DtlsPsk) -> Result<DtlsClient, LinkyFail> {
+        let target = match addr.to_socket_addrs().map(|mut addrs| addrs.next()) {
+            Ok(Some(value)) => value,
+            Ok(None) => return linky_fail!("dtls-connect-fail", "addr:{} not resolved", addr),
+            Err(error) => return linky_fail!("dtls-connect-fail", "addr:{} error:{}", addr, error),
+        };
+        let local = match target {
+            SocketAddr::V4(_) => "0.0.0.0:0",
+            SocketAddr::V6(_) => "[::]:0",
+        };
+        let socket = match UdpSocket::bind(local).and_then(|socket| {
+            socket.connect(target)?;
+            socket.set_read_timeout(Some(Duration::from_secs(TCP_CONNECT_TIMEOUT)))?;
+            Ok(socket)
+        }) {
+            Err(error) => return linky_fail!("dtls-connect-fail", "addr:{} error:{}", addr, error),
+            Ok(value) => value,
+        };
+
+        let context = psk.get_context(false)?;
+        let stream = DtlsClient::handshake(&context, socket)?;
+        Ok(DtlsClient {
+            context,
+            stream,
+            stamp: Instant::now(),
+        })
+    }
+
+    fn handshake(
+        context: &SslContext,
+        socket: UdpSocket,
+    ) -> Result<SslStream<DtlsSocket>, LinkyFail> {
+        let ssl = DtlsPsk::new_ssl(context)?;
+        let mut stream = match SslStream::new(ssl, DtlsSocket(socket)) {
+            Err(error) => return linky_fail!("dtls-connect-fail", "error:{}", error),
+            Ok(value) => value,
+        };
+        if let Err(error) = stream.connect() {
+            return linky_fail!("dtls-connect-fail", "handshake error:{}", error);
+        }
+        Ok(stream)
+    }
+
+    pub fn send(&mut self, data: &[u8]) -> Result<(), LinkyError> {
+        if self.stamp.elapsed() > DTLS_CLIENT_REKEY {
+            let socket = match self.stream.get_ref().0.try_clone() {
+                Err(error) => return Err(LinkyError::SerialError(error.to_string().into())),
+                Ok(value) => value,
+            };
+            self.stream = match DtlsClient::handshake(&self.context, socket) {
+                Err(error) => return Err(LinkyError::SerialError(error.to_string().into())),
+                Ok(value) => value,
+            };
+            self.stamp = Instant::now();
+        }
+
+        match self.stream.ssl_write(data) {
+            Err(error) => Err(LinkyError::SerialError(format!("dtls send {}", error).into())),
+            Ok(_) => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::prelude::*;
+
+enum MemoryRead {
+    Data(Vec<u8>),
+    Error(LinkyError),
+}
+
+#[derive(Default)]
+struct MemoryState {
+    reads: VecDeque<MemoryRead>,
+    opened: u32,
+    closed: u32,
+    served: u32,
+}
+
+// in memory source for tests, every push is returned by one read. Clones share the same
+// queue, keep one to feed data once the other is boxed within a LinkyHandle.
+#[derive(Clone, Default)]
+pub struct MemorySource {
+    state: Arc<SyncLock<MemoryState>>,
+}
+
+impl MemorySource {
+    pub fn new() -> MemorySource {
+        MemorySource::default()
+    }
+
+    pub fn push(&self, data: &[u8]) -> &Self {
+        self.state.lock().reads.push_back(MemoryRead::Data(data.to_vec()));
+        self
+    }
+
+    // next read fails, ReopenDev/SerialError simulate a lost device
+    pub fn push_error(&self, error: LinkyError) -> &Self {
+        self.state.lock().reads.push_back(MemoryRead::Error(error));
+        self
+    }
+
+    // open and close calls received from LinkyHandle
+    pub fn get_counts(&self) -> (u32, u32) {
+        let state = self.state.lock();
+        (state.opened, state.closed)
+    }
+
+    // read calls that returned data, one per syscall on a real device
+    pub fn get_reads(&self) -> u32 {
+        self.state.lock().served
+    }
+}
+
+impl SourceHandle for MemorySource {
+    fn open(&self) -> Result<(), LinkyFail> {
+        self.state.lock().opened += 1;
+        Ok(())
+    }
+
+    fn close(&self) {
+        self.state.lock().closed += 1;
+    }
+
+    // data longer than buffer is returned by next reads
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut state = self.state.lock();
+        match state.reads.pop_front() {
+            None => Err(LinkyError::RetryLater),
+            Some(MemoryRead::Error(error)) => Err(error),
+            Some(MemoryRead::Data(mut data)) => {
+                state.served += 1;
+                let count = usize::min(data.len(), buffer.len());
+                buffer[0..count].copy_from_slice(&data[0..count]);
+                if count < data.len() {
+                    state.reads.push_front(MemoryRead::Data(data.split_off(count)));
+                }
+                Ok(count)
+            }
+        }
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.state.lock().reads.is_empty()
+    }
+}
@@ -0,0 +1,632 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::cell::{Cell, RefCell};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{
+    IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket,
+};
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::prelude::*;
+
+// common interface for every Linky data source (serial, network, ...)
+pub trait SourceHandle: Send {
+    fn open(&self) -> Result<(), LinkyFail>;
+    fn close(&self);
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError>;
+    fn get_raw_fd(&self) -> raw::c_int;
+
+    // data already received but not yet returned by read(), poll won't wake for it
+    fn has_pending(&self) -> bool {
+        false
+    }
+
+    // remote gateway address, only meaningful for network sources
+    fn get_peer(&self) -> Option<SocketAddr> {
+        None
+    }
+
+    // non-blocking connect in progress, fd turns writable once it settles
+    fn is_connecting(&self) -> bool {
+        false
+    }
+
+    // called when fd turned writable, reports connect failure
+    fn set_connected(&self) -> Result<(), LinkyFail> {
+        Ok(())
+    }
+}
+
+pub(crate) const TCP_CONNECT_TIMEOUT: u64 = 5; // seconds
+
+// raw TCP client for ser2net or ESP32 bridges pushing TIC lines
+pub struct TcpHandle {
+    pub(crate) host: &'static str,
+    pub(crate) port: u16,
+    pub(crate) stream: RefCell<Option<TcpStream>>,
+    pub(crate) connecting: Cell<bool>,
+}
+
+impl TcpHandle {
+    #[track_caller]
+    pub fn new(host: &'static str, port: u16) -> Result<TcpHandle, LinkyFail> {
+        let handle = TcpHandle {
+            host,
+            port,
+            stream: RefCell::new(None),
+            connecting: Cell::new(false),
+        };
+
+        // connection is only started, it completes within caller event loop
+        handle.open()?;
+        Ok(handle)
+    }
+}
+
+pub(crate) fn tcp_connect(host: &str, port: u16) -> Result<TcpStream, LinkyFail> {
+    let addrs = match (host, port).to_socket_addrs() {
+        Err(error) => return linky_fail!("tcp-invalid-host", "host:{} error:{}", host, error),
+        Ok(value) => value,
+    };
+
+    let timeout = Duration::from_secs(TCP_CONNECT_TIMEOUT);
+    for addr in addrs {
+        if let Ok(stream) = TcpStream::connect_timeout(&addr, timeout) {
+            linky_log!(Debug, "Connect host={} addr={}", host, addr);
+            return Ok(stream);
+        }
+    }
+    linky_fail!("tcp-connect-fail", "host:{} port:{} unreachable", host, port)
+}
+
+// start connection to first resolved address without waiting for it, stream is non-blocking
+pub(crate) fn tcp_connect_async(host: &str, port: u16) -> Result<TcpStream, LinkyFail> {
+    let addr = match (host, port).to_socket_addrs() {
+        Err(error) => return linky_fail!("tcp-invalid-host", "host:{} error:{}", host, error),
+        Ok(mut value) => match value.next() {
+            None => return linky_fail!("tcp-invalid-host", "host:{} no address", host),
+            Some(addr) => addr,
+        },
+    };
+
+    let socket = match Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP)) {
+        Err(error) => return linky_fail!("tcp-socket-fail", "host:{} error:{}", host, error),
+        Ok(value) => value,
+    };
+    if let Err(error) = socket.set_nonblocking(true) {
+        return linky_fail!("tcp-socket-fail", "host:{} error:{}", host, error);
+    }
+    match socket.connect(&addr.into()) {
+        Ok(()) => {}
+        Err(error) if error.raw_os_error() == Some(libc::EINPROGRESS) => {}
+        Err(error) => {
+            return linky_fail!("tcp-connect-fail", "host:{} addr:{} error:{}", host, addr, error)
+        }
+    }
+    linky_log!(Debug, "Connecting host={} addr={}", host, addr);
+    Ok(socket.into())
+}
+
+impl SourceHandle for TcpHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        let stream = tcp_connect_async(self.host, self.port)?;
+        self.stream.replace(Some(stream));
+        self.connecting.set(true);
+        Ok(())
+    }
+
+    fn close(&self) {
+        // dropping the stream closes the socket
+        self.stream.replace(None);
+        self.connecting.set(false);
+    }
+
+    fn is_connecting(&self) -> bool {
+        self.connecting.get()
+    }
+
+    fn set_connected(&self) -> Result<(), LinkyFail> {
+        let stream = self.stream.borrow();
+        let stream = match stream.as_ref() {
+            None => return linky_fail!("tcp-connect-fail", "host:{} closed", self.host),
+            Some(value) => value,
+        };
+        if let Ok(Some(error)) | Err(error) = stream.take_error() {
+            return linky_fail!("tcp-connect-fail", "host:{} error:{}", self.host, error);
+        }
+        if let Err(error) = stream.peer_addr() {
+            return linky_fail!("tcp-connect-fail", "host:{} error:{}", self.host, error);
+        }
+        self.connecting.set(false);
+        linky_log!(Debug, "Connect host={} port={}", self.host, self.port);
+        Ok(())
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let stream = self.stream.borrow();
+        let mut stream = match stream.as_ref() {
+            None => return Err(LinkyError::ReopenDev),
+            Some(value) => value,
+        };
+
+        match stream.read(buffer) {
+            Ok(0) => Err(LinkyError::ReopenDev), // remote closed connection
+            Ok(count) => {
+                self.connecting.set(false);
+                Ok(count)
+            }
+            Err(error) => match error.kind() {
+                ErrorKind::Interrupted | ErrorKind::WouldBlock => Err(LinkyError::RetryLater),
+                ErrorKind::NotConnected if self.connecting.get() => Err(LinkyError::RetryLater),
+                _ => Err(LinkyError::ReopenDev),
+            },
+        }
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        match self.stream.borrow().as_ref() {
+            None => -1,
+            Some(stream) => stream.as_raw_fd(),
+        }
+    }
+
+    fn get_peer(&self) -> Option<SocketAddr> {
+        self.stream.borrow().as_ref()?.peer_addr().ok()
+    }
+}
+
+// passive TCP server for gateways only acting as client, one connection at a time.
+// Raw fd moves from listener to client stream and back when client leaves.
+pub struct TcpListenHandle {
+    pub(crate) addr: &'static str,
+    pub(crate) port: u16,
+    pub(crate) listener: RefCell<Option<TcpListener>>,
+    pub(crate) stream: RefCell<Option<TcpStream>>,
+}
+
+impl TcpListenHandle {
+    #[track_caller]
+    pub fn new(addr: &'static str, port: u16) -> Result<TcpListenHandle, LinkyFail> {
+        let handle = TcpListenHandle {
+            addr,
+            port,
+            listener: RefCell::new(None),
+            stream: RefCell::new(None),
+        };
+
+        handle.open()?;
+        Ok(handle)
+    }
+
+    fn accept(&self) -> Result<usize, LinkyError> {
+        let listener = self.listener.borrow();
+        let listener = match listener.as_ref() {
+            None => return Err(LinkyError::ReopenDev),
+            Some(value) => value,
+        };
+
+        match listener.accept() {
+            Ok((stream, remote)) => {
+                linky_log!(Debug, "Accept port={} remote={}", self.port, remote);
+                self.stream.replace(Some(stream));
+                Err(LinkyError::RetryLater)
+            }
+            Err(error) => match error.kind() {
+                ErrorKind::Interrupted | ErrorKind::WouldBlock => Err(LinkyError::RetryLater),
+                _ => Err(LinkyError::ReopenDev),
+            },
+        }
+    }
+}
+
+impl SourceHandle for TcpListenHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        if self.listener.borrow().is_some() {
+            return Ok(());
+        }
+
+        let listener = match TcpListener::bind((self.addr, self.port)) {
+            Err(error) => {
+                return linky_fail!(
+                    "tcp-listen-fail",
+                    "addr:{} port:{} error:{}",
+                    self.addr,
+                    self.port,
+                    error,
+                )
+            }
+            Ok(value) => value,
+        };
+
+        // spurious wakeup should not block binder main loop
+        if let Err(error) = listener.set_nonblocking(true) {
+            return linky_fail!("tcp-listen-fail", "port:{} error:{}", self.port, error);
+        }
+
+        self.listener.replace(Some(listener));
+        Ok(())
+    }
+
+    fn close(&self) {
+        self.stream.replace(None);
+        self.listener.replace(None);
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let guard = self.stream.borrow();
+        let mut stream = match guard.as_ref() {
+            None => {
+                drop(guard);
+                return self.accept();
+            }
+            Some(value) => value,
+        };
+
+        let status = match stream.read(buffer) {
+            Ok(0) => LinkyError::ReopenDev,
+            Ok(count) => return Ok(count),
+            Err(error) => match error.kind() {
+                ErrorKind::Interrupted | ErrorKind::WouldBlock => return Err(LinkyError::RetryLater),
+                _ => LinkyError::SerialError(error.to_string().into()),
+            },
+        };
+
+        // gateway left, drop client and wait for next one on listener
+        drop(guard);
+        self.stream.replace(None);
+        linky_log!(Debug, "Client left port={} status={:?}", self.port, status);
+        Err(LinkyError::RetryLater)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        if let Some(stream) = self.stream.borrow().as_ref() {
+            return stream.as_raw_fd();
+        }
+        match self.listener.borrow().as_ref() {
+            None => -1,
+            Some(listener) => listener.as_raw_fd(),
+        }
+    }
+
+    fn get_peer(&self) -> Option<SocketAddr> {
+        self.stream.borrow().as_ref()?.peer_addr().ok()
+    }
+}
+
+// split 'scheme://host[:port][/path]' url, path keeps its query and defaults to '/'
+pub(crate) fn url_split(
+    uid: &str,
+    url: &str,
+    scheme: &str,
+    port: u16,
+) -> Result<(String, u16, String), LinkyFail> {
+    let rest = match url.strip_prefix(scheme).and_then(|rest| rest.strip_prefix("://")) {
+        None => return linky_fail!(uid, "url:{} only {}:// supported", url, scheme),
+        Some(value) => value,
+    };
+
+    let (authority, path) = match rest.find(['/', '?']) {
+        None => (rest, "/".to_string()),
+        Some(idx) if rest[idx..].starts_with('?') => (&rest[..idx], format!("/{}", &rest[idx..])),
+        Some(idx) => (&rest[..idx], rest[idx..].to_string()),
+    };
+
+    // '[::1]' ipv6 host without port has no trailing ':port'
+    let (host, port) = match authority.rsplit_once(':').filter(|(_, port)| !port.ends_with(']')) {
+        None => (authority, port),
+        Some((host, value)) => match value.parse::<u16>() {
+            Err(error) => return linky_fail!(uid, "url:{} error:{}", url, error),
+            Ok(port) => (host, port),
+        },
+    };
+
+    if host.is_empty() {
+        return linky_fail!(uid, "url:{} host missing", url);
+    }
+    Ok((host.to_string(), port, path))
+}
+
+// HTTP bridge exposing latest frame, fetched on each poll tick (no fd to wait on)
+pub struct HttpHandle {
+    pub(crate) url: &'static str,
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) path: String,
+    pub(crate) pending: RefCell<Vec<u8>>,
+}
+
+impl HttpHandle {
+    #[track_caller]
+    pub fn new(url: &'static str) -> Result<HttpHandle, LinkyFail> {
+        let (host, port, path) = url_split("http-invalid-url", url, "http", 80)?;
+
+        Ok(HttpHandle {
+            url,
+            host,
+            port,
+            path,
+            pending: RefCell::new(Vec::new()),
+        })
+    }
+
+    // HTTP/1.0 GET prevents chunked encoding, body ends with connection
+    pub fn fetch(&self) -> Result<Vec<u8>, LinkyFail> {
+        let mut stream = tcp_connect(&self.host, self.port)?;
+        let request = format!(
+            "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            self.path, self.host
+        );
+
+        let mut response = Vec::new();
+        let status = stream
+            .set_read_timeout(Some(Duration::from_secs(TCP_CONNECT_TIMEOUT)))
+            .and_then(|_| stream.write_all(request.as_bytes()))
+            .and_then(|_| stream.read_to_end(&mut response));
+
+        if let Err(error) = status {
+            return linky_fail!("http-fetch-fail", "url:{} error:{}", self.url, error);
+        }
+
+        let header = match response.windows(4).position(|bytes| bytes == b"\r\n\r\n") {
+            None => return linky_fail!("http-fetch-fail", "url:{} invalid response", self.url),
+            Some(value) => value,
+        };
+
+        let status = String::from_utf8_lossy(&response[0..header]);
+        match status.split_whitespace().nth(1) {
+            Some("200") => Ok(response.split_off(header + 4)),
+            _ => linky_fail!(
+                "http-fetch-fail",
+                "url:{} status:{}",
+                self.url,
+                status.lines().next().unwrap_or("")
+            ),
+        }
+    }
+}
+
+impl SourceHandle for HttpHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        Ok(())
+    }
+
+    fn close(&self) {
+        self.pending.borrow_mut().clear();
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut pending = self.pending.borrow_mut();
+
+        if pending.is_empty() {
+            match self.fetch() {
+                Err(error) => return Err(LinkyError::SerialError(error.to_string().into())),
+                Ok(body) if body.is_empty() => return Err(LinkyError::RetryLater),
+                Ok(body) => *pending = body,
+            }
+        }
+
+        let count = usize::min(buffer.len(), pending.len());
+        buffer[0..count].copy_from_slice(&pending[0..count]);
+        pending.drain(0..count);
+        Ok(count)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.pending.borrow().is_empty()
+    }
+}
+
+const UDP_FRAGMENT_TIMEOUT: Duration = Duration::from_millis(1000);
+// UDP gateway stream, optional multicast group lets many consumers share one gateway.
+// Gateways may split a line across datagrams, only complete lines are returned and the
+// trailing fragment waits for its tail from the same peer.
+pub struct NetworkHandle {
+    pub(crate) addr: &'static str,
+    pub(crate) port: u16,
+    pub(crate) group: Option<IpAddr>,
+    pub(crate) iface: Ipv4Addr,
+    pub(crate) socket: RefCell<Option<UdpSocket>>,
+    pub(crate) fragment: RefCell<Vec<u8>>,
+    pub(crate) peer: Cell<Option<(SocketAddr, Instant)>>, // last datagram sender
+    #[cfg(feature = "dtls")]
+    pub(crate) dtls: Option<DtlsServer>, // DTLS records only
+}
+
+impl NetworkHandle {
+    #[track_caller]
+    pub fn new(
+        addr: &'static str,
+        port: u16,
+        group: Option<&str>,
+        iface: Option<&str>,
+    ) -> Result<NetworkHandle, LinkyFail> {
+        let group = match group {
+            None => None,
+            Some(value) => match value.parse::<IpAddr>() {
+                Ok(group) if group.is_multicast() => Some(group),
+                _ => {
+                    return linky_fail!(
+                        "udp-invalid-group",
+                        "group:{} not a multicast address",
+                        value,
+                    )
+                }
+            },
+        };
+
+        let iface = match iface {
+            None => Ipv4Addr::UNSPECIFIED,
+            Some(value) => match value.parse::<Ipv4Addr>() {
+                Ok(iface) => iface,
+                Err(error) => {
+                    return linky_fail!("udp-invalid-iface", "iface:{} error:{}", value, error)
+                }
+            },
+        };
+
+        let handle = NetworkHandle {
+            addr,
+            port,
+            group,
+            iface,
+            socket: RefCell::new(None),
+            fragment: RefCell::new(Vec::new()),
+            peer: Cell::new(None),
+            #[cfg(feature = "dtls")]
+            dtls: None,
+        };
+
+        handle.open()?;
+        Ok(handle)
+    }
+
+    // accept DTLS sessions using this pre-shared key only, DTLS is unicast
+    #[cfg(feature = "dtls")]
+    pub fn set_dtls(&mut self, identity: &str, key: &str) -> Result<&mut Self, LinkyFail> {
+        if let Some(group) = self.group {
+            return linky_fail!("udp-dtls-fail", "group:{} dtls requires unicast", group);
+        }
+        self.dtls = Some(DtlsServer::new(&DtlsPsk::new(identity, key)?)?);
+        Ok(self)
+    }
+
+    #[cfg(not(feature = "dtls"))]
+    pub fn set_dtls(&mut self, identity: &str, _key: &str) -> Result<&mut Self, LinkyFail> {
+        linky_fail!("dtls-not-built", "identity:{} requires 'dtls' feature", identity)
+    }
+
+    // plain datagram or decrypted DTLS record
+    fn recv_from(
+        &self,
+        socket: &UdpSocket,
+        buffer: &mut [u8],
+    ) -> Result<(usize, SocketAddr), LinkyError> {
+        #[cfg(feature = "dtls")]
+        if let Some(dtls) = &self.dtls {
+            return dtls.recv_from(socket, buffer);
+        }
+        match socket.recv_from(buffer) {
+            Ok(value) => Ok(value),
+            Err(error) => match error.kind() {
+                ErrorKind::Interrupted | ErrorKind::WouldBlock => Err(LinkyError::RetryLater),
+                _ => Err(LinkyError::SerialError(error.to_string().into())),
+            },
+        }
+    }
+}
+
+impl SourceHandle for NetworkHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        let socket = match UdpSocket::bind((self.addr, self.port)) {
+            Err(error) => {
+                return linky_fail!(
+                    "udp-bind-fail",
+                    "addr:{} port:{} error:{}",
+                    self.addr,
+                    self.port,
+                    error,
+                )
+            }
+            Ok(value) => value,
+        };
+
+        // IP_ADD_MEMBERSHIP / IPV6_ADD_MEMBERSHIP
+        let status = match self.group {
+            None => Ok(()),
+            Some(IpAddr::V4(group)) => socket.join_multicast_v4(&group, &self.iface),
+            Some(IpAddr::V6(group)) => socket.join_multicast_v6(&group, 0),
+        };
+
+        if let Err(error) = status.and_then(|_| socket.set_nonblocking(true)) {
+            return linky_fail!("udp-group-fail", "group:{:?} error:{}", self.group, error);
+        }
+
+        self.socket.replace(Some(socket));
+        Ok(())
+    }
+
+    fn close(&self) {
+        // dropping socket leaves multicast group
+        self.socket.replace(None);
+        self.fragment.borrow_mut().clear();
+        self.peer.set(None);
+        #[cfg(feature = "dtls")]
+        if let Some(dtls) = &self.dtls {
+            dtls.clear();
+        }
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let socket = self.socket.borrow();
+        let socket = match socket.as_ref() {
+            None => return Err(LinkyError::ReopenDev),
+            Some(value) => value,
+        };
+
+        // kernel silently truncates datagrams, a full buffer means payload did not fit
+        let mut fragment = self.fragment.borrow_mut();
+        // spoofed or replayed DTLS records are dropped before touching fragment or peer
+        let (mut count, peer) = match self.recv_from(socket, buffer) {
+            Ok((count, _)) if count == buffer.len() => {
+                fragment.clear();
+                return Err(LinkyError::TooLong(format!("datagram >= {} bytes", count).into()));
+            }
+            Ok(value) => value,
+            Err(error) => return Err(error),
+        };
+
+        // a fragment from another peer or left by a lost datagram would corrupt the line
+        let resume = match self.peer.replace(Some((peer, Instant::now()))) {
+            Some((addr, stamp)) => addr == peer && stamp.elapsed() < UDP_FRAGMENT_TIMEOUT,
+            None => false,
+        };
+        if resume && !fragment.is_empty() && fragment.len() + count < buffer.len() {
+            buffer.copy_within(0..count, fragment.len());
+            buffer[0..fragment.len()].copy_from_slice(&fragment);
+            count += fragment.len();
+        }
+        fragment.clear();
+
+        let end = match buffer[0..count]
+            .iter()
+            .rposition(|byte| *byte == b'\n' || *byte == RING_ETX)
+        {
+            Some(eol) => eol + 1,
+            None => 0,
+        };
+        fragment.extend_from_slice(&buffer[end..count]);
+        if end == 0 {
+            return Err(LinkyError::RetryLater);
+        }
+        Ok(end)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        match self.socket.borrow().as_ref() {
+            None => -1,
+            Some(socket) => socket.as_raw_fd(),
+        }
+    }
+
+    fn get_peer(&self) -> Option<SocketAddr> {
+        self.peer.get().map(|(addr, _)| addr)
+    }
+}
@@ -0,0 +1,113 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+// raw source bytes written as replay capture, each '\n' terminated record is prefixed with
+// its epoch ms '1699610899123\t'. STX/ETX and checksums are kept untouched.
+pub struct FrameRecorder {
+    path: String,
+    file: Option<File>,
+    newline: bool,
+    lines: u64,
+    bytes: u64,
+}
+
+impl FrameRecorder {
+    pub fn new() -> FrameRecorder {
+        FrameRecorder {
+            path: String::new(),
+            file: None,
+            newline: true,
+            lines: 0,
+            bytes: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    // records written since start
+    pub fn get_lines(&self) -> u64 {
+        self.lines
+    }
+
+    pub fn get_bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    // existing capture is truncated
+    pub fn start(&mut self, path: &str) -> Result<(), LinkyFail> {
+        if self.is_active() {
+            return linky_fail!("linky-record-busy", "already recording to {}", self.path);
+        }
+        let file = match OpenOptions::new().create(true).write(true).truncate(true).open(path) {
+            Ok(value) => value,
+            Err(error) => return linky_fail!("linky-record-fail", "path:{} error:{}", path, error),
+        };
+        self.path = path.to_string();
+        self.file = Some(file);
+        self.newline = true;
+        self.lines = 0;
+        self.bytes = 0;
+        Ok(())
+    }
+
+    // at is epoch ms, file is closed on write error
+    pub fn write(&mut self, at: u64, data: &[u8]) -> Result<(), LinkyFail> {
+        let file = match &mut self.file {
+            None => return Ok(()),
+            Some(value) => value,
+        };
+
+        let mut record = Vec::with_capacity(data.len() + 16);
+        for byte in data {
+            if self.newline {
+                record.extend_from_slice(format!("{}\t", at).as_bytes());
+                self.lines += 1;
+            }
+            record.push(*byte);
+            self.newline = *byte == b'\n';
+        }
+
+        if let Err(error) = file.write_all(&record) {
+            self.file = None;
+            return linky_fail!("linky-record-fail", "path:{} error:{}", self.path, error);
+        }
+        self.bytes += record.len() as u64;
+        Ok(())
+    }
+
+    // return false when no capture was running
+    pub fn stop(&mut self) -> bool {
+        match self.file.take() {
+            None => false,
+            Some(mut file) => {
+                let _ = file.flush();
+                true
+            }
+        }
+    }
+}
+
+impl Default for FrameRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
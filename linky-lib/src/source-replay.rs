@@ -0,0 +1,214 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "compress")]
+use flate2::bufread::MultiGzDecoder;
+
+use crate::prelude::*;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+// one capture record, stamp in ms when line was prefixed with '<ms>\t'
+struct ReplayRecord {
+    stamp: Option<u64>,
+    data: Vec<u8>,
+}
+
+struct ReplayState {
+    reader: Option<Box<dyn BufRead + Send>>,
+    next: Option<ReplayRecord>,
+    origin: Option<(u64, Instant)>,
+    ended: bool,
+}
+
+// replay a TIC capture from plain, gzip or zstd file. Records prefixed with a millisecond
+// timestamp '1699610899123\tSINSTS\t00022\tJ\r\n' are paced, raw lines replay immediately.
+pub struct ReplayHandle {
+    pub(crate) path: &'static str,
+    pub(crate) speed: u32, // time multiplier, 0=as fast as possible
+    pub(crate) repeat: bool,
+    state: RefCell<ReplayState>,
+}
+
+impl ReplayHandle {
+    #[track_caller]
+    pub fn new(path: &'static str, speed: u32, repeat: bool) -> Result<ReplayHandle, LinkyFail> {
+        let handle = ReplayHandle {
+            path,
+            speed,
+            repeat,
+            state: RefCell::new(ReplayState {
+                reader: None,
+                next: None,
+                origin: None,
+                ended: false,
+            }),
+        };
+        handle.open()?;
+        Ok(handle)
+    }
+
+    // compression is detected from file magic, not from its extension
+    fn open_reader(path: &str) -> Result<Box<dyn BufRead + Send>, LinkyFail> {
+        let file = match File::open(path) {
+            Err(error) => return linky_fail!("replay-open-fail", "path:{} error:{}", path, error),
+            Ok(value) => value,
+        };
+
+        let mut reader = BufReader::new(file);
+        let magic = match reader.fill_buf() {
+            Err(error) => return linky_fail!("replay-read-fail", "path:{} error:{}", path, error),
+            Ok(value) => value,
+        };
+
+        let reader: Box<dyn BufRead + Send> =
+            if magic.starts_with(GZIP_MAGIC) || magic.starts_with(ZSTD_MAGIC) {
+                Self::open_decoder(path, reader)?
+            } else {
+                Box::new(reader)
+            };
+        Ok(reader)
+    }
+
+    #[cfg(feature = "compress")]
+    fn open_decoder(
+        path: &str,
+        reader: BufReader<File>,
+    ) -> Result<Box<dyn BufRead + Send>, LinkyFail> {
+        if reader.buffer().starts_with(GZIP_MAGIC) {
+            return Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))));
+        }
+        match zstd::stream::read::Decoder::with_buffer(reader) {
+            Err(error) => linky_fail!("replay-zstd-fail", "path:{} error:{}", path, error),
+            Ok(value) => Ok(Box::new(BufReader::new(value))),
+        }
+    }
+
+    #[cfg(not(feature = "compress"))]
+    fn open_decoder(
+        path: &str,
+        _reader: BufReader<File>,
+    ) -> Result<Box<dyn BufRead + Send>, LinkyFail> {
+        linky_fail!(
+            "replay-compress-fail",
+            "path:{} compressed capture requires 'compress' feature",
+            path
+        )
+    }
+
+    fn parse_record(mut data: Vec<u8>) -> ReplayRecord {
+        let digits = data.iter().take_while(|byte| byte.is_ascii_digit()).count();
+        if digits == 0 || data.get(digits) != Some(&b'\t') {
+            return ReplayRecord { stamp: None, data };
+        }
+
+        let stamp = std::str::from_utf8(&data[0..digits])
+            .ok()
+            .and_then(|value| value.parse::<u64>().ok());
+        data.drain(0..digits + 1);
+        ReplayRecord { stamp, data }
+    }
+
+    // load next record when needed, return true when it should be delivered now
+    fn is_due(&self, state: &mut ReplayState) -> Result<bool, LinkyError> {
+        if state.next.is_none() {
+            let reader = match state.reader.as_mut() {
+                None => return Err(LinkyError::ReopenDev),
+                Some(value) => value,
+            };
+
+            let mut data = Vec::new();
+            match reader.read_until(b'\n', &mut data) {
+                Err(error) => return Err(LinkyError::SerialError(error.to_string().into())),
+                Ok(0) => {
+                    if !state.ended {
+                        linky_log!(Notice, "replay:{} end of capture", self.path);
+                    }
+                    state.ended = true;
+                    return Ok(false);
+                }
+                Ok(_) => state.next = Some(ReplayHandle::parse_record(data)),
+            }
+        }
+
+        let stamp = match state.next.as_ref().and_then(|record| record.stamp) {
+            Some(value) if self.speed > 0 => value,
+            _ => return Ok(true),
+        };
+
+        // first timestamp anchors capture time on local clock
+        let (first, start) = *state.origin.get_or_insert((stamp, Instant::now()));
+        let delay = Duration::from_millis(stamp.saturating_sub(first) / self.speed as u64);
+        Ok(start.elapsed() >= delay)
+    }
+}
+
+impl SourceHandle for ReplayHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        let reader = ReplayHandle::open_reader(self.path)?;
+        let mut state = self.state.borrow_mut();
+        state.reader = Some(reader);
+        state.next = None;
+        state.origin = None;
+        state.ended = false;
+        Ok(())
+    }
+
+    fn close(&self) {
+        let mut state = self.state.borrow_mut();
+        state.reader = None;
+        state.next = None;
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut state = self.state.borrow_mut();
+
+        if !self.is_due(&mut state)? {
+            if state.ended && self.repeat {
+                drop(state);
+                if let Err(error) = self.open() {
+                    return Err(LinkyError::SerialError(error.to_string().into()));
+                }
+            }
+            return Err(LinkyError::RetryLater);
+        }
+
+        let record = match state.next.as_mut() {
+            None => return Err(LinkyError::RetryLater),
+            Some(value) => value,
+        };
+
+        let count = usize::min(buffer.len(), record.data.len());
+        buffer[0..count].copy_from_slice(&record.data[0..count]);
+        record.data.drain(0..count);
+        if record.data.is_empty() {
+            state.next = None;
+        }
+        Ok(count)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+
+    fn has_pending(&self) -> bool {
+        let mut state = self.state.borrow_mut();
+        matches!(self.is_due(&mut state), Ok(true))
+    }
+}
@@ -0,0 +1,191 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::network::{tcp_connect, TCP_CONNECT_TIMEOUT};
+use crate::prelude::*;
+
+fn tls_load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, LinkyFail> {
+    let file = match File::open(path) {
+        Err(error) => return linky_fail!("tls-cert-fail", "path:{} error:{}", path, error),
+        Ok(value) => value,
+    };
+
+    let mut certs = Vec::new();
+    for cert in rustls_pemfile::certs(&mut BufReader::new(file)) {
+        match cert {
+            Err(error) => return linky_fail!("tls-cert-fail", "path:{} error:{}", path, error),
+            Ok(value) => certs.push(value),
+        }
+    }
+
+    if certs.is_empty() {
+        return linky_fail!("tls-cert-fail", "path:{} no PEM certificate found", path);
+    }
+    Ok(certs)
+}
+
+fn tls_load_key(path: &str) -> Result<PrivateKeyDer<'static>, LinkyFail> {
+    let file = match File::open(path) {
+        Err(error) => return linky_fail!("tls-key-fail", "path:{} error:{}", path, error),
+        Ok(value) => value,
+    };
+
+    match rustls_pemfile::private_key(&mut BufReader::new(file)) {
+        Err(error) => linky_fail!("tls-key-fail", "path:{} error:{}", path, error),
+        Ok(None) => linky_fail!("tls-key-fail", "path:{} no PEM private key found", path),
+        Ok(Some(key)) => Ok(key),
+    }
+}
+
+// TLS over TCP for gateways crossing untrusted networks. Server is verified against 'ca',
+// optional 'cert'+'key' are presented when gateway requests client authentication.
+pub struct TlsHandle {
+    pub(crate) host: &'static str,
+    pub(crate) port: u16,
+    pub(crate) config: Arc<ClientConfig>,
+    pub(crate) stream: RefCell<Option<StreamOwned<ClientConnection, TcpStream>>>,
+}
+
+impl TlsHandle {
+    #[track_caller]
+    pub fn new(
+        host: &'static str,
+        port: u16,
+        ca: &str,
+        cert: Option<&str>,
+        key: Option<&str>,
+    ) -> Result<TlsHandle, LinkyFail> {
+        let mut roots = RootCertStore::empty();
+        for cert in tls_load_certs(ca)? {
+            if let Err(error) = roots.add(cert) {
+                return linky_fail!("tls-ca-fail", "path:{} error:{}", ca, error);
+            }
+        }
+
+        let builder = match ClientConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        {
+            Err(error) => return linky_fail!("tls-config-fail", error.to_string()),
+            Ok(value) => value.with_root_certificates(roots),
+        };
+
+        let config = match (cert, key) {
+            (Some(cert), Some(key)) => {
+                match builder.with_client_auth_cert(tls_load_certs(cert)?, tls_load_key(key)?) {
+                    Err(error) => return linky_fail!("tls-config-fail", error.to_string()),
+                    Ok(value) => value,
+                }
+            }
+            (None, None) => builder.with_no_client_auth(),
+            _ => return linky_fail!("tls-config-fail", "client 'cert' and 'key' go together"),
+        };
+
+        let handle = TlsHandle {
+            host,
+            port,
+            config: Arc::new(config),
+            stream: RefCell::new(None),
+        };
+
+        handle.open()?;
+        Ok(handle)
+    }
+}
+
+impl SourceHandle for TlsHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        let name = match ServerName::try_from(self.host) {
+            Err(error) => {
+                return linky_fail!("tls-invalid-host", "host:{} error:{}", self.host, error)
+            }
+            Ok(value) => value,
+        };
+
+        let connection = match ClientConnection::new(self.config.clone(), name) {
+            Err(error) => return linky_fail!("tls-connect-fail", error.to_string()),
+            Ok(value) => value,
+        };
+
+        // handshake is blocking, then socket switches to non blocking for poll driven reads
+        let socket = tcp_connect(self.host, self.port)?;
+        let timeout = Some(Duration::from_secs(TCP_CONNECT_TIMEOUT));
+        let mut stream = StreamOwned::new(connection, socket);
+        let status = stream
+            .sock
+            .set_read_timeout(timeout)
+            .and_then(|_| {
+                while stream.conn.is_handshaking() {
+                    stream.conn.complete_io(&mut stream.sock)?;
+                }
+                Ok(())
+            })
+            .and_then(|_| stream.sock.set_nonblocking(true));
+
+        if let Err(error) = status {
+            return linky_fail!("tls-handshake-fail", "host:{} error:{}", self.host, error);
+        }
+
+        self.stream.replace(Some(stream));
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Some(mut stream) = self.stream.take() {
+            stream.conn.send_close_notify();
+            let _ = stream.conn.complete_io(&mut stream.sock);
+        }
+    }
+
+    // a single TLS record may hold more than one line, leftover plaintext is
+    // delivered on next socket activity
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut stream = self.stream.borrow_mut();
+        let stream = match stream.as_mut() {
+            None => return Err(LinkyError::ReopenDev),
+            Some(value) => value,
+        };
+
+        match stream.read(buffer) {
+            Ok(0) => Err(LinkyError::ReopenDev), // close_notify received
+            Ok(count) => Ok(count),
+            Err(error) => match error.kind() {
+                ErrorKind::Interrupted | ErrorKind::WouldBlock => Err(LinkyError::RetryLater),
+                _ => Err(LinkyError::ReopenDev),
+            },
+        }
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        match self.stream.borrow().as_ref() {
+            None => -1,
+            Some(stream) => stream.sock.as_raw_fd(),
+        }
+    }
+
+    fn get_peer(&self) -> Option<SocketAddr> {
+        self.stream.borrow().as_ref()?.sock.peer_addr().ok()
+    }
+}
@@ -0,0 +1,120 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use ::std::os::raw;
+use std::cell::RefCell;
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+use tungstenite::{Message, WebSocket};
+
+use crate::network::{tcp_connect, url_split, TCP_CONNECT_TIMEOUT};
+use crate::prelude::*;
+
+// WebSocket client, text or binary frames carry one or more TIC lines
+pub struct WsHandle {
+    pub(crate) url: &'static str,
+    pub(crate) socket: RefCell<Option<WebSocket<TcpStream>>>,
+    pub(crate) pending: RefCell<Vec<u8>>,
+}
+
+impl WsHandle {
+    #[track_caller]
+    pub fn new(url: &'static str) -> Result<WsHandle, LinkyFail> {
+        let handle = WsHandle {
+            url,
+            socket: RefCell::new(None),
+            pending: RefCell::new(Vec::new()),
+        };
+
+        handle.open()?;
+        Ok(handle)
+    }
+}
+
+impl SourceHandle for WsHandle {
+    fn open(&self) -> Result<(), LinkyFail> {
+        let (host, port, _path) = url_split("ws-invalid-url", self.url, "ws", 80)?;
+        let stream = tcp_connect(&host, port)?;
+        if let Err(error) = stream.set_read_timeout(Some(Duration::from_secs(TCP_CONNECT_TIMEOUT))) {
+            return linky_fail!("ws-connect-fail", "url:{} error:{}", self.url, error);
+        }
+
+        // upgrade is blocking, then socket switches to non blocking for poll driven reads
+        let socket = match tungstenite::client::client(self.url, stream) {
+            Err(error) => {
+                return linky_fail!("ws-handshake-fail", "url:{} error:{}", self.url, error)
+            }
+            Ok((socket, _response)) => socket,
+        };
+
+        if let Err(error) = socket.get_ref().set_nonblocking(true) {
+            return linky_fail!("ws-connect-fail", "url:{} error:{}", self.url, error);
+        }
+
+        self.pending.borrow_mut().clear();
+        self.socket.replace(Some(socket));
+        Ok(())
+    }
+
+    fn close(&self) {
+        if let Some(mut socket) = self.socket.take() {
+            let _ = socket.close(None);
+            let _ = socket.flush();
+        }
+        self.pending.borrow_mut().clear();
+    }
+
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut pending = self.pending.borrow_mut();
+
+        if pending.is_empty() {
+            let mut socket = self.socket.borrow_mut();
+            let socket = match socket.as_mut() {
+                None => return Err(LinkyError::ReopenDev),
+                Some(value) => value,
+            };
+
+            match socket.read() {
+                Ok(Message::Text(text)) => pending.extend_from_slice(text.as_bytes()),
+                Ok(Message::Binary(data)) => pending.extend_from_slice(&data),
+                Ok(Message::Close(_)) => return Err(LinkyError::ReopenDev),
+                Ok(_) => return Err(LinkyError::RetryLater), // ping/pong
+                Err(tungstenite::Error::Io(error)) => match error.kind() {
+                    ErrorKind::Interrupted | ErrorKind::WouldBlock => {
+                        return Err(LinkyError::RetryLater)
+                    }
+                    _ => return Err(LinkyError::ReopenDev),
+                },
+                Err(_) => return Err(LinkyError::ReopenDev),
+            }
+        }
+
+        let count = usize::min(buffer.len(), pending.len());
+        buffer[0..count].copy_from_slice(&pending[0..count]);
+        pending.drain(0..count);
+        Ok(count)
+    }
+
+    fn get_raw_fd(&self) -> raw::c_int {
+        match self.socket.borrow().as_ref() {
+            None => -1,
+            Some(socket) => socket.get_ref().as_raw_fd(),
+        }
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.pending.borrow().is_empty()
+    }
+}
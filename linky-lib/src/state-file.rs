@@ -0,0 +1,73 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+// derived counters restored at startup, so daily figures survive binder restarts
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LinkyState {
+    pub periods: Vec<PeriodState>,
+    pub tariffs: Option<TariffState>,
+    pub cost: Option<CostState>,
+}
+
+// json state file, written aside then renamed so a crash never leaves it truncated
+pub struct StateFile {
+    path: String,
+}
+
+impl StateFile {
+    pub fn new(path: &str) -> StateFile {
+        StateFile {
+            path: path.to_string(),
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        self.path.as_str()
+    }
+
+    // none on first start
+    pub fn load(&self) -> Result<Option<LinkyState>, LinkyFail> {
+        let text = match fs::read_to_string(&self.path) {
+            Ok(value) => value,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return linky_fail!("linky-state-fail", "path:{} error:{}", self.path, error)
+            }
+        };
+        match serde_json::from_str::<LinkyState>(&text) {
+            Ok(state) => Ok(Some(state)),
+            Err(error) => linky_fail!("linky-state-fail", "path:{} error:{}", self.path, error),
+        }
+    }
+
+    pub fn save(&self, state: &LinkyState) -> Result<(), LinkyFail> {
+        let text = match serde_json::to_string(state) {
+            Ok(value) => value,
+            Err(error) => {
+                return linky_fail!("linky-state-fail", "path:{} error:{}", self.path, error)
+            }
+        };
+        let temp = format!("{}.tmp", self.path);
+        if let Err(error) = fs::write(&temp, text) {
+            return linky_fail!("linky-state-fail", "path:{} error:{}", temp, error);
+        }
+        match fs::rename(&temp, &self.path) {
+            Ok(()) => Ok(()),
+            Err(error) => linky_fail!("linky-state-fail", "path:{} error:{}", self.path, error),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use crate::prelude::*;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+
+const PURGE_PERIOD: u64 = 3600 * 1000; // ms between two retention purges
+
+fn store_check<T>(result: rusqlite::Result<T>) -> Result<T, LinkyFail> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(error) => linky_fail!("linky-store-fail", "sqlite error:{}", error),
+    }
+}
+
+// sensor samples persisted in sqlite as one row per value index. Downsampling keeps
+// the first sample of each interval per sensor, rows older than retention are purged.
+pub struct SampleStore {
+    conn: Connection,
+    uids: Vec<String>, // empty: every sensor
+    retention: u64,    // ms, 0: keep forever
+    interval: u64,     // ms, 0: every update
+    stored: HashMap<String, u64>,
+    purged: u64,
+}
+
+impl SampleStore {
+    // path ':memory:' opens a transient database
+    pub fn open(
+        path: &str,
+        retention_s: u64,
+        interval_s: u64,
+        uids: Vec<String>,
+    ) -> Result<SampleStore, LinkyFail> {
+        let conn = match Connection::open(path) {
+            Ok(value) => value,
+            Err(error) => return linky_fail!("linky-store-fail", "path:{} error:{}", path, error),
+        };
+        store_check(conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS samples (
+                uid TEXT NOT NULL,
+                ts INTEGER NOT NULL,
+                idx INTEGER NOT NULL,
+                value INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS samples_uid_ts ON samples (uid, ts);",
+        ))?;
+
+        Ok(SampleStore {
+            conn,
+            uids,
+            retention: retention_s * 1000,
+            interval: interval_s * 1000,
+            stored: HashMap::new(),
+            purged: 0,
+        })
+    }
+
+    pub fn is_selected(&self, uid: &str) -> bool {
+        self.uids.is_empty() || self.uids.iter().any(|value| value == uid)
+    }
+
+    pub fn get_uids(&self) -> &[String] {
+        &self.uids
+    }
+
+    // at is epoch ms, return false when sample was dropped by selection or downsampling
+    pub fn insert(&mut self, uid: &str, at: u64, values: &[i64]) -> Result<bool, LinkyFail> {
+        if !self.is_selected(uid) {
+            return Ok(false);
+        }
+        if let Some(stored) = self.stored.get(uid) {
+            if at < stored + self.interval {
+                return Ok(false);
+            }
+        }
+
+        let transaction = store_check(self.conn.transaction())?;
+        for (idx, value) in values.iter().enumerate() {
+            store_check(transaction.execute(
+                "INSERT INTO samples (uid, ts, idx, value) VALUES (?1, ?2, ?3, ?4)",
+                params![uid, at as i64, idx as i64, value],
+            ))?;
+        }
+        store_check(transaction.commit())?;
+        self.stored.insert(uid.to_string(), at);
+
+        if at >= self.purged + PURGE_PERIOD {
+            self.purge(at)?;
+        }
+        Ok(true)
+    }
+
+    // drop samples older than retention, return deleted rows
+    pub fn purge(&mut self, at: u64) -> Result<usize, LinkyFail> {
+        self.purged = at;
+        if self.retention == 0 {
+            return Ok(0);
+        }
+        let limit = at.saturating_sub(self.retention) as i64;
+        store_check(self.conn.execute("DELETE FROM samples WHERE ts < ?1", params![limit]))
+    }
+
+    // samples within [from, to] epoch ms, oldest first, at most count timestamps
+    pub fn query(
+        &self,
+        uid: &str,
+        from: u64,
+        to: u64,
+        count: usize,
+    ) -> Result<Vec<(u64, Vec<i64>)>, LinkyFail> {
+        let mut statement = store_check(self.conn.prepare(
+            "SELECT ts, idx, value FROM samples WHERE uid = ?1 AND ts >= ?2 AND ts <= ?3
+             ORDER BY ts, idx",
+        ))?;
+        let rows = store_check(statement.query_map(
+            params![uid, from as i64, to.min(i64::MAX as u64) as i64],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)),
+        ))?;
+
+        let mut samples: Vec<(u64, Vec<i64>)> = Vec::new();
+        for row in rows {
+            let (ts, idx, value) = store_check(row)?;
+            let ts = ts as u64;
+            let idx = idx as usize;
+            match samples.last_mut() {
+                Some((stamp, values)) if *stamp == ts => {
+                    if values.len() <= idx {
+                        values.resize(idx + 1, 0);
+                    }
+                    values[idx] = value;
+                }
+                _ => {
+                    if samples.len() >= count {
+                        break;
+                    }
+                    let mut values = vec![0; idx + 1];
+                    values[idx] = value;
+                    samples.push((ts, values));
+                }
+            }
+        }
+        Ok(samples)
+    }
+}
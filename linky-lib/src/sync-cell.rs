@@ -0,0 +1,83 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+use std::sync::{Mutex, MutexGuard};
+
+// a panic within one callback must not lock sensors for the other binder threads
+fn sync_lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// Cell counterpart shareable between binder threads, value is copied in and out
+#[derive(Debug, Default)]
+pub struct SyncCell<T: Copy> {
+    value: Mutex<T>,
+}
+
+impl<T: Copy> SyncCell<T> {
+    pub const fn new(value: T) -> SyncCell<T> {
+        SyncCell {
+            value: Mutex::new(value),
+        }
+    }
+
+    pub fn get(&self) -> T {
+        *sync_lock(&self.value)
+    }
+
+    pub fn set(&self, value: T) {
+        *sync_lock(&self.value) = value;
+    }
+
+    pub fn replace(&self, value: T) -> T {
+        std::mem::replace(&mut *sync_lock(&self.value), value)
+    }
+}
+
+impl<T: Copy + Default> SyncCell<T> {
+    pub fn take(&self) -> T {
+        self.replace(T::default())
+    }
+}
+
+// RefCell counterpart. Lock is exclusive even for reading, a guard must be dropped
+// before calling anything that locks the same value again.
+#[derive(Debug, Default)]
+pub struct SyncLock<T> {
+    value: Mutex<T>,
+}
+
+impl<T> SyncLock<T> {
+    pub const fn new(value: T) -> SyncLock<T> {
+        SyncLock {
+            value: Mutex::new(value),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        sync_lock(&self.value)
+    }
+
+    pub fn replace(&self, value: T) -> T {
+        std::mem::replace(&mut *self.lock(), value)
+    }
+
+    pub fn into_inner(self) -> T {
+        match self.value.into_inner() {
+            Ok(value) => value,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
@@ -0,0 +1,123 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VoltageKind {
+    SAG,
+    SWELL,
+}
+
+// excursion reported once it lasted the minimal duration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoltageExcursion {
+    pub kind: VoltageKind,
+    pub phase: usize, // 1-3
+    pub volts: i32,
+}
+
+#[derive(Clone, Copy)]
+struct PhaseState {
+    kind: VoltageKind,
+    since: f64,
+    reported: bool,
+}
+
+// track URMS1-3 against low/high thresholds (V), excursions shorter than duration (s)
+// are considered as noise and neither counted nor reported
+pub struct VoltageMonitor {
+    low: i32,
+    high: i32,
+    duration: f64,
+    states: [Option<PhaseState>; 3],
+    sags: [u32; 3],
+    swells: [u32; 3],
+}
+
+impl VoltageMonitor {
+    pub fn new(low: i32, high: i32, duration: f64) -> VoltageMonitor {
+        VoltageMonitor {
+            low,
+            high,
+            duration,
+            states: [None; 3],
+            sags: [0; 3],
+            swells: [0; 3],
+        }
+    }
+
+    pub fn get_limits(&self) -> (i32, i32) {
+        (self.low, self.high)
+    }
+
+    // phase 1-3, at is sample time in seconds (any origin)
+    pub fn push(&mut self, phase: usize, at: f64, volts: i32) -> Option<VoltageExcursion> {
+        if !(1..=3).contains(&phase) {
+            return None;
+        }
+        let idx = phase - 1;
+        let kind = if volts < self.low {
+            VoltageKind::SAG
+        } else if volts > self.high {
+            VoltageKind::SWELL
+        } else {
+            self.states[idx] = None;
+            return None;
+        };
+
+        let state = match self.states[idx] {
+            Some(state) if state.kind == kind => state,
+            _ => PhaseState {
+                kind,
+                since: at,
+                reported: false,
+            },
+        };
+        self.states[idx] = Some(state);
+        if state.reported || at - state.since < self.duration {
+            return None;
+        }
+
+        match kind {
+            VoltageKind::SAG => self.sags[idx] += 1,
+            VoltageKind::SWELL => self.swells[idx] += 1,
+        }
+        self.states[idx] = Some(PhaseState {
+            reported: true,
+            ..state
+        });
+        Some(VoltageExcursion { kind, phase, volts })
+    }
+
+    // ongoing excursion per phase
+    pub fn get_state(&self, phase: usize) -> Option<VoltageKind> {
+        let state = (*self.states.get(phase.checked_sub(1)?)?)?;
+        if state.reported {
+            Some(state.kind)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_sags(&self) -> &[u32; 3] {
+        &self.sags
+    }
+
+    pub fn get_swells(&self) -> &[u32; 3] {
+        &self.swells
+    }
+
+    pub fn reset(&mut self) {
+        self.states = [None; 3];
+        self.sags = [0; 3];
+        self.swells = [0; 3];
+    }
+}
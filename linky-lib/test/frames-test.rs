@@ -0,0 +1,230 @@
+// golden frames => cargo test --lib golden
+// ----------------------------------------
+// test/frames/*.tic are complete captures STX..ETX, matching *.gold hold the decoded
+// groups. Any grammar change altering a decoded value must update its golden file.
+
+use crate::prelude::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::os::raw;
+
+// counts heap allocations of the calling thread only, tests run in parallel
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, size)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+// replays one capture, as a serial device would
+struct FrameSource {
+    frame: std::sync::Mutex<&'static [u8]>,
+}
+
+impl SourceHandle for FrameSource {
+    fn open(&self) -> Result<(), LinkyFail> {
+        Ok(())
+    }
+    fn close(&self) {}
+    // capture is delivered in chunks as large as reader buffer
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut frame = self.frame.lock().unwrap();
+        if frame.is_empty() {
+            return Err(LinkyError::RetryLater);
+        }
+        let count = usize::min(frame.len(), buffer.len());
+        buffer[0..count].copy_from_slice(&frame[0..count]);
+        *frame = &frame[count..];
+        Ok(count)
+    }
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+}
+
+fn frame_decode(frame: &'static [u8]) -> TicLines {
+    let source = FrameSource {
+        frame: std::sync::Mutex::new(frame),
+    };
+    let handle = LinkyHandle::from_source("/dev/ttyUSB0", "serial".to_string(), Box::new(source));
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    // source is read once per wakeup, loop until capture is drained
+    loop {
+        let count = lines.len();
+        handle.decode_all(&mut buffer, &mut lines).unwrap();
+        if lines.len() == count {
+            break lines;
+        }
+    }
+}
+
+// one 'label value' line per group, error lines keep their code only
+fn frame_golden(lines: &[TicLine]) -> String {
+    let mut golden = String::new();
+    for line in lines {
+        let label = tic_label(&String::from_utf8_lossy(&line.raw)).to_string();
+        match &line.value {
+            Err(LinkyError::RetryLater) => continue,
+            Ok(value) => golden.push_str(&format!("{} {:?}\n", label, value)),
+            Err(error) => golden.push_str(&format!("{} {}\n", label, error.get_code())),
+        }
+    }
+    golden
+}
+
+fn frame_check(frame: &'static [u8], golden: &str, groups: u32) {
+    let lines = frame_decode(frame);
+    assert_eq!(frame_golden(&lines), golden);
+
+    let frames: Vec<&TicFrame> = lines.iter().filter_map(|line| line.frame.as_ref()).collect();
+    assert_eq!(frames.len(), 1);
+    assert!(frames[0].is_complete());
+    assert_eq!(frames[0].groups, groups);
+}
+
+#[test]
+fn golden_standard_mono() {
+    frame_check(
+        include_bytes!("frames/standard-mono.tic"),
+        include_str!("frames/standard-mono.gold"),
+        38,
+    );
+}
+
+#[test]
+fn golden_standard_tri() {
+    frame_check(
+        include_bytes!("frames/standard-tri.tic"),
+        include_str!("frames/standard-tri.gold"),
+        32,
+    );
+}
+
+#[test]
+fn golden_standard_producer() {
+    frame_check(
+        include_bytes!("frames/standard-producer.tic"),
+        include_str!("frames/standard-producer.gold"),
+        25,
+    );
+}
+
+#[test]
+fn golden_historic_mono() {
+    frame_check(
+        include_bytes!("frames/historic-mono.tic"),
+        include_str!("frames/historic-mono.gold"),
+        11,
+    );
+}
+
+#[test]
+fn golden_historic_tri() {
+    frame_check(
+        include_bytes!("frames/historic-tri.tic"),
+        include_str!("frames/historic-tri.gold"),
+        22,
+    );
+}
+
+
+// replays one capture in loop, a read never spans two passes
+struct LoopSource {
+    frame: &'static [u8],
+    offset: std::sync::Mutex<usize>,
+}
+
+impl SourceHandle for LoopSource {
+    fn open(&self) -> Result<(), LinkyFail> {
+        Ok(())
+    }
+    fn close(&self) {}
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        let mut offset = self.offset.lock().unwrap();
+        let count = usize::min(self.frame.len() - *offset, buffer.len());
+        buffer[0..count].copy_from_slice(&self.frame[*offset..*offset + count]);
+        *offset = (*offset + count) % self.frame.len();
+        Ok(count)
+    }
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+}
+
+// decode until a frame is closed, lines are cleared on every wakeup as the binding does
+fn loop_pass(handle: &LinkyHandle, buffer: &mut [u8], lines: &mut TicLines) {
+    loop {
+        lines.clear();
+        handle.decode_all(buffer, lines).unwrap();
+        if let Some(frame) = lines.iter().find_map(|line| line.frame.as_ref()) {
+            assert!(frame.is_complete());
+            return;
+        }
+    }
+}
+
+// once labels, text values, ring and line slots were learned, frames decode without
+// allocating. Ring growth shifts read boundaries during the first passes.
+#[test]
+fn steady_state_alloc() {
+    let captures: [&'static [u8]; 3] = [
+        include_bytes!("frames/standard-mono.tic"),
+        include_bytes!("frames/standard-tri.tic"),
+        include_bytes!("frames/historic-tri.tic"),
+    ];
+    for frame in captures {
+        let source = LoopSource {
+            frame,
+            offset: std::sync::Mutex::new(0),
+        };
+        let handle =
+            LinkyHandle::from_source("/dev/ttyUSB0", "serial".to_string(), Box::new(source));
+        let mut buffer = [0u8; TIC_LINE_MAX];
+        let mut lines = TicLines::new();
+        for _ in 0..3 {
+            loop_pass(&handle, &mut buffer, &mut lines);
+        }
+        let allocations = ALLOCATIONS.with(|count| count.get());
+        for _ in 0..5 {
+            loop_pass(&handle, &mut buffer, &mut lines);
+        }
+        assert_eq!(ALLOCATIONS.with(|count| count.get()), allocations);
+    }
+}
+
+// fuzz/corpus is replayed as regression, same calls as fuzz target 'tic_decode'
+#[test]
+fn fuzz_corpus() {
+    let corpus = concat!(env!("CARGO_MANIFEST_DIR"), "/fuzz/corpus/tic_decode");
+    let mut count = 0;
+    for entry in std::fs::read_dir(corpus).unwrap() {
+        let data = std::fs::read(entry.unwrap().path()).unwrap();
+        for count in [data.len(), data.len().saturating_sub(1), data.len() + 1] {
+            if let Ok(line) = tic_checksum(&data, count) {
+                let _ = tic_from_str(line);
+            }
+        }
+        if let Ok(line) = std::str::from_utf8(&data) {
+            let _ = tic_from_str(line);
+        }
+        count += 1;
+    }
+    assert!(count > 0);
+}
@@ -5,8 +5,23 @@
 // Attention pour simplifier l'écriture des test le séparateur '\i' est remplacé par '|'
 
 use crate::prelude::*;
-use std::cell::Cell;
-use std::ffi::CString;
+use std::os::raw;
+
+// backend agnostic source, parsing tests never touch the device
+struct NullSource;
+
+impl SourceHandle for NullSource {
+    fn open(&self) -> Result<(), LinkyFail> {
+        Ok(())
+    }
+    fn close(&self) {}
+    fn read(&self, _buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        Err(LinkyError::RetryLater)
+    }
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+}
 
 fn parse_test(data: &str) -> Result<TicValue, LinkyError> {
     let text: String = data
@@ -39,6 +54,209 @@ fn parse_meta() {
     // Profil du prochain jour calendrier fournisseur
 }
 
+#[test]
+fn parse_text() {
+    match parse_test("LTARF|    HEURE  CREUSE   |P\r\n").unwrap() {
+        TicValue::LTARF(label) => assert_eq!(label, "HEURE  CREUSE"),
+        _ => panic!("LTARF not decoded"),
+    }
+    match parse_test("NGTF|H PLEINE-CREUSE|Z\r\n").unwrap() {
+        TicValue::NGTF(label) => assert_eq!(label, "H PLEINE-CREUSE"),
+        _ => panic!("NGTF not decoded"),
+    }
+}
+
+#[test]
+fn parse_power() {
+    match parse_test("SMAXSN|H231110083212|07250|Z\r\n").unwrap() {
+        TicValue::SMAXSN(power) => {
+            assert_eq!(power.get_value(), 7250);
+            assert_eq!(power.get_stamp().to_rfc3339(), "2023-11-10T08:32:12+01:00");
+        }
+        _ => panic!("SMAXSN not decoded"),
+    }
+
+    // yesterday and per phase maxima remain ignored
+    assert_eq!(parse_test("SMAXSN-1|H231109183010|09120|Z\r\n").unwrap(), TicValue::UNSET);
+
+    // producer meters report injected maxima for today and yesterday
+    match parse_test("SMAXIN|E240612131502|03120|Z\r\n").unwrap() {
+        TicValue::SMAXIN(power) => assert_eq!(power.get_value(), 3120),
+        _ => panic!("SMAXIN not decoded"),
+    }
+    match parse_test("SMAXIN-1|E240611124010|02870|Z\r\n").unwrap() {
+        TicValue::SMAXIN1(power) => assert_eq!(power.get_value(), 2870),
+        _ => panic!("SMAXIN-1 not decoded"),
+    }
+}
+
+#[test]
+fn parse_register() {
+    let closed = match parse_test("STGE|002A0010|Z\r\n").unwrap() {
+        TicValue::STGE(status) => status,
+        _ => panic!("STGE not decoded"),
+    };
+    let opened = match parse_test("STGE|002A0013|Z\r\n").unwrap() {
+        TicValue::STGE(status) => status,
+        _ => panic!("STGE not decoded"),
+    };
+    assert_eq!(opened.raw, 0x002A0013);
+    assert!(closed.changes(&closed).is_empty());
+    assert_eq!(opened.changes(&closed), vec!["relay_open", "cut"]);
+
+    // supplier index 4 (bits 10-13 = 3), distributor index 2 (bits 14-15 = 1)
+    let indexed = match parse_test("STGE|002A4C10|Z\r\n").unwrap() {
+        TicValue::STGE(status) => status,
+        _ => panic!("STGE not decoded"),
+    };
+    assert_eq!(closed.get_supplier_index(), 1);
+    assert_eq!(indexed.get_supplier_index(), 4);
+    assert_eq!(indexed.get_distributor_index(), 2);
+    assert_eq!(indexed.changes(&closed), vec!["supplier_index", "distributor_index"]);
+}
+
+#[test]
+fn parse_tempo() {
+    // today white (bits 24-25), tomorrow red (bits 26-27)
+    match parse_test("STGE|0E0A0001|Z\r\n").unwrap() {
+        TicValue::STGE(status) => {
+            assert_eq!(status.get_tempo_today(), TempoColour::WHITE);
+            assert_eq!(status.get_tempo_tomorrow(), TempoColour::RED);
+        }
+        _ => panic!("STGE not decoded"),
+    }
+
+    // historic mode
+    assert_eq!(parse_test("DEMAIN|BLEU|Z\r\n").unwrap(), TicValue::DEMAIN(TempoColour::BLUE));
+    assert_eq!(parse_test("DEMAIN|----|Z\r\n").unwrap(), TicValue::DEMAIN(TempoColour::UNKNOWN));
+    match parse_test("PTEC|HPJR|Z\r\n").unwrap() {
+        TicValue::PTEC(period) => assert_eq!(TempoColour::from_period(&period), TempoColour::RED),
+        _ => panic!("PTEC not decoded"),
+    }
+}
+
+#[test]
+fn parse_phase_presence() {
+    let all = PhasePresence::from_bits(0);
+    // phase 2 potential missing (bit 2)
+    match parse_test("PPOT|04|Z\r\n").unwrap() {
+        TicValue::PPOT(presence) => {
+            assert_eq!(presence.get_present(), [true, false, true]);
+            assert_eq!(presence.lost(&all), vec![2]);
+            assert!(all.lost(&presence).is_empty());
+        }
+        _ => panic!("PPOT not decoded"),
+    }
+}
+
+#[test]
+fn parse_ejp_notice() {
+    assert_eq!(parse_test("PEJP|30|Z\r\n").unwrap(), TicValue::PEJP(30));
+}
+
+#[test]
+fn parse_date() {
+    // winter time UTC+1
+    match parse_test("DATE|H231110100819|Z\r\n").unwrap() {
+        TicValue::DATE(stamp) => {
+            assert_eq!(stamp.to_rfc3339(), "2023-11-10T10:08:19+01:00");
+            assert_eq!(stamp.get_epoch(), 1699607299);
+            assert!(!stamp.is_degraded());
+        }
+        _ => panic!("DATE not decoded"),
+    }
+
+    // summer time UTC+2 with empty value field and degraded clock
+    match parse_test("DATE|e240701000005||Z\r\n").unwrap() {
+        TicValue::DATE(stamp) => {
+            assert_eq!(stamp.to_rfc3339(), "2024-07-01T00:00:05+02:00");
+            assert_eq!(stamp.get_epoch(), 1719784805);
+            assert!(stamp.is_degraded());
+        }
+        _ => panic!("DATE not decoded"),
+    }
+}
+
+#[test]
+fn parse_profile() {
+    match parse_test("PJOURF+1|00004001 0600C002 16000003 NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE|Z\r\n").unwrap() {
+        TicValue::PJOURF1(profile) => {
+            let slots = profile.get_slots();
+            assert_eq!(slots.len(), 11);
+            assert_eq!(slots[0].get_index(), 1);
+            assert_eq!(slots[0].get_relay(), RelayAction::TEMPO);
+            assert_eq!(slots[1].get_index(), 2);
+            assert_eq!(slots[1].get_relay(), RelayAction::CLOSE);
+            assert_eq!(slots[2].get_index(), 3);
+            assert_eq!(slots[2].get_relay(), RelayAction::NOCHANGE);
+            assert!(!slots[3].is_used());
+        }
+        _ => panic!("PJOURF+1 not decoded"),
+    }
+
+    match parse_test("PPOINTE|00000011 NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE|Z\r\n").unwrap() {
+        TicValue::PPOINTE(profile) => {
+            let slot = &profile.get_slots()[0];
+            assert_eq!(slot.get_index(), 1);
+            assert!(slot.get_virtual(2));
+            assert!(!slot.get_virtual(3));
+        }
+        _ => panic!("PPOINTE not decoded"),
+    }
+
+    // calendar length depends on supplier configuration
+    match parse_test("PJOURF+1|00004001 0600C002|Z\r\n").unwrap() {
+        TicValue::PJOURF1(profile) => assert_eq!(profile.get_slots().len(), 2),
+        _ => panic!("short PJOURF+1 not decoded"),
+    }
+
+    match parse_test("PPOINTE|00004001 NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE NONUTILE|Z\r\n").unwrap() {
+        TicValue::PPOINTE(profile) => assert_eq!(profile.get_slots().len(), 14),
+        _ => panic!("long PPOINTE not decoded"),
+    }
+}
+
+#[test]
+fn serialize_values() {
+    use serde_json::json;
+
+    // event payload (TicValue) wraps the same object as sensor READ reply
+    let data = parse_test("DATE|H231110100819|Z\r\n").unwrap();
+    let expected = json!({
+        "date":"2023-11-10T10:08:19+01:00","epoch":1699607299,"summer":false,"degraded":false
+    });
+    assert_eq!(serde_json::to_value(&data).unwrap(), json!({ "DATE": expected }));
+
+    let data = parse_test("SMAXSN|H231110061500|07250|Z\r\n").unwrap();
+    let expected = json!({"value":7250,"date":"2023-11-10T06:15:00+01:00","epoch":1699593300});
+    assert_eq!(serde_json::to_value(&data).unwrap(), json!({ "SMAXSN": expected }));
+
+    let data = parse_test("PJOURF+1|0600C002 NONUTILE|Z\r\n").unwrap();
+    let expected = json!([
+        {"used":true,"start":"06:00","index":2,"relay":"close","virtual":[false, false, false,
+            false, false, false]},
+        {"used":false}
+    ]);
+    assert_eq!(serde_json::to_value(&data).unwrap(), json!({ "PJOURF1": expected }));
+
+    // raw register value stays out of serialized fields
+    let data = parse_test("STGE|002A4C10|Z\r\n").unwrap();
+    let jvalue = serde_json::to_value(&data).unwrap();
+    assert!(jvalue["STGE"].get("raw").is_none());
+    assert_eq!(jvalue["STGE"]["supplier_index"], json!(4));
+}
+
+#[test]
+fn calibrated_values() {
+    // corrected value keeps its label, energy counters never go negative
+    assert_eq!(TicValue::IRMS2(12).with_value(30), TicValue::IRMS2(30));
+    assert_eq!(TicValue::EAST(54878).with_value(54900), TicValue::EAST(54900));
+    assert_eq!(TicValue::EAIT(10).with_value(-5), TicValue::EAIT(0));
+    assert_eq!(TicValue::CUSTOM(2, 7).with_value(-7), TicValue::CUSTOM(2, -7));
+    let data = parse_test("NGTF|H PLEINE-CREUSE|Z\r\n").unwrap();
+    assert_eq!(data.with_value(1), data);
+}
+
 #[test]
 fn parse_depassement() {
     parse_test("ADPS|23|J\r\n").unwrap(); // puissance dépassée A
@@ -48,6 +266,7 @@ fn parse_depassement() {
 fn parse_puissance() {
     parse_test("PREF|22|J\r\n").unwrap(); // puissance préférée kVA
     parse_test("PCOUP|22|;\r\n").unwrap(); // puissance de coupure
+    assert_eq!(parse_test("ISOUSC|45|?\r\n").unwrap(), TicValue::ISOUSC(45)); // intensité A
 }
 
 #[test]
@@ -56,6 +275,7 @@ fn parse_insts() {
     parse_test("SINSTS1|00022|;\r\n").unwrap();
     parse_test("SINSTS2|00000|8\r\n").unwrap();
     parse_test("SINSTS3|00000|9\r\n").unwrap();
+    assert_eq!(parse_test("SINSTI|01250|9\r\n").unwrap(), TicValue::SINSTI(1250));
 }
 
 #[test]
@@ -106,6 +326,25 @@ fn parse_urms() {
     parse_test("URMS3|229|$\r\n").unwrap();
 }
 
+#[test]
+fn parse_energy() {
+    // energy counters Wh should not wrap beyond i32
+    match parse_test("EAST|000054878|/\r\n").unwrap() {
+        TicValue::EAST(value) => assert_eq!(value, 54878),
+        _ => panic!("EAST not decoded"),
+    }
+    match parse_test("EASF01|4294967296|5\r\n").unwrap() {
+        TicValue::EASF01(value) => assert_eq!(value, 4294967296),
+        _ => panic!("EASF01 not decoded"),
+    }
+    match parse_test("EAIT|999999999|/\r\n").unwrap() {
+        TicValue::EAIT(value) => assert_eq!(value, 999999999),
+        _ => panic!("EAIT not decoded"),
+    }
+    assert_eq!(parse_test("ERQ1|000001258|#\r\n").unwrap(), TicValue::ERQ1(1258));
+    assert_eq!(parse_test("ERQ4|000000017|#\r\n").unwrap(), TicValue::ERQ4(17));
+}
+
 #[test]
 fn parse_mobile() {
     // Debut/Fin point mobile
@@ -143,21 +382,502 @@ fn parse_misc() {
 }
 
 #[test]
-fn checksum() {
-    let serial = SerialRaw {
-        raw_fd: Cell::new(0),
-        devname: CString::new("totot").unwrap(),
-        speed: SerialSpeed::B9600,
-        pflags: 0,
-        iflags: 0 as cglue::tcflag_t,
-        cflags: 0 as cglue::tcflag_t,
-        lflags: 0 as cglue::tcflag_t,
+fn parse_custom() {
+    let custom: &'static TicObject = Box::leak(Box::new(TicObject::new(
+        "FOO",
+        "foo-power",
+        "vendor label",
+        TicUnit::from_label("W").unwrap(),
+        1,
+    )));
+    let customs = vec![custom];
+
+    let text = "FOO|00042|Z\r\n".replace('|', "\t");
+    match tic_from_custom(text.as_str(), &customs) {
+        Some(TicValue::CUSTOM(idx, value)) => {
+            assert_eq!(idx, 0);
+            assert_eq!(value, 42);
+        }
+        _ => panic!("custom label not decoded"),
+    }
+
+    // builtin labels are not caught by custom registry, config rejects colliding names
+    let text = "SINSTS|00022|J\r\n".replace('|', "\t");
+    assert!(tic_from_custom(text.as_str(), &customs).is_none());
+    assert!(tic_is_builtin("SINSTS"));
+    assert!(tic_is_builtin("SMAXSN-1"));
+    assert!(!tic_is_builtin("FOO"));
+    assert!(tic_is_builtin("MSG2"));
+    for label in ["HC", "HCX", "PRMX", "MSG3"] {
+        assert!(!tic_is_builtin(label));
+    }
+
+    // trailing data after checksum group or missing checksum is not a custom value
+    let text = "FOO|00042|Z\r\nFOO".replace('|', "\t");
+    assert!(tic_from_custom(text.as_str(), &customs).is_none());
+    let text = "FOO|00042\r\n".replace('|', "\t");
+    assert!(tic_from_custom(text.as_str(), &customs).is_none());
+    assert!(TicUnit::from_label("kW").is_err());
+}
+
+#[test]
+fn buffer_ring() {
+    let mut ring = BufferRing::new();
+    let mut buffer = [0u8; 64];
+
+    // network chunks split lines at random position
+    ring.push(b"SINSTS\t000");
+    assert!(ring.get_one_line(&mut buffer).is_none());
+    ring.push(b"22\tJ\r\nIRMS1\t");
+    let count = ring.get_one_line(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"SINSTS\t00022\tJ\r\n");
+    assert!(ring.get_one_line(&mut buffer).is_none());
+    assert_eq!(ring.get_pending(), 6);
+
+    ring.push(b"003\t1\r\nURMS1\t232\t?\r\n");
+    let count = ring.get_one_line(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"IRMS1\t003\t1\r\n");
+    let count = ring.get_one_line(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"URMS1\t232\t?\r\n");
+    assert_eq!(ring.get_pending(), 0);
+
+    // frame end closes last group, checksum stays at count-3
+    ring.push(b"PJOURF+1\t00008001\t/\r\x03\x02\nADSC\t");
+    let count = ring.get_one_line(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"PJOURF+1\t00008001\t/\r\x03");
+    let count = ring.get_one_line(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"\x02\n");
+    assert_eq!(ring.get_pending(), 5);
+    ring.reset();
+
+    // long lines grow the ring, garbage is only dropped once growth limit is reached
+    ring.push(&[b'X'; RING_BUFFER_SZ]);
+    ring.push(b"\n");
+    assert_eq!(ring.get_one_line(&mut buffer), Some(buffer.len()));
+    ring.push(&[b'X'; RING_BUFFER_MAX]);
+    assert!(ring.get_one_line(&mut buffer).is_none());
+    assert_eq!(ring.get_overflow(), 0);
+
+    // overflow drops garbage tail up to next line ending, following lines stay intact
+    ring.push(b"XXXX\r\nEAST\t000001234\t#\r\n");
+    assert_eq!(ring.get_overflow(), 1);
+    let count = ring.get_one_line(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001234\t#\r\n");
+    assert!(ring.get_one_line(&mut buffer).is_none());
+}
+
+#[test]
+fn buffer_ring_datagram() {
+    let mut frame = Vec::new();
+    for idx in 0..40 {
+        frame.extend_from_slice(format!("EAST\t{:09}\t#\r\n", idx).as_bytes());
+    }
+    assert!(frame.len() > RING_BUFFER_SZ);
+
+    // a whole frame per datagram fits when ring is sized for it
+    let mut ring = BufferRing::with_size(1024);
+    let mut buffer = [0u8; 64];
+    let free = ring.get_free();
+    assert!(free.len() >= frame.len());
+    free[0..frame.len()].copy_from_slice(&frame);
+    ring.commit(frame.len());
+    for idx in 0..40 {
+        let count = ring.get_one_line(&mut buffer).unwrap();
+        assert_eq!(&buffer[0..count], format!("EAST\t{:09}\t#\r\n", idx).as_bytes());
+    }
+    assert_eq!(ring.get_pending(), 0);
+    assert_eq!(ring.get_peak(), frame.len());
+    ring.reset_peak();
+
+    // partial line left by previous datagram does not steal room from next one
+    ring.push(b"EAST\t0000");
+    assert!(ring.get_free().len() >= ring.get_size());
+    assert_eq!(ring.get_peak(), 9);
+}
+
+// serial port woken with VMIN=1 hands back whatever bytes are already there
+struct FragmentSource {
+    fragments: std::cell::RefCell<std::collections::VecDeque<&'static [u8]>>,
+}
+
+impl SourceHandle for FragmentSource {
+    fn open(&self) -> Result<(), LinkyFail> {
+        Ok(())
+    }
+    fn close(&self) {}
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, LinkyError> {
+        match self.fragments.borrow_mut().pop_front() {
+            None => Err(LinkyError::RetryLater),
+            Some(fragment) => {
+                buffer[0..fragment.len()].copy_from_slice(fragment);
+                Ok(fragment.len())
+            }
+        }
+    }
+    fn get_raw_fd(&self) -> raw::c_int {
+        -1
+    }
+}
+
+#[test]
+fn serial_fragment() {
+    let fragments: [&'static [u8]; 4] =
+        [b"SINST", b"S\t00022\tJ", b"\r\nEAST\t0000", b"54878\t/\r\n"];
+    let source = FragmentSource {
+        fragments: std::cell::RefCell::new(fragments.into_iter().collect()),
+    };
+    let handle = LinkyHandle::from_source("/dev/ttyUSB0", "serial".to_string(), Box::new(source));
+
+    // every wakeup only decodes complete lines, fragments wait within ring
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert!(lines.is_empty());
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert!(lines.is_empty());
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].raw, b"SINSTS\t00022\tJ\r\n");
+    assert!(matches!(lines[0].value, Ok(TicValue::SINSTS(_))));
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].value.is_ok());
+    assert_eq!(handle.get_stats().checksum, 0);
+}
+
+#[test]
+fn memory_source() {
+    let source = MemorySource::new();
+    let handle = LinkyHandle::from_source("memory", "test".to_string(), Box::new(source.clone()));
+    handle.reopen().unwrap();
+    assert_eq!(source.get_counts(), (1, 1));
+
+    // lines decoded before a lost device are kept for dispatch
+    source
+        .push(b"SINSTS\t00022\tJ\r\n")
+        .push_error(LinkyError::ReopenDev)
+        .push(b"EAST\t000054878\t/\r\n");
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    assert_eq!(handle.decode_all(&mut buffer, &mut lines), Err(LinkyError::ReopenDev));
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].value, Ok(TicValue::SINSTS(22)));
+
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1].value, Ok(TicValue::EAST(54878)));
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert_eq!(lines.len(), 2);
+}
+
+#[test]
+fn serial_bulk_read() {
+    let config = |buffered: bool| LinkyConfig::Serial {
+        device: "/dev/ttyUSB0",
+        speed: 9600,
+        parity: "even",
+        reconnect: 0,
+        canonical: true,
+        buffered,
+        flags: SerialFlags::default(),
+    };
+    assert_eq!(config(false).get_buffer(RING_BUFFER_SZ), RING_BUFFER_SZ);
+    assert_eq!(config(true).get_buffer(RING_BUFFER_SZ), SERIAL_BULK_SZ);
+
+    let mut data = Vec::new();
+    for idx in 0..100 {
+        data.extend_from_slice(format!("EAST\t{:09}\t#\r\n", idx).as_bytes());
+    }
+    assert!(data.len() > RING_BUFFER_SZ && data.len() < SERIAL_BULK_SZ);
+
+    // what kernel holds is drained in chunks, one read() each
+    let reads = |size: usize| {
+        let source = MemorySource::new();
+        let mut handle =
+            LinkyHandle::from_source("tty", "serial".to_string(), Box::new(source.clone()));
+        handle.set_buffer(size);
+        source.push(&data);
+        let mut buffer = [0u8; TIC_LINE_MAX];
+        let mut lines = TicLines::new();
+        handle.decode_all(&mut buffer, &mut lines).unwrap();
+        assert_eq!(lines.len(), 100);
+        source.get_reads()
+    };
+    assert!(reads(RING_BUFFER_SZ) > 1);
+    assert_eq!(reads(config(true).get_buffer(RING_BUFFER_SZ)), 1);
+}
+
+#[test]
+fn frame_check() {
+    let fragments: [&'static [u8]; 4] = [
+        b"EAST\t000054878\t/\r\x03",
+        b"\x02\nSINSTS\t00022\tJ\r\nEAST\t000054878\t/\r\x03",
+        b"\x02\nSINSTS\t00022\tX\r\nEAST\t000054878\t/\r\x03",
+        b"\x02\nSINSTS\t00022\tJ\r\n\x02\nSINSTS\t00022\tJ\r\nEAST\t000054878\t/\r\x03",
+    ];
+    let source = FragmentSource {
+        fragments: std::cell::RefCell::new(fragments.into_iter().collect()),
     };
-    let handle = LinkyHandle {
-        portname: "/dev/dummy",
-        handle: serial,
+    let handle = LinkyHandle::from_source("/dev/ttyUSB0", "serial".to_string(), Box::new(source));
+
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    for _ in 0..fragments.len() {
+        handle.decode_all(&mut buffer, &mut lines).unwrap();
+    }
+    let frames: Vec<TicFrame> = lines.iter().filter_map(|line| line.frame.clone()).collect();
+    assert_eq!(frames.len(), 5);
+
+    // binding started mid frame
+    assert!(!frames[0].started && frames[0].ended);
+    assert!(!frames[0].is_complete());
+
+    assert!(frames[1].is_complete());
+    assert_eq!(frames[1].groups, 2);
+
+    // corrupted group is rejected and reported missing
+    assert_eq!(frames[2].rejected, 1);
+    assert_eq!(frames[2].missing, vec!["SINSTS".to_string()]);
+    assert!(!frames[2].is_complete());
+
+    // next STX truncates frame before ETX
+    assert!(frames[3].started && !frames[3].ended);
+    assert_eq!(frames[3].missing, vec!["EAST".to_string()]);
+    assert!(frames[4].is_complete());
+    assert_eq!(frames[4].seq, 4);
+
+    let stats = handle.get_stats();
+    assert_eq!(stats.frames, 4);
+    assert_eq!(stats.partial, 3);
+}
+
+#[test]
+fn gateway_packing() {
+    const FRAME: &[u8] = b"\x02\nSINSTS\t00022\tJ\r\nEAST\t000054878\t/\r\x03";
+    let source = MemorySource::new();
+    let handle = LinkyHandle::from_source("udp", "udp".to_string(), Box::new(source.clone()));
+
+    // one wakeup per datagram, (decoded groups, complete frames) it delivered
+    let wakeup = |datagram: &[u8]| {
+        source.push(datagram);
+        let mut buffer = [0u8; TIC_LINE_MAX];
+        let mut lines = TicLines::new();
+        handle.decode_all(&mut buffer, &mut lines).unwrap();
+        let groups = lines.iter().filter(|line| line.value.is_ok()).count();
+        let frames = lines.iter().filter_map(|line| line.frame.as_ref());
+        (groups, frames.filter(|frame| frame.is_complete()).count())
     };
 
+    // line per datagram
+    assert_eq!(wakeup(b"\x02\n"), (0, 0));
+    assert_eq!(wakeup(b"SINSTS\t00022\tJ\r\n"), (1, 0));
+    assert_eq!(wakeup(b"EAST\t000054878\t/\r\x03"), (1, 1));
+
+    // frame per datagram
+    assert_eq!(wakeup(FRAME), (2, 1));
+
+    // several frames and the head of next one, its tail completes it on next datagram
+    let mut datagram = [FRAME, FRAME, FRAME].concat();
+    datagram.extend_from_slice(b"\x02\nSINSTS\t000");
+    assert_eq!(wakeup(&datagram), (6, 3));
+    assert_eq!(wakeup(b"22\tJ\r\nEAST\t000054878\t/\r\x03"), (2, 1));
+
+    // STX/ETX stripped by gateway, CRLF terminated batch
+    assert_eq!(wakeup(b"SINSTS\t00022\tJ\r\nEAST\t000054878\t/\r\n"), (2, 0));
+    assert_eq!(handle.get_stats().frames, 6);
+}
+
+#[test]
+fn sync_handle() {
+    fn shared<T: Send + Sync>(_value: &T) {}
+
+    let handle = LinkyHandle::from_source("null", "null".to_string(), Box::new(NullSource));
+    let handle = std::sync::Arc::new(handle);
+    shared(&handle);
+
+    // concurrent callbacks never lose a counter update
+    let line = b"SINSTS\t00022\tJ\r\n";
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            let handle = handle.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    handle.parse(line, line.len()).unwrap();
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    assert_eq!(handle.get_stats().lines, 400);
+
+    let cell = SyncCell::new(Some(12));
+    assert_eq!(cell.take(), Some(12));
+    assert_eq!(cell.get(), None);
+    let lock = SyncLock::new(vec![1]);
+    lock.lock().push(2);
+    assert_eq!(lock.replace(Vec::new()), vec![1, 2]);
+}
+
+#[test]
+fn udp_datagram() {
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    drop(socket);
+
+    let source = NetworkHandle::new("127.0.0.1", port, None, None).unwrap();
+    let mut handle = LinkyHandle::from_source("127.0.0.1", "udp".to_string(), Box::new(source));
+    handle.set_buffer(2048);
+
+    let mut frame = Vec::new();
+    for idx in 0..60 {
+        frame.extend_from_slice(format!("EAST\t{:09}\t#\r\n", idx).as_bytes());
+    }
+    assert!(frame.len() > RING_BUFFER_SZ);
+
+    let gateway = UdpSocket::bind("127.0.0.1:0").unwrap();
+    gateway.send_to(&frame, ("127.0.0.1", port)).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+
+    let mut buffer = [0u8; 64];
+    for idx in 0..60 {
+        let count = handle.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[0..count], format!("EAST\t{:09}\t#\r\n", idx).as_bytes());
+    }
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+
+    // oversized datagram is dropped and counted, next one is read cleanly
+    handle.set_buffer(512);
+    gateway.send_to(&frame, ("127.0.0.1", port)).unwrap();
+    gateway.send_to(b"EAST\t000001234\t#\r\n", ("127.0.0.1", port)).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::TooLong(_))));
+    assert_eq!(handle.get_overflow(), 1);
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001234\t#\r\n");
+}
+
+#[test]
+fn udp_fragments() {
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    drop(socket);
+
+    let source = NetworkHandle::new("127.0.0.1", port, None, None).unwrap();
+    let handle = LinkyHandle::from_source("127.0.0.1", "udp".to_string(), Box::new(source));
+    let gateway = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let send = |socket: &UdpSocket, data: &[u8]| {
+        socket.send_to(data, ("127.0.0.1", port)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+    };
+    let mut buffer = [0u8; 64];
+    assert!(handle.get_gateway().is_none());
+
+    // split mid-label, then mid-checksum
+    send(&gateway, b"SIN");
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+    send(&gateway, b"STS\t00022\tJ\r\nIRMS1\t003\t");
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"SINSTS\t00022\tJ\r\n");
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+    send(&gateway, b"1\r\n");
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"IRMS1\t003\t1\r\n");
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+
+    // fragment is not stitched to another gateway datagram
+    let other = UdpSocket::bind("127.0.0.1:0").unwrap();
+    send(&gateway, b"URMS1\t2");
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+    send(&other, b"EAST\t000001234\t#\r\n");
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001234\t#\r\n");
+
+    // last sender is tracked as gateway
+    let (addr, _) = handle.get_gateway().unwrap();
+    assert_eq!(addr, other.local_addr().unwrap());
+}
+
+#[cfg(feature = "compress")]
+#[test]
+fn replay_capture() {
+    use std::io::Write;
+
+    let capture = b"1699610899000\tSINSTS\t00022\tJ\r\n1699610899200\tIRMS1\t003\t1\r\nURMS1\t232\t?\r\n";
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(capture).unwrap();
+    let captures = [
+        ("plain", capture.to_vec()),
+        ("gz", gzip.finish().unwrap()),
+        ("zst", zstd::encode_all(&capture[..], 0).unwrap()),
+    ];
+
+    for (ext, data) in captures {
+        let path = std::env::temp_dir().join(format!("linky-{}.tic.{}", std::process::id(), ext));
+        std::fs::write(&path, data).unwrap();
+        let path: &'static str = Box::leak(path.to_string_lossy().into_owned().into_boxed_str());
+
+        // no pacing, timestamps are stripped
+        let replay = ReplayHandle::new(path, 0, false).unwrap();
+        let handle = LinkyHandle::from_source(path, "replay".to_string(), Box::new(replay));
+        let mut buffer = [0u8; 64];
+        for line in [&b"SINSTS\t00022\tJ\r\n"[..], b"IRMS1\t003\t1\r\n", b"URMS1\t232\t?\r\n"] {
+            let count = handle.read(&mut buffer).unwrap();
+            assert_eq!(&buffer[0..count], line);
+        }
+        assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+
+        // real time pacing holds second record for 200ms
+        let replay = ReplayHandle::new(path, 1, false).unwrap();
+        let handle = LinkyHandle::from_source(path, "replay".to_string(), Box::new(replay));
+        assert!(handle.read(&mut buffer).is_ok());
+        assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        let count = handle.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[0..count], b"IRMS1\t003\t1\r\n");
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn record_capture() {
+    let path = std::env::temp_dir().join(format!("linky-{}.rec.tic", std::process::id()));
+    let path: &'static str = Box::leak(path.to_string_lossy().into_owned().into_boxed_str());
+
+    let mut recorder = FrameRecorder::new();
+    recorder.write(1000, b"ignored\n").unwrap();
+    recorder.start(path).unwrap();
+    assert!(recorder.start(path).is_err());
+
+    // a line split over two reads keeps its first stamp
+    recorder.write(1699610899000, b"SINSTS\t00022").unwrap();
+    recorder.write(1699610899100, b"\tJ\r\nIRMS1\t003\t1\r\n").unwrap();
+    assert!(recorder.stop());
+    assert!(!recorder.stop());
+    assert_eq!(recorder.get_lines(), 2);
+    let text = std::fs::read_to_string(path).unwrap();
+    assert_eq!(text, "1699610899000\tSINSTS\t00022\tJ\r\n1699610899100\tIRMS1\t003\t1\r\n");
+
+    let replay = ReplayHandle::new(path, 0, false).unwrap();
+    let handle = LinkyHandle::from_source(path, "replay".to_string(), Box::new(replay));
+    let mut buffer = [0u8; 64];
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"SINSTS\t00022\tJ\r\n");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn checksum() {
+    let handle = LinkyHandle::from_source("/dev/dummy", "9600-7E1".to_string(), Box::new(NullSource));
+
     let buffer1 = [
         83, 84, 71, 69, 9, 48, 48, 50, 65, 48, 48, 49, 49, 9, 58, 13, 10,
     ];
@@ -174,4 +894,1204 @@ fn checksum() {
     let buffer3= [83, 77, 65, 88, 83, 78, 49, 45, 49, 9, 72, 50, 51, 49, 49, 49, 53, 49, 55, 48, 56, 52, 50, 9, 48, 48, 48, 50, 50, 9, 67, 13, 10];
     let line = handle.checksum(&buffer3, buffer3.len()).unwrap();
     println!("buffer3 = {}", line);
+
+    // last frame group ends with ETX
+    let buffer4 = b"SINSTS\t00022\tJ\r\x03";
+    assert!(handle.parse(buffer4, buffer4.len()).is_ok());
+
+    // short read or count beyond buffer never reaches checksum indexing
+    assert!(matches!(handle.checksum(b"J\r\n", 3), Err(LinkyError::ParsingError(_))));
+    let count = buffer1.len() + 8;
+    assert!(matches!(handle.checksum(&buffer1, count), Err(LinkyError::ParsingError(_))));
+}
+
+#[test]
+fn strict_mode() {
+    let unknown = b"FOOBAR\t00042\t!\r\n";
+    let malformed = b"SINSTS\tabc\t<\r\n";
+
+    // permissive mode skips unknown labels, malformed values stay parsing errors
+    let mut handle =
+        LinkyHandle::from_source("/dev/dummy", "9600-7E1".to_string(), Box::new(NullSource));
+    assert_eq!(handle.parse(unknown, unknown.len()), Ok(TicValue::UNSET));
+    assert!(matches!(handle.parse(malformed, malformed.len()), Err(LinkyError::ParsingError(_))));
+
+    // strict mode reports raw line of unknown labels only
+    handle.set_strict(true);
+    match handle.parse(unknown, unknown.len()) {
+        Err(LinkyError::UnknownLabel(line)) => assert!(line.starts_with("FOOBAR\t00042")),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(matches!(handle.parse(malformed, malformed.len()), Err(LinkyError::ParsingError(_))));
+    assert!(matches!(handle.parse(b"SINSTS\t00022\tJ\r\n", 16), Ok(TicValue::SINSTS(22))));
+}
+
+// reference checksum, standard mode sums up to last HT, historic mode stops before last SP
+fn checksum_line(label: &str, horodate: &str, value: &str, historic: bool) -> Vec<u8> {
+    let data = match (historic, horodate) {
+        (true, _) => format!("{} {}", label, value),
+        (false, "") => format!("{}\t{}\t", label, value),
+        (false, _) => format!("{}\t{}\t{}\t", label, horodate, value),
+    };
+    let sum: u32 = data.bytes().map(|byte| byte as u32).sum();
+    let checksum = ((sum & 0x3f) as u8 + 0x20) as char;
+    match historic {
+        true => format!("{} {}\r\n", data, checksum).into_bytes(),
+        false => format!("{}{}\r\n", data, checksum).into_bytes(),
+    }
+}
+
+proptest::proptest! {
+    #[test]
+    fn checksum_proptest(
+        label in "[A-Z][A-Z0-9+-]{0,8}",
+        horodate in "([EHeh][0-9]{12})?",
+        value in "[!-~]([ -~]{0,30}[!-~])?",
+        historic in proptest::bool::ANY,
+        offset in 0usize..64,
+        delta in 1u8..64,
+    ) {
+        // historic values are single words, standard text keeps its spaces
+        let value = match historic {
+            true => value.replace(' ', "_"),
+            false => value,
+        };
+        let line = checksum_line(&label, &horodate, &value, historic);
+        proptest::prop_assert!(tic_checksum(&line, line.len()).is_ok());
+
+        // any printable change within label or value but a multiple of 64 breaks checksum
+        let tail = if historic { 5 } else { 4 };
+        let idx = offset % (label.len() + value.len());
+        let idx = match idx < label.len() {
+            true => idx,
+            false => line.len() - tail - value.len() + idx - label.len(),
+        };
+        let byte = ((line[idx] as u32 + 0x5e - 0x21 + delta as u32) % 0x5e + 0x21) as u8;
+        proptest::prop_assume!((byte as i32 - line[idx] as i32) % 64 != 0);
+
+        let mut mutated = line.clone();
+        mutated[idx] = byte;
+        let result = tic_checksum(&mutated, mutated.len());
+        proptest::prop_assert!(matches!(result, Err(LinkyError::ChecksumError(_))));
+
+        // checksum byte itself
+        let mut mutated = line.clone();
+        let idx = line.len() - 3;
+        mutated[idx] = (line[idx] - 0x20 + delta) % 0x40 + 0x20;
+        let result = tic_checksum(&mutated, mutated.len());
+        proptest::prop_assert!(matches!(result, Err(LinkyError::ChecksumError(_))));
+    }
+}
+
+#[test]
+fn decode_all() {
+    let path = std::env::temp_dir().join(format!("linky-{}-decode.tic", std::process::id()));
+    std::fs::write(&path, b"SINSTS\t00022\tJ\r\nSINSTS\t00023\tJ\r\nSINSTS\t00022\tJ\r\x03").unwrap();
+    let path: &'static str = Box::leak(path.to_string_lossy().into_owned().into_boxed_str());
+
+    let replay = ReplayHandle::new(path, 0, false).unwrap();
+    let handle = LinkyHandle::from_source(path, "replay".to_string(), Box::new(replay));
+
+    // whole capture is decoded within one call, bad checksum line keeps its slot
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert_eq!(lines.len(), 3);
+    assert!(matches!(lines[0].value, Ok(TicValue::SINSTS(22))));
+    assert!(matches!(lines[1].value, Err(LinkyError::ChecksumError(_))));
+    assert_eq!(lines[2].raw, b"SINSTS\t00022\tJ\r\x03");
+    assert_eq!(handle.get_stats().frames, 1);
+    std::fs::remove_file(path).unwrap();
+
+    // line longer than caller buffer is reported instead of parsed truncated
+    let data: &'static [u8] = b"SINSTS\t00022\tJ\r\nEAST\t000054878\t/\r\n";
+    let source = FragmentSource {
+        fragments: std::cell::RefCell::new([data].into()),
+    };
+    let handle = LinkyHandle::from_source("/dev/ttyUSB0", "serial".to_string(), Box::new(source));
+    let mut buffer = [0u8; 16];
+    let mut lines = TicLines::new();
+    handle.decode_all(&mut buffer, &mut lines).unwrap();
+    assert_eq!(lines.len(), 2);
+    assert!(matches!(lines[0].value, Ok(TicValue::SINSTS(22))));
+    assert!(matches!(lines[1].value, Err(LinkyError::TooLong(_))));
+}
+
+#[test]
+fn decode_budget() {
+    let data: &'static [u8] = b"SINSTS\t00022\tJ\r\nSINSTS\t00023\tK\r\nSINSTS\t00024\tL\r\n";
+    let source = FragmentSource {
+        fragments: std::cell::RefCell::new([data].into()),
+    };
+    let handle = LinkyHandle::from_source("/dev/ttyUSB0", "serial".to_string(), Box::new(source));
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    let mut lines = TicLines::new();
+
+    // budget exhausted, last line waits within ring for next call
+    assert_eq!(handle.decode_budget(&mut buffer, &mut lines, 2), Ok(true));
+    assert_eq!(lines.len(), 2);
+    assert!(matches!(lines[1].value, Ok(TicValue::SINSTS(23))));
+    lines.clear();
+    assert_eq!(handle.decode_budget(&mut buffer, &mut lines, 2), Ok(false));
+    assert_eq!(lines.len(), 1);
+    assert!(matches!(lines[0].value, Ok(TicValue::SINSTS(24))));
+}
+
+#[test]
+fn link_quality() {
+    assert_eq!(tic_label("\nSINSTS\t00022\tX\r"), "SINSTS");
+    assert_eq!(tic_label("PAPP 01250 ?"), "PAPP");
+    assert_eq!(tic_label("garbage"), "?");
+
+    let start = std::time::Instant::now();
+    let mut quality = LinkQuality::new(2);
+    quality.push("SINSTS", start);
+    assert_eq!(quality.check(start), None);
+    quality.push("SINSTS", start);
+    quality.push("IRMS1", start);
+    assert_eq!(quality.check(start), Some(true));
+    assert_eq!(quality.check(start), None);
+    assert_eq!(quality.get_labels().get("SINSTS"), Some(&2));
+
+    // failures older than one minute leave the window
+    let later = start + std::time::Duration::from_secs(61);
+    assert_eq!(quality.get_rate(later), 0);
+    assert_eq!(quality.check(later), Some(false));
+
+    // handle counts checksum failures by label
+    let handle = LinkyHandle::from_source("/dev/dummy", "9600-7E1".to_string(), Box::new(NullSource));
+    let line = b"SINSTS\t00022\tX\r\n";
+    assert!(handle.parse(line, line.len()).is_err());
+    assert_eq!(handle.get_checksum_rate(), 1);
+    assert_eq!(handle.get_checksum_labels().get("SINSTS"), Some(&1));
+
+    // mangled checksum byte is an encoding error, label and value stay readable
+    let line = b"SINSTS\t00022\t\xE9\r\n";
+    match handle.parse(line, line.len()) {
+        Err(LinkyError::EncodingError(text)) => assert!(text.starts_with("SINSTS\t00022\t")),
+        _ => panic!("encoding error expected"),
+    }
+    assert_eq!(handle.get_stats().encoding, 1);
+    assert_eq!(handle.get_checksum_labels().get("SINSTS"), Some(&2));
+}
+
+#[test]
+fn linky_error() {
+    let error = LinkyError::ChecksumError("SINSTS\t00022\tX\r\n".into());
+    assert_eq!(error.get_code(), "linky-checksum-fail");
+    assert_eq!(error.to_string(), "linky-checksum-fail SINSTS\t00022\tX");
+    assert_eq!(LinkyError::ReopenDev.to_string(), "linky-source-closed");
+    assert_eq!(LinkyError::TooLong("line > 512 bytes".into()).get_info(), "line > 512 bytes");
+}
+
+#[test]
+fn reconfigure() {
+    let path = std::env::temp_dir().join(format!("linky-{}-reconfigure.tic", std::process::id()));
+    std::fs::write(&path, b"SINSTS\t00022\tJ\r\n").unwrap();
+    let path: &'static str = Box::leak(path.to_string_lossy().into_owned().into_boxed_str());
+
+    let handle = LinkyHandle::from_source("/dev/dummy", "9600-7E1".to_string(), Box::new(NullSource));
+
+    // failing source leaves current one in place
+    let missing = LinkyConfig::Replay { path: "/nonexistent/capture.tic", speed: 0, interval: 100, repeat: false };
+    assert!(handle.reconfigure(&missing).is_err());
+    assert_eq!(handle.get_name(), "/dev/dummy");
+
+    let replay = LinkyConfig::Replay { path, speed: 0, interval: 100, repeat: false };
+    handle.reconfigure(&replay).unwrap();
+    assert_eq!(handle.get_name(), path);
+    assert_eq!(handle.get_mode(), "replay");
+    let mut buffer = [0u8; 64];
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"SINSTS\t00022\tJ\r\n");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn stats() {
+    let handle = LinkyHandle::from_source("/dev/dummy", "9600-7E1".to_string(), Box::new(NullSource));
+
+    assert!(handle.parse(b"SINSTS\t00022\tJ\r\n", 16).is_ok());
+    assert!(handle.parse(b"SINSTS\t00023\tJ\r\n", 16).is_err());
+    assert!(handle.parse(b"SINSTS\t00022\tJ\r\x03", 16).is_ok());
+    handle.reopen().unwrap();
+
+    let stats = handle.get_stats();
+    assert_eq!(stats.lines, 2);
+    assert_eq!(stats.checksum, 1);
+    assert_eq!(stats.frames, 1);
+    assert_eq!(stats.reconnect, 1);
+
+    handle.reset_stats();
+    assert_eq!(handle.get_stats().lines, 0);
+}
+
+#[test]
+fn tcp_listen() {
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    let handle = TcpListenHandle::new("127.0.0.1", 0).unwrap();
+    let port = handle.listener.borrow().as_ref().unwrap().local_addr().unwrap().port();
+    let listen_fd = handle.get_raw_fd();
+    let mut buffer = [0u8; 64];
+
+    // first read accepts gateway connection and swaps fd
+    let mut gateway = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+    assert_ne!(handle.get_raw_fd(), listen_fd);
+
+    gateway.write_all(b"SINSTS\t00022\tJ\r\n").unwrap();
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"SINSTS\t00022\tJ\r\n");
+
+    // gateway leaves, handle falls back on listener
+    drop(gateway);
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+    assert_eq!(handle.get_raw_fd(), listen_fd);
+}
+
+#[test]
+fn tcp_connect() {
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    // connect settles while caller loop runs, not within new()
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+    let handle = TcpHandle::new("127.0.0.1", port).unwrap();
+    assert!(handle.is_connecting());
+    let mut retry = 0;
+    while handle.set_connected().is_err() {
+        retry += 1;
+        assert!(retry < 100);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert!(!handle.is_connecting());
+
+    let (mut client, _) = server.accept().unwrap();
+    client.write_all(b"SINSTS\t00022\tJ\r\n").unwrap();
+    let mut buffer = [0u8; 64];
+    let mut retry = 0;
+    let count = loop {
+        match handle.read(&mut buffer) {
+            Err(LinkyError::RetryLater) if retry < 100 => retry += 1,
+            result => break result.unwrap(),
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+    assert_eq!(&buffer[0..count], b"SINSTS\t00022\tJ\r\n");
+
+    // refused connection is reported once settled
+    drop(client);
+    drop(server);
+    let handle = TcpHandle::new("127.0.0.1", port).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    assert!(handle.set_connected().is_err());
+}
+
+#[test]
+fn http_fetch() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let server = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+    let gateway = std::thread::spawn(move || {
+        let (mut client, _) = server.accept().unwrap();
+        let mut request = [0u8; 256];
+        let _ = client.read(&mut request).unwrap();
+        client
+            .write_all(b"HTTP/1.0 200 OK\r\n\r\nSINSTS\t00022\tJ\r\nIRMS1\t003\t1\r\n")
+            .unwrap();
+    });
+
+    let url: &'static str = Box::leak(format!("http://127.0.0.1:{}/tic", port).into_boxed_str());
+    let handle = HttpHandle::new(url).unwrap();
+    let mut buffer = [0u8; 16];
+
+    // body larger than buffer stays pending
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(count, 16);
+    assert!(handle.has_pending());
+    let count = handle.read(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..count], b"IRMS1\t003\t1\r\n");
+    assert!(!handle.has_pending());
+    gateway.join().unwrap();
+}
+
+#[test]
+fn failover() {
+    let mut handle = LinkyHandle::from_source("primary", "serial".to_string(), Box::new(NullSource));
+    let backup = LinkyHandle::from_source("backup", "serial".to_string(), Box::new(NullSource));
+    handle.set_backup(backup, 50);
+
+    // backup is ignored while primary talks
+    assert_eq!(handle.select(LinkySource::Primary), Some(false));
+    assert_eq!(handle.select(LinkySource::Backup), None);
+    assert_eq!(handle.get_active(), LinkySource::Primary);
+
+    // primary silence hands over to backup, then primary recovers
+    std::thread::sleep(std::time::Duration::from_millis(60));
+    assert_eq!(handle.select(LinkySource::Backup), Some(true));
+    assert_eq!(handle.select(LinkySource::Backup), Some(false));
+    assert_eq!(handle.get_active(), LinkySource::Backup);
+    assert_eq!(handle.select(LinkySource::Primary), Some(true));
+    assert_eq!(handle.get_active(), LinkySource::Primary);
+}
+
+#[test]
+fn tariff_accumulator() {
+    let mut accu = TariffAccumulator::new();
+
+    // EAST deltas go to current NTARF index, first value is the baseline
+    accu.set_tariff(1);
+    assert!(!accu.set_total(1000));
+    assert!(accu.set_total(1250));
+    accu.set_tariff(2);
+    assert!(accu.set_total(1300));
+    assert_eq!(accu.get_total()[0..2], [250, 50]);
+
+    // EASF takes over once seen
+    assert!(!accu.set_index(1, 500));
+    assert!(accu.set_index(1, 520));
+    assert!(!accu.set_total(1400));
+    assert_eq!(accu.get_total()[0..2], [250, 70]);
+
+    // meter midnight clears daily counters only
+    let day1 = match parse_test("DATE|H231110235959||Z\r\n").unwrap() {
+        TicValue::DATE(value) => value,
+        _ => panic!("DATE not decoded"),
+    };
+    let day2 = match parse_test("DATE|H231111000001||Z\r\n").unwrap() {
+        TicValue::DATE(value) => value,
+        _ => panic!("DATE not decoded"),
+    };
+    assert!(!accu.set_date(&day1));
+    assert!(accu.set_date(&day2));
+    assert_eq!(accu.get_today()[0..2], [0, 0]);
+    assert_eq!(accu.get_total()[0..2], [250, 70]);
+    assert_eq!(accu.get_tariff(), Some(2));
+}
+
+#[test]
+fn tariff_names() {
+    let mut names = TariffNames::new(vec!["Creuse".to_string()]);
+
+    // LTARF is learned for current index, config names take precedence
+    names.set_calendar("H PLEINE/CREUSE");
+    names.set_label("HEURE  PLEINE");
+    names.set_tariff(2);
+    names.set_tariff(1);
+    assert_eq!(names.get_name(1).as_deref(), Some("Creuse"));
+    assert_eq!(names.get_name(2).as_deref(), Some("HEURE  PLEINE"));
+    assert_eq!(names.get_name(0), None);
+
+    // Tempo calendar names its 6 indexes, learned labels are dropped
+    names.set_calendar("TEMPO");
+    assert_eq!(names.get_name(3).as_deref(), Some("HC Blanc"));
+    assert_eq!(names.get_name(7), None);
+}
+
+#[test]
+fn period_aggregator() {
+    let stamp = |data: &str| match parse_test(data).unwrap() {
+        TicValue::DATE(value) => value,
+        _ => panic!("DATE not decoded"),
+    };
+    let mut daily = PeriodAggregator::new(EnergyPeriod::DAY, 2);
+    let mut weekly = PeriodAggregator::new(EnergyPeriod::WEEK, 2);
+
+    // friday 2023-11-10, first period is partial
+    for aggr in [&mut daily, &mut weekly] {
+        aggr.set_withdrawn(1000);
+        aggr.set_injected(10);
+        assert!(aggr.set_date(&stamp("DATE|H231110235959||Z\r\n")).is_none());
+        aggr.set_withdrawn(1400);
+    }
+
+    // saturday rolls day but not week
+    let record = daily.set_date(&stamp("DATE|H231111000001||Z\r\n")).unwrap();
+    assert_eq!((record.withdrawn, record.injected, record.partial), (400, 0, true));
+    assert!(weekly.set_date(&stamp("DATE|H231111000001||Z\r\n")).is_none());
+
+    // monday 2023-11-13 opens a new week
+    daily.set_withdrawn(1500);
+    weekly.set_withdrawn(1500);
+    let record = daily.set_date(&stamp("DATE|H231113000001||Z\r\n")).unwrap();
+    assert_eq!((record.withdrawn, record.partial), (100, false));
+    let record = weekly.set_date(&stamp("DATE|H231113000001||Z\r\n")).unwrap();
+    assert_eq!(record.withdrawn, 500);
+    assert_eq!(daily.get_history().len(), 2);
+    assert_eq!(daily.get_current().unwrap().withdrawn, 0);
+}
+
+#[test]
+fn cost_estimator() {
+    let stamp = |data: &str| match parse_test(data).unwrap() {
+        TicValue::DATE(value) => value,
+        _ => panic!("DATE not decoded"),
+    };
+    let mut prices = PriceTable::new();
+    assert!(prices.set_price(1, 0.2));
+    assert!(prices.set_tempo("red", 0.5, 0.75));
+    assert!(!prices.set_tempo("green", 0.1, 0.1));
+    assert_eq!(prices.get_price(6), Some(0.75));
+
+    let mut cost = CostEstimator::new(prices);
+    let mut totals = [0; TARIFF_INDEX_MAX];
+    totals[0] = 1000;
+    totals[5] = 2000;
+    totals[7] = 5000; // unpriced index
+    cost.set_totals(&totals);
+    assert!(cost.set_date(&stamp("DATE|H231130235959||Z\r\n")).is_none());
+    assert!((cost.get_today() - 1.7).abs() < 1e-9);
+
+    // last day of month closes both day and month
+    let rollover = cost.set_date(&stamp("DATE|H231201000001||Z\r\n")).unwrap();
+    assert!((rollover.day - 1.7).abs() < 1e-9);
+    assert!((rollover.month.unwrap() - 1.7).abs() < 1e-9);
+    totals[0] = 2000;
+    cost.set_totals(&totals);
+    assert!((cost.get_today() - 0.2).abs() < 1e-9);
+    let rollover = cost.set_date(&stamp("DATE|H231202000001||Z\r\n")).unwrap();
+    assert!(rollover.month.is_none());
+    assert!((cost.get_month() - 0.2).abs() < 1e-9);
+}
+
+#[test]
+fn state_file() {
+    let stamp = |data: &str| match parse_test(data).unwrap() {
+        TicValue::DATE(value) => value,
+        _ => panic!("DATE not decoded"),
+    };
+    let summer = stamp("DATE|E230710120000||Z\r\n");
+    let json = serde_json::to_string(&summer).unwrap();
+    assert_eq!(serde_json::from_str::<TimeStampData>(&json).unwrap(), summer);
+
+    let mut daily = PeriodAggregator::new(EnergyPeriod::DAY, 2);
+    daily.set_withdrawn(1000);
+    daily.set_date(&stamp("DATE|H231110080000||Z\r\n"));
+    daily.set_withdrawn(1400);
+    let mut tariffs = TariffAccumulator::new();
+    tariffs.set_tariff(2);
+    tariffs.set_total(1000);
+    tariffs.set_total(1400);
+    let mut cost = CostEstimator::new(PriceTable::new());
+    cost.set_totals(tariffs.get_total());
+    cost.set_date(&stamp("DATE|H231110080000||Z\r\n"));
+
+    let path = std::env::temp_dir().join(format!("linky-{}-state.json", std::process::id()));
+    let file = StateFile::new(path.to_str().unwrap());
+    assert_eq!(file.load().unwrap(), None);
+    let state = LinkyState {
+        periods: vec![daily.get_state()],
+        tariffs: Some(tariffs.get_state()),
+        cost: Some(cost.get_state()),
+    };
+    file.save(&state).unwrap();
+    let restored = file.load().unwrap().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(restored, state);
+
+    // restarted binder keeps counting the same day, energy while down is not lost
+    let mut daily = PeriodAggregator::new(EnergyPeriod::DAY, 2);
+    assert!(!PeriodAggregator::new(EnergyPeriod::WEEK, 2).set_state(&restored.periods[0]));
+    assert!(daily.set_state(&restored.periods[0]));
+    daily.set_withdrawn(1500);
+    assert!(daily.set_date(&stamp("DATE|H231110200000||Z\r\n")).is_none());
+    assert_eq!(daily.get_current().unwrap().withdrawn, 500);
+
+    let mut tariffs = TariffAccumulator::new();
+    tariffs.set_state(restored.tariffs.as_ref().unwrap());
+    assert!(tariffs.set_total(1500));
+    assert_eq!(tariffs.get_today()[1], 500);
+    assert!(!tariffs.set_date(&stamp("DATE|H231110200000||Z\r\n")));
+}
+
+#[test]
+fn power_headroom() {
+    let mut headroom = PowerHeadroom::new(600);
+    headroom.set_power(0, 4000);
+    assert_eq!(headroom.get_available(0), None);
+
+    // cutting power takes precedence over subscription
+    headroom.set_reference(9);
+    assert_eq!(headroom.get_available(0), Some(4400));
+    headroom.set_cutting(12);
+    assert_eq!(headroom.get_available(0), Some(7400));
+
+    // three phase splits limit and margin evenly
+    headroom.set_power(1, 2000);
+    headroom.set_power(2, 1000);
+    headroom.set_power(3, 4500);
+    assert_eq!(headroom.get_phases(), 3);
+    assert_eq!(headroom.get_available(1), Some(1800));
+    assert_eq!(headroom.get_available(3), Some(-700));
+}
+
+#[test]
+fn overload_predictor() {
+    let mut predictor = OverloadPredictor::new(10, 4);
+
+    // steady load never warns
+    for at in 0..4 {
+        assert_eq!(predictor.push(at as f64, 5000, 9200), None);
+    }
+
+    // +300VA/s ramp reaches 9200VA in 10s from 6200VA
+    assert_eq!(predictor.push(4.0, 5300, 9200), None);
+    assert_eq!(predictor.push(5.0, 5600, 9200), None);
+    assert_eq!(predictor.push(6.0, 5900, 9200), None);
+    assert_eq!(predictor.push(7.0, 6200, 9200), Some(OverloadEvent::WARNING(10.0)));
+    assert!(predictor.get_warning().is_some());
+
+    // load drop clears warning, reaching limit warns immediately
+    assert_eq!(predictor.push(8.0, 3000, 9200), Some(OverloadEvent::CLEARED));
+    assert_eq!(predictor.push(9.0, 9500, 9200), Some(OverloadEvent::WARNING(0.0)));
+}
+
+#[test]
+fn outage_tracker() {
+    let mut outages = OutageTracker::new(2);
+
+    // silence or link failure alone is not an outage
+    assert_eq!(outages.set_silent(1000), None);
+    assert_eq!(outages.set_data(2000), None);
+    assert_eq!(outages.set_link_lost(), None);
+    assert_eq!(outages.set_data(3000), None);
+
+    // both make one outage starting with last valid data
+    let start = OutageRecord { start: 4000, end: None };
+    assert_eq!(outages.set_link_lost(), None);
+    assert_eq!(outages.set_silent(4000), Some(OutageEvent::START(start)));
+    assert_eq!(outages.set_silent(4000), None);
+    assert_eq!(outages.get_active().unwrap().get_duration(9000), 5000);
+    let end = OutageRecord { start: 4000, end: Some(64000) };
+    assert_eq!(outages.set_data(64000), Some(OutageEvent::END(end)));
+    assert_eq!(end.get_duration(99000), 60000);
+    assert!(outages.get_active().is_none());
+
+    // oldest records are dropped
+    for start in [70000, 80000] {
+        outages.set_silent(start);
+        outages.set_link_lost();
+        outages.set_data(start + 5000);
+    }
+    let starts: Vec<u64> = outages.get_records().iter().map(|record| record.start).collect();
+    assert_eq!(starts, vec![70000, 80000]);
+}
+
+#[test]
+fn fault_tracker() {
+    // failed reopen is pushed again, source gives up on third fault
+    let mut faults = FaultTracker::new(3);
+    assert_eq!(faults.push(false), FaultAction::Ignore);
+    faults.set_data();
+    assert_eq!(faults.push(true), FaultAction::Reopen);
+    assert_eq!(faults.push(true), FaultAction::Reopen);
+    assert!(!faults.is_failed());
+    assert_eq!(faults.push(true), FaultAction::Fail);
+    assert!(faults.is_failed());
+    assert_eq!(faults.push(true), FaultAction::Ignore);
+    faults.reset();
+    assert_eq!(faults.push(true), FaultAction::Reopen);
+
+    // 0 never gives up
+    let mut faults = FaultTracker::new(0);
+    for _ in 0..100 {
+        assert_eq!(faults.push(true), FaultAction::Reopen);
+    }
+    assert_eq!(faults.get_faults(), 100);
+    assert!(!faults.is_failed());
+}
+
+#[test]
+fn power_factor() {
+    match parse_test("CCASN|H231110100000|03600|Z\r\n") {
+        Ok(TicValue::CCASN(point)) => assert_eq!(point.get_value(), 3600),
+        _ => panic!("CCASN not decoded"),
+    }
+    assert_eq!(parse_test("CCASN-1|H231110093000|03200|Z\r\n").unwrap(), TicValue::UNSET);
+
+    // apparent power is averaged between two load curve points
+    let mut factor = PowerFactor::new();
+    assert_eq!(factor.set_active(3600), None);
+    factor.push_apparent(3800);
+    factor.push_apparent(4200);
+    assert_eq!(factor.set_active(3600), Some(0.9));
+    factor.push_apparent(3000);
+    assert_eq!(factor.set_active(3600), Some(1.0));
+}
+
+#[test]
+fn phase_imbalance() {
+    let mut imbalance = PhaseImbalance::new(20);
+    imbalance.set_phase(1, 10);
+    imbalance.set_phase(2, 10);
+    assert_eq!(imbalance.update(), None);
+
+    // mean 10A, phase 3 at 13A is 30% away
+    imbalance.set_phase(3, 13);
+    imbalance.set_phase(2, 7);
+    assert_eq!(imbalance.get_imbalance(), Some(30));
+    assert_eq!(imbalance.update(), Some(true));
+    assert_eq!(imbalance.update(), None);
+
+    imbalance.set_phase(3, 10);
+    imbalance.set_phase(2, 10);
+    assert_eq!(imbalance.update(), Some(false));
+}
+
+#[test]
+fn evse_energy() {
+    let mut energy = EvseEnergy::new();
+    energy.set_current(1, 12);
+    energy.set_subscribed(9);
+    assert_eq!(energy.get_phases(), 1);
+    assert_eq!(energy.get_subscribed_current(), Some(39));
+
+    // flag only reports transitions
+    assert!(energy.set_over_current(1, true));
+    assert!(!energy.set_over_current(1, true));
+    assert!(!energy.set_over_current(4, true));
+    assert_eq!(energy.get_over_currents(), [true, false, false]);
+
+    // any phase 2-3 value switches to three phase, subscription is split
+    energy.set_tension(2, 231);
+    assert_eq!(energy.get_phases(), 3);
+    assert_eq!(energy.get_subscribed_current(), Some(13));
+    assert!(energy.set_over_current(1, false));
+}
+
+#[test]
+fn charge_limit() {
+    let mut limit = ChargeLimit::new(2, 6, 32, 2, 1);
+    limit.set_current(1, 10);
+    assert_eq!(limit.update(0.0), None);
+
+    // 45A subscription, 10A household and 2A margin
+    limit.set_subscribed(45);
+    assert_eq!(limit.update(0.0), Some(32));
+
+    // EV draws its limit, other loads toggling within hysteresis are ignored
+    limit.set_current(1, 42);
+    assert_eq!(limit.update(1.0), None);
+    limit.set_current(1, 47);
+    assert_eq!(limit.update(2.0), Some(28));
+    limit.set_current(1, 42);
+    assert_eq!(limit.update(3.0), None);
+
+    // larger headroom ramps by 1A/s
+    limit.set_current(1, 33);
+    assert_eq!(limit.update(4.0), Some(29));
+    assert_eq!(limit.update(7.0), Some(32));
+
+    // below 6A charge pauses, resumes at min once hysteresis is reached
+    limit.set_current(1, 70);
+    assert_eq!(limit.update(8.0), Some(0));
+    limit.set_current(1, 36);
+    assert_eq!(limit.update(9.0), None);
+    limit.set_current(1, 35);
+    assert_eq!(limit.update(10.0), Some(6));
+
+    // three phase PREF is split on nominal tension
+    let mut limit = ChargeLimit::new(0, 6, 32, 2, 1);
+    limit.set_reference(12);
+    limit.set_current(2, 5);
+    assert_eq!(limit.get_subscribed(), Some(17));
+    assert_eq!(limit.update(0.0), Some(12));
+}
+
+#[test]
+fn charging_planner() {
+    let profile = match parse_test("PJOURF+1|00004001 0600C002 2200C001 NONUTILE|Z\r\n").unwrap() {
+        TicValue::PJOURF1(profile) => profile,
+        _ => panic!("PJOURF+1 not decoded"),
+    };
+
+    // index 2 listed as reduced, max follows subscription
+    let mut planner = ChargingPlanner::new(0, 1400, vec![2]);
+    planner.set_profile(profile.clone());
+    assert_eq!(planner.get_schedule(), None);
+    planner.set_subscribed(9);
+    let starts: Vec<(u32, i32)> = planner
+        .get_schedule()
+        .unwrap()
+        .iter()
+        .map(|period| (period.start, period.limit))
+        .collect();
+    assert_eq!(starts, vec![(0, 9000), (21600, 1400), (79200, 9000)]);
+
+    // index priced above threshold is reduced
+    let mut prices = PriceTable::new();
+    prices.set_price(1, 0.15);
+    prices.set_price(2, 0.25);
+    let mut planner = ChargingPlanner::new(7400, 0, Vec::new());
+    planner.set_prices(prices, 0.20);
+    planner.set_profile(profile);
+    assert_eq!(planner.get_schedule().unwrap()[1].limit, 0);
+
+    // peak day calendar replaces next day one
+    let peak = match parse_test("PPOINTE|00004002|Z\r\n").unwrap() {
+        TicValue::PPOINTE(profile) => profile,
+        _ => panic!("PPOINTE not decoded"),
+    };
+    planner.set_peak_profile(peak);
+    assert_eq!(planner.get_source(), "PJOURF1");
+    planner.set_peak_day(true);
+    assert_eq!(planner.get_source(), "PPOINTE");
+    let schedule = planner.get_schedule().unwrap();
+    assert_eq!(schedule, vec![ChargingPeriod { start: 0, index: 2, limit: 0 }]);
+}
+
+#[test]
+fn voltage_monitor() {
+    let mut monitor = VoltageMonitor::new(207, 253, 2.0);
+
+    // short sag is ignored
+    assert_eq!(monitor.push(1, 0.0, 200), None);
+    assert_eq!(monitor.push(1, 1.0, 230), None);
+
+    // sag lasting 2s is reported once
+    assert_eq!(monitor.push(1, 2.0, 200), None);
+    let sag = monitor.push(1, 4.0, 198).unwrap();
+    assert_eq!((sag.kind, sag.phase, sag.volts), (VoltageKind::SAG, 1, 198));
+    assert_eq!(monitor.push(1, 5.0, 198), None);
+    assert_eq!(monitor.get_state(1), Some(VoltageKind::SAG));
+
+    // zero duration reports swell immediately
+    let mut monitor = VoltageMonitor::new(207, 253, 0.0);
+    assert!(monitor.push(3, 0.0, 260).is_some());
+    assert_eq!(monitor.get_swells(), &[0, 0, 1]);
+}
+
+#[test]
+fn injection_detector() {
+    // without SINSTI, EAIT progression drives the state
+    let mut detector = InjectionDetector::new(0, 60.0);
+    assert_eq!(detector.set_energy(0.0, 1000), None);
+    assert_eq!(detector.set_energy(2.0, 1001), Some(true));
+    assert_eq!(detector.set_energy(30.0, 1001), None);
+    assert_eq!(detector.set_energy(62.0, 1001), Some(false));
+
+    // SINSTI takes precedence once received
+    assert_eq!(detector.set_power(0), None);
+    assert_eq!(detector.set_energy(64.0, 1002), None);
+    assert_eq!(detector.set_power(850), Some(true));
+    assert_eq!(detector.is_producing(), Some(true));
+}
+
+#[test]
+fn shedding_advisor() {
+    let load = |uid: &str, priority, power| SheddingLoad {
+        uid: uid.to_string(),
+        priority,
+        power,
+    };
+    let advisor = SheddingAdvisor::new(vec![
+        load("heater", 2, 2000),
+        load("evse", 1, 7400),
+        load("pool", 3, 1500),
+    ]);
+
+    assert!(advisor.get_plan(0).loads.is_empty());
+    assert_eq!(advisor.get_plan(8000).loads, vec!["evse", "heater"]);
+    let plan = advisor.get_plan(20000);
+    assert_eq!(plan.shed, 10900);
+    assert!(!plan.is_covered());
+}
+
+#[test]
+fn self_consumption() {
+    let stamp = |data: &str| match parse_test(data).unwrap() {
+        TicValue::DATE(value) => value,
+        _ => panic!("DATE not decoded"),
+    };
+    let mut selfconso = SelfConsumption::new(7);
+    selfconso.set_withdrawn(5000);
+    selfconso.set_injected(100);
+    selfconso.set_production(20000);
+    selfconso.set_date(&stamp("DATE|H231110080000||Z\r\n"));
+
+    // 4kWh produced, 1kWh exported
+    selfconso.set_injected(1100);
+    selfconso.set_production(24000);
+    let day = selfconso.set_date(&stamp("DATE|H231111000001||Z\r\n")).unwrap();
+    assert_eq!(day.get_ratio(), Some(0.75));
+    assert_eq!(selfconso.get_history().len(), 1);
+
+    assert_eq!(selfconso.get_instant(), None);
+    selfconso.set_production_power(2000);
+    selfconso.set_injected_power(500);
+    assert_eq!(selfconso.get_instant(), Some(0.75));
+}
+
+#[test]
+fn ramp_rate() {
+    let mut ramp = RampRate::new(5.0, 500);
+    assert_eq!(ramp.push(0.0, 1000), None);
+    assert_eq!(ramp.push(2.0, 1400), None);
+
+    // oven switching on, 3000VA within the 5s window
+    assert_eq!(ramp.push(4.0, 4000), Some(750.0));
+    assert_eq!(ramp.push(5.0, 4000), None);
+
+    // old samples leave the window and ramp falls back
+    assert_eq!(ramp.push(10.0, 4000), None);
+    assert_eq!(ramp.get_ramp(), Some(0.0));
+    assert_eq!(ramp.push(11.0, 500), Some(-3500.0));
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn sample_store() {
+    let uids = vec!["SINSTS".to_string(), "EAST".to_string()];
+    let mut store = SampleStore::open(":memory:", 3600, 10, uids).unwrap();
+    assert!(!store.insert("IINST", 1000, &[12]).unwrap());
+    assert!(store.insert("SINSTS", 1000, &[4000, 1000, 1500, 1500]).unwrap());
+
+    // downsampled within 10s interval
+    assert!(!store.insert("SINSTS", 5000, &[4200, 1200, 1500, 1500]).unwrap());
+    assert!(store.insert("SINSTS", 11000, &[500, 100, 200, 200]).unwrap());
+    assert!(store.insert("EAST", 12000, &[123456]).unwrap());
+
+    let samples = store.query("SINSTS", 0, u64::MAX, 10).unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0], (1000, vec![4000, 1000, 1500, 1500]));
+    assert_eq!(samples[1].0, 11000);
+    assert_eq!(store.query("SINSTS", 2000, u64::MAX, 10).unwrap().len(), 1);
+    assert_eq!(store.query("SINSTS", 0, u64::MAX, 1).unwrap().len(), 1);
+
+    // one hour retention
+    assert_eq!(store.purge(3600 * 1000 + 11500).unwrap(), 8);
+    assert_eq!(store.query("EAST", 0, u64::MAX, 10).unwrap(), vec![(12000, vec![123456])]);
+}
+
+#[test]
+fn csv_sink() {
+    let dir = std::env::temp_dir().join(format!("linky-csv-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("linky.csv");
+    let path = path.to_str().unwrap();
+
+    let mut sink = CsvSink::new(path, vec!["SINSTS".to_string()], 64, true);
+    assert!(!sink.append("IINST", 1000, &[12]).unwrap());
+    assert!(sink.append("SINSTS", 1000, &[4000, 1000]).unwrap());
+    let text = std::fs::read_to_string(path).unwrap();
+    assert_eq!(text, "timestamp,uid,index,value\n1000,SINSTS,0,4000\n1000,SINSTS,1,1000\n");
+
+    // file went over 64 bytes, next sample rotates it
+    assert!(sink.append("SINSTS", 2000, &[500, 100]).unwrap());
+    assert!(std::fs::metadata(format!("{}.2000", path)).is_ok());
+    let text = std::fs::read_to_string(path).unwrap();
+    assert_eq!(text, "timestamp,uid,index,value\n2000,SINSTS,0,500\n2000,SINSTS,1,100\n");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "influx")]
+fn influx_sink() {
+    let mut sink =
+        InfluxSink::new("http://127.0.0.1:9", "home", "linky", "", "linky", 3, Vec::new()).unwrap();
+    assert!(sink.push("SINSTS", 1000, &[4000, 1000]));
+    assert_eq!(sink.get_pending()[0], "linky,uid=SINSTS,index=0 value=4000i 1000");
+
+    // buffer keeps the 3 newest lines
+    assert!(sink.push("EAST", 2000, &[123456, 0]));
+    assert_eq!(sink.get_dropped(), 1);
+    assert_eq!(sink.get_pending()[0], "linky,uid=SINSTS,index=1 value=1000i 1000");
+
+    // unreachable database keeps lines for next flush
+    assert!(sink.flush().is_err());
+    assert_eq!(sink.get_pending().len(), 3);
+}
+
+#[test]
+#[cfg(feature = "redis")]
+fn redis_sink() {
+    assert_eq!(redis_command(&["PING"]), b"*1\r\n$4\r\nPING\r\n".to_vec());
+    let reply = redis_reply(b"$15\r\n1699610899123-0\r\n+OK");
+    assert_eq!(reply, Some((22, Ok("1699610899123-0".to_string()))));
+    assert_eq!(redis_reply(b"-ERR wrong\r\n"), Some((12, Err("ERR wrong".to_string()))));
+    assert_eq!(redis_reply(b"$15\r\n1699"), None);
+    let reply = redis_reply(b"$9223372036854775807\r\n1699");
+    assert!(matches!(reply, Some((26, Err(_)))));
+
+    let mut sink = RedisSink::new("127.0.0.1", 9, "", "linky", 100, 2, vec!["EAST".to_string()]);
+    assert!(!sink.push("SINSTS", 1000, &[4000, 1000]));
+    assert!(sink.push("EAST", 1000, &[123456, 0]));
+    let entry = sink.get_pending()[0].join(" ");
+    assert_eq!(entry, "XADD linky MAXLEN ~ 100 * uid EAST ts 1000 values 123456,0");
+
+    // buffer keeps the 2 newest entries, unreachable server keeps them for next flush
+    assert!(sink.push("EAST", 2000, &[123457, 0]));
+    assert!(sink.push("EAST", 3000, &[123458, 0]));
+    assert_eq!(sink.get_dropped(), 1);
+    assert!(sink.flush().is_err());
+    assert_eq!(sink.get_pending().len(), 2);
+}
+
+#[test]
+#[cfg(feature = "mqtt")]
+fn mqtt_packets() {
+    let packet = mqtt_publish_packet("a/b", b"xy", false);
+    assert_eq!(packet, b"\x30\x07\x00\x03a/bxy".to_vec());
+    assert_eq!(mqtt_decode(&packet[0..4]), None);
+    let (consumed, header, body) = mqtt_decode(&packet).unwrap();
+    assert_eq!(consumed, packet.len());
+    let message = mqtt_decode_publish(header, body).unwrap();
+    assert_eq!(message, ("a/b".to_string(), b"xy".to_vec()));
+
+    // remaining length above 127 takes two bytes
+    let packet = mqtt_publish_packet("t", &[0u8; 200], false);
+    assert_eq!(&packet[0..3], &[0x30, 0xCB, 0x01]);
+
+    let will = MqttWill {
+        topic: "w",
+        payload: b"!",
+        retain: false,
+    };
+    let packet = mqtt_connect_packet("id", 30, "user", "", Some(&will));
+    assert_eq!(&packet[2..10], b"\x00\x04MQTT\x04\x8e");
+}
+
+#[test]
+#[cfg(feature = "mqtt")]
+fn sparkplug_node() {
+    let mut node = SparkplugNode::new("linky", "meter", Vec::new());
+    let (topic, death) = node.new_session();
+    assert_eq!(topic, "spBv1.0/linky/NDEATH/meter");
+    assert!(death.ends_with(&[0x58, 1]));
+    assert!(node.data(1000).is_none());
+
+    assert!(node.set_value(&TicObject::IRMS, &[10, 11, 12]));
+    assert!(!node.is_born());
+    let (topic, birth) = node.birth(1000);
+    assert_eq!(topic, "spBv1.0/linky/NBIRTH/meter");
+    assert!(birth.windows(6).any(|name| name == b"IRMS/2"));
+    assert!(birth.windows(7).any(|unit| unit == b"engUnit"));
+    assert!(node.data(2000).is_none());
+
+    // only changed values are sent by alias, without name
+    node.set_value(&TicObject::IRMS, &[10, 13, 12]);
+    let (topic, data) = node.data(2000).unwrap();
+    assert_eq!(topic, "spBv1.0/linky/NDATA/meter");
+    assert!(!data.windows(4).any(|name| name == b"IRMS"));
+    assert!(data.ends_with(&[0x18, 1]));
+    assert_ne!(spb_alias("IRMS", 1), spb_alias("IRMS", 2));
+
+    // NCMD { metrics:[{ name:"Node Control/Rebirth", datatype:Boolean, boolean_value:true }] }
+    let mut metric = vec![0x0a, 20];
+    metric.extend_from_slice(SPARKPLUG_REBIRTH.as_bytes());
+    metric.extend_from_slice(&[0x20, 11, 0x70, 1]);
+    let mut ncmd = vec![0x12, metric.len() as u8];
+    ncmd.extend_from_slice(&metric);
+    assert!(spb_is_rebirth(&ncmd));
+    let last = ncmd.len() - 1;
+    ncmd[last] = 0;
+    assert!(!spb_is_rebirth(&ncmd));
+
+    // length varint close to u64::MAX must not overflow offset
+    let mut ncmd = vec![0x12];
+    ncmd.extend_from_slice(&[0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]);
+    assert!(!spb_is_rebirth(&ncmd));
+}
+
+#[test]
+fn ocpp_meter() {
+    assert_eq!(epoch_rfc3339(1699610899123), "2023-11-10T10:08:19.123Z");
+    assert_eq!(epoch_rfc3339(951782400000), "2000-02-29T00:00:00.000Z");
+    assert_eq!(OcppVersion::from_label("2.0.1"), Some(OcppVersion::V201));
+
+    let mut meter = OcppMeter::new();
+    assert!(meter.get_samples().is_empty());
+    meter.set_energy(123456);
+    meter.set_power(2300);
+    meter.set_current(2, 10);
+    let samples = meter.get_samples();
+    assert_eq!(samples.len(), 3);
+    assert_eq!(samples[0].measurand, "Energy.Active.Import.Register");
+    assert_eq!(samples[0].value, 123456.0);
+    assert_eq!(samples[2].measurand, "Current.Import");
+    assert_eq!(samples[2].phase, Some("L2"));
+}
+
+#[test]
+#[cfg(feature = "modbus")]
+fn modbus_registers() {
+    let mut regs = ModbusRegisters::new();
+    regs.set_tic(&TicValue::EAST(123456));
+    regs.set_tic(&TicValue::URMS2(231));
+
+    // read EAST pair, transaction 0x0102 unit 1
+    let request = [0x01, 0x02, 0, 0, 0, 6, 1, 0x03, 0, 5, 0, 2];
+    let (consumed, reply) = modbus_reply(&regs, &request).unwrap();
+    assert_eq!(consumed, 12);
+    assert_eq!(reply, vec![0x01, 0x02, 0, 0, 0, 7, 1, 0x03, 4, 0x00, 0x01, 0xE2, 0x40]);
+
+    // URMS1 not received yet, URMS2 known
+    let request = [0, 1, 0, 0, 0, 6, 1, 0x04, 0, 16, 0, 2];
+    let (_, reply) = modbus_reply(&regs, &request).unwrap();
+    assert_eq!(&reply[9..], &[0xFF, 0xFF, 0, 231]);
+
+    // incomplete request, out of map and unsupported function
+    assert!(modbus_reply(&regs, &request[0..10]).is_none());
+    let request = [0, 1, 0, 0, 0, 6, 1, 0x03, 0, 20, 0, 10];
+    assert_eq!(&modbus_reply(&regs, &request).unwrap().1[7..], &[0x83, 0x02]);
+    let request = [0, 1, 0, 0, 0, 6, 1, 0x06, 0, 5, 0, 1];
+    assert_eq!(&modbus_reply(&regs, &request).unwrap().1[7..], &[0x86, 0x01]);
+
+    // out of spec MBAP length is rejected without waiting for its payload
+    let request = [0, 1, 0, 0, 0x01, 0x2C, 1, 0x03];
+    assert_eq!(modbus_reply(&regs, &request), Some((8, Vec::new())));
+}
+
+#[cfg(feature = "dbus")]
+#[test]
+fn dbus_export() {
+    let mut values = std::collections::BTreeMap::new();
+    values.insert("SINSTS".to_string(), vec![4000, 1000, 1500, 1500]);
+
+    // replies need the serial a bus would have given to the call
+    let call = |member: &str| {
+        let mut msg = dbus::Message::new_method_call(DBUS_NAME, DBUS_PATH, DBUS_IFACE, member);
+        msg.as_mut().unwrap().set_serial(1);
+        msg.unwrap()
+    };
+    let reply = dbus_reply(&values, &call("ListSensors")).unwrap();
+    assert_eq!(reply.read1::<Vec<String>>().unwrap(), vec!["SINSTS".to_string()]);
+
+    let reply = dbus_reply(&values, &call("GetValue").append1("SINSTS")).unwrap();
+    assert_eq!(reply.read1::<Vec<i64>>().unwrap(), vec![4000, 1000, 1500, 1500]);
+
+    let reply = dbus_reply(&values, &call("GetValue").append1("EAST")).unwrap();
+    assert_eq!(reply.msg_type(), dbus::message::MessageType::Error);
+}
+
+#[cfg(feature = "dtls")]
+#[test]
+fn udp_dtls() {
+    use std::net::UdpSocket;
+    use std::time::{Duration, Instant};
+
+    let key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+    assert!(DtlsPsk::new("gateway", "0001").is_err());
+    assert!(DtlsPsk::new("gateway", &key.replace('0', "x")).is_err());
+    assert!(DtlsPsk::new("", key).is_err());
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    drop(socket);
+
+    let mut source = NetworkHandle::new("127.0.0.1", port, None, None).unwrap();
+    source.set_dtls("gateway", key).unwrap();
+    let handle = LinkyHandle::from_source("127.0.0.1", "udp".to_string(), Box::new(source));
+    let target = format!("127.0.0.1:{}", port);
+
+    // listener drives handshakes from read, gateways run in their own thread
+    let poll = |buffer: &mut [u8], done: &dyn Fn() -> bool| -> Option<usize> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            match handle.read(buffer) {
+                Ok(count) => return Some(count),
+                Err(LinkyError::RetryLater) if done() => return None,
+                Err(LinkyError::RetryLater) => std::thread::sleep(Duration::from_millis(5)),
+                Err(error) => panic!("unexpected error:{:?}", error),
+            }
+        }
+        None
+    };
+    let gateway = |identity: &'static str, key: String, lines: Vec<&'static [u8]>| {
+        let target = target.clone();
+        std::thread::spawn(move || {
+            let mut client = DtlsClient::connect(&target, &DtlsPsk::new(identity, &key)?)?;
+            for line in lines {
+                client.send(line)?;
+            }
+            Ok::<DtlsClient, LinkyFail>(client)
+        })
+    };
+    let mut buffer = [0u8; 64];
+
+    // plain datagram is dropped
+    let plain = UdpSocket::bind("127.0.0.1:0").unwrap();
+    plain.send_to(b"EAST\t000001234\t#\r\n", &target).unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(matches!(handle.read(&mut buffer), Err(LinkyError::RetryLater)));
+
+    // foreign key and foreign identity never get a session, failed records are silently
+    // discarded and gateway only gives up on its handshake timeout
+    for (identity, key) in [("gateway", key.replace('0', "f")), ("other", key.to_string())] {
+        let _thread = gateway(identity, key, vec![b"EAST\t000001234\t#\r\n"]);
+        let start = Instant::now();
+        let done = || start.elapsed() > Duration::from_millis(500);
+        assert_eq!(poll(&mut buffer, &done), None);
+    }
+
+    // two gateways, each one with its own session and record sequence
+    let first = gateway("gateway", key.to_string(), vec![b"EAST\t000001234\t#\r\n"]);
+    let count = poll(&mut buffer, &|| false).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001234\t#\r\n");
+    let mut first = first.join().unwrap().unwrap();
+    let second = gateway("gateway", key.to_string(), vec![b"EAST\t000001235\t#\r\n"]);
+    let count = poll(&mut buffer, &|| false).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001235\t#\r\n");
+    second.join().unwrap().unwrap();
+    first.send(b"EAST\t000001236\t#\r\n").unwrap();
+    let count = poll(&mut buffer, &|| false).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001236\t#\r\n");
+
+    // reopen drops sessions, a new handshake from the same gateway is accepted
+    handle.reopen().unwrap();
+    first.send(b"EAST\t000001237\t#\r\n").unwrap();
+    assert_eq!(poll(&mut buffer, &|| true), None);
+    let third = gateway("gateway", key.to_string(), vec![b"EAST\t000001238\t#\r\n"]);
+    let count = poll(&mut buffer, &|| false).unwrap();
+    assert_eq!(&buffer[0..count], b"EAST\t000001238\t#\r\n");
+    third.join().unwrap().unwrap();
+
+    // dtls is unicast only
+    let mut source = NetworkHandle::new("0.0.0.0", 0, Some("239.0.0.1"), None).unwrap();
+    assert!(source.set_dtls("gateway", key).is_err());
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn stream_udp() {
+    use std::future::poll_fn;
+    use std::net::UdpSocket;
+    use std::pin::Pin;
+    use std::time::Duration;
+    use futures_core::Stream;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    drop(socket);
+
+    async fn next(stream: &mut LinkyStream) -> Option<Result<TicValue, LinkyError>> {
+        poll_fn(|context| Pin::new(&mut *stream).poll_next(context)).await
+    }
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let source = NetworkHandle::new("127.0.0.1", port, None, None).unwrap();
+        let handle = LinkyHandle::from_source("127.0.0.1", "udp".to_string(), Box::new(source));
+        let mut stream = LinkyStream::from_handle(handle, 0).unwrap();
+
+        // nothing sent yet, stream waits on the socket
+        let idle = tokio::time::timeout(Duration::from_millis(50), next(&mut stream)).await;
+        assert!(idle.is_err());
+
+        let gateway = UdpSocket::bind("127.0.0.1:0").unwrap();
+        gateway.send_to(b"EAST\t000054878\t/\r\nIRMS1\t003\t1\r\n", ("127.0.0.1", port)).unwrap();
+        assert_eq!(next(&mut stream).await, Some(Ok(TicValue::EAST(54878))));
+        assert_eq!(next(&mut stream).await, Some(Ok(TicValue::IRMS1(3))));
+        let idle = tokio::time::timeout(Duration::from_millis(50), next(&mut stream)).await;
+        assert!(idle.is_err());
+        assert_eq!(stream.get_handle().get_stats().lines, 2);
+    });
 }
@@ -0,0 +1,359 @@
+/*
+ * Copyright (C) 2015-2022 IoT.bzh Company
+ * Author: Fulup Ar Foll <fulup@iot.bzh>
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *   http://www.apache.org/licenses/LICENSE-2.0
+ *
+ */
+
+// emit TIC frames from a capture or synthetic values to udp or a pty at meter pacing,
+// 'linky-simulator --synthetic standard --pty' then point binder serial device to printed pty
+
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::Write;
+use std::net::UdpSocket;
+use std::os::unix::io::FromRawFd;
+use std::process::exit;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use linky::prelude::*;
+
+const FRAME_STX: u8 = 0x02;
+const FRAME_ETX: u8 = 0x03;
+const CHAR_BITS: u64 = 10; // 7E1 with start and stop bits
+
+const USAGE: &str = "usage: linky-simulator <source> <target> [options]
+source:
+  --file PATH                  replay a plain, gzip or zstd capture
+  --synthetic standard|historic generate frames with evolving values
+target:
+  --udp HOST:PORT              one datagram per group
+  --pty                        create a pseudo terminal and print its device name
+options:
+  --speed BAUD                 pacing, default 9600 standard, 1200 historic
+  --repeat                     restart capture at its end
+  --psk IDENTITY:HEXKEY        dtls session to a udp listener with the same pre-shared key";
+
+#[derive(Clone, Copy, PartialEq)]
+enum SimMode {
+    Standard,
+    Historic,
+}
+
+enum SimSource {
+    Capture(ReplayHandle),
+    Synthetic { mode: SimMode, state: SimState },
+}
+
+enum SimTarget {
+    Udp(UdpSocket, String),
+    #[cfg(feature = "dtls")]
+    Dtls(Option<DtlsClient>, String, DtlsPsk),
+    Pty(File),
+}
+
+struct SimArgs {
+    source: SimSource,
+    target: SimTarget,
+    speed: u32,
+    repeat: bool,
+}
+
+// synthetic meter state, energy follows integrated power
+struct SimState {
+    tick: u64,
+    energy: u64,
+    power: u32,
+}
+
+fn to_static_str(value: String) -> &'static str {
+    Box::leak(value.into_boxed_str())
+}
+
+// group with its checksum, standard mode sums up to last HT, historic mode stops before last SP
+fn tic_group(mode: SimMode, label: &str, horodate: Option<&str>, value: &str) -> Vec<u8> {
+    let data = match (mode, horodate) {
+        (SimMode::Historic, _) => format!("{} {}", label, value),
+        (SimMode::Standard, None) => format!("{}\t{}\t", label, value),
+        (SimMode::Standard, Some(stamp)) => format!("{}\t{}\t{}\t", label, stamp, value),
+    };
+    let sum: u64 = data.bytes().map(|byte| byte as u64).sum();
+    let checksum = ((sum & 0x3f) as u8 + 0x20) as char;
+    let group = match mode {
+        SimMode::Historic => format!("\n{} {}\r", data, checksum),
+        SimMode::Standard => format!("\n{}{}\r", data, checksum),
+    };
+    group.into_bytes()
+}
+
+// 'SAAMMJJhhmmss' from UTC clock, season is not tracked
+fn tic_horodate() -> String {
+    let now = match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(value) => value.as_millis() as u64,
+        Err(_) => 0,
+    };
+    let date: String = epoch_rfc3339(now)
+        .chars()
+        .skip(2)
+        .take(17)
+        .filter(|chr| chr.is_ascii_digit())
+        .collect();
+    format!("H{}", date)
+}
+
+impl SimState {
+    fn new() -> SimState {
+        SimState {
+            tick: 0,
+            energy: 54878,
+            power: 750,
+        }
+    }
+
+    // slow power random walk within 200..6000 VA
+    fn next_frame(&mut self, mode: SimMode, period: Duration) -> Vec<Vec<u8>> {
+        self.tick += 1;
+        let step = (self.tick.wrapping_mul(2654435761) >> 7) % 301;
+        self.power = (self.power as i64 + step as i64 - 150).clamp(200, 6000) as u32;
+        self.energy += self.power as u64 * period.as_millis() as u64 / 3_600_000 + 1;
+        let current = format!("{:03}", (self.power + 115) / 230);
+
+        let mut groups = Vec::new();
+        match mode {
+            SimMode::Standard => {
+                let stamp = tic_horodate();
+                groups.push(tic_group(mode, "ADSC", None, "041876097152"));
+                groups.push(tic_group(mode, "VTIC", None, "02"));
+                groups.push(tic_group(mode, "DATE", Some(&stamp), ""));
+                groups.push(tic_group(mode, "NGTF", None, "     BASE       "));
+                groups.push(tic_group(mode, "LTARF", None, "      BASE      "));
+                groups.push(tic_group(mode, "EAST", None, &format!("{:09}", self.energy)));
+                groups.push(tic_group(mode, "EASF01", None, &format!("{:09}", self.energy)));
+                groups.push(tic_group(mode, "IRMS1", None, &current));
+                groups.push(tic_group(mode, "URMS1", None, "230"));
+                groups.push(tic_group(mode, "PREF", None, "06"));
+                groups.push(tic_group(mode, "PCOUP", None, "06"));
+                groups.push(tic_group(mode, "SINSTS", None, &format!("{:05}", self.power)));
+                groups.push(tic_group(mode, "STGE", None, "003A0001"));
+                groups.push(tic_group(mode, "RELAIS", None, "000"));
+                groups.push(tic_group(mode, "NTARF", None, "01"));
+            }
+            SimMode::Historic => {
+                groups.push(tic_group(mode, "ADCO", None, "031762120543"));
+                groups.push(tic_group(mode, "OPTARIF", None, "BASE"));
+                groups.push(tic_group(mode, "ISOUSC", None, "30"));
+                groups.push(tic_group(mode, "BASE", None, &format!("{:09}", self.energy)));
+                groups.push(tic_group(mode, "PTEC", None, "TH.."));
+                groups.push(tic_group(mode, "IINST", None, &current));
+                groups.push(tic_group(mode, "IMAX", None, "090"));
+                groups.push(tic_group(mode, "PAPP", None, &format!("{:05}", self.power)));
+                groups.push(tic_group(mode, "HHPHC", None, "A"));
+                groups.push(tic_group(mode, "MOTDETAT", None, "000000"));
+            }
+        }
+
+        // STX opens first group, ETX closes last one
+        if let Some(first) = groups.first_mut() {
+            first.insert(0, FRAME_STX);
+        }
+        if let Some(last) = groups.last_mut() {
+            last.push(FRAME_ETX);
+        }
+        groups
+    }
+}
+
+// master side of a new pty, slave name is printed for the binder config
+fn open_pty() -> Result<File, String> {
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC);
+        if fd < 0 || libc::grantpt(fd) < 0 || libc::unlockpt(fd) < 0 {
+            return Err(format!("fail to create pty: {}", std::io::Error::last_os_error()));
+        }
+        let name = libc::ptsname(fd);
+        if name.is_null() {
+            libc::close(fd);
+            return Err("fail to get pty name".to_string());
+        }
+        println!("linky-simulator: serial device {}", CStr::from_ptr(name).to_string_lossy());
+        Ok(File::from_raw_fd(fd))
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<SimArgs, String> {
+    let mut source = None;
+    let mut target = None;
+    let mut speed = None;
+    let mut repeat = false;
+    let mut psk = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--repeat" => repeat = true,
+            "--pty" => target = Some(SimTarget::Pty(open_pty()?)),
+            _ => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None => return Err(format!("missing value for {}", arg)),
+                };
+                match arg.as_str() {
+                    "--file" => source = Some(value),
+                    "--synthetic" => source = Some(format!("@{}", value)),
+                    "--udp" => {
+                        let socket = match UdpSocket::bind("0.0.0.0:0") {
+                            Ok(value) => value,
+                            Err(error) => return Err(format!("fail to bind udp: {}", error)),
+                        };
+                        target = Some(SimTarget::Udp(socket, value));
+                    }
+                    "--psk" => psk = Some(value),
+                    "--speed" => match value.parse::<u32>() {
+                        Ok(value) if value > 0 => speed = Some(value),
+                        _ => return Err(format!("invalid speed:{}", value)),
+                    },
+                    _ => return Err(format!("unknown option:{}", arg)),
+                }
+            }
+        }
+    }
+
+    let (source, default_speed) = match source.as_deref() {
+        Some("@standard") => (
+            SimSource::Synthetic {
+                mode: SimMode::Standard,
+                state: SimState::new(),
+            },
+            9600,
+        ),
+        Some("@historic") => (
+            SimSource::Synthetic {
+                mode: SimMode::Historic,
+                state: SimState::new(),
+            },
+            1200,
+        ),
+        Some(value) if value.starts_with('@') => {
+            let mode = &value[1..];
+            return Err(format!("invalid synthetic mode:{} should be standard|historic", mode));
+        }
+        Some(path) => {
+            let handle = match ReplayHandle::new(to_static_str(path.to_string()), 0, repeat) {
+                Ok(value) => value,
+                Err(error) => return Err(format!("fail to open capture: {}", error)),
+            };
+            (SimSource::Capture(handle), 9600)
+        }
+        None => return Err("missing source".to_string()),
+    };
+
+    let target = match (target, psk) {
+        (Some(SimTarget::Udp(_, addr)), Some(psk)) => dtls_target(addr, &psk)?,
+        (Some(_), Some(_)) => return Err("psk only applies to udp target".to_string()),
+        (Some(value), None) => value,
+        (None, _) => return Err("missing target".to_string()),
+    };
+
+    Ok(SimArgs {
+        source,
+        target,
+        speed: speed.unwrap_or(default_speed),
+        repeat,
+    })
+}
+
+impl SimTarget {
+    // wait as long as the meter would need to send data, then write it at once
+    fn send(&mut self, data: &[u8], speed: u32) -> Result<(), String> {
+        let delay = data.len() as u64 * CHAR_BITS * 1_000_000 / speed as u64;
+        std::thread::sleep(Duration::from_micros(delay));
+        let status = match self {
+            SimTarget::Udp(socket, target) => socket.send_to(data, target.as_str()).map(|_| ()),
+            #[cfg(feature = "dtls")]
+            SimTarget::Dtls(client, target, psk) => {
+                // frames are lost until listener accepts the handshake, as with plain udp
+                if client.is_none() {
+                    match DtlsClient::connect(target, psk) {
+                        Ok(value) => *client = Some(value),
+                        Err(error) => eprintln!("linky-simulator: {}", error),
+                    }
+                }
+                let status = match client.as_mut() {
+                    Some(session) => session.send(data),
+                    None => Ok(()),
+                };
+                if let Err(error) = status {
+                    eprintln!("linky-simulator: {}", error);
+                    *client = None;
+                }
+                Ok(())
+            }
+            SimTarget::Pty(master) => master.write_all(data),
+        };
+        match status {
+            Ok(()) => Ok(()),
+            // pty has no reader yet, data is lost as on a real meter
+            Err(error) if error.raw_os_error() == Some(libc::EIO) => Ok(()),
+            Err(error) => Err(format!("fail to send: {}", error)),
+        }
+    }
+}
+
+// handshake waits for first frame, listener may start after the simulator
+#[cfg(feature = "dtls")]
+fn dtls_target(addr: String, psk: &str) -> Result<SimTarget, String> {
+    match psk.split_once(':') {
+        Some((identity, key)) => match DtlsPsk::new(identity, key) {
+            Ok(value) => Ok(SimTarget::Dtls(None, addr, value)),
+            Err(error) => Err(format!("invalid psk: {}", error)),
+        },
+        None => Err(format!("invalid psk:{} expect IDENTITY:HEXKEY", psk)),
+    }
+}
+
+#[cfg(not(feature = "dtls"))]
+fn dtls_target(_addr: String, _psk: &str) -> Result<SimTarget, String> {
+    Err("psk requires linky-simulator built with 'dtls' feature".to_string())
+}
+
+fn run(args: &mut SimArgs) -> Result<(), String> {
+    let mut buffer = [0u8; TIC_LINE_MAX];
+    loop {
+        match &mut args.source {
+            SimSource::Synthetic { mode, state } => {
+                // a whole frame takes its own transmission time, no pause in between
+                let period = Duration::from_secs(1);
+                for group in state.next_frame(*mode, period) {
+                    args.target.send(&group, args.speed)?;
+                }
+            }
+            SimSource::Capture(handle) => match handle.read(&mut buffer) {
+                Ok(count) => args.target.send(&buffer[0..count], args.speed)?,
+                // capture is reopened by ReplayHandle when repeat is set
+                Err(LinkyError::RetryLater) if args.repeat => {}
+                Err(LinkyError::RetryLater) => return Ok(()),
+                Err(error) => return Err(format!("capture read fail: {}", error)),
+            },
+        }
+    }
+}
+
+fn main() {
+    let mut args = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            exit(1);
+        }
+    };
+
+    if let Err(error) = run(&mut args) {
+        eprintln!("linky-simulator: {}", error);
+        exit(1);
+    }
+}